@@ -0,0 +1,134 @@
+use std::io::{Read, Write};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::Error;
+
+/// Serialization of page payloads.
+pub trait PageCodec {
+    /// Value stored in the file header to identify the codec.
+    const FLAG: u8;
+
+    fn serialize<T, W>(object: T, destination: W) -> Result<(), Error>
+    where
+        T: Serialize,
+        W: Write;
+
+    fn deserialize<T, R>(source: R, limit: u64) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        R: Read;
+}
+
+pub struct MessagePackCodec;
+
+impl PageCodec for MessagePackCodec {
+    const FLAG: u8 = 0x00;
+
+    fn serialize<T, W>(object: T, destination: W) -> Result<(), Error>
+    where
+        T: Serialize,
+        W: Write,
+    {
+        let mut serializer = rmp_serde::Serializer::new(destination)
+            .with_binary()
+            .with_string_variants()
+            .with_struct_map();
+
+        match object.serialize(&mut serializer) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(Error::Other(Box::new(error))),
+        }
+    }
+
+    fn deserialize<T, R>(source: R, limit: u64) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        R: Read,
+    {
+        let mut deserializer = rmp_serde::Deserializer::new(source.take(limit)).with_binary();
+
+        match T::deserialize(&mut deserializer) {
+            Ok(value) => Ok(value),
+            Err(error) => Err(Error::Other(Box::new(error))),
+        }
+    }
+}
+
+pub struct BincodeCodec;
+
+impl PageCodec for BincodeCodec {
+    const FLAG: u8 = 0x10;
+
+    #[cfg(feature = "codec_bincode")]
+    fn serialize<T, W>(object: T, destination: W) -> Result<(), Error>
+    where
+        T: Serialize,
+        W: Write,
+    {
+        use bincode::Options;
+
+        bincode::DefaultOptions::new()
+            .serialize_into(destination, &object)
+            .map_err(|error| Error::Other(error))
+    }
+
+    #[cfg(not(feature = "codec_bincode"))]
+    fn serialize<T, W>(_object: T, _destination: W) -> Result<(), Error>
+    where
+        T: Serialize,
+        W: Write,
+    {
+        Err(Error::CodecUnavailable)
+    }
+
+    #[cfg(feature = "codec_bincode")]
+    fn deserialize<T, R>(source: R, limit: u64) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        R: Read,
+    {
+        use bincode::Options;
+
+        bincode::DefaultOptions::new()
+            .with_limit(limit)
+            .deserialize_from(source)
+            .map_err(|error| Error::Other(error))
+    }
+
+    #[cfg(not(feature = "codec_bincode"))]
+    fn deserialize<T, R>(_source: R, _limit: u64) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        R: Read,
+    {
+        Err(Error::CodecUnavailable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<C: PageCodec>() {
+        let mut buffer = Vec::new();
+
+        C::serialize(("hello world", 123u64, Some(vec![1u8, 2, 3])), &mut buffer).unwrap();
+
+        let value: (String, u64, Option<Vec<u8>>) =
+            C::deserialize(buffer.as_slice(), buffer.len() as u64).unwrap();
+
+        assert_eq!(value, ("hello world".to_string(), 123, Some(vec![1, 2, 3])));
+    }
+
+    #[test]
+    fn test_message_pack() {
+        round_trip::<MessagePackCodec>();
+    }
+
+    #[cfg(feature = "codec_bincode")]
+    #[test]
+    fn test_bincode() {
+        round_trip::<BincodeCodec>();
+    }
+}
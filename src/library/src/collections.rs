@@ -0,0 +1,838 @@
+//! Data structures built on a database.
+
+use crate::{vfs::Vfs, Cursor, Database, Error};
+
+const QUEUE_METADATA_LENGTH: usize = 16;
+const MULTIMAP_METADATA_LENGTH: usize = 8;
+const SEQUENCE_LENGTH: usize = 8;
+
+/// First-in, first-out queue of values stored in a database.
+///
+/// Values are stored with keys that are big-endian sequence numbers, so the
+/// oldest value is the first key in the tree. The sequence numbers of the
+/// head and tail are stored in the application metadata
+/// ([`Database::set_app_metadata()`]) and are persisted together with the
+/// values on each flush. The queue requires exclusive use of the database.
+///
+/// ```
+/// use grebedb::{collections::Queue, Database, Options};
+///
+/// # fn main() -> Result<(), grebedb::Error> {
+/// let db = Database::open_memory(Options::default())?;
+/// let mut queue = Queue::new(db)?;
+///
+/// queue.push_back("hello")?;
+/// queue.push_back("world")?;
+///
+/// assert_eq!(queue.len(), 2);
+/// assert_eq!(queue.pop_front()?, Some(b"hello".to_vec()));
+///
+/// queue.flush()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Queue<F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    database: Database<F>,
+    head: u64, // sequence number of the front value
+    tail: u64, // sequence number of the next value pushed
+}
+
+impl<F> Queue<F>
+where
+    F: Vfs,
+{
+    /// Use the database as a queue.
+    ///
+    /// The database must be empty or previously used as a queue.
+    pub fn new(database: Database<F>) -> Result<Self, Error> {
+        let (head, tail) = match database.get_app_metadata() {
+            Some(metadata) if metadata.len() == QUEUE_METADATA_LENGTH => {
+                let (head, tail) = metadata.split_at(QUEUE_METADATA_LENGTH / 2);
+
+                (
+                    u64::from_be_bytes(head.try_into().unwrap()),
+                    u64::from_be_bytes(tail.try_into().unwrap()),
+                )
+            }
+            Some(_) => {
+                return Err(Error::InvalidMetadata {
+                    message: "invalid queue metadata",
+                })
+            }
+            None if database.metadata().key_value_count() == 0 => (0, 0),
+            None => {
+                return Err(Error::InvalidMetadata {
+                    message: "database is not a queue",
+                })
+            }
+        };
+
+        if head > tail {
+            return Err(Error::InvalidMetadata {
+                message: "invalid queue metadata",
+            });
+        }
+
+        Ok(Self {
+            database,
+            head,
+            tail,
+        })
+    }
+
+    /// Return a reference to the underlying database.
+    pub fn database(&self) -> &Database<F> {
+        &self.database
+    }
+
+    /// Return the underlying database.
+    pub fn into_inner(self) -> Database<F> {
+        self.database
+    }
+
+    /// Return the number of values in the queue.
+    pub fn len(&self) -> u64 {
+        self.tail - self.head
+    }
+
+    /// Return whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head == self.tail
+    }
+
+    /// Add a value to the back of the queue.
+    pub fn push_back<V>(&mut self, value: V) -> Result<(), Error>
+    where
+        V: Into<Vec<u8>>,
+    {
+        self.database.put(self.tail.to_be_bytes(), value)?;
+        self.tail += 1;
+        self.save_metadata()
+    }
+
+    /// Return the value at the front of the queue without removing it.
+    pub fn front(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.get_front()?))
+    }
+
+    /// Remove and return the value at the front of the queue.
+    pub fn pop_front(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+
+        let value = self.get_front()?;
+
+        self.database.remove(self.head.to_be_bytes())?;
+        self.head += 1;
+        self.save_metadata()?;
+
+        Ok(Some(value))
+    }
+
+    /// Persist the queue to the file system.
+    ///
+    /// See [`Database::flush()`].
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.database.flush()
+    }
+
+    fn get_front(&mut self) -> Result<Vec<u8>, Error> {
+        match self.database.get(self.head.to_be_bytes())? {
+            Some(value) => Ok(value),
+            None => Err(Error::InvalidMetadata {
+                message: "queue value missing",
+            }),
+        }
+    }
+
+    fn save_metadata(&mut self) -> Result<(), Error> {
+        let mut metadata = Vec::with_capacity(QUEUE_METADATA_LENGTH);
+        metadata.extend_from_slice(&self.head.to_be_bytes());
+        metadata.extend_from_slice(&self.tail.to_be_bytes());
+
+        self.database.set_app_metadata(metadata)
+    }
+}
+
+/// Signed 64-bit counters stored in a database.
+///
+/// Each counter is a key whose value is a big-endian `i64`. Incrementing
+/// reads and writes the value with a single traversal of the tree, and like
+/// other modifications, the new value is persisted on the next flush.
+///
+/// ```
+/// use grebedb::{collections::Counter, Database, Options};
+///
+/// # fn main() -> Result<(), grebedb::Error> {
+/// let db = Database::open_memory(Options::default())?;
+/// let mut counter = Counter::new(db);
+///
+/// counter.increment("page_views", 1)?;
+/// counter.increment("page_views", 2)?;
+///
+/// assert_eq!(counter.get("page_views")?, 3);
+/// assert_eq!(counter.get("downloads")?, 0);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Counter<F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    database: Database<F>,
+}
+
+impl<F> Counter<F>
+where
+    F: Vfs,
+{
+    /// Use the database for counters.
+    pub fn new(database: Database<F>) -> Self {
+        Self { database }
+    }
+
+    /// Return a reference to the underlying database.
+    pub fn database(&self) -> &Database<F> {
+        &self.database
+    }
+
+    /// Return the underlying database.
+    pub fn into_inner(self) -> Database<F> {
+        self.database
+    }
+
+    /// Return the value of a counter.
+    ///
+    /// A counter that does not exist has a value of 0. Returns
+    /// [`Error::InvalidValue`] if the stored value is not a counter.
+    pub fn get<K>(&mut self, key: K) -> Result<i64, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+
+        match self.database.get(key)? {
+            Some(value) => decode_counter(key, Some(&value)),
+            None => Ok(0),
+        }
+    }
+
+    /// Add a value to a counter and return the new value.
+    ///
+    /// A counter that does not exist is created with a value of 0 before
+    /// adding. Like atomic integers, the value wraps around on overflow.
+    /// Returns [`Error::InvalidValue`] if the stored value is not a counter.
+    pub fn increment<K>(&mut self, key: K, delta: i64) -> Result<i64, Error>
+    where
+        K: Into<Vec<u8>>,
+    {
+        let key = key.into();
+        let mut new_value = 0;
+
+        self.database.update_value(key.clone(), |value| {
+            new_value = decode_counter(&key, value)?.wrapping_add(delta);
+
+            Ok(new_value.to_be_bytes().to_vec())
+        })?;
+
+        Ok(new_value)
+    }
+
+    /// Persist the counters to the file system.
+    ///
+    /// See [`Database::flush()`].
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.database.flush()
+    }
+}
+
+/// Map of keys to multiple values stored in a database.
+///
+/// Multiple values can be stored under the same key, such as for
+/// secondary indexes. Each value is stored as a separate key-value pair
+/// whose key is the escaped key followed by a sequence number, so the values
+/// of a key are adjacent in the tree and are returned in insertion order.
+/// The next sequence number is stored in the application metadata
+/// ([`Database::set_app_metadata()`]). The map requires exclusive use of
+/// the database.
+///
+/// ```
+/// use grebedb::{collections::MultiMap, Database, Options};
+///
+/// # fn main() -> Result<(), grebedb::Error> {
+/// let db = Database::open_memory(Options::default())?;
+/// let mut map = MultiMap::new(db)?;
+///
+/// map.insert("color:red", "apple")?;
+/// map.insert("color:red", "cherry")?;
+/// map.insert("color:yellow", "banana")?;
+///
+/// assert_eq!(
+///     map.get_all("color:red")?,
+///     vec![b"apple".to_vec(), b"cherry".to_vec()]
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MultiMap<F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    database: Database<F>,
+    sequence: u64, // sequence number of the next value inserted
+}
+
+impl<F> MultiMap<F>
+where
+    F: Vfs,
+{
+    /// Use the database as a multimap.
+    ///
+    /// The database must be empty or previously used as a multimap.
+    pub fn new(database: Database<F>) -> Result<Self, Error> {
+        let sequence = match database.get_app_metadata() {
+            Some(metadata) => match metadata.try_into() {
+                Ok(bytes) => u64::from_be_bytes(bytes),
+                Err(_) => {
+                    return Err(Error::InvalidMetadata {
+                        message: "invalid multimap metadata",
+                    })
+                }
+            },
+            None if database.metadata().key_value_count() == 0 => 0,
+            None => {
+                return Err(Error::InvalidMetadata {
+                    message: "database is not a multimap",
+                })
+            }
+        };
+
+        Ok(Self { database, sequence })
+    }
+
+    /// Return a reference to the underlying database.
+    pub fn database(&self) -> &Database<F> {
+        &self.database
+    }
+
+    /// Return the underlying database.
+    pub fn into_inner(self) -> Database<F> {
+        self.database
+    }
+
+    /// Add a value to a key.
+    ///
+    /// Existing values of the key are kept, including equal values.
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: Into<Vec<u8>>,
+    {
+        let mut entry_key = entry_key_prefix(key.as_ref());
+        entry_key.extend_from_slice(&self.sequence.to_be_bytes());
+
+        self.database.put(entry_key, value)?;
+        self.sequence += 1;
+
+        let metadata: [u8; MULTIMAP_METADATA_LENGTH] = self.sequence.to_be_bytes();
+        self.database.set_app_metadata(metadata)
+    }
+
+    /// Return whether the key has any values.
+    pub fn contains_key<K>(&mut self, key: K) -> Result<bool, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let (start, end) = entry_key_range(key.as_ref());
+        let mut cursor = self.database.cursor_range(start..end)?;
+
+        cursor.next_buf(&mut Vec::new(), &mut Vec::new())
+    }
+
+    /// Return all the values of a key in insertion order.
+    pub fn get_all<K>(&mut self, key: K) -> Result<Vec<Vec<u8>>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let (start, end) = entry_key_range(key.as_ref());
+        let mut cursor = self.database.cursor_range(start..end)?;
+        let mut entry_key = Vec::new();
+        let mut values = Vec::new();
+        let mut value = Vec::new();
+
+        while cursor.next_buf(&mut entry_key, &mut value)? {
+            values.push(std::mem::take(&mut value));
+        }
+
+        Ok(values)
+    }
+
+    /// Remove all the values of a key and return the number removed.
+    pub fn remove_all<K>(&mut self, key: K) -> Result<usize, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let (start, end) = entry_key_range(key.as_ref());
+        let mut cursor = self.database.cursor_range(start..end)?;
+        let mut entry_keys = Vec::new();
+        let mut entry_key = Vec::new();
+        let mut value = Vec::new();
+
+        while cursor.next_buf(&mut entry_key, &mut value)? {
+            entry_keys.push(std::mem::take(&mut entry_key));
+        }
+
+        self.database.remove_many(&entry_keys)?;
+
+        Ok(entry_keys.len())
+    }
+
+    /// Return a cursor for iterating the key-value pairs in key order.
+    pub fn cursor(&mut self) -> Result<MultiMapCursor<'_, F>, Error> {
+        Ok(MultiMapCursor {
+            cursor: self.database.cursor()?,
+            error: None,
+        })
+    }
+
+    /// Persist the map to the file system.
+    ///
+    /// See [`Database::flush()`].
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.database.flush()
+    }
+}
+
+/// Cursor for navigating the key-value pairs of a [`MultiMap`].
+///
+/// A key is returned once for each of its values.
+#[derive(Debug)]
+pub struct MultiMapCursor<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    cursor: Cursor<'a, F>,
+    error: Option<Error>,
+}
+
+impl<'a, F> MultiMapCursor<'a, F>
+where
+    F: Vfs,
+{
+    /// Return the most recent error.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    /// Reposition the cursor at the first value of the given key or the
+    /// key after it.
+    pub fn seek<K>(&mut self, key: K) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.cursor.seek(entry_key_prefix(key.as_ref()))
+    }
+}
+
+impl<'a, F> Iterator for MultiMapCursor<'a, F>
+where
+    F: Vfs,
+{
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let mut entry_key = Vec::new();
+        let mut value = Vec::new();
+
+        match self.cursor.next_buf(&mut entry_key, &mut value) {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(error) => {
+                self.error = Some(error);
+                return None;
+            }
+        }
+
+        match decode_entry_key(&entry_key) {
+            Some((key, _)) => Some((key, value)),
+            None => {
+                self.error = Some(Error::InvalidValue {
+                    key: entry_key,
+                    message: "not a multimap key",
+                });
+                None
+            }
+        }
+    }
+}
+
+/// Map that retains previous versions of values stored in a database.
+///
+/// Each version is stored as a separate key-value pair whose key is the
+/// escaped key followed by the inverted version number, so the versions of a
+/// key are adjacent in the tree with the newest first. Versions are numbered
+/// from 0 for each key.
+///
+/// Only the newest `max_versions` versions of a key are returned. Older
+/// versions remain in the database until [`Self::compact()`] is called. The
+/// map requires exclusive use of the database.
+///
+/// ```
+/// use grebedb::{collections::VersionedMap, Database, Options};
+///
+/// # fn main() -> Result<(), grebedb::Error> {
+/// let db = Database::open_memory(Options::default())?;
+/// let mut map = VersionedMap::new(db, 2)?;
+///
+/// map.put("status", "draft")?;
+/// map.put("status", "review")?;
+/// map.put("status", "published")?;
+///
+/// assert_eq!(map.get("status")?, Some(b"published".to_vec()));
+/// assert_eq!(map.get_version("status", 1)?, Some(b"review".to_vec()));
+/// assert_eq!(map.get_version("status", 2)?, None);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct VersionedMap<F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    database: Database<F>,
+    max_versions: usize,
+}
+
+impl<F> VersionedMap<F>
+where
+    F: Vfs,
+{
+    /// Use the database as a versioned map that retains the given number of
+    /// versions of each key.
+    ///
+    /// The number of versions must be at least 1.
+    pub fn new(database: Database<F>, max_versions: usize) -> Result<Self, Error> {
+        if max_versions == 0 {
+            return Err(Error::InvalidConfig {
+                message: "max versions must be at least 1",
+            });
+        }
+
+        Ok(Self {
+            database,
+            max_versions,
+        })
+    }
+
+    /// Return a reference to the underlying database.
+    pub fn database(&self) -> &Database<F> {
+        &self.database
+    }
+
+    /// Return the underlying database.
+    pub fn into_inner(self) -> Database<F> {
+        self.database
+    }
+
+    /// Store a new version of the value of a key and return its version
+    /// number.
+    pub fn put<K, V>(&mut self, key: K, value: V) -> Result<u64, Error>
+    where
+        K: AsRef<[u8]>,
+        V: Into<Vec<u8>>,
+    {
+        let key = key.as_ref();
+        let version = match self.history(key)?.next_version()? {
+            Some((version, _)) => version + 1,
+            None => 0,
+        };
+
+        let mut entry_key = entry_key_prefix(key);
+        entry_key.extend_from_slice(&(u64::MAX - version).to_be_bytes());
+
+        self.database.put(entry_key, value)?;
+
+        Ok(version)
+    }
+
+    /// Return the newest value of a key.
+    pub fn get<K>(&mut self, key: K) -> Result<Option<Vec<u8>>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.get_version(key, 0)
+    }
+
+    /// Return a previous value of a key.
+    ///
+    /// Index 0 is the newest value, 1 is the value before it, and so on.
+    pub fn get_version<K>(&mut self, key: K, index: usize) -> Result<Option<Vec<u8>>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut history = self.history(key)?;
+
+        for _ in 0..index {
+            if history.next_version()?.is_none() {
+                return Ok(None);
+            }
+        }
+
+        Ok(history.next_version()?.map(|(_, value)| value))
+    }
+
+    /// Return an iterator over the version numbers and values of a key,
+    /// newest first.
+    pub fn history<K>(&mut self, key: K) -> Result<History<'_, F>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let (start, end) = entry_key_range(key.as_ref());
+
+        Ok(History {
+            cursor: self.database.cursor_range(start..end)?,
+            remaining: self.max_versions,
+            error: None,
+        })
+    }
+
+    /// Remove all versions of a key.
+    pub fn remove<K>(&mut self, key: K) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let (start, end) = entry_key_range(key.as_ref());
+        let mut cursor = self.database.cursor_range(start..end)?;
+        let mut entry_keys = Vec::new();
+        let mut entry_key = Vec::new();
+        let mut value = Vec::new();
+
+        while cursor.next_buf(&mut entry_key, &mut value)? {
+            entry_keys.push(std::mem::take(&mut entry_key));
+        }
+
+        self.database.remove_many(&entry_keys)
+    }
+
+    /// Remove the versions older than the retained number of versions.
+    ///
+    /// Returns the number of versions removed.
+    pub fn compact(&mut self) -> Result<usize, Error> {
+        let mut cursor = self.database.cursor()?;
+        let mut entry_keys = Vec::new();
+        let mut current_key = Vec::new();
+        let mut version_count = 0;
+        let mut entry_key = Vec::new();
+        let mut value = Vec::new();
+
+        while cursor.next_buf(&mut entry_key, &mut value)? {
+            let key = match decode_entry_key(&entry_key) {
+                Some((key, _)) => key,
+                None => {
+                    return Err(Error::InvalidValue {
+                        key: entry_key,
+                        message: "not a versioned map key",
+                    })
+                }
+            };
+
+            if version_count > 0 && key == current_key {
+                version_count += 1;
+            } else {
+                current_key = key;
+                version_count = 1;
+            }
+
+            if version_count > self.max_versions {
+                entry_keys.push(entry_key.clone());
+            }
+        }
+
+        self.database.remove_many(&entry_keys)?;
+
+        Ok(entry_keys.len())
+    }
+
+    /// Persist the map to the file system.
+    ///
+    /// See [`Database::flush()`].
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.database.flush()
+    }
+}
+
+/// Iterator over the versions of a key in a [`VersionedMap`].
+///
+/// Items are version numbers and values, newest first. Obtain one with
+/// [`VersionedMap::history()`].
+#[derive(Debug)]
+pub struct History<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    cursor: Cursor<'a, F>,
+    remaining: usize,
+    error: Option<Error>,
+}
+
+impl<'a, F> History<'a, F>
+where
+    F: Vfs,
+{
+    /// Return the most recent error.
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    fn next_version(&mut self) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        let mut entry_key = Vec::new();
+        let mut value = Vec::new();
+
+        if !self.cursor.next_buf(&mut entry_key, &mut value)? {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+
+        match decode_entry_key(&entry_key) {
+            Some((_, inverted_version)) => Ok(Some((u64::MAX - inverted_version, value))),
+            None => Err(Error::InvalidValue {
+                key: entry_key,
+                message: "not a versioned map key",
+            }),
+        }
+    }
+}
+
+impl<'a, F> Iterator for History<'a, F>
+where
+    F: Vfs,
+{
+    type Item = (u64, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        match self.next_version() {
+            Ok(item) => item,
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+// Escape null bytes and add a terminator such that the order of keys is
+// preserved and no key is a prefix of another key.
+fn entry_key_prefix(key: &[u8]) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(key.len() + 2 + SEQUENCE_LENGTH);
+
+    for &byte in key {
+        prefix.push(byte);
+
+        if byte == 0x00 {
+            prefix.push(0xff);
+        }
+    }
+
+    prefix.extend_from_slice(&[0x00, 0x00]);
+
+    prefix
+}
+
+fn entry_key_range(key: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let start = entry_key_prefix(key);
+    let mut end = start.clone();
+    *end.last_mut().unwrap() = 0x01;
+
+    (start, end)
+}
+
+// Return the key and the number following it.
+fn decode_entry_key(entry_key: &[u8]) -> Option<(Vec<u8>, u64)> {
+    let mut key = Vec::with_capacity(entry_key.len());
+    let mut index = 0;
+
+    while index + 1 < entry_key.len() {
+        match (entry_key[index], entry_key[index + 1]) {
+            (0x00, 0x00) => {
+                let number = entry_key[index + 2..].try_into().ok()?;
+
+                return Some((key, u64::from_be_bytes(number)));
+            }
+            (0x00, 0xff) => {
+                key.push(0x00);
+                index += 2;
+            }
+            (0x00, _) => return None,
+            (byte, _) => {
+                key.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    None
+}
+
+fn decode_counter(key: &[u8], value: Option<&[u8]>) -> Result<i64, Error> {
+    match value {
+        Some(value) => match value.try_into() {
+            Ok(bytes) => Ok(i64::from_be_bytes(bytes)),
+            Err(_) => Err(Error::InvalidValue {
+                key: key.to_vec(),
+                message: "not a counter",
+            }),
+        },
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_key_order() {
+        let keys: [&[u8]; 6] = [b"", b"\x00", b"\x00\x00", b"\x00\x01", b"a", b"a\x00"];
+
+        for pair in keys.windows(2) {
+            let mut first = entry_key_prefix(pair[0]);
+            first.extend_from_slice(&u64::MAX.to_be_bytes());
+            let mut second = entry_key_prefix(pair[1]);
+            second.extend_from_slice(&0u64.to_be_bytes());
+
+            assert!(first < second);
+            assert_eq!(
+                decode_entry_key(&first).unwrap(),
+                (pair[0].to_vec(), u64::MAX)
+            );
+            assert_eq!(decode_entry_key(&second).unwrap(), (pair[1].to_vec(), 0));
+        }
+
+        assert_eq!(decode_entry_key(b"a\x00\x00"), None);
+        assert_eq!(decode_entry_key(b"a\x00\x02\x00\x00"), None);
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt::Debug;
+
+use crate::error::Error;
+
+#[cfg(feature = "encryption")]
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+/// Secret key for encrypting database files.
+///
+/// The key is 256 bits and should be generated from a cryptographically
+/// secure source of randomness or a key derivation function.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey {
+    key: [u8; KEY_LENGTH],
+}
+
+impl EncryptionKey {
+    /// Create a key from the given bytes.
+    pub fn new(key: [u8; KEY_LENGTH]) -> Self {
+        Self { key }
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't leak the key into logs
+        f.write_str("EncryptionKey { .. }")
+    }
+}
+
+/// Encrypt and authenticate the plaintext and append the nonce and
+/// ciphertext to the destination.
+#[cfg(feature = "encryption")]
+pub fn encrypt(
+    key: &EncryptionKey,
+    associated_data: &[u8],
+    plaintext: &[u8],
+    destination: &mut Vec<u8>,
+) -> Result<(), Error> {
+    use chacha20poly1305::{
+        aead::{Aead, NewAead, Payload},
+        Key, XChaCha20Poly1305, XNonce,
+    };
+
+    let mut nonce = [0u8; NONCE_LENGTH];
+    getrandom::getrandom(&mut nonce).map_err(|error| Error::Other(Box::new(error)))?;
+
+    let cipher = XChaCha20Poly1305::new(&Key::from(key.key));
+    let ciphertext = cipher
+        .encrypt(
+            &XNonce::from(nonce),
+            Payload {
+                msg: plaintext,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "encryption failed",
+            ))
+        })?;
+
+    destination.extend_from_slice(&nonce);
+    destination.extend_from_slice(&ciphertext);
+
+    Ok(())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn encrypt(
+    _key: &EncryptionKey,
+    _associated_data: &[u8],
+    _plaintext: &[u8],
+    _destination: &mut Vec<u8>,
+) -> Result<(), Error> {
+    Err(Error::EncryptionUnavailable)
+}
+
+/// Verify and decrypt data containing the nonce and ciphertext.
+///
+/// Returns `None` if the data is not authentic.
+#[cfg(feature = "encryption")]
+pub fn decrypt(
+    key: &EncryptionKey,
+    associated_data: &[u8],
+    data: &[u8],
+) -> Result<Option<Vec<u8>>, Error> {
+    use chacha20poly1305::{
+        aead::{Aead, NewAead, Payload},
+        Key, XChaCha20Poly1305, XNonce,
+    };
+
+    if data.len() < NONCE_LENGTH {
+        return Ok(None);
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+    let nonce: [u8; NONCE_LENGTH] = nonce.try_into().unwrap();
+    let cipher = XChaCha20Poly1305::new(&Key::from(key.key));
+
+    Ok(cipher
+        .decrypt(
+            &XNonce::from(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: associated_data,
+            },
+        )
+        .ok())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt(
+    _key: &EncryptionKey,
+    _associated_data: &[u8],
+    _data: &[u8],
+) -> Result<Option<Vec<u8>>, Error> {
+    Err(Error::EncryptionUnavailable)
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = EncryptionKey::new([1u8; KEY_LENGTH]);
+        let other_key = EncryptionKey::new([2u8; KEY_LENGTH]);
+        let mut data = Vec::new();
+
+        encrypt(&key, b"header", b"hello world", &mut data).unwrap();
+
+        assert_eq!(
+            decrypt(&key, b"header", &data).unwrap(),
+            Some(b"hello world".to_vec())
+        );
+        assert_eq!(decrypt(&other_key, b"header", &data).unwrap(), None);
+        assert_eq!(decrypt(&key, b"other", &data).unwrap(), None);
+
+        let last_index = data.len() - 1;
+        data[last_index] ^= 0xff;
+        assert_eq!(decrypt(&key, b"header", &data).unwrap(), None);
+    }
+
+    #[test]
+    fn test_key_debug() {
+        let key = EncryptionKey::new([1u8; KEY_LENGTH]);
+
+        assert_eq!(format!("{:?}", key), "EncryptionKey { .. }");
+    }
+}
@@ -7,6 +7,18 @@ pub enum Error {
     #[error("compression support not available")]
     CompressionUnavailable,
 
+    /// Support for a checksum function is not available due to a disabled feature.
+    #[error("checksum support not available")]
+    ChecksumUnavailable,
+
+    /// Support for a page codec is not available due to a disabled feature.
+    #[error("codec support not available")]
+    CodecUnavailable,
+
+    /// Support for encryption is not available due to a disabled feature.
+    #[error("encryption support not available")]
+    EncryptionUnavailable,
+
     /// Support for file locking is not available due to a disabled feature.
     #[error("file locking support not available")]
     FileLockingUnavailable,
@@ -25,6 +37,15 @@ pub enum Error {
         path: String,
     },
 
+    /// A file could not be decrypted.
+    ///
+    /// The encryption key is incorrect or the file was modified.
+    #[error("decryption failed: {path}")]
+    DecryptionFailed {
+        /// Path to file that failed decryption.
+        path: String,
+    },
+
     /// A file contained unexpected data or is not a database file.
     #[error("invalid file format: {message}, {path}")]
     InvalidFileFormat {
@@ -74,17 +95,174 @@ pub enum Error {
     #[error("database read only")]
     ReadOnly,
 
+    /// The database was modified by another process after it was opened.
+    ///
+    /// This occurs when a database opened in read-only mode without
+    /// exclusive file locking is committed to by a writer. The database must
+    /// be opened again to read the new contents.
+    #[error("database externally modified")]
+    ExternallyModified,
+
+    /// A key being merged already exists in the database.
+    #[error("key conflict")]
+    KeyConflict {
+        /// The conflicting key.
+        key: Vec<u8>,
+    },
+
+    /// A stored value is not valid for the requested operation.
+    #[error("invalid value: {message}")]
+    InvalidValue {
+        /// The key of the value.
+        key: Vec<u8>,
+        /// Custom message.
+        message: &'static str,
+    },
+
+    /// A file path given to a file system is not valid.
+    #[error("invalid path: {message}, {path}")]
+    InvalidPath {
+        /// The path.
+        path: String,
+        /// Custom message.
+        message: &'static str,
+    },
+
     /// Other std IO error.
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    /// Error from the in-memory file system.
+    #[error(transparent)]
+    Vfs(#[from] vfs::VfsError),
+
     /// Other internal errors.
     #[error(transparent)]
     Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
-impl From<vfs::VfsError> for Error {
-    fn from(error: vfs::VfsError) -> Self {
-        Self::Other(Box::new(error))
+impl Error {
+    /// Return the category of the error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::CompressionUnavailable
+            | Self::ChecksumUnavailable
+            | Self::CodecUnavailable
+            | Self::EncryptionUnavailable
+            | Self::FileLockingUnavailable => ErrorKind::Unsupported,
+            Self::InvalidConfig { .. } => ErrorKind::InvalidConfig,
+            Self::BadChecksum { .. }
+            | Self::DecryptionFailed { .. }
+            | Self::InvalidFileFormat { .. }
+            | Self::InvalidMetadata { .. }
+            | Self::InvalidPageData { .. }
+            | Self::LimitExceeded => ErrorKind::Corruption,
+            Self::Closed => ErrorKind::Closed,
+            Self::Locked => ErrorKind::Locked,
+            Self::ReadOnly => ErrorKind::ReadOnly,
+            Self::ExternallyModified => ErrorKind::ExternallyModified,
+            Self::Io(_) | Self::Vfs(_) => ErrorKind::Io,
+            Self::KeyConflict { .. }
+            | Self::InvalidValue { .. }
+            | Self::InvalidPath { .. }
+            | Self::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    /// Return whether the database files are damaged or aren't database
+    /// files.
+    pub fn is_corruption(&self) -> bool {
+        self.kind() == ErrorKind::Corruption
+    }
+
+    /// Return whether the error was returned by the file system, such as
+    /// when the disk is full.
+    pub fn is_io(&self) -> bool {
+        self.kind() == ErrorKind::Io
+    }
+
+    /// Return whether the operation may succeed if it is attempted again.
+    ///
+    /// This includes a database locked by another process, a database that
+    /// must be opened again after another process modified it, and
+    /// interrupted or timed out file system operations.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Locked | Self::ExternallyModified => true,
+            Self::Io(error) => matches!(
+                error.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::TimedOut
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// Category of an [`Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A feature required by the options or the database is disabled.
+    Unsupported,
+
+    /// Provided configuration is invalid.
+    InvalidConfig,
+
+    /// A file is damaged, was modified, or is not a database file.
+    ///
+    /// Decryption failures are included because an authentication failure
+    /// can't be distinguished from a modified file.
+    Corruption,
+
+    /// Database is closed due to a previous error.
+    Closed,
+
+    /// Database is locked by another process.
+    Locked,
+
+    /// Database is opened in read-only mode.
+    ReadOnly,
+
+    /// Database was modified by another process.
+    ExternallyModified,
+
+    /// File system error.
+    Io,
+
+    /// Other internal errors.
+    Other,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        let error = Error::BadChecksum {
+            path: "file".to_string(),
+        };
+        assert_eq!(error.kind(), ErrorKind::Corruption);
+        assert!(error.is_corruption());
+        assert!(!error.is_io());
+        assert!(!error.is_retryable());
+
+        let error = Error::Io(std::io::Error::new(
+            std::io::ErrorKind::Interrupted,
+            "interrupted",
+        ));
+        assert!(error.is_io());
+        assert!(error.is_retryable());
+
+        let error = Error::Io(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "permission denied",
+        ));
+        assert!(error.is_io());
+        assert!(!error.is_retryable());
+
+        assert!(Error::Locked.is_retryable());
+        assert_eq!(Error::Closed.kind(), ErrorKind::Closed);
     }
 }
@@ -3,10 +3,24 @@
 //! The functions allow saving database contents into another file
 //! which can be used for migrating data or for backup purposes.
 //!
-//! The export file format is a JSON text sequence (RFC 7464).
+//! The export file format is a JSON text sequence (RFC 7464). A more compact
+//! binary format, where each record is a big-endian 32-bit length followed by
+//! MessagePack data, can be written with [`export_binary()`]. Both formats
+//! contain the same records and [`import()`] reads either format.
+//!
+//! The records can also be obtained without a file using [`rows()`].
+//!
+//! Files can be compressed with [`CompressedWriter`] and read with
+//! [`DecompressedReader`].
+
+pub mod foreign;
 
 const RECORD_SEPARATOR: u8 = 0x1e;
 const NEWLINE: u8 = 0x0a;
+const BINARY_SIGNATURE: [u8; 8] = *b"GrebeDB\x00";
+const MAX_BINARY_RECORD_LENGTH: u32 = 1 << 30;
+const BATCH_ROWS: usize = 1000;
+const BATCH_BYTES: usize = 1 << 20;
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -16,11 +30,20 @@ enum Row {
     Eof,
 }
 
-use std::io::{BufRead, Write};
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Read, Write},
+    sync::{mpsc, Arc, Mutex},
+    thread::Scope,
+    time::{Duration, Instant},
+};
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{Database, Error};
+use crate::{
+    vfs::{MemoryVfs, Vfs},
+    Cursor, Database, Error, OpenMode, Options,
+};
 
 /// Import and export errors.
 #[derive(thiserror::Error, Debug)]
@@ -71,6 +94,50 @@ pub enum ExportError {
     /// The file is incomplete.
     #[error("unexpected end of file")]
     UnexpectedEof,
+
+    /// Record length is too large.
+    ///
+    /// The binary file is corrupted.
+    #[error("record too large")]
+    RecordTooLarge,
+
+    /// Number of rows does not match the header.
+    ///
+    /// The file is truncated or corrupted.
+    #[error("row count mismatch, expected = {expected}, actual = {actual}")]
+    RowCountMismatch {
+        /// Number of pairs in the header
+        expected: u64,
+        /// Number of pairs read
+        actual: u64,
+    },
+
+    /// Target database contains data from a different source.
+    ///
+    /// The file was exported from a database other than the one of the
+    /// previous import.
+    #[error("source mismatch, source = {source_uuid}")]
+    SourceMismatch {
+        /// Database UUID in the header
+        source_uuid: String,
+    },
+    /// Record of a foreign dump format cannot be parsed.
+    ///
+    /// See the [`foreign`] module.
+    #[error("invalid record, {message}, row = {row}")]
+    InvalidRecord {
+        /// Row index (0 based)
+        row: u64,
+        /// Custom message
+        message: &'static str,
+    },
+
+    /// File does not contain the data needed to import it.
+    ///
+    /// The file was exported with only the keys or with keys and values
+    /// encoded as other than hex. See [`ExportOptions`].
+    #[error("file is a listing and cannot be imported")]
+    NotImportable,
 }
 
 impl From<ExportError> for Error {
@@ -88,6 +155,47 @@ impl From<serde_json::Error> for Error {
 #[derive(Default, Serialize, Deserialize)]
 struct MetadataRow {
     pub key_value_count: u64,
+
+    // Present in incremental exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since_revision: Option<u64>,
+
+    // Identity of the exported database, absent in files of older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options_digest: Option<String>,
+
+    // Present in listings that cannot be imported
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keys_only: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_encoding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_encoding: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ListingRow {
+    KeyValue(ListingKeyValueRow),
+}
+
+// Key-value row of a listing with the pairs encoded as text
+#[derive(Serialize)]
+struct ListingKeyValueRow {
+    pub key: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+
+    pub index: u64,
+
+    pub key_crc32c: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_crc32c: Option<u32>,
 }
 
 #[derive(Default, Serialize, Deserialize)]
@@ -106,10 +214,15 @@ struct KeyValueRow {
     pub value_crc32c: u32,
 }
 
+// Binary formats store the bytes as is.
 fn vec_to_hex<S>(vec: &[u8], serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
+    if !serializer.is_human_readable() {
+        return serializer.serialize_bytes(vec);
+    }
+
     serializer.serialize_str(&data_encoding::HEXUPPER.encode(vec))
 }
 
@@ -117,6 +230,10 @@ fn hex_to_vec<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
 where
     D: Deserializer<'de>,
 {
+    if !deserializer.is_human_readable() {
+        return Ok(serde_bytes::ByteBuf::deserialize(deserializer)?.into_vec());
+    }
+
     let s = <&str>::deserialize(deserializer)?;
     match data_encoding::HEXUPPER.decode(s.as_bytes()) {
         Ok(value) => Ok(value),
@@ -124,48 +241,256 @@ where
     }
 }
 
-struct ImportReader<'a, R: BufRead> {
-    database: &'a mut Database,
+/// Progress of an export or import passed to the progress callback.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    rows: u64,
+    total_rows: Option<u64>,
+    bytes: u64,
+    elapsed: Duration,
+}
+
+impl Progress {
+    /// Return the number of key-value pairs processed.
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// Return the estimated total number of key-value pairs.
+    ///
+    /// The value is the approximate count from the database metadata or the
+    /// header of the file. It is `None` if not known, such as for
+    /// incremental exports, or before the header of an import is read.
+    pub fn total_rows(&self) -> Option<u64> {
+        self.total_rows
+    }
+
+    /// Return the number of bytes written to or read from the file.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Return the time since the export or import started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Return the average number of key-value pairs processed per second.
+    pub fn rows_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+
+        if seconds > 0.0 {
+            self.rows as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Return the estimated time remaining based on the average rate.
+    ///
+    /// Returns `None` if the total is not known or no pairs have been
+    /// processed.
+    pub fn estimated_remaining(&self) -> Option<Duration> {
+        let total_rows = self.total_rows?;
+        let rate = self.rows_per_second();
+
+        if self.rows == 0 || rate <= 0.0 {
+            return None;
+        }
+
+        let remaining_rows = total_rows.saturating_sub(self.rows);
+
+        Some(Duration::from_secs_f64(remaining_rows as f64 / rate))
+    }
+}
+
+struct ProgressTracker {
+    start: Instant,
+    progress: Progress,
+}
+
+impl ProgressTracker {
+    fn new(total_rows: Option<u64>) -> Self {
+        Self {
+            start: Instant::now(),
+            progress: Progress {
+                total_rows,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn update(&mut self, rows: u64, bytes: u64) -> &Progress {
+        self.progress.rows = rows;
+        self.progress.bytes = bytes;
+        self.progress.elapsed = self.start.elapsed();
+
+        &self.progress
+    }
+}
+
+struct ImportReader<'a, R: BufRead, F: Vfs> {
+    database: Option<&'a mut Database<F>>, // none when only validating
     input_file: &'a mut R,
-    header_found: bool,
+    binary: bool,
+    header_row: Option<MetadataRow>,
     footer_found: bool,
+    offset: u64,
+    skip_count: u64,
+    expected_count: Option<u64>,
+    threads: usize,
+    flush_rows: Option<u64>,
+    flush_bytes: Option<u64>,
+    unflushed_rows: u64,
+    unflushed_bytes: u64,
 }
 
-impl<'a, R: BufRead> ImportReader<'a, R> {
-    fn new(input_file: &'a mut R, database: &'a mut Database) -> Self {
+impl<'a, R: BufRead, F: Vfs> ImportReader<'a, R, F> {
+    fn new(input_file: &'a mut R, database: Option<&'a mut Database<F>>) -> Self {
         Self {
             database,
             input_file,
-            header_found: false,
+            binary: false,
+            header_row: None,
             footer_found: false,
+            offset: 0,
+            skip_count: 0,
+            expected_count: None,
+            threads: 0,
+            flush_rows: None,
+            flush_bytes: None,
+            unflushed_rows: 0,
+            unflushed_bytes: 0,
         }
     }
 
-    fn import<C>(&mut self, mut progress: C) -> Result<(), Error>
+    fn import<C>(&mut self, mut progress: C) -> Result<u64, Error>
     where
-        C: FnMut(u64),
+        C: FnMut(&Progress),
     {
+        let mut tracker = ProgressTracker::new(None);
+
+        self.binary = self.read_binary_signature()?;
+
+        let counter = if self.threads == 0 {
+            self.import_rows(&mut tracker, &mut progress)?
+        } else {
+            let threads = self.threads;
+            let binary = self.binary;
+
+            std::thread::scope(|scope| {
+                let pool =
+                    WorkerPool::new(scope, threads, move |batch| decode_batch(batch, binary));
+                self.import_batches(pool, &mut tracker, &mut progress)
+            })?
+        };
+
+        let result = self.validate_footer(counter);
+
+        if let Some(database) = self.database.as_mut() {
+            if result.is_ok() {
+                database.set_import_progress(None)?;
+            }
+
+            database.flush()?;
+        }
+
+        result?;
+
+        Ok(counter)
+    }
+
+    fn import_rows(
+        &mut self,
+        tracker: &mut ProgressTracker,
+        progress: &mut dyn FnMut(&Progress),
+    ) -> Result<u64, Error> {
         let mut buffer = Vec::new();
         let mut counter = 0u64;
 
-        while self.read_record_separator()? {
-            buffer.clear();
-            self.input_file.read_until(NEWLINE, &mut buffer)?;
+        while let Some(row) = self.read_row(&mut buffer)? {
+            match row {
+                Row::Metadata(row) => {
+                    self.process_metadata(row)?;
+                    tracker.progress.total_rows = self.expected_count;
+                }
+                Row::KeyValue(row) => {
+                    counter += 1;
+                    self.process_key_value_row(row, counter)?;
+                    progress(tracker.update(counter, self.offset));
+                }
+                Row::Eof => {
+                    self.process_eof_row()?;
+                }
+            }
+        }
+
+        Ok(counter)
+    }
 
-            if buffer.last().cloned().unwrap_or(0) != NEWLINE {
-                return Err(ExportError::UnexpectedEof.into());
+    // Records are read on the calling thread, decoded and verified by the
+    // workers, and applied to the database on the calling thread in order.
+    fn import_batches(
+        &mut self,
+        mut pool: WorkerPool<RecordBatch, DecodedBatch>,
+        tracker: &mut ProgressTracker,
+        progress: &mut dyn FnMut(&Progress),
+    ) -> Result<u64, Error> {
+        let mut counter = 0u64;
+        let mut batch = RecordBatch::default();
+
+        loop {
+            let mut record = Vec::new();
+            let has_record = self.read_record(&mut record)?;
+
+            if has_record {
+                batch.bytes += record.len();
+                batch.records.push(record);
+                batch.end_offset = self.offset;
+            }
+
+            let is_full = batch.records.len() >= BATCH_ROWS || batch.bytes >= BATCH_BYTES;
+
+            if is_full || !has_record && !batch.records.is_empty() {
+                for decoded_batch in pool.send(std::mem::take(&mut batch))? {
+                    self.process_batch(decoded_batch, &mut counter, tracker, progress)?;
+                }
+            }
+
+            if !has_record {
+                break;
             }
+        }
+
+        for decoded_batch in pool.finish()? {
+            self.process_batch(decoded_batch, &mut counter, tracker, progress)?;
+        }
+
+        Ok(counter)
+    }
 
-            let row: Row = serde_json::from_slice(&buffer)?;
+    fn process_batch(
+        &mut self,
+        batch: DecodedBatch,
+        counter: &mut u64,
+        tracker: &mut ProgressTracker,
+        progress: &mut dyn FnMut(&Progress),
+    ) -> Result<(), Error> {
+        let mut rows = Vec::new();
 
+        for row in batch.rows {
             match row {
                 Row::Metadata(row) => {
-                    self.process_metadata(&row)?;
+                    self.process_metadata(row)?;
+                    tracker.progress.total_rows = self.expected_count;
                 }
                 Row::KeyValue(row) => {
-                    self.process_key_value_row(row)?;
-                    counter += 1;
-                    progress(counter);
+                    self.check_header()?;
+                    *counter += 1;
+
+                    if *counter > self.skip_count {
+                        rows.push(row);
+                    }
                 }
                 Row::Eof => {
                     self.process_eof_row()?;
@@ -173,12 +498,110 @@ impl<'a, R: BufRead> ImportReader<'a, R> {
             }
         }
 
-        self.database.flush()?;
-        self.validate_footer()?;
+        if let Some(database) = self.database.as_mut() {
+            if !rows.is_empty() {
+                // Stable sort keeps the last of duplicate keys last
+                rows.sort_by(|row_1, row_2| row_1.key.cmp(&row_2.key));
+
+                let row_count = rows.len() as u64;
+                let mut bytes = 0;
+
+                for row in rows {
+                    bytes += (row.key.len() + row.value.len()) as u64;
+                    database.put(row.key, row.value)?;
+                }
+
+                database.set_import_progress(Some((*counter, batch.end_offset)))?;
+                self.flush_periodically(row_count, bytes)?;
+            }
+        }
+
+        progress(tracker.update(*counter, batch.end_offset));
 
         Ok(())
     }
 
+    fn read_binary_signature(&mut self) -> Result<bool, Error> {
+        match self.input_file.fill_buf()?.first() {
+            None | Some(&RECORD_SEPARATOR) => Ok(false),
+            Some(_) => {
+                let mut signature = [0u8; BINARY_SIGNATURE.len()];
+                self.input_file.read_exact(&mut signature)?;
+
+                if signature == BINARY_SIGNATURE {
+                    self.offset += signature.len() as u64;
+                    Ok(true)
+                } else {
+                    Err(ExportError::MissingRecordSeparator.into())
+                }
+            }
+        }
+    }
+
+    fn read_row(&mut self, buffer: &mut Vec<u8>) -> Result<Option<Row>, Error> {
+        if self.read_record(buffer)? {
+            Ok(Some(decode_record(buffer, self.binary)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Read the data of the next record without the separator or length
+    fn read_record(&mut self, buffer: &mut Vec<u8>) -> Result<bool, Error> {
+        buffer.clear();
+
+        if self.binary {
+            let length = match self.read_record_length()? {
+                Some(length) => length,
+                None => return Ok(false),
+            };
+
+            if length > MAX_BINARY_RECORD_LENGTH {
+                return Err(ExportError::RecordTooLarge.into());
+            }
+
+            let read_length = self.input_file.take(length as u64).read_to_end(buffer)?;
+
+            if read_length != length as usize {
+                return Err(ExportError::UnexpectedEof.into());
+            }
+
+            self.offset += 4 + length as u64;
+        } else {
+            if !self.read_record_separator()? {
+                return Ok(false);
+            }
+
+            self.input_file.read_until(NEWLINE, buffer)?;
+
+            if buffer.last().cloned().unwrap_or(0) != NEWLINE {
+                return Err(ExportError::UnexpectedEof.into());
+            }
+
+            self.offset += 1 + buffer.len() as u64;
+        }
+
+        Ok(true)
+    }
+
+    fn read_record_length(&mut self) -> Result<Option<u32>, Error> {
+        let mut length = [0u8; 4];
+
+        if self.input_file.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        if let Err(error) = self.input_file.read_exact(&mut length) {
+            if let std::io::ErrorKind::UnexpectedEof = error.kind() {
+                return Err(ExportError::UnexpectedEof.into());
+            } else {
+                return Err(error.into());
+            }
+        }
+
+        Ok(Some(u32::from_be_bytes(length)))
+    }
+
     fn read_record_separator(&mut self) -> Result<bool, Error> {
         let mut record_flag = [0u8; 1];
 
@@ -197,42 +620,91 @@ impl<'a, R: BufRead> ImportReader<'a, R> {
         }
     }
 
-    fn process_metadata(&mut self, _row: &MetadataRow) -> Result<(), Error> {
-        if self.header_found {
+    fn process_metadata(&mut self, row: MetadataRow) -> Result<(), Error> {
+        if self.header_row.is_some() {
             return Err(ExportError::DuplicateHeader.into());
         }
 
-        self.header_found = true;
+        if row.keys_only || row.key_encoding.is_some() || row.value_encoding.is_some() {
+            return Err(ExportError::NotImportable.into());
+        }
+
+        // Incremental exports contain only some of the pairs
+        if row.since_revision.is_none() {
+            self.expected_count = Some(row.key_value_count);
+        }
+
+        if let (Some(database), Some(source_uuid)) = (self.database.as_mut(), &row.uuid) {
+            Self::check_source(database, source_uuid)?;
+            database.set_import_source(source_uuid.clone())?;
+        }
+
+        self.header_row = Some(row);
 
         Ok(())
     }
 
-    fn process_key_value_row(&mut self, row: KeyValueRow) -> Result<(), Error> {
-        if !self.header_found {
-            return Err(ExportError::HeaderNotFound.into());
+    fn check_source(database: &Database<F>, source_uuid: &str) -> Result<(), Error> {
+        let metadata = database.metadata();
+
+        if metadata.key_value_count() == 0
+            || metadata.import_source() == Some(source_uuid)
+            || database.uuid().to_string() == source_uuid
+        {
+            Ok(())
+        } else {
+            Err(ExportError::SourceMismatch {
+                source_uuid: source_uuid.to_string(),
+            }
+            .into())
         }
+    }
 
-        let key_crc = crc32c::crc32c(&row.key);
+    fn check_header(&self) -> Result<(), Error> {
+        if self.header_row.is_none() {
+            Err(ExportError::HeaderNotFound.into())
+        } else {
+            Ok(())
+        }
+    }
 
-        if key_crc != row.key_crc32c {
-            return Err(ExportError::BadChecksum {
-                column: "key",
-                row: row.index,
-            }
-            .into());
+    fn process_key_value_row(&mut self, row: KeyValueRow, counter: u64) -> Result<(), Error> {
+        self.check_header()?;
+        verify_checksums(&row)?;
+
+        if counter <= self.skip_count {
+            return Ok(());
         }
 
-        let value_crc = crc32c::crc32c(&row.value);
+        if let Some(database) = self.database.as_mut() {
+            let bytes = (row.key.len() + row.value.len()) as u64;
 
-        if value_crc != row.value_crc32c {
-            return Err(ExportError::BadChecksum {
-                column: "value",
-                row: row.index,
-            }
-            .into());
+            database.put(row.key, row.value)?;
+            database.set_import_progress(Some((counter, self.offset)))?;
+            self.flush_periodically(1, bytes)?;
         }
 
-        self.database.put(row.key, row.value)?;
+        Ok(())
+    }
+
+    // Flushes the stored pairs together with the import progress once
+    // either limit is reached
+    fn flush_periodically(&mut self, rows: u64, bytes: u64) -> Result<(), Error> {
+        self.unflushed_rows += rows;
+        self.unflushed_bytes += bytes;
+
+        let rows_exceeded = matches!(self.flush_rows, Some(limit) if self.unflushed_rows >= limit);
+        let bytes_exceeded =
+            matches!(self.flush_bytes, Some(limit) if self.unflushed_bytes >= limit);
+
+        if rows_exceeded || bytes_exceeded {
+            if let Some(database) = self.database.as_mut() {
+                database.flush()?;
+            }
+
+            self.unflushed_rows = 0;
+            self.unflushed_bytes = 0;
+        }
 
         Ok(())
     }
@@ -247,61 +719,174 @@ impl<'a, R: BufRead> ImportReader<'a, R> {
         Ok(())
     }
 
-    fn validate_footer(&self) -> Result<(), Error> {
+    fn validate_footer(&self, row_count: u64) -> Result<(), Error> {
         if !self.footer_found {
-            Err(ExportError::FooterNotFound.into())
-        } else {
-            Ok(())
+            return Err(ExportError::FooterNotFound.into());
+        }
+
+        match self.expected_count {
+            Some(expected) if expected != row_count => Err(ExportError::RowCountMismatch {
+                expected,
+                actual: row_count,
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn decode_record(data: &[u8], binary: bool) -> Result<Row, Error> {
+    if binary {
+        let mut deserializer = rmp_serde::Deserializer::new(data).with_binary();
+
+        match Row::deserialize(&mut deserializer) {
+            Ok(row) => Ok(row),
+            Err(error) => Err(Error::Other(Box::new(error))),
+        }
+    } else {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+fn verify_checksums(row: &KeyValueRow) -> Result<(), Error> {
+    if crc32c::crc32c(&row.key) != row.key_crc32c {
+        return Err(ExportError::BadChecksum {
+            column: "key",
+            row: row.index,
+        }
+        .into());
+    }
+
+    if crc32c::crc32c(&row.value) != row.value_crc32c {
+        return Err(ExportError::BadChecksum {
+            column: "value",
+            row: row.index,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct RecordBatch {
+    records: Vec<Vec<u8>>,
+    bytes: usize,
+    end_offset: u64,
+}
+
+struct DecodedBatch {
+    rows: Vec<Row>,
+    end_offset: u64,
+}
+
+fn decode_batch(batch: RecordBatch, binary: bool) -> Result<DecodedBatch, Error> {
+    let mut rows = Vec::with_capacity(batch.records.len());
+
+    for record in batch.records {
+        let row = decode_record(&record, binary)?;
+
+        if let Row::KeyValue(row) = &row {
+            verify_checksums(row)?;
         }
+
+        rows.push(row);
     }
+
+    Ok(DecodedBatch {
+        rows,
+        end_offset: batch.end_offset,
+    })
 }
 
-struct ExportWriter<'a, W: Write> {
-    database: Option<&'a mut Database>,
+struct ExportWriter<'a, W: Write, F: Vfs> {
+    database: Option<&'a mut Database<F>>,
     counter: u64,
     output_file: &'a mut W,
+    binary: bool,
+    buffer: Vec<u8>,
+    since_revision: Option<u64>,
+    bytes: u64,
+    threads: usize,
+    listing: Option<ListingOptions>,
+}
+
+#[derive(Clone, Copy)]
+struct ListingOptions {
+    keys_only: bool,
+    key_encoding: ByteEncoding,
+    value_encoding: ByteEncoding,
 }
 
-impl<'a, W: Write> ExportWriter<'a, W> {
-    fn new(output_file: &'a mut W, database: &'a mut Database) -> Self {
+impl<'a, W: Write, F: Vfs> ExportWriter<'a, W, F> {
+    fn new(output_file: &'a mut W, database: &'a mut Database<F>, binary: bool) -> Self {
         Self {
             database: Some(database),
             counter: 0,
             output_file,
+            binary,
+            buffer: Vec::new(),
+            since_revision: None,
+            bytes: 0,
+            threads: 0,
+            listing: None,
         }
     }
 
     fn export<C>(&mut self, mut progress: C) -> Result<(), Error>
     where
-        C: FnMut(u64),
+        C: FnMut(&Progress),
     {
+        self.set_pinned(true);
+        let result = self.export_(&mut progress);
+        self.set_pinned(false);
+
+        result
+    }
+
+    fn export_(&mut self, progress: &mut dyn FnMut(&Progress)) -> Result<(), Error> {
+        if self.binary {
+            self.output_file.write_all(&BINARY_SIGNATURE)?;
+            self.bytes += BINARY_SIGNATURE.len() as u64;
+        }
+
         self.write_header()?;
-        self.write_key_values(&mut progress)?;
+        self.write_key_values(progress)?;
         self.write_footer()?;
 
         Ok(())
     }
 
+    fn set_pinned(&mut self, pinned: bool) {
+        if let Some(database) = self.database.as_mut() {
+            database.set_pinned(pinned);
+        }
+    }
+
     fn write_row<T>(&mut self, row: T) -> Result<(), Error>
     where
         T: Serialize,
     {
-        self.output_file.write_all(&[RECORD_SEPARATOR])?;
-
-        let mut serializer = serde_json::Serializer::new(&mut self.output_file);
-        row.serialize(&mut serializer)?;
-
-        self.output_file.write_all(&[NEWLINE])?;
+        self.buffer.clear();
+        encode_row(&row, self.binary, &mut self.buffer)?;
+        self.output_file.write_all(&self.buffer)?;
+        self.bytes += self.buffer.len() as u64;
 
         Ok(())
     }
 
     fn write_header(&mut self) -> Result<(), Error> {
         let database = self.database.take().unwrap();
+        let mut header_row = header_row(database, self.since_revision)?;
 
-        let header_row = MetadataRow {
-            key_value_count: database.metadata().key_value_count(),
-        };
+        if let Some(listing) = self.listing {
+            header_row.keys_only = listing.keys_only;
+            header_row.key_encoding = Some(listing.key_encoding.name().to_string());
+
+            if !listing.keys_only {
+                header_row.value_encoding = Some(listing.value_encoding.name().to_string());
+            }
+        }
 
         self.write_row(Row::Metadata(header_row))?;
 
@@ -314,66 +899,1429 @@ impl<'a, W: Write> ExportWriter<'a, W> {
         self.write_row(Row::Eof)
     }
 
-    fn write_key_values(&mut self, progress: &mut dyn FnMut(u64)) -> Result<(), Error> {
+    fn write_key_values(&mut self, progress: &mut dyn FnMut(&Progress)) -> Result<(), Error> {
         let database = self.database.take().unwrap();
-        let mut cursor = database.cursor()?;
 
-        loop {
-            let mut row = KeyValueRow::default();
-            let has_item = cursor.next_buf(&mut row.key, &mut row.value)?;
+        let result = if self.threads == 0 {
+            self.write_key_values_(database, None, progress)
+        } else {
+            let threads = self.threads;
+            let binary = self.binary;
 
-            if !has_item {
-                break;
-            }
+            std::thread::scope(|scope| {
+                let encoder = ParallelEncoder::new(scope, threads, binary);
+                self.write_key_values_(database, Some(encoder), progress)
+            })
+        };
 
-            row.index = self.counter;
-            row.key_crc32c = crc32c::crc32c(&row.key);
-            row.value_crc32c = crc32c::crc32c(&row.value);
-            self.counter += 1;
+        self.database = Some(database);
+
+        result
+    }
+
+    fn write_key_values_(
+        &mut self,
+        database: &mut Database<F>,
+        mut encoder: Option<ParallelEncoder>,
+        progress: &mut dyn FnMut(&Progress),
+    ) -> Result<(), Error> {
+        let mut tracker = if self.since_revision.is_some() {
+            ProgressTracker::new(None)
+        } else {
+            ProgressTracker::new(Some(database.metadata().key_value_count()))
+        };
+
+        if let Some(since_revision) = self.since_revision {
+            database.for_each_changed_pair(since_revision, |key, value| {
+                let row = KeyValueRow {
+                    key: key.to_vec(),
+                    value: value.to_vec(),
+                    ..Default::default()
+                };
+                let rows = self.write_key_value_row(row, encoder.as_mut())?;
+                progress(tracker.update(rows, self.bytes));
+
+                Ok(())
+            })?;
+        } else {
+            let mut cursor = database.cursor()?;
+
+            loop {
+                let mut row = KeyValueRow::default();
+                let has_item = cursor.next_buf(&mut row.key, &mut row.value)?;
 
-            self.write_row(Row::KeyValue(row))?;
+                if !has_item {
+                    break;
+                }
 
-            progress(self.counter);
+                let rows = self.write_key_value_row(row, encoder.as_mut())?;
+                progress(tracker.update(rows, self.bytes));
+            }
         }
 
-        self.database = Some(database);
+        if let Some(encoder) = encoder {
+            for buffer in encoder.finish()? {
+                self.output_file.write_all(&buffer)?;
+                self.bytes += buffer.len() as u64;
+            }
+
+            progress(tracker.update(self.counter, self.bytes));
+        }
 
         Ok(())
     }
+
+    // Returns the number of rows written
+    fn write_key_value_row(
+        &mut self,
+        mut row: KeyValueRow,
+        encoder: Option<&mut ParallelEncoder>,
+    ) -> Result<u64, Error> {
+        row.index = self.counter;
+        self.counter += 1;
+
+        if let Some(listing) = self.listing {
+            self.write_row(listing_row(&row, listing))?;
+
+            return Ok(self.counter);
+        }
+
+        match encoder {
+            Some(encoder) => {
+                for buffer in encoder.push(row)? {
+                    self.output_file.write_all(&buffer)?;
+                    self.bytes += buffer.len() as u64;
+                }
+
+                Ok(encoder.written_rows)
+            }
+            None => {
+                add_checksums(&mut row);
+                self.write_row(Row::KeyValue(row))?;
+
+                Ok(self.counter)
+            }
+        }
+    }
+}
+
+fn listing_row(row: &KeyValueRow, listing: ListingOptions) -> ListingRow {
+    let mut key = String::new();
+    encode_bytes(&row.key, listing.key_encoding, &mut key);
+
+    let (value, value_crc32c) = if listing.keys_only {
+        (None, None)
+    } else {
+        let mut value = String::new();
+        encode_bytes(&row.value, listing.value_encoding, &mut value);
+
+        (Some(value), Some(crc32c::crc32c(&row.value)))
+    };
+
+    ListingRow::KeyValue(ListingKeyValueRow {
+        key,
+        value,
+        index: row.index,
+        key_crc32c: crc32c::crc32c(&row.key),
+        value_crc32c,
+    })
+}
+
+fn add_checksums(row: &mut KeyValueRow) {
+    row.key_crc32c = crc32c::crc32c(&row.key);
+    row.value_crc32c = crc32c::crc32c(&row.value);
+}
+
+fn encode_batch(rows: Vec<KeyValueRow>, binary: bool) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+
+    for mut row in rows {
+        add_checksums(&mut row);
+        encode_row(&Row::KeyValue(row), binary, &mut buffer)?;
+    }
+
+    Ok(buffer)
+}
+
+// Encodes batches of rows on worker threads
+struct ParallelEncoder {
+    pool: WorkerPool<Vec<KeyValueRow>, (usize, Vec<u8>)>,
+    batch: Vec<KeyValueRow>,
+    batch_bytes: usize,
+    written_rows: u64,
+}
+
+impl ParallelEncoder {
+    fn new<'scope>(scope: &'scope Scope<'scope, '_>, threads: usize, binary: bool) -> Self {
+        let pool = WorkerPool::new(scope, threads, move |rows: Vec<KeyValueRow>| {
+            let row_count = rows.len();
+            encode_batch(rows, binary).map(|buffer| (row_count, buffer))
+        });
+
+        Self {
+            pool,
+            batch: Vec::new(),
+            batch_bytes: 0,
+            written_rows: 0,
+        }
+    }
+
+    // Returns the encoded batches that are ready to be written
+    fn push(&mut self, row: KeyValueRow) -> Result<Vec<Vec<u8>>, Error> {
+        self.batch_bytes += row.key.len() + row.value.len();
+        self.batch.push(row);
+
+        if self.batch.len() < BATCH_ROWS && self.batch_bytes < BATCH_BYTES {
+            return Ok(Vec::new());
+        }
+
+        self.batch_bytes = 0;
+        let results = self.pool.send(std::mem::take(&mut self.batch))?;
+
+        Ok(self.count_rows(results))
+    }
+
+    fn finish(mut self) -> Result<Vec<Vec<u8>>, Error> {
+        let mut results = Vec::new();
+
+        if !self.batch.is_empty() {
+            results = self.pool.send(std::mem::take(&mut self.batch))?;
+        }
+
+        results.extend(self.pool.finish()?);
+
+        Ok(self.count_rows(results))
+    }
+
+    fn count_rows(&mut self, results: Vec<(usize, Vec<u8>)>) -> Vec<Vec<u8>> {
+        results
+            .into_iter()
+            .map(|(row_count, buffer)| {
+                self.written_rows += row_count as u64;
+                buffer
+            })
+            .collect()
+    }
+}
+
+// Runs jobs on worker threads and returns the results in the same order as
+// the jobs were sent. The number of pending jobs is limited to bound memory
+// usage when the workers are slower than the caller.
+struct WorkerPool<J, T> {
+    job_sender: mpsc::Sender<(u64, J)>,
+    result_receiver: mpsc::Receiver<(u64, Result<T, Error>)>,
+    max_pending_count: u64,
+    sent_count: u64,
+    returned_count: u64,
+    finished: BTreeMap<u64, Result<T, Error>>,
+}
+
+impl<J: Send, T: Send> WorkerPool<J, T> {
+    fn new<'scope, P>(scope: &'scope Scope<'scope, '_>, threads: usize, process: P) -> Self
+    where
+        J: 'scope,
+        T: 'scope,
+        P: Fn(J) -> Result<T, Error> + Clone + Send + 'scope,
+    {
+        let (job_sender, job_receiver) = mpsc::channel::<(u64, J)>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        for _ in 0..threads {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let process = process.clone();
+
+            scope.spawn(move || loop {
+                let job = job_receiver.lock().unwrap().recv();
+                let (index, job) = match job {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+
+                if result_sender.send((index, process(job))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            job_sender,
+            result_receiver,
+            max_pending_count: threads as u64 * 2,
+            sent_count: 0,
+            returned_count: 0,
+            finished: BTreeMap::new(),
+        }
+    }
+
+    // Returns the results that are ready
+    fn send(&mut self, job: J) -> Result<Vec<T>, Error> {
+        // Workers only stop after the sender is dropped
+        self.job_sender.send((self.sent_count, job)).unwrap();
+        self.sent_count += 1;
+
+        let mut results = Vec::new();
+
+        while self.sent_count - self.returned_count >= self.max_pending_count {
+            self.receive(true, &mut results)?;
+        }
+
+        self.receive(false, &mut results)?;
+
+        Ok(results)
+    }
+
+    fn finish(&mut self) -> Result<Vec<T>, Error> {
+        let mut results = Vec::new();
+
+        while self.returned_count < self.sent_count {
+            self.receive(true, &mut results)?;
+        }
+
+        Ok(results)
+    }
+
+    fn receive(&mut self, block: bool, results: &mut Vec<T>) -> Result<(), Error> {
+        if block {
+            let (index, result) = self
+                .result_receiver
+                .recv()
+                .map_err(|error| Error::Other(Box::new(error)))?;
+            self.finished.insert(index, result);
+        }
+
+        while let Ok((index, result)) = self.result_receiver.try_recv() {
+            self.finished.insert(index, result);
+        }
+
+        while let Some(result) = self.finished.remove(&self.returned_count) {
+            results.push(result?);
+            self.returned_count += 1;
+        }
+
+        Ok(())
+    }
+}
+
+fn header_row<F: Vfs>(
+    database: &Database<F>,
+    since_revision: Option<u64>,
+) -> Result<MetadataRow, Error> {
+    let uuid = database.uuid();
+
+    Ok(MetadataRow {
+        key_value_count: database.metadata().key_value_count(),
+        since_revision,
+        uuid: if uuid.is_nil() {
+            None
+        } else {
+            Some(uuid.to_string())
+        },
+        revision: Some(database.revision()),
+        options_digest: Some(options_digest(database.options())?),
+        ..Default::default()
+    })
+}
+
+// Append a complete record, including its separator or length prefix
+fn encode_row<T>(row: &T, binary: bool, buffer: &mut Vec<u8>) -> Result<(), Error>
+where
+    T: Serialize,
+{
+    if !binary {
+        buffer.push(RECORD_SEPARATOR);
+
+        let mut serializer = serde_json::Serializer::new(&mut *buffer);
+        row.serialize(&mut serializer)?;
+
+        buffer.push(NEWLINE);
+
+        return Ok(());
+    }
+
+    let start = buffer.len();
+    buffer.extend_from_slice(&[0u8; 4]);
+
+    let mut serializer = rmp_serde::Serializer::new(&mut *buffer)
+        .with_binary()
+        .with_string_variants()
+        .with_struct_map();
+
+    if let Err(error) = row.serialize(&mut serializer) {
+        return Err(Error::Other(Box::new(error)));
+    }
+
+    let length: u32 = match (buffer.len() - start - 4).try_into() {
+        Ok(length) if length <= MAX_BINARY_RECORD_LENGTH => length,
+        _ => return Err(ExportError::RecordTooLarge.into()),
+    };
+
+    buffer[start..start + 4].copy_from_slice(&length.to_be_bytes());
+
+    Ok(())
+}
+
+// Checksum of the options that affect how the database is stored.
+// Options of the current session, such as the open mode, are excluded.
+fn options_digest(options: &Options) -> Result<String, Error> {
+    let options = Options {
+        open_mode: OpenMode::default(),
+        ..options.clone()
+    };
+    let data = serde_json::to_vec(&options)?;
+
+    Ok(data_encoding::HEXUPPER.encode(&crc32c::crc32c(&data).to_be_bytes()))
 }
 
 /// Import key-value pairs from the given source file into the database.
 ///
-/// The provided progress callback will be called with the number of pairs
+/// The file can be in either the JSON text sequence or the binary format.
+///
+/// If the database is not empty, the file must be exported from the same
+/// database as the previous import or from the database itself, otherwise
+/// [`ExportError::SourceMismatch`] is returned. The number of pairs read is
+/// verified against the header to detect truncated files.
+///
+/// The provided progress callback will be called after each pair is
 /// processed.
 ///
+/// The number of pairs applied is recorded in the database metadata as the
+/// pairs are stored, so an interrupted import can be continued with
+/// [`import_resume()`].
+///
 /// It is the caller's responsibility to call [`Database::flush()`] after
 /// the function completes.
-pub fn import<R, C>(database: &mut Database, input_file: &mut R, progress: C) -> Result<(), Error>
+pub fn import<R, C, F>(
+    database: &mut Database<F>,
+    input_file: &mut R,
+    progress: C,
+) -> Result<(), Error>
 where
-    C: FnMut(u64),
+    C: FnMut(&Progress),
     R: BufRead,
+    F: Vfs,
 {
-    let mut reader = ImportReader::new(input_file, database);
+    let mut reader = ImportReader::new(input_file, Some(database));
     reader.import(progress)?;
 
     Ok(())
 }
 
+/// Continue an interrupted import from the start of the same source file.
+///
+/// The pairs recorded as applied by the previous [`import()`] in
+/// [`crate::Metadata::import_progress()`] are read and verified but not
+/// stored again. Because the progress is saved together with the pairs on
+/// each flush, pairs stored after the last flush are applied again. If no
+/// import is in progress, all pairs are imported.
+///
+/// The provided progress callback will be called after each pair is
+/// processed, including skipped pairs.
+pub fn import_resume<R, C, F>(
+    database: &mut Database<F>,
+    input_file: &mut R,
+    progress: C,
+) -> Result<(), Error>
+where
+    C: FnMut(&Progress),
+    R: BufRead,
+    F: Vfs,
+{
+    let options = ImportOptions {
+        resume: true,
+        ..Default::default()
+    };
+
+    import_with_options(database, input_file, &options, progress)
+}
+
+/// Options for [`import_with_options()`].
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Whether to continue an interrupted import like [`import_resume()`].
+    ///
+    /// Default: false.
+    pub resume: bool,
+
+    /// Number of worker threads that decode and verify the records.
+    ///
+    /// When greater than 0, the calling thread reads the records from the
+    /// file and stores the pairs while the worker threads decode them in
+    /// batches. The pairs of each batch are stored in key order. When 0,
+    /// records are decoded on the calling thread.
+    ///
+    /// Default: 0.
+    pub threads: usize,
+
+    /// Flush the database after storing this number of pairs.
+    ///
+    /// Periodic flushes limit the memory used by modified pages and save the
+    /// import progress, so an interrupted import can be continued with
+    /// [`Self::resume`] without storing all pairs again. If worker threads
+    /// are used, the database is flushed after the batch that reaches the
+    /// limit.
+    ///
+    /// Default: None (flush only when the import completes).
+    pub flush_rows: Option<u64>,
+
+    /// Flush the database after storing this number of key and value bytes.
+    ///
+    /// Like [`Self::flush_rows`] but limited by the size of the pairs. The
+    /// database is flushed when either limit is reached.
+    ///
+    /// Default: None (flush only when the import completes).
+    pub flush_bytes: Option<u64>,
+}
+
+/// Import key-value pairs from the given source file with the given options.
+///
+/// The import is the same as [`import()`] or [`import_resume()`]. If worker
+/// threads are used, the progress callback is called after each batch of
+/// pairs is stored and the progress is recorded in the database metadata
+/// for each batch instead of for each pair.
+pub fn import_with_options<R, C, F>(
+    database: &mut Database<F>,
+    input_file: &mut R,
+    options: &ImportOptions,
+    progress: C,
+) -> Result<(), Error>
+where
+    C: FnMut(&Progress),
+    R: BufRead,
+    F: Vfs,
+{
+    let skip_count = if options.resume {
+        database
+            .metadata()
+            .import_progress()
+            .map(|(count, _offset)| count)
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    let mut reader = ImportReader::new(input_file, Some(database));
+    reader.skip_count = skip_count;
+    reader.threads = options.threads;
+    reader.flush_rows = options.flush_rows;
+    reader.flush_bytes = options.flush_bytes;
+    reader.import(progress)?;
+
+    Ok(())
+}
+
+/// Summary of an export file returned by [`validate()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    key_value_count: u64,
+    binary: bool,
+    bytes: u64,
+    since_revision: Option<u64>,
+    uuid: Option<String>,
+    revision: Option<u64>,
+}
+
+impl ExportSummary {
+    /// Return the number of key-value pairs in the file.
+    pub fn key_value_count(&self) -> u64 {
+        self.key_value_count
+    }
+
+    /// Return whether the file is in the binary format.
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    /// Return the size of the file in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Return the revision that an incremental export started from.
+    ///
+    /// Returns `None` if the file contains all key-value pairs.
+    pub fn since_revision(&self) -> Option<u64> {
+        self.since_revision
+    }
+
+    /// Return the UUID of the exported database, if recorded.
+    pub fn uuid(&self) -> Option<&str> {
+        self.uuid.as_deref()
+    }
+
+    /// Return the revision of the exported database, if recorded.
+    pub fn revision(&self) -> Option<u64> {
+        self.revision
+    }
+}
+
+/// Check the given export file without importing it.
+///
+/// The whole file is read and the record separators, checksums, header,
+/// footer, and number of pairs are verified in the same way as [`import()`],
+/// but nothing is written. This allows checking a backup before restoring
+/// it.
+pub fn validate<R>(input_file: &mut R) -> Result<ExportSummary, Error>
+where
+    R: BufRead,
+{
+    let mut reader = ImportReader::<R, MemoryVfs>::new(input_file, None);
+    let key_value_count = reader.import(|_| {})?;
+    let header_row = reader.header_row.take().unwrap_or_default();
+
+    Ok(ExportSummary {
+        key_value_count,
+        binary: reader.binary,
+        bytes: reader.offset,
+        since_revision: header_row.since_revision,
+        uuid: header_row.uuid,
+        revision: header_row.revision,
+    })
+}
+
 /// Export key-value pairs from the database to the destination file.
 ///
-/// The provided progress callback will be called with the number of pairs
+/// The file contains the pairs as of the revision of the database when the
+/// export started. When exporting from a database opened in read-only mode
+/// while another process continues writing, pages replaced by the writer
+/// are read from the files kept for previous generations. The writer must
+/// keep enough [`crate::Options::metadata_generations`] for the duration of
+/// the export, otherwise [`Error::ExternallyModified`] is returned instead
+/// of writing a file that mixes revisions.
+///
+/// The provided progress callback will be called after each pair is
+/// processed.
+///
+/// It is the caller's responsibility to ensure data has been persisted using
+/// functions such as `flush()` or `sync_data()`.
+pub fn export<W, C, F>(
+    database: &mut Database<F>,
+    output_file: &mut W,
+    progress: C,
+) -> Result<(), Error>
+where
+    W: Write,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut writer = ExportWriter::new(output_file, database, false);
+    writer.export(progress)?;
+
+    Ok(())
+}
+
+/// Export key-value pairs from the database to the destination file in the
+/// binary format.
+///
+/// The file contains the same records as [`export()`] encoded as
+/// length-prefixed MessagePack, which is smaller and faster to process.
+///
+/// The provided progress callback will be called after each pair is
 /// processed.
 ///
 /// It is the caller's responsibility to ensure data has been persisted using
 /// functions such as `flush()` or `sync_data()`.
-pub fn export<W, C>(database: &mut Database, output_file: &mut W, progress: C) -> Result<(), Error>
+pub fn export_binary<W, C, F>(
+    database: &mut Database<F>,
+    output_file: &mut W,
+    progress: C,
+) -> Result<(), Error>
+where
+    W: Write,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut writer = ExportWriter::new(output_file, database, true);
+    writer.export(progress)?;
+
+    Ok(())
+}
+
+/// Options for [`export_with_options()`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Whether to write the binary format instead of the JSON text sequence
+    /// format.
+    ///
+    /// Default: false.
+    pub binary: bool,
+
+    /// Number of worker threads that checksum and encode the records.
+    ///
+    /// When greater than 0, the calling thread reads the pairs from the
+    /// database and writes the encoded records in order while the worker
+    /// threads encode them in batches. This is faster for large values
+    /// because encoding takes most of the export time. When 0, records are
+    /// encoded on the calling thread.
+    ///
+    /// Default: 0.
+    pub threads: usize,
+
+    /// Whether to write only the keys without the values.
+    ///
+    /// The file is a listing that cannot be imported.
+    ///
+    /// Default: false.
+    pub keys_only: bool,
+
+    /// Encoding of keys in the JSON text sequence format.
+    ///
+    /// If not hex, the file is a listing that cannot be imported.
+    ///
+    /// Default: Hex.
+    pub key_encoding: ByteEncoding,
+
+    /// Encoding of values in the JSON text sequence format.
+    ///
+    /// If not hex, the file is a listing that cannot be imported.
+    ///
+    /// Default: Hex.
+    pub value_encoding: ByteEncoding,
+}
+
+impl ExportOptions {
+    fn listing(&self) -> Option<ListingOptions> {
+        if self.keys_only
+            || self.key_encoding != ByteEncoding::Hex
+            || self.value_encoding != ByteEncoding::Hex
+        {
+            Some(ListingOptions {
+                keys_only: self.keys_only,
+                key_encoding: self.key_encoding,
+                value_encoding: self.value_encoding,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Export key-value pairs from the database with the given options.
+///
+/// The file is the same as written by [`export()`] or [`export_binary()`]
+/// unless the options select a listing. A listing has the same structure
+/// with the keys and values encoded as text as specified, and the header
+/// records the options. It is intended for viewing and is rejected by the
+/// import functions with [`ExportError::NotImportable`]. Listings are
+/// written only in the JSON text sequence format and on the calling
+/// thread.
+///
+/// The provided progress callback will be called after each pair is
+/// processed. If worker threads are used, the number of rows reported
+/// increases as batches of records are written.
+///
+/// It is the caller's responsibility to ensure data has been persisted using
+/// functions such as `flush()` or `sync_data()`.
+pub fn export_with_options<W, C, F>(
+    database: &mut Database<F>,
+    output_file: &mut W,
+    options: &ExportOptions,
+    progress: C,
+) -> Result<(), Error>
+where
+    W: Write,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let listing = options.listing();
+
+    if listing.is_some() && options.binary {
+        return Err(Error::InvalidConfig {
+            message: "listing in binary format",
+        });
+    }
+
+    let mut writer = ExportWriter::new(output_file, database, options.binary);
+    writer.listing = listing;
+    writer.threads = if listing.is_some() {
+        0
+    } else {
+        options.threads
+    };
+    writer.export(progress)?;
+
+    Ok(())
+}
+
+/// Export the key-value pairs changed since the previous incremental export.
+///
+/// The database is flushed and the pairs of the nodes saved after the
+/// revision recorded by the previous call are written in the JSON text
+/// sequence format. The first call exports all pairs. The current revision
+/// is then recorded in the database metadata and flushed, so the database
+/// must not be read-only.
+///
+/// Because a node is exported as a whole, the file may include unchanged
+/// pairs. Removed keys are not recorded, so importing the files in order
+/// into a copy restores all current pairs but not removals.
+///
+/// The provided progress callback will be called after each pair is
+/// processed.
+pub fn export_incremental<W, C, F>(
+    database: &mut Database<F>,
+    output_file: &mut W,
+    progress: C,
+) -> Result<(), Error>
 where
     W: Write,
-    C: FnMut(u64),
+    C: FnMut(&Progress),
+    F: Vfs,
 {
-    let mut writer = ExportWriter::new(output_file, database);
+    database.flush()?;
+
+    let since_revision = database.metadata().last_export_revision().unwrap_or(0);
+    let revision = database.revision();
+
+    let mut writer = ExportWriter::new(output_file, database, false);
+    writer.since_revision = Some(since_revision);
     writer.export(progress)?;
 
+    database.set_last_export_revision(revision)?;
+    database.flush()?;
+
+    Ok(())
+}
+
+/// Return an iterator of the records of an export file.
+///
+/// Each item is a complete record of the JSON text sequence format as
+/// written by [`export()`], including the header and footer records, so the
+/// items can be sent to a destination other than a file. The iterator also
+/// implements [`Read`] for use with functions such as [`std::io::copy()`].
+///
+/// Like [`export()`], the records contain the pairs as of the revision when
+/// the iterator was created.
+pub fn rows<F>(database: &mut Database<F>) -> Result<ExportRows<'_, F>, Error>
+where
+    F: Vfs,
+{
+    ExportRows::new(database, false)
+}
+
+/// Return an iterator of the records of an export file in the binary
+/// format.
+///
+/// The first item also contains the signature of the file. See [`rows()`].
+pub fn rows_binary<F>(database: &mut Database<F>) -> Result<ExportRows<'_, F>, Error>
+where
+    F: Vfs,
+{
+    ExportRows::new(database, true)
+}
+
+/// Iterator of encoded export records returned by [`rows()`].
+pub struct ExportRows<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    cursor: Cursor<'a, F>,
+    binary: bool,
+    header_row: Option<MetadataRow>,
+    finished: bool,
+    counter: u64,
+    key: Vec<u8>,
+    value: Vec<u8>,
+    read_buffer: Vec<u8>,
+    read_position: usize,
+}
+
+impl<'a, F> ExportRows<'a, F>
+where
+    F: Vfs,
+{
+    fn new(database: &'a mut Database<F>, binary: bool) -> Result<Self, Error> {
+        let header_row = header_row(database, None)?;
+        let mut cursor = database.cursor()?;
+        cursor.set_pinned(true);
+
+        Ok(Self {
+            cursor,
+            binary,
+            header_row: Some(header_row),
+            finished: false,
+            counter: 0,
+            key: Vec::new(),
+            value: Vec::new(),
+            read_buffer: Vec::new(),
+            read_position: 0,
+        })
+    }
+
+    fn next_record(&mut self) -> Result<Vec<u8>, Error> {
+        let mut record = Vec::new();
+
+        if let Some(header_row) = self.header_row.take() {
+            if self.binary {
+                record.extend_from_slice(&BINARY_SIGNATURE);
+            }
+
+            encode_row(&Row::Metadata(header_row), self.binary, &mut record)?;
+        } else if self.cursor.next_buf(&mut self.key, &mut self.value)? {
+            let row = KeyValueRow {
+                key_crc32c: crc32c::crc32c(&self.key),
+                value_crc32c: crc32c::crc32c(&self.value),
+                key: std::mem::take(&mut self.key),
+                value: std::mem::take(&mut self.value),
+                index: self.counter,
+            };
+
+            encode_row(&Row::KeyValue(row), self.binary, &mut record)?;
+            self.counter += 1;
+        } else {
+            encode_row(&Row::Eof, self.binary, &mut record)?;
+            self.finished = true;
+        }
+
+        Ok(record)
+    }
+}
+
+impl<'a, F> Iterator for ExportRows<'a, F>
+where
+    F: Vfs,
+{
+    type Item = Result<Vec<u8>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.next_record() {
+            Ok(record) => Some(Ok(record)),
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+impl<'a, F> Read for ExportRows<'a, F>
+where
+    F: Vfs,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.read_position >= self.read_buffer.len() {
+            match self.next() {
+                Some(Ok(record)) => {
+                    self.read_buffer = record;
+                    self.read_position = 0;
+                }
+                Some(Err(Error::Io(error))) => return Err(error),
+                Some(Err(error)) => {
+                    #[allow(clippy::io_other_error)]
+                    return Err(std::io::Error::new(std::io::ErrorKind::Other, error));
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let length = buf.len().min(self.read_buffer.len() - self.read_position);
+        buf[..length]
+            .copy_from_slice(&self.read_buffer[self.read_position..self.read_position + length]);
+        self.read_position += length;
+
+        Ok(length)
+    }
+}
+
+impl<'a, F> Drop for ExportRows<'a, F>
+where
+    F: Vfs,
+{
+    fn drop(&mut self) {
+        self.cursor.set_pinned(false);
+    }
+}
+
+/// Compression of export files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportCompression {
+    /// No compression.
+    #[default]
+    None,
+
+    /// Zstandard compression with the given level.
+    ///
+    /// Levels range from 1 (fastest) to 22 (smallest). Requires the
+    /// `compression` feature.
+    Zstd(i32),
+}
+
+/// Writer that compresses an export file.
+///
+/// Pass the writer to the export functions and call [`Self::finish()`]
+/// afterwards to write the end of the compressed data.
+pub struct CompressedWriter<W: Write> {
+    inner: CompressedWriterInner<W>,
+}
+
+enum CompressedWriterInner<W: Write> {
+    None(W),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Wrap the given destination file.
+    pub fn new(output_file: W, compression: ExportCompression) -> Result<Self, Error> {
+        let inner = match compression {
+            ExportCompression::None => CompressedWriterInner::None(output_file),
+            #[cfg(feature = "zstd")]
+            ExportCompression::Zstd(level) => {
+                CompressedWriterInner::Zstd(zstd::Encoder::new(output_file, level)?)
+            }
+            #[cfg(not(feature = "zstd"))]
+            ExportCompression::Zstd(_) => return Err(Error::CompressionUnavailable),
+        };
+
+        Ok(Self { inner })
+    }
+
+    /// Finish the compressed data and return the destination file.
+    pub fn finish(self) -> Result<W, Error> {
+        match self.inner {
+            CompressedWriterInner::None(output_file) => Ok(output_file),
+            #[cfg(feature = "zstd")]
+            CompressedWriterInner::Zstd(encoder) => Ok(encoder.finish()?),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            CompressedWriterInner::None(output_file) => output_file.write(buf),
+            #[cfg(feature = "zstd")]
+            CompressedWriterInner::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.inner {
+            CompressedWriterInner::None(output_file) => output_file.flush(),
+            #[cfg(feature = "zstd")]
+            CompressedWriterInner::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+const ZSTD_MAGIC_NUMBER: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Reader that decompresses an export file.
+///
+/// The compression is detected from the start of the file, so uncompressed
+/// files are read as is.
+pub struct DecompressedReader<R: BufRead> {
+    inner: DecompressedReaderInner<R>,
+}
+
+enum DecompressedReaderInner<R: BufRead> {
+    None(R),
+    #[cfg(feature = "zstd")]
+    Zstd(std::io::BufReader<zstd::Decoder<'static, R>>),
+}
+
+impl<R: BufRead> DecompressedReader<R> {
+    /// Wrap the given source file.
+    ///
+    /// Returns [`Error::CompressionUnavailable`] if the file is compressed
+    /// and the `compression` feature is disabled.
+    pub fn new(mut input_file: R) -> Result<Self, Error> {
+        let compressed = input_file.fill_buf()?.starts_with(&ZSTD_MAGIC_NUMBER);
+
+        let inner = if !compressed {
+            DecompressedReaderInner::None(input_file)
+        } else {
+            #[cfg(feature = "zstd")]
+            {
+                DecompressedReaderInner::Zstd(std::io::BufReader::new(zstd::Decoder::with_buffer(
+                    input_file,
+                )?))
+            }
+            #[cfg(not(feature = "zstd"))]
+            {
+                return Err(Error::CompressionUnavailable);
+            }
+        };
+
+        Ok(Self { inner })
+    }
+
+    /// Return whether the file is compressed.
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self.inner, DecompressedReaderInner::None(_))
+    }
+}
+
+impl<R: BufRead> Read for DecompressedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            DecompressedReaderInner::None(input_file) => input_file.read(buf),
+            #[cfg(feature = "zstd")]
+            DecompressedReaderInner::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for DecompressedReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        match &mut self.inner {
+            DecompressedReaderInner::None(input_file) => input_file.fill_buf(),
+            #[cfg(feature = "zstd")]
+            DecompressedReaderInner::Zstd(decoder) => decoder.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match &mut self.inner {
+            DecompressedReaderInner::None(input_file) => input_file.consume(amt),
+            #[cfg(feature = "zstd")]
+            DecompressedReaderInner::Zstd(decoder) => decoder.consume(amt),
+        }
+    }
+}
+
+/// Text encoding of keys and values in CSV, TSV, and listing exports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteEncoding {
+    /// Uppercase hexadecimal.
+    #[default]
+    Hex,
+
+    /// Standard base64 with padding (RFC 4648).
+    Base64,
+
+    /// UTF-8 text where invalid sequences are replaced with U+FFFD.
+    ///
+    /// This encoding cannot be reversed if the data is not valid UTF-8.
+    Utf8Lossy,
+}
+
+impl ByteEncoding {
+    fn name(&self) -> &'static str {
+        match self {
+            ByteEncoding::Hex => "hex",
+            ByteEncoding::Base64 => "base64",
+            ByteEncoding::Utf8Lossy => "utf8",
+        }
+    }
+}
+
+/// Field separator of CSV and TSV exports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Delimiter {
+    /// Comma-separated values (RFC 4180).
+    ///
+    /// Fields containing commas, quotation marks, or line breaks are quoted
+    /// and rows end with CRLF.
+    #[default]
+    Comma,
+
+    /// Tab-separated values.
+    ///
+    /// Backslashes, tabs, and line breaks in fields are escaped as `\\`,
+    /// `\t`, `\n`, and `\r`, and rows end with LF.
+    Tab,
+}
+
+/// Options for [`export_csv()`].
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    /// Field separator.
+    ///
+    /// Default: Comma.
+    pub delimiter: Delimiter,
+
+    /// Encoding of keys.
+    ///
+    /// Default: Hex.
+    pub key_encoding: ByteEncoding,
+
+    /// Encoding of values.
+    ///
+    /// Default: Hex.
+    pub value_encoding: ByteEncoding,
+
+    /// Whether to skip writing the `key` and `value` header row.
+    ///
+    /// Default: false.
+    pub omit_header: bool,
+
+    /// Whether to write only the key column.
+    ///
+    /// Default: false.
+    pub keys_only: bool,
+}
+
+/// Export key-value pairs from the database to a CSV or TSV file.
+///
+/// Each row contains a key and a value, or only a key, encoded as text.
+/// Unlike [`export()`], the file has no checksums and cannot be imported.
+/// It is intended for viewing the data in spreadsheets or loading it into
+/// other systems.
+///
+/// The provided progress callback will be called after each pair is
+/// processed.
+pub fn export_csv<W, C, F>(
+    database: &mut Database<F>,
+    output_file: &mut W,
+    options: &CsvOptions,
+    mut progress: C,
+) -> Result<(), Error>
+where
+    W: Write,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut tracker = ProgressTracker::new(Some(database.metadata().key_value_count()));
+    let mut output_file = CountingWriter {
+        inner: output_file,
+        count: 0,
+    };
+    let output_file = &mut output_file;
+
+    if !options.omit_header {
+        write_csv_row(output_file, options, b"key", b"value")?;
+    }
+
+    let mut cursor = database.cursor()?;
+    let mut key = Vec::new();
+    let mut value = Vec::new();
+    let mut key_text = String::new();
+    let mut value_text = String::new();
+    let mut counter = 0;
+
+    while cursor.next_buf(&mut key, &mut value)? {
+        encode_bytes(&key, options.key_encoding, &mut key_text);
+
+        if !options.keys_only {
+            encode_bytes(&value, options.value_encoding, &mut value_text);
+        }
+
+        write_csv_row(
+            output_file,
+            options,
+            key_text.as_bytes(),
+            value_text.as_bytes(),
+        )?;
+
+        counter += 1;
+        progress(tracker.update(counter, output_file.count));
+    }
+
+    Ok(())
+}
+
+struct CountingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    count: u64,
+}
+
+impl<'a, W: Write> Write for CountingWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let length = self.inner.write(buf)?;
+        self.count += length as u64;
+
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Export key-value pairs from the database to a SQLite script.
+///
+/// The script creates a `kv(key BLOB PRIMARY KEY, value BLOB)` table and
+/// inserts the pairs as blob literals within a single transaction. A SQLite
+/// database file can be created with standard tooling, for example
+/// `sqlite3 data.sqlite < data.sql`. Like [`export_csv()`], the file cannot
+/// be imported.
+///
+/// The provided progress callback will be called after each pair is
+/// processed.
+pub fn export_sqlite_script<W, C, F>(
+    database: &mut Database<F>,
+    output_file: &mut W,
+    mut progress: C,
+) -> Result<(), Error>
+where
+    W: Write,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut tracker = ProgressTracker::new(Some(database.metadata().key_value_count()));
+    let mut output_file = CountingWriter {
+        inner: output_file,
+        count: 0,
+    };
+
+    output_file.write_all(b"BEGIN TRANSACTION;\n")?;
+    output_file.write_all(b"CREATE TABLE kv(key BLOB PRIMARY KEY, value BLOB);\n")?;
+
+    let mut cursor = database.cursor()?;
+    let mut key = Vec::new();
+    let mut value = Vec::new();
+    let mut counter = 0;
+
+    while cursor.next_buf(&mut key, &mut value)? {
+        writeln!(
+            output_file,
+            "INSERT INTO kv VALUES(X'{}',X'{}');",
+            data_encoding::HEXUPPER.encode(&key),
+            data_encoding::HEXUPPER.encode(&value)
+        )?;
+
+        counter += 1;
+        progress(tracker.update(counter, output_file.count));
+    }
+
+    output_file.write_all(b"COMMIT;\n")?;
+
+    Ok(())
+}
+
+/// Export key-value pairs from the database to a SQLite database file.
+///
+/// The pairs are inserted into a new `kv(key BLOB PRIMARY KEY, value BLOB)`
+/// table, the same table created by [`export_sqlite_script()`], within a
+/// single transaction. The file is created if it does not exist. It is an
+/// error if the file already contains a `kv` table. Like [`export_csv()`],
+/// the file cannot be imported.
+///
+/// The provided progress callback will be called after each pair is
+/// processed. The number of bytes is the size of the keys and values.
+///
+/// Requires the `sqlite` feature.
+#[cfg(feature = "sqlite")]
+pub fn export_sqlite<P, C, F>(
+    database: &mut Database<F>,
+    path: P,
+    mut progress: C,
+) -> Result<(), Error>
+where
+    P: AsRef<std::path::Path>,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut tracker = ProgressTracker::new(Some(database.metadata().key_value_count()));
+    let mut connection = rusqlite::Connection::open(path).map_err(map_sqlite_error)?;
+    let transaction = connection.transaction().map_err(map_sqlite_error)?;
+
+    transaction
+        .execute("CREATE TABLE kv(key BLOB PRIMARY KEY, value BLOB)", [])
+        .map_err(map_sqlite_error)?;
+
+    {
+        let mut statement = transaction
+            .prepare("INSERT INTO kv VALUES(?1, ?2)")
+            .map_err(map_sqlite_error)?;
+        let mut cursor = database.cursor()?;
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        let mut counter = 0;
+        let mut byte_count = 0;
+
+        while cursor.next_buf(&mut key, &mut value)? {
+            statement
+                .execute(rusqlite::params![key, value])
+                .map_err(map_sqlite_error)?;
+
+            counter += 1;
+            byte_count += (key.len() + value.len()) as u64;
+            progress(tracker.update(counter, byte_count));
+        }
+    }
+
+    transaction.commit().map_err(map_sqlite_error)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn map_sqlite_error(error: rusqlite::Error) -> Error {
+    Error::Other(Box::new(error))
+}
+
+fn encode_bytes(data: &[u8], encoding: ByteEncoding, destination: &mut String) {
+    destination.clear();
+
+    match encoding {
+        ByteEncoding::Hex => data_encoding::HEXUPPER.encode_append(data, destination),
+        ByteEncoding::Base64 => data_encoding::BASE64.encode_append(data, destination),
+        ByteEncoding::Utf8Lossy => destination.push_str(&String::from_utf8_lossy(data)),
+    }
+}
+
+fn write_csv_row<W: Write>(
+    output_file: &mut W,
+    options: &CsvOptions,
+    key: &[u8],
+    value: &[u8],
+) -> Result<(), Error> {
+    match options.delimiter {
+        Delimiter::Comma => {
+            write_csv_field(output_file, key)?;
+
+            if !options.keys_only {
+                output_file.write_all(b",")?;
+                write_csv_field(output_file, value)?;
+            }
+
+            output_file.write_all(b"\r\n")?;
+        }
+        Delimiter::Tab => {
+            write_tsv_field(output_file, key)?;
+
+            if !options.keys_only {
+                output_file.write_all(b"\t")?;
+                write_tsv_field(output_file, value)?;
+            }
+
+            output_file.write_all(b"\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_csv_field<W: Write>(output_file: &mut W, field: &[u8]) -> Result<(), Error> {
+    if !field
+        .iter()
+        .any(|byte| matches!(byte, b',' | b'"' | b'\r' | b'\n'))
+    {
+        output_file.write_all(field)?;
+        return Ok(());
+    }
+
+    output_file.write_all(b"\"")?;
+
+    for part in field.split_inclusive(|&byte| byte == b'"') {
+        output_file.write_all(part)?;
+
+        if part.ends_with(b"\"") {
+            output_file.write_all(b"\"")?;
+        }
+    }
+
+    output_file.write_all(b"\"")?;
+
+    Ok(())
+}
+
+fn write_tsv_field<W: Write>(output_file: &mut W, field: &[u8]) -> Result<(), Error> {
+    for &byte in field {
+        match byte {
+            b'\\' => output_file.write_all(b"\\\\")?,
+            b'\t' => output_file.write_all(b"\\t")?,
+            b'\n' => output_file.write_all(b"\\n")?,
+            b'\r' => output_file.write_all(b"\\r")?,
+            _ => output_file.write_all(&[byte])?,
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,285 @@
+//! Import key-value pairs from the dump formats of other databases.
+//!
+//! The functions help migrating existing key-value datasets into a GrebeDB
+//! database. Like [`super::import()`], the database is flushed when the
+//! function completes.
+//!
+//! Supported formats:
+//!
+//! * Text output of the RocksDB and LevelDB dump tools: [`import_rocksdb_dump()`]
+//! * Pairs exported from sled: [`import_sled()`]
+//! * Length-prefixed binary pairs: [`import_length_prefixed()`]
+
+use std::io::{BufRead, Read};
+
+use super::{ExportError, Progress, ProgressTracker, MAX_BINARY_RECORD_LENGTH};
+use crate::{vfs::Vfs, Database, Error};
+
+const ENTRY_TYPE_DELETION: u8 = 0x0;
+const ENTRY_TYPE_VALUE: u8 = 0x1;
+const ENTRY_TYPE_SINGLE_DELETION: u8 = 0x7;
+
+/// Import the text output of the RocksDB or LevelDB dump tools.
+///
+/// Each line contains a key and a value separated by ` ==> ` as printed by
+/// `ldb dump` or by ` => ` as printed by `sst_dump --command=scan`. Keys and
+/// values starting with `0x`, as printed with the `--hex` or `--output_hex`
+/// options, are decoded as hexadecimal. Other keys and values are stored as
+/// text. Lines without a separator, such as summaries, are skipped.
+///
+/// The quoted key printed by `sst_dump` may be followed by the sequence
+/// number and entry type. Deletion entries remove the key and older
+/// entries of the same key following the newest entry are ignored.
+///
+/// The provided progress callback will be called after each line containing
+/// a pair is processed.
+pub fn import_rocksdb_dump<R, C, F>(
+    database: &mut Database<F>,
+    input_file: &mut R,
+    mut progress: C,
+) -> Result<(), Error>
+where
+    R: BufRead,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut tracker = ProgressTracker::new(None);
+    let mut line = String::new();
+    let mut previous_key = None;
+    let mut bytes = 0;
+    let mut counter = 0;
+
+    loop {
+        line.clear();
+        let length = input_file.read_line(&mut line)?;
+
+        if length == 0 {
+            break;
+        }
+
+        bytes += length as u64;
+
+        let entry = match parse_dump_line(line.trim_end_matches(&['\r', '\n'][..])) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let (key, entry_type, value) = entry.map_err(|message| ExportError::InvalidRecord {
+            row: counter,
+            message,
+        })?;
+
+        counter += 1;
+
+        if previous_key.as_ref() != Some(&key) {
+            match entry_type {
+                ENTRY_TYPE_VALUE => database.put(key.as_slice(), value)?,
+                ENTRY_TYPE_DELETION | ENTRY_TYPE_SINGLE_DELETION => {
+                    database.remove(key.as_slice())?
+                }
+                _ => {
+                    return Err(ExportError::InvalidRecord {
+                        row: counter - 1,
+                        message: "unsupported entry type",
+                    }
+                    .into())
+                }
+            }
+
+            previous_key = Some(key);
+        }
+
+        progress(tracker.update(counter, bytes));
+    }
+
+    database.flush()?;
+
+    Ok(())
+}
+
+type DumpEntry = (Vec<u8>, u8, Vec<u8>);
+
+// Returns none if the line does not contain a pair
+fn parse_dump_line(line: &str) -> Option<Result<DumpEntry, &'static str>> {
+    let (key_part, value_part) = line
+        .split_once(" ==> ")
+        .or_else(|| line.split_once(" => "))?;
+
+    let (key_text, entry_type) = match key_part.strip_prefix('\'') {
+        Some(quoted_part) => match quoted_part.rsplit_once('\'') {
+            Some((key_text, attributes)) => (key_text, parse_entry_type(attributes)),
+            None => return Some(Err("missing closing quote")),
+        },
+        None => (key_part, Ok(ENTRY_TYPE_VALUE)),
+    };
+
+    Some(entry_type.and_then(|entry_type| {
+        Ok((
+            decode_dump_text(key_text)?,
+            entry_type,
+            decode_dump_text(value_part)?,
+        ))
+    }))
+}
+
+// Attributes are formatted like " seq:5, type:1"
+fn parse_entry_type(attributes: &str) -> Result<u8, &'static str> {
+    match attributes.split_once("type:") {
+        Some((_, entry_type)) => entry_type.trim().parse().map_err(|_| "invalid entry type"),
+        None => Ok(ENTRY_TYPE_VALUE),
+    }
+}
+
+fn decode_dump_text(text: &str) -> Result<Vec<u8>, &'static str> {
+    match text.strip_prefix("0x") {
+        Some(hex) => data_encoding::HEXUPPER_PERMISSIVE
+            .decode(hex.as_bytes())
+            .map_err(|_| "invalid hexadecimal"),
+        None => Ok(text.as_bytes().to_vec()),
+    }
+}
+
+/// Import the pairs of a tree exported from sled.
+///
+/// The items are the `[key, value]` vectors of a collection returned by
+/// `sled::Db::export()`.
+///
+/// The provided progress callback will be called after each pair is
+/// processed.
+pub fn import_sled<I, C, F>(
+    database: &mut Database<F>,
+    items: I,
+    mut progress: C,
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = Vec<Vec<u8>>>,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut tracker = ProgressTracker::new(None);
+    let mut bytes = 0;
+    let mut counter = 0;
+
+    for item in items {
+        let (key, value) = match <[Vec<u8>; 2]>::try_from(item) {
+            Ok([key, value]) => (key, value),
+            Err(_) => {
+                return Err(ExportError::InvalidRecord {
+                    row: counter,
+                    message: "expected key and value",
+                }
+                .into())
+            }
+        };
+
+        bytes += (key.len() + value.len()) as u64;
+        database.put(key, value)?;
+
+        counter += 1;
+        progress(tracker.update(counter, bytes));
+    }
+
+    database.flush()?;
+
+    Ok(())
+}
+
+/// Import pairs stored as length-prefixed binary data.
+///
+/// Each pair is the big-endian 32-bit length of the key, the key, the
+/// big-endian 32-bit length of the value, and the value. The file ends
+/// after the last pair without a terminator.
+///
+/// The provided progress callback will be called after each pair is
+/// processed.
+pub fn import_length_prefixed<R, C, F>(
+    database: &mut Database<F>,
+    input_file: &mut R,
+    mut progress: C,
+) -> Result<(), Error>
+where
+    R: Read,
+    C: FnMut(&Progress),
+    F: Vfs,
+{
+    let mut tracker = ProgressTracker::new(None);
+    let mut bytes = 0;
+    let mut counter = 0;
+
+    while let Some(key) = read_length_prefixed(input_file, true)? {
+        let value = read_length_prefixed(input_file, false)?.unwrap_or_default();
+
+        bytes += (8 + key.len() + value.len()) as u64;
+        database.put(key, value)?;
+
+        counter += 1;
+        progress(tracker.update(counter, bytes));
+    }
+
+    database.flush()?;
+
+    Ok(())
+}
+
+// Returns none if the file ends before the length and the end is allowed
+fn read_length_prefixed<R: Read>(
+    input_file: &mut R,
+    allow_eof: bool,
+) -> Result<Option<Vec<u8>>, Error> {
+    let mut length = [0u8; 4];
+    let mut read_length = 0;
+
+    while read_length < length.len() {
+        match input_file.read(&mut length[read_length..]) {
+            Ok(0) if read_length == 0 && allow_eof => return Ok(None),
+            Ok(0) => return Err(ExportError::UnexpectedEof.into()),
+            Ok(count) => read_length += count,
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    let length = u32::from_be_bytes(length);
+
+    if length > MAX_BINARY_RECORD_LENGTH {
+        return Err(ExportError::RecordTooLarge.into());
+    }
+
+    let mut data = Vec::new();
+    input_file.take(length as u64).read_to_end(&mut data)?;
+
+    if data.len() != length as usize {
+        return Err(ExportError::UnexpectedEof.into());
+    }
+
+    Ok(Some(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dump_line() {
+        assert_eq!(
+            parse_dump_line("0x6B6579 ==> 0x76616c7565"),
+            Some(Ok((b"key".to_vec(), ENTRY_TYPE_VALUE, b"value".to_vec())))
+        );
+        assert_eq!(
+            parse_dump_line("'key' seq:12, type:0 => "),
+            Some(Ok((b"key".to_vec(), ENTRY_TYPE_DELETION, Vec::new())))
+        );
+        assert_eq!(
+            parse_dump_line("'it's' seq:3, type:1 => value"),
+            Some(Ok((b"it's".to_vec(), ENTRY_TYPE_VALUE, b"value".to_vec())))
+        );
+        assert_eq!(parse_dump_line("Keys in range: 3"), None);
+        assert_eq!(
+            parse_dump_line("0xZZ ==> value"),
+            Some(Err("invalid hexadecimal"))
+        );
+        assert_eq!(
+            parse_dump_line("'key seq:1, type:1 => value"),
+            Some(Err("missing closing quote"))
+        );
+    }
+}
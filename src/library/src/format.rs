@@ -1,23 +1,245 @@
+#[cfg(feature = "zstd")]
+mod zstd_context;
+
 use std::io::{Cursor, Read, Write};
 
 use relative_path::RelativePath;
-use rmp_serde::{Deserializer, Serializer};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
+    codec::{BincodeCodec, MessagePackCodec, PageCodec},
+    encryption::{self, EncryptionKey},
     error::Error,
     lru::LruVec,
+    stats::IoCounters,
     vfs::{Vfs, VfsSyncOption},
 };
 
 const MAGIC_BYTES: [u8; 8] = [0xFE, b'G', b'r', b'e', b'b', b'e', 0x00, 0x00];
 
+const COMPRESSION_FLAG_NONE: u8 = 0x00;
+const COMPRESSION_FLAG_ZSTD: u8 = 0x01;
+const COMPRESSION_FLAG_LZ4: u8 = 0x02;
+const COMPRESSION_FLAG_MASK: u8 = 0x03;
+const CHECKSUM_FLAG_CRC32C: u8 = 0x00;
+const CHECKSUM_FLAG_XXH3: u8 = 0x04;
+const CHECKSUM_FLAG_CRC64: u8 = 0x08;
+const CHECKSUM_FLAG_MASK: u8 = 0x0C;
+const CODEC_FLAG_MASK: u8 = 0x70;
+const ENCRYPTION_FLAG: u8 = 0x80;
+const HEADER_LENGTH: usize = 9;
+const METADATA_FILENAME_PREFIX: &str = "grebedb_meta";
+const BUFFER_RETAIN_CAPACITY: usize = 1024 * 1024;
+
+/// Compression applied to pages when writing files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageCompression {
+    None,
+    Zstd(i32),
+    Lz4,
+}
+
+/// Serialization format of page payloads.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadCodec {
+    #[default]
+    MessagePack,
+    Bincode,
+}
+
+impl PayloadCodec {
+    fn flag(self) -> u8 {
+        match self {
+            Self::MessagePack => MessagePackCodec::FLAG,
+            Self::Bincode => BincodeCodec::FLAG,
+        }
+    }
+
+    fn from_flag(flag: u8) -> Option<Self> {
+        match flag {
+            MessagePackCodec::FLAG => Some(Self::MessagePack),
+            BincodeCodec::FLAG => Some(Self::Bincode),
+            _ => None,
+        }
+    }
+}
+
+/// Checksum function for page payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageChecksum {
+    Crc32c,
+    Xxh3,
+    Crc64,
+}
+
+impl PageChecksum {
+    fn flag(self) -> u8 {
+        match self {
+            Self::Crc32c => CHECKSUM_FLAG_CRC32C,
+            Self::Xxh3 => CHECKSUM_FLAG_XXH3,
+            Self::Crc64 => CHECKSUM_FLAG_CRC64,
+        }
+    }
+
+    fn from_flag(flag: u8) -> Option<Self> {
+        match flag {
+            CHECKSUM_FLAG_CRC32C => Some(Self::Crc32c),
+            CHECKSUM_FLAG_XXH3 => Some(Self::Xxh3),
+            CHECKSUM_FLAG_CRC64 => Some(Self::Crc64),
+            _ => None,
+        }
+    }
+
+    fn len(self) -> usize {
+        match self {
+            Self::Crc32c => 4,
+            Self::Xxh3 | Self::Crc64 => 8,
+        }
+    }
+
+    fn hasher(self) -> Result<ChecksumHasher, Error> {
+        match self {
+            Self::Crc32c => Ok(ChecksumHasher::Crc32c(0)),
+            #[cfg(feature = "checksum_xxh3")]
+            Self::Xxh3 => Ok(ChecksumHasher::Xxh3(Box::new(
+                xxhash_rust::xxh3::Xxh3::new(),
+            ))),
+            #[cfg(feature = "checksum_crc64")]
+            Self::Crc64 => Ok(ChecksumHasher::Crc64(CRC64.digest())),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::ChecksumUnavailable),
+        }
+    }
+}
+
+#[cfg(feature = "checksum_crc64")]
+static CRC64: crc::Crc<u64> = crc::Crc::<u64>::new(&crc::CRC_64_XZ);
+
+// Incremental checksum computation so pages don't need to be buffered whole
+enum ChecksumHasher {
+    Crc32c(u32),
+    #[cfg(feature = "checksum_xxh3")]
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+    #[cfg(feature = "checksum_crc64")]
+    Crc64(crc::Digest<'static, u64>),
+}
+
+impl ChecksumHasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, data),
+            #[cfg(feature = "checksum_xxh3")]
+            Self::Xxh3(hasher) => hasher.update(data),
+            #[cfg(feature = "checksum_crc64")]
+            Self::Crc64(digest) => digest.update(data),
+        }
+    }
+
+    // Append the big-endian checksum to the destination
+    fn finish(self, destination: &mut Vec<u8>) {
+        match self {
+            Self::Crc32c(crc) => destination.extend_from_slice(&crc.to_be_bytes()),
+            #[cfg(feature = "checksum_xxh3")]
+            Self::Xxh3(hasher) => destination.extend_from_slice(&hasher.digest().to_be_bytes()),
+            #[cfg(feature = "checksum_crc64")]
+            Self::Crc64(digest) => destination.extend_from_slice(&digest.finalize().to_be_bytes()),
+        }
+    }
+}
+
+struct ChecksumReader<R> {
+    inner: R,
+    hasher: ChecksumHasher,
+    content_hasher: blake3::Hasher,
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let amount = self.inner.read(buf)?;
+        self.hasher.update(&buf[..amount]);
+        self.content_hasher.update(&buf[..amount]);
+        Ok(amount)
+    }
+}
+
+// Computes the checksum and content hash of the payload while it is
+// serialized into the file or the compressor
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: ChecksumHasher,
+    content_hasher: blake3::Hasher,
+    length: u64,
+}
+
+impl<W> ChecksumWriter<W> {
+    fn new(inner: W, checksum: PageChecksum) -> Result<Self, Error> {
+        Ok(Self {
+            inner,
+            hasher: checksum.hasher()?,
+            content_hasher: blake3::Hasher::new(),
+            length: 0,
+        })
+    }
+
+    // Return the big-endian checksum and the content hash
+    fn finish(self) -> (Vec<u8>, ContentHash) {
+        let mut checksum_bytes = Vec::with_capacity(8);
+        self.hasher.finish(&mut checksum_bytes);
+
+        let hash = ContentHash {
+            length: self.length,
+            hash: *self.content_hasher.finalize().as_bytes(),
+        };
+
+        (checksum_bytes, hash)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let amount = self.inner.write(buf)?;
+        self.hasher.update(&buf[..amount]);
+        self.content_hasher.update(&buf[..amount]);
+        self.length += amount as u64;
+        Ok(amount)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hash of a value as serialized by the payload codec.
+///
+/// The hash is cryptographic so that pages with different contents can be
+/// assumed to never have the same hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentHash {
+    length: u64,
+    hash: [u8; 32],
+}
+
+impl ContentHash {
+    /// Return the length of the serialized value.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+}
+
 pub struct Format {
     file_buffer: Vec<u8>,
     page_buffer: Vec<u8>,
-    payload_buffer: Vec<u8>,
-    compression_level: Option<i32>,
+    compression: PageCompression,
+    codec: PayloadCodec,
+    checksum: PageChecksum,
+    encryption_key: Option<EncryptionKey>,
+    previous_encryption_keys: Vec<EncryptionKey>,
     dir_create_cache: LruVec<String>,
+    io_counters: IoCounters,
+    last_payload_hash: Option<ContentHash>,
+    #[cfg(feature = "zstd")]
+    zstd_contexts: zstd_context::ZstdContexts,
 }
 
 impl Default for Format {
@@ -25,27 +247,200 @@ impl Default for Format {
         Self {
             file_buffer: Vec::new(),
             page_buffer: Vec::new(),
-            payload_buffer: Vec::new(),
-            compression_level: if cfg!(feature = "zstd") {
-                Some(0)
+            compression: if cfg!(feature = "zstd") {
+                PageCompression::Zstd(0)
             } else {
-                None
+                PageCompression::None
             },
+            codec: PayloadCodec::MessagePack,
+            checksum: PageChecksum::Crc32c,
+            encryption_key: None,
+            previous_encryption_keys: Vec::new(),
             dir_create_cache: LruVec::new(8),
+            io_counters: IoCounters::default(),
+            last_payload_hash: None,
+            #[cfg(feature = "zstd")]
+            zstd_contexts: zstd_context::ZstdContexts::default(),
         }
     }
 }
 
 impl Format {
-    pub fn set_compression_level(&mut self, value: Option<i32>) {
-        self.compression_level = value;
+    pub fn set_compression(&mut self, value: PageCompression) {
+        self.compression = value;
+    }
+
+    pub fn codec(&self) -> PayloadCodec {
+        self.codec
+    }
+
+    pub fn set_codec(&mut self, value: PayloadCodec) {
+        self.codec = value;
+    }
+
+    pub fn set_checksum(&mut self, value: PageChecksum) {
+        self.checksum = value;
+    }
+
+    pub fn set_encryption_key(&mut self, value: Option<EncryptionKey>) {
+        self.encryption_key = value;
+    }
+
+    pub fn set_previous_encryption_keys(&mut self, value: Vec<EncryptionKey>) {
+        self.previous_encryption_keys = value;
+    }
+
+    pub fn io_counters(&self) -> IoCounters {
+        self.io_counters
+    }
+
+    pub fn reset_io_counters(&mut self) {
+        self.io_counters = IoCounters::default();
+    }
+
+    /// Return the hash of the payload of the file that was last read or
+    /// written successfully.
+    ///
+    /// The hash is computed from the payload bytes as they are read or
+    /// written, so the value doesn't need to be serialized again.
+    pub fn last_payload_hash(&self) -> Option<ContentHash> {
+        self.last_payload_hash
+    }
+
+    /// Encrypt the file again with the current key if it is encrypted with
+    /// a previous key.
+    ///
+    /// The payload is not decoded, so this works for any kind of file. The
+    /// new contents are written to a temporary file that replaces the file.
+    /// Returns whether the file was rewritten.
+    pub fn reencrypt_file<V>(
+        &mut self,
+        vfs: &mut V,
+        path: &str,
+        sync_option: VfsSyncOption,
+    ) -> Result<bool, Error>
+    where
+        V: Vfs + ?Sized,
+    {
+        let data = vfs.read(path)?;
+
+        if data.len() < HEADER_LENGTH
+            || MAGIC_BYTES != data[..8]
+            || data[HEADER_LENGTH - 1] & ENCRYPTION_FLAG == 0
+        {
+            return Ok(false);
+        }
+
+        if let Some(key) = &self.encryption_key {
+            let associated_data = make_associated_data(path, &data[..HEADER_LENGTH]);
+
+            if encryption::decrypt(key, &associated_data, &data[HEADER_LENGTH..])?.is_some() {
+                return Ok(false);
+            }
+        }
+
+        let mut file = Cursor::new(data);
+        let plaintext = self.decrypt_file(path, &mut file)?;
+
+        self.file_buffer.clear();
+        self.file_buffer
+            .extend_from_slice(&file.get_ref()[..HEADER_LENGTH]);
+        self.file_buffer.extend_from_slice(&plaintext);
+        self.file_buffer[HEADER_LENGTH - 1] &= !ENCRYPTION_FLAG;
+        self.encrypt_file_buffer(path)?;
+
+        let temp_path = format!("{}.tmp", path);
+        vfs.write(&temp_path, &self.file_buffer, sync_option)?;
+        vfs.rename_file(&temp_path, path)?;
+
+        self.release_large_buffers();
+
+        Ok(true)
+    }
+
+    /// Return a hash of the value's serialized bytes.
+    pub fn content_hash<T>(&mut self, value: T) -> Result<ContentHash, Error>
+    where
+        T: Serialize,
+    {
+        // The checksum is not used
+        let mut writer = ChecksumWriter::new(std::io::sink(), PageChecksum::Crc32c)?;
+        serialize_payload(self.codec, value, &mut writer)?;
+
+        Ok(writer.finish().1)
+    }
+
+    pub fn read_file<T, V>(&mut self, vfs: &mut V, path: &str) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        V: Vfs + ?Sized,
+    {
+        let result = self.read_file_(vfs, path);
+
+        if result.is_ok() {
+            self.io_counters.page_reads += 1;
+        }
+
+        self.release_large_buffers();
+
+        result
+    }
+
+    // Read a file and also return its contents as stored
+    pub fn read_file_and_data<T, V>(
+        &mut self,
+        vfs: &mut V,
+        path: &str,
+    ) -> Result<(T, Vec<u8>), Error>
+    where
+        T: DeserializeOwned,
+        V: Vfs + ?Sized,
+    {
+        let data = vfs.read(path)?;
+        let result = self.decode_file(path, data.clone());
+
+        if result.is_ok() {
+            self.io_counters.page_reads += 1;
+        }
+
+        self.release_large_buffers();
+
+        Ok((result?, data))
     }
 
-    pub fn read_file<'de, T>(&mut self, vfs: &mut dyn Vfs, path: &str) -> Result<T, Error>
+    /// Decode the contents of a file that was not read through a [`Vfs`].
+    ///
+    /// The path is used like in [`Self::read_file()`] but doesn't need to be
+    /// valid for a [`Vfs`].
+    pub fn decode_data<T>(&mut self, path: &str, data: Vec<u8>) -> Result<T, Error>
     where
-        T: Deserialize<'de>,
+        T: DeserializeOwned,
     {
-        let mut file = Cursor::new(vfs.read(path)?);
+        let result = self.decode_file(path, data);
+
+        self.release_large_buffers();
+
+        result
+    }
+
+    fn read_file_<T, V>(&mut self, vfs: &mut V, path: &str) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        V: Vfs + ?Sized,
+    {
+        self.decode_file(path, vfs.read(path)?)
+    }
+
+    // The whole file is in memory, but Zstandard and uncompressed pages are
+    // decompressed and checksummed while they are deserialized. LZ4 blocks
+    // and encrypted files are decoded into a buffer first.
+    fn decode_file<T>(&mut self, path: &str, data: Vec<u8>) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.last_payload_hash = None;
+
+        let mut file = Cursor::new(data);
 
         let mut magic_bytes: [u8; 8] = [0u8; 8];
         file.read_exact(&mut magic_bytes)?;
@@ -60,40 +455,131 @@ impl Format {
         let mut compression_flag: [u8; 1] = [0u8; 1];
         file.read_exact(&mut compression_flag)?;
 
-        if compression_flag[0] == 0x01 {
-            self.decompress_to_page_buffer(&mut file)?;
-        } else {
-            self.page_buffer.clear();
-            file.read_to_end(&mut self.page_buffer)?;
+        if compression_flag[0] & ENCRYPTION_FLAG != 0 {
+            file = Cursor::new(self.decrypt_file(path, &mut file)?);
+            compression_flag[0] &= !ENCRYPTION_FLAG;
+        } else if self.encryption_key.is_some() {
+            return Err(Error::InvalidFileFormat {
+                path: path.to_string(),
+                message: "file not encrypted",
+            });
         }
 
-        self.deserialize_page(path)
+        let codec = parse_codec_flag(path, compression_flag[0])?;
+        let checksum = parse_checksum_flag(path, compression_flag[0])?;
+
+        let (payload, hash) = match compression_flag[0] & COMPRESSION_FLAG_MASK {
+            COMPRESSION_FLAG_NONE => deserialize_page(path, &mut file, codec, checksum)?,
+            COMPRESSION_FLAG_ZSTD => self.read_zstd_page(path, &mut file, codec, checksum)?,
+            COMPRESSION_FLAG_LZ4 => {
+                self.decompress_lz4_to_page_buffer(&mut file)?;
+                self.io_counters.bytes_decompressed += self.page_buffer.len() as u64;
+                deserialize_page(path, self.page_buffer.as_slice(), codec, checksum)?
+            }
+            _ => {
+                return Err(Error::InvalidFileFormat {
+                    path: path.to_string(),
+                    message: "unknown compression format",
+                })
+            }
+        };
+
+        self.last_payload_hash = Some(hash);
+
+        Ok(payload)
     }
 
-    pub fn write_file<T>(
+    pub fn write_file<T, V>(
         &mut self,
-        vfs: &mut dyn Vfs,
+        vfs: &mut V,
         path: &str,
         payload: T,
         sync_option: VfsSyncOption,
     ) -> Result<(), Error>
     where
         T: Serialize,
+        V: Vfs + ?Sized,
     {
+        let result = self.write_file_(vfs, path, payload, sync_option);
+
+        if result.is_ok() {
+            self.io_counters.page_writes += 1;
+        }
+
+        self.release_large_buffers();
+
+        result
+    }
+
+    /// Serialize and write a metadata file.
+    ///
+    /// Metadata is always encoded with MessagePack regardless of the codec
+    /// so that fields can be added later. Bincode can't decode a struct
+    /// that was encoded before a field was added.
+    pub fn write_metadata_file<T, V>(
+        &mut self,
+        vfs: &mut V,
+        path: &str,
+        payload: T,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error>
+    where
+        T: Serialize,
+        V: Vfs + ?Sized,
+    {
+        let codec = std::mem::replace(&mut self.codec, PayloadCodec::MessagePack);
+        let result = self.write_file(vfs, path, payload, sync_option);
+        self.codec = codec;
+
+        result
+    }
+
+    fn write_file_<T, V>(
+        &mut self,
+        vfs: &mut V,
+        path: &str,
+        payload: T,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error>
+    where
+        T: Serialize,
+        V: Vfs + ?Sized,
+    {
+        self.last_payload_hash = None;
         self.file_buffer.clear();
         self.page_buffer.clear();
-        self.payload_buffer.clear();
 
+        // The payload is serialized into the file or the compressor without
+        // an intermediate buffer. The file is buffered because the file
+        // system interface and encryption take whole files.
         self.file_buffer.write_all(&MAGIC_BYTES)?;
 
-        if self.compression_level.is_some() {
-            self.file_buffer.write_all(&[0x01])?;
-            self.serialize_page(payload)?;
-            self.write_compressed_page_to_file_buffer()?;
-        } else {
-            self.file_buffer.write_all(&[0x00])?;
-            self.serialize_page(payload)?;
-            self.file_buffer.write_all(&self.page_buffer)?;
+        let flags = self.codec.flag() | self.checksum.flag();
+
+        let hash = match self.compression {
+            PageCompression::None => {
+                self.file_buffer
+                    .write_all(&[COMPRESSION_FLAG_NONE | flags])?;
+                write_page(&mut self.file_buffer, self.codec, self.checksum, payload)?
+            }
+            PageCompression::Zstd(level) => {
+                self.file_buffer
+                    .write_all(&[COMPRESSION_FLAG_ZSTD | flags])?;
+                let hash = self.write_zstd_page_to_file_buffer(level, payload)?;
+                self.io_counters.bytes_compressed += self.page_length(&hash);
+                hash
+            }
+            PageCompression::Lz4 => {
+                self.file_buffer
+                    .write_all(&[COMPRESSION_FLAG_LZ4 | flags])?;
+                let hash = self.write_lz4_page_to_file_buffer(payload)?;
+                self.io_counters.bytes_compressed += self.page_length(&hash);
+                hash
+            }
+        };
+
+        if self.encryption_key.is_some() {
+            self.encrypt_file_buffer(path)?;
         }
 
         let rel_path = RelativePath::new(path);
@@ -105,49 +591,151 @@ impl Format {
 
         vfs.write(path, &self.file_buffer, sync_option)?;
 
+        self.last_payload_hash = Some(hash);
+
         Ok(())
     }
 
-    fn serialize_page<T>(&mut self, object: T) -> Result<(), Error>
+    // Length of the page before compression
+    fn page_length(&self, hash: &ContentHash) -> u64 {
+        8 + hash.length + self.checksum.len() as u64
+    }
+
+    fn read_zstd_page<T>(
+        &mut self,
+        path: &str,
+        source: &mut Cursor<Vec<u8>>,
+        codec: PayloadCodec,
+        checksum: PageChecksum,
+    ) -> Result<(T, ContentHash), Error>
+    where
+        T: DeserializeOwned,
+    {
+        #[cfg(feature = "zstd")]
+        {
+            let decoder = self.zstd_contexts.decoder()?;
+            let decompressor = CountingReader {
+                inner: zstd::stream::zio::Reader::new(source, decoder),
+                count: &mut self.io_counters.bytes_decompressed,
+            };
+            deserialize_page(path, decompressor, codec, checksum)
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            let _ = (path, source, codec, checksum);
+            Err(Error::CompressionUnavailable)
+        }
+    }
+
+    // Compress the page while the payload is serialized
+    fn write_zstd_page_to_file_buffer<T>(
+        &mut self,
+        compression_level: i32,
+        payload: T,
+    ) -> Result<ContentHash, Error>
     where
         T: Serialize,
     {
-        serialize_payload(object, &mut self.payload_buffer)?;
+        #[cfg(feature = "zstd")]
+        {
+            let codec = self.codec;
 
-        let size_bytes = self.payload_buffer.len().to_be_bytes();
+            // The size precedes the payload in the compressed stream, so
+            // the payload is serialized once to count its bytes instead of
+            // buffering it
+            let mut counter = ChecksumWriter::new(std::io::sink(), PageChecksum::Crc32c)?;
+            serialize_payload(codec, &payload, &mut counter)?;
+            let size = counter.length;
 
-        self.page_buffer.write_all(&size_bytes)?;
-        self.page_buffer.write_all(&self.payload_buffer)?;
+            let encoder = self.zstd_contexts.encoder(compression_level)?;
+            let mut compressor = zstd::stream::zio::Writer::new(&mut self.file_buffer, encoder);
+            compressor.write_all(&size.to_be_bytes())?;
 
-        let crc = crc32c::crc32c(&self.payload_buffer);
-        let crc_bytes = crc.to_be_bytes();
+            let mut writer = ChecksumWriter::new(&mut compressor, self.checksum)?;
+            serialize_payload(codec, &payload, &mut writer)?;
+            let (checksum_bytes, hash) = writer.finish();
 
-        self.page_buffer.write_all(&crc_bytes)?;
+            if hash.length != size {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "serialized size of payload changed",
+                )));
+            }
 
-        Ok(())
-    }
+            compressor.write_all(&checksum_bytes)?;
+            compressor.finish()?;
 
-    fn write_compressed_page_to_file_buffer(&mut self) -> Result<(), Error> {
-        #[cfg(feature = "zstd")]
+            Ok(hash)
+        }
+        #[cfg(not(feature = "zstd"))]
         {
-            let mut temp_buffer = Vec::with_capacity(0);
-            std::mem::swap(&mut self.file_buffer, &mut temp_buffer);
+            let _ = (compression_level, payload);
+            Err(Error::CompressionUnavailable)
+        }
+    }
 
-            let compression_level = self.compression_level.unwrap();
-            let mut compressor = zstd::Encoder::new(temp_buffer, compression_level)?;
-            compressor.write_all(&self.page_buffer)?;
-            let mut old_writer = compressor.finish()?;
+    fn write_lz4_page_to_file_buffer<T>(&mut self, payload: T) -> Result<ContentHash, Error>
+    where
+        T: Serialize,
+    {
+        #[cfg(feature = "compression_lz4")]
+        {
+            // LZ4 blocks require contiguous input
+            let hash = write_page(&mut self.page_buffer, self.codec, self.checksum, payload)?;
 
-            std::mem::swap(&mut self.file_buffer, &mut old_writer);
+            let compressed = lz4_flex::compress_prepend_size(&self.page_buffer);
+            self.file_buffer.write_all(&compressed)?;
 
-            Ok(())
+            Ok(hash)
         }
-        #[cfg(not(feature = "zstd"))]
+        #[cfg(not(feature = "compression_lz4"))]
         {
+            let _ = payload;
             Err(Error::CompressionUnavailable)
         }
     }
 
+    fn encrypt_file_buffer(&mut self, path: &str) -> Result<(), Error> {
+        let key = self.encryption_key.as_ref().unwrap();
+        let plaintext = self.file_buffer.split_off(HEADER_LENGTH);
+        self.file_buffer[HEADER_LENGTH - 1] |= ENCRYPTION_FLAG;
+
+        let associated_data = make_associated_data(path, &self.file_buffer);
+
+        encryption::encrypt(key, &associated_data, &plaintext, &mut self.file_buffer)
+    }
+
+    fn decrypt_file(&mut self, path: &str, file: &mut Cursor<Vec<u8>>) -> Result<Vec<u8>, Error> {
+        let key = match &self.encryption_key {
+            Some(key) => key,
+            None => {
+                return Err(Error::InvalidFileFormat {
+                    path: path.to_string(),
+                    message: "encryption key required",
+                })
+            }
+        };
+
+        let data = file.get_ref();
+        let associated_data = make_associated_data(path, &data[..HEADER_LENGTH]);
+        let ciphertext = &data[HEADER_LENGTH..];
+
+        for key in std::iter::once(key).chain(&self.previous_encryption_keys) {
+            if let Some(plaintext) = encryption::decrypt(key, &associated_data, ciphertext)? {
+                return Ok(plaintext);
+            }
+        }
+
+        Err(Error::DecryptionFailed {
+            path: path.to_string(),
+        })
+    }
+
+    /// Forget created directories after directories are removed.
+    pub fn clear_dir_cache(&mut self) {
+        self.dir_create_cache.clear();
+    }
+
     fn is_in_dir_cache(&mut self, dir_path: &RelativePath) -> bool {
         let dir_path = dir_path.to_string();
 
@@ -159,76 +747,188 @@ impl Format {
         }
     }
 
-    fn decompress_to_page_buffer(&mut self, source: &mut dyn Read) -> Result<(), Error> {
+    fn decompress_lz4_to_page_buffer(&mut self, source: &mut dyn Read) -> Result<(), Error> {
         self.page_buffer.clear();
 
-        #[cfg(feature = "zstd")]
+        #[cfg(feature = "compression_lz4")]
         {
-            let mut decompressor = zstd::Decoder::new(source)?;
-            decompressor.read_to_end(&mut self.page_buffer)?;
+            let mut compressed = Vec::new();
+            source.read_to_end(&mut compressed)?;
+
+            self.page_buffer = lz4_flex::decompress_size_prepended(&compressed)
+                .map_err(|error| Error::Other(Box::new(error)))?;
+
             Ok(())
         }
-        #[cfg(not(feature = "zstd"))]
+        #[cfg(not(feature = "compression_lz4"))]
         {
             let _ = source;
             Err(Error::CompressionUnavailable)
         }
     }
 
-    fn deserialize_page<'de, T>(&mut self, path: &str) -> Result<T, Error>
-    where
-        T: Deserialize<'de>,
-    {
-        let mut size_bytes: [u8; 8] = [0u8; 8];
-        let mut data = Cursor::new(&mut self.page_buffer);
-
-        data.read_exact(&mut size_bytes)?;
-        let size = u64::from_be_bytes(size_bytes) as usize;
-
-        let payload = deserialize_payload(&mut data)?;
-
-        let mut crc_bytes: [u8; 4] = [0; 4];
-        data.read_exact(&mut crc_bytes)?;
-        let crc = u32::from_be_bytes(crc_bytes);
+    // Don't hold on to memory used by an unusually large page
+    fn release_large_buffers(&mut self) {
+        for buffer in [&mut self.file_buffer, &mut self.page_buffer] {
+            if buffer.capacity() > BUFFER_RETAIN_CAPACITY {
+                *buffer = Vec::new();
+            }
+        }
+    }
+}
 
-        let test_crc = crc32c::crc32c(&self.page_buffer[8..8 + size]);
+// Adds the number of bytes read from the inner reader to the count
+#[cfg(feature = "zstd")]
+struct CountingReader<'a, R> {
+    inner: R,
+    count: &'a mut u64,
+}
 
-        if crc != test_crc {
-            Err(Error::BadChecksum {
-                path: path.to_string(),
-            })
-        } else {
-            Ok(payload)
-        }
+#[cfg(feature = "zstd")]
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let amount = self.inner.read(buf)?;
+        *self.count += amount as u64;
+        Ok(amount)
     }
 }
 
-fn serialize_payload<T, W>(object: T, destination: W) -> Result<(), Error>
+fn serialize_payload<T, W>(codec: PayloadCodec, object: T, destination: W) -> Result<(), Error>
 where
     T: Serialize,
     W: Write,
 {
-    let mut serializer = Serializer::new(destination)
-        .with_binary()
-        .with_string_variants()
-        .with_struct_map();
-
-    match object.serialize(&mut serializer) {
-        Ok(_) => Ok(()),
-        Err(error) => Err(Error::Other(Box::new(error))),
+    match codec {
+        PayloadCodec::MessagePack => MessagePackCodec::serialize(object, destination),
+        PayloadCodec::Bincode => BincodeCodec::serialize(object, destination),
     }
 }
 
-fn deserialize_payload<'de, T, R>(source: R) -> Result<T, Error>
+// Append the uncompressed page to the buffer. The size is filled in after
+// the payload is serialized.
+fn write_page<T>(
+    buffer: &mut Vec<u8>,
+    codec: PayloadCodec,
+    checksum: PageChecksum,
+    payload: T,
+) -> Result<ContentHash, Error>
 where
-    T: Deserialize<'de>,
+    T: Serialize,
+{
+    let size_offset = buffer.len();
+    buffer.extend_from_slice(&[0u8; 8]);
+
+    let mut writer = ChecksumWriter::new(&mut *buffer, checksum)?;
+    serialize_payload(codec, payload, &mut writer)?;
+    let (checksum_bytes, hash) = writer.finish();
+
+    buffer[size_offset..size_offset + 8].copy_from_slice(&hash.length.to_be_bytes());
+    buffer.extend_from_slice(&checksum_bytes);
+
+    Ok(hash)
+}
+
+// Deserialize the payload while it is read from the (decompressing) source
+fn deserialize_page<T, R>(
+    path: &str,
+    mut source: R,
+    codec: PayloadCodec,
+    checksum: PageChecksum,
+) -> Result<(T, ContentHash), Error>
+where
+    T: DeserializeOwned,
     R: Read,
 {
-    let mut deserializer = Deserializer::new(source).with_binary();
+    let mut size_bytes: [u8; 8] = [0u8; 8];
+    source.read_exact(&mut size_bytes)?;
+    let size = u64::from_be_bytes(size_bytes);
+
+    let mut reader = ChecksumReader {
+        inner: (&mut source).take(size),
+        hasher: checksum.hasher()?,
+        content_hasher: blake3::Hasher::new(),
+    };
+
+    let payload = match codec {
+        PayloadCodec::MessagePack => MessagePackCodec::deserialize(&mut reader, size)?,
+        PayloadCodec::Bincode => BincodeCodec::deserialize(&mut reader, size)?,
+    };
+
+    // Checksum covers the entire payload even if the codec didn't read all of it
+    std::io::copy(&mut reader, &mut std::io::sink())?;
 
-    match Deserialize::deserialize(&mut deserializer) {
-        Ok(value) => Ok(value),
-        Err(error) => Err(Error::Other(Box::new(error))),
+    if reader.inner.limit() != 0 {
+        return Err(Error::InvalidFileFormat {
+            path: path.to_string(),
+            message: "bad page size",
+        });
+    }
+
+    let mut test_checksum = Vec::with_capacity(8);
+    reader.hasher.finish(&mut test_checksum);
+    let hash = ContentHash {
+        length: size,
+        hash: *reader.content_hasher.finalize().as_bytes(),
+    };
+
+    let mut checksum_bytes = vec![0u8; checksum.len()];
+    source.read_exact(&mut checksum_bytes)?;
+
+    if checksum_bytes != test_checksum {
+        Err(Error::BadChecksum {
+            path: path.to_string(),
+        })
+    } else {
+        Ok((payload, hash))
+    }
+}
+
+// Authenticate the header and the identity of the file, so an encrypted
+// file can't be swapped in for the file of another page. Page files are
+// renamed between revisions and metadata files are copied to backup files,
+// so the identity is the page ID or the metadata prefix instead of the path.
+fn make_associated_data(path: &str, header: &[u8]) -> Vec<u8> {
+    let filename = path.rsplit('/').next().unwrap_or(path);
+    let identity = if filename.starts_with(METADATA_FILENAME_PREFIX) {
+        METADATA_FILENAME_PREFIX
+    } else {
+        match filename
+            .strip_prefix("grebedb_")
+            .and_then(|name| name.split_once('_'))
+        {
+            Some((page_id, _))
+                if page_id.len() == 16 && page_id.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                &filename[.."grebedb_".len() + page_id.len()]
+            }
+            _ => filename,
+        }
+    };
+
+    let mut associated_data = Vec::with_capacity(header.len() + identity.len());
+    associated_data.extend_from_slice(header);
+    associated_data.extend_from_slice(identity.as_bytes());
+
+    associated_data
+}
+
+fn parse_checksum_flag(path: &str, flags: u8) -> Result<PageChecksum, Error> {
+    match PageChecksum::from_flag(flags & CHECKSUM_FLAG_MASK) {
+        Some(checksum) => Ok(checksum),
+        None => Err(Error::InvalidFileFormat {
+            path: path.to_string(),
+            message: "unknown checksum",
+        }),
+    }
+}
+
+fn parse_codec_flag(path: &str, flags: u8) -> Result<PayloadCodec, Error> {
+    match PayloadCodec::from_flag(flags & CODEC_FLAG_MASK) {
+        Some(codec) => Ok(codec),
+        None => Err(Error::InvalidFileFormat {
+            path: path.to_string(),
+            message: "unknown codec",
+        }),
     }
 }
 
@@ -250,4 +950,293 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_format_no_compression() -> Result<(), Error> {
+        let mut format = Format::default();
+        format.set_compression(PageCompression::None);
+        let mut vfs = MemoryVfs::new();
+
+        format.write_file(&mut vfs, "my_file", "hello world", VfsSyncOption::None)?;
+
+        assert_eq!(vfs.read("my_file")?[8], COMPRESSION_FLAG_NONE);
+
+        let payload: String = format.read_file(&mut vfs, "my_file")?;
+
+        assert_eq!(&payload, "hello world");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "compression_lz4")]
+    #[test]
+    fn test_format_lz4() -> Result<(), Error> {
+        let mut format = Format::default();
+        format.set_compression(PageCompression::Lz4);
+        let mut vfs = MemoryVfs::new();
+
+        format.write_file(&mut vfs, "my_file", "hello world", VfsSyncOption::None)?;
+
+        assert_eq!(vfs.read("my_file")?[8], COMPRESSION_FLAG_LZ4);
+
+        let mut format = Format::default();
+        let payload: String = format.read_file(&mut vfs, "my_file")?;
+
+        assert_eq!(&payload, "hello world");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_format_encryption() -> Result<(), Error> {
+        let mut format = Format::default();
+        format.set_compression(PageCompression::None);
+        format.set_encryption_key(Some(EncryptionKey::new([1u8; 32])));
+        let mut vfs = MemoryVfs::new();
+
+        format.write_file(&mut vfs, "my_file", "hello world", VfsSyncOption::None)?;
+
+        let data = vfs.read("my_file")?;
+        assert_eq!(data[8], COMPRESSION_FLAG_NONE | ENCRYPTION_FLAG);
+        assert!(!data.windows(11).any(|window| window == b"hello world"));
+
+        let payload: String = format.read_file(&mut vfs, "my_file")?;
+        assert_eq!(&payload, "hello world");
+
+        let mut format = Format::default();
+        assert!(format.read_file::<String, _>(&mut vfs, "my_file").is_err());
+
+        format.set_encryption_key(Some(EncryptionKey::new([2u8; 32])));
+        assert!(matches!(
+            format.read_file::<String, _>(&mut vfs, "my_file"),
+            Err(Error::DecryptionFailed { .. })
+        ));
+
+        format.set_previous_encryption_keys(vec![EncryptionKey::new([1u8; 32])]);
+        let payload: String = format.read_file(&mut vfs, "my_file")?;
+        assert_eq!(&payload, "hello world");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_format_encryption_file_identity() -> Result<(), Error> {
+        let mut format = Format::default();
+        format.set_encryption_key(Some(EncryptionKey::new([1u8; 32])));
+        let mut vfs = MemoryVfs::new();
+        let path_1 = "00/grebedb_0000000000000001_1.grebedb";
+        let path_2 = "00/grebedb_0000000000000002_0.grebedb";
+
+        vfs.create_dir("00")?;
+        format.write_file(&mut vfs, path_1, "page 1", VfsSyncOption::None)?;
+        format.write_file(&mut vfs, path_2, "page 2", VfsSyncOption::None)?;
+
+        // A file of the same page is still readable after it is renamed
+        vfs.rename_file(path_1, "00/grebedb_0000000000000001_0.grebedb")?;
+        let payload: String =
+            format.read_file(&mut vfs, "00/grebedb_0000000000000001_0.grebedb")?;
+        assert_eq!(&payload, "page 1");
+
+        // A file of another page is not
+        let data = vfs.read("00/grebedb_0000000000000001_0.grebedb")?;
+        vfs.write(path_2, &data, VfsSyncOption::None)?;
+        assert!(matches!(
+            format.read_file::<String, _>(&mut vfs, path_2),
+            Err(Error::DecryptionFailed { .. })
+        ));
+
+        // Metadata backup files are copies of the main file
+        format.write_file(
+            &mut vfs,
+            "grebedb_meta.grebedb",
+            "meta",
+            VfsSyncOption::None,
+        )?;
+        let data = vfs.read("grebedb_meta.grebedb")?;
+        vfs.write("grebedb_meta_prev.grebedb", &data, VfsSyncOption::None)?;
+        let payload: String = format.read_file(&mut vfs, "grebedb_meta_prev.grebedb")?;
+        assert_eq!(&payload, "meta");
+
+        vfs.write(path_2, &data, VfsSyncOption::None)?;
+        assert!(matches!(
+            format.read_file::<String, _>(&mut vfs, path_2),
+            Err(Error::DecryptionFailed { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "codec_bincode")]
+    #[test]
+    fn test_format_bincode() -> Result<(), Error> {
+        let mut format = Format::default();
+        format.set_codec(PayloadCodec::Bincode);
+        let mut vfs = MemoryVfs::new();
+
+        format.write_file(&mut vfs, "my_file", "hello world", VfsSyncOption::None)?;
+
+        assert_eq!(
+            parse_codec_flag("my_file", vfs.read("my_file")?[HEADER_LENGTH - 1])?,
+            PayloadCodec::Bincode
+        );
+
+        let mut format = Format::default();
+        let payload: String = format.read_file(&mut vfs, "my_file")?;
+
+        assert_eq!(&payload, "hello world");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_checksums() -> Result<(), Error> {
+        let mut checksums = vec![PageChecksum::Crc32c];
+
+        if cfg!(feature = "checksum_xxh3") {
+            checksums.push(PageChecksum::Xxh3);
+        }
+        if cfg!(feature = "checksum_crc64") {
+            checksums.push(PageChecksum::Crc64);
+        }
+
+        for checksum in checksums {
+            let mut format = Format::default();
+            format.set_compression(PageCompression::None);
+            format.set_checksum(checksum);
+            let mut vfs = MemoryVfs::new();
+
+            format.write_file(&mut vfs, "my_file", "hello world", VfsSyncOption::None)?;
+
+            let mut format = Format::default();
+            let payload: String = format.read_file(&mut vfs, "my_file")?;
+            assert_eq!(&payload, "hello world");
+
+            let mut data = vfs.read("my_file")?;
+            let last_index = data.len() - 1;
+            data[last_index] ^= 0xff;
+            vfs.write("my_file", &data, VfsSyncOption::None)?;
+
+            assert!(matches!(
+                format.read_file::<String, _>(&mut vfs, "my_file"),
+                Err(Error::BadChecksum { .. })
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_large_page() -> Result<(), Error> {
+        let mut format = Format::default();
+        let mut vfs = MemoryVfs::new();
+        let value = vec![0xabu8; 4 * 1024 * 1024];
+
+        format.write_file(
+            &mut vfs,
+            "my_file",
+            serde_bytes::Bytes::new(&value),
+            VfsSyncOption::None,
+        )?;
+
+        assert!(format.file_buffer.capacity() <= BUFFER_RETAIN_CAPACITY);
+        assert!(format.page_buffer.capacity() <= BUFFER_RETAIN_CAPACITY);
+
+        let payload: serde_bytes::ByteBuf = format.read_file(&mut vfs, "my_file")?;
+
+        assert_eq!(payload.as_slice(), value.as_slice());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_format_zstd_context_reuse() -> Result<(), Error> {
+        let mut format = Format::default();
+        let mut vfs = MemoryVfs::new();
+
+        for (index, level) in [0, 0, 3, 3, 0].iter().enumerate() {
+            format.set_compression(PageCompression::Zstd(*level));
+            let path = format!("my_file_{}", index);
+            let value = format!("hello world {}", index);
+
+            format.write_file(&mut vfs, &path, &value, VfsSyncOption::None)?;
+        }
+
+        let mut file = vfs.read("my_file_1")?;
+        let last_index = file.len() - 1;
+        file[last_index] ^= 0xff;
+        vfs.write("my_file_bad", &file, VfsSyncOption::None)?;
+
+        assert!(format
+            .read_file::<String, _>(&mut vfs, "my_file_bad")
+            .is_err());
+
+        for index in 0..5 {
+            let path = format!("my_file_{}", index);
+            let payload: String = format.read_file(&mut vfs, &path)?;
+
+            assert_eq!(payload, format!("hello world {}", index));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_content_hash() -> Result<(), Error> {
+        let mut format = Format::default();
+
+        let hash_1 = format.content_hash("hello world")?;
+        let hash_2 = format.content_hash("hello world")?;
+        let hash_3 = format.content_hash("hello world!")?;
+
+        assert_eq!(hash_1, hash_2);
+        assert_ne!(hash_1, hash_3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_page_layout() -> Result<(), Error> {
+        let mut format = Format::default();
+        let mut vfs = MemoryVfs::new();
+
+        format.set_compression(PageCompression::None);
+        format.write_file(&mut vfs, "my_file", "hello world", VfsSyncOption::None)?;
+
+        // MessagePack string of 11 bytes
+        let mut payload = vec![0xab];
+        payload.extend_from_slice(b"hello world");
+
+        let mut expected = MAGIC_BYTES.to_vec();
+        expected.push(COMPRESSION_FLAG_NONE);
+        expected.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        expected.extend_from_slice(&payload);
+        expected.extend_from_slice(&crc32c::crc32c(&payload).to_be_bytes());
+
+        assert_eq!(vfs.read("my_file")?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_last_payload_hash() -> Result<(), Error> {
+        let mut vfs = MemoryVfs::new();
+        let mut format = Format::default();
+
+        format.write_file(&mut vfs, "my_file", "hello world", VfsSyncOption::None)?;
+        let write_hash = format.last_payload_hash();
+        assert_eq!(write_hash, Some(format.content_hash("hello world")?));
+
+        let value: String = format.read_file(&mut vfs, "my_file")?;
+        assert_eq!(value, "hello world");
+        assert_eq!(format.last_payload_hash(), write_hash);
+
+        vfs.write("my_file", b"damaged", VfsSyncOption::None)?;
+        assert!(format.read_file::<String, _>(&mut vfs, "my_file").is_err());
+        assert_eq!(format.last_payload_hash(), None);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,91 @@
+use std::{
+    io,
+    sync::{Mutex, PoisonError},
+};
+
+use zstd::stream::raw::{Decoder, Encoder, InBuffer, Operation, OutBuffer, WriteBuf};
+
+/// Zstandard contexts kept for the lifetime of a `Format`.
+///
+/// Allocating and initializing a context is costly relative to compressing
+/// a single page, so the contexts are reset and reused for every page.
+///
+/// The contexts are not `Sync`, so they are wrapped in a mutex to keep the
+/// database `Sync`. The mutex is never locked because access always goes
+/// through `&mut self`.
+#[derive(Default)]
+pub struct ZstdContexts {
+    encoder: Mutex<Option<(i32, Encoder<'static>)>>,
+    decoder: Mutex<Option<Decoder<'static>>>,
+}
+
+impl ZstdContexts {
+    /// Return the encoder ready for a new frame.
+    ///
+    /// The encoder is only recreated when the compression level changes.
+    pub fn encoder(&mut self, level: i32) -> io::Result<ContextRef<'_, Encoder<'static>>> {
+        let slot = self
+            .encoder
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        match slot {
+            Some((current_level, encoder)) if *current_level == level => {
+                encoder.reinit()?;
+            }
+            _ => {
+                *slot = Some((level, Encoder::new(level)?));
+            }
+        }
+
+        Ok(ContextRef(&mut slot.as_mut().unwrap().1))
+    }
+
+    /// Return the decoder ready for a new frame.
+    pub fn decoder(&mut self) -> io::Result<ContextRef<'_, Decoder<'static>>> {
+        let slot = self
+            .decoder
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        match slot {
+            Some(decoder) => {
+                decoder.reinit()?;
+            }
+            None => {
+                *slot = Some(Decoder::new()?);
+            }
+        }
+
+        Ok(ContextRef(slot.as_mut().unwrap()))
+    }
+}
+
+/// Borrowed context so the stream adapters don't take ownership of it.
+pub struct ContextRef<'a, O>(&'a mut O);
+
+impl<O: Operation> Operation for ContextRef<'_, O> {
+    fn run<C: WriteBuf + ?Sized>(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_, C>,
+    ) -> io::Result<usize> {
+        self.0.run(input, output)
+    }
+
+    fn flush<C: WriteBuf + ?Sized>(&mut self, output: &mut OutBuffer<'_, C>) -> io::Result<usize> {
+        self.0.flush(output)
+    }
+
+    fn reinit(&mut self) -> io::Result<()> {
+        self.0.reinit()
+    }
+
+    fn finish<C: WriteBuf + ?Sized>(
+        &mut self,
+        output: &mut OutBuffer<'_, C>,
+        finished_frame: bool,
+    ) -> io::Result<usize> {
+        self.0.finish(output, finished_frame)
+    }
+}
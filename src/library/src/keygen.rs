@@ -0,0 +1,171 @@
+//! Generation of time-ordered keys.
+//!
+//! Keys that start with a timestamp are inserted at the end of the tree,
+//! which keeps nodes full and makes the database suitable for logs and
+//! queues. The generated keys are similar to ULIDs: a 48-bit timestamp in
+//! milliseconds followed by 80 random bits.
+//!
+//! ```
+//! use grebedb::{keygen::KeyGenerator, Database, Options};
+//!
+//! # fn main() -> Result<(), grebedb::Error> {
+//! let mut db = Database::open_memory(Options::default())?;
+//! let mut generator = KeyGenerator::new();
+//!
+//! let key_1 = generator.next_key()?;
+//! let key_2 = generator.next_key()?;
+//! assert!(key_1 < key_2);
+//!
+//! db.put(key_1, "first")?;
+//! db.put(key_2, "second")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+
+const TIMESTAMP_LENGTH: usize = 6;
+const RANDOM_LENGTH: usize = 10;
+const MAX_TIMESTAMP: u64 = (1 << (TIMESTAMP_LENGTH * 8)) - 1;
+const MAX_RANDOM: u128 = (1 << (RANDOM_LENGTH * 8)) - 1;
+
+/// Length of a generated key in bytes.
+pub const KEY_LENGTH: usize = TIMESTAMP_LENGTH + RANDOM_LENGTH;
+
+/// Generator of lexicographically sortable keys.
+///
+/// Keys are ordered by the time they were generated. Keys from the same
+/// generator are strictly increasing: within the same millisecond, or if the
+/// system clock goes backwards, the random part of the previous key is
+/// incremented instead of generated. Keys from different generators or
+/// processes are unlikely to collide because of the random part.
+#[derive(Debug, Default)]
+pub struct KeyGenerator {
+    last_timestamp: u64,
+    last_random: u128,
+}
+
+impl KeyGenerator {
+    /// Create a generator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a new key as bytes.
+    ///
+    /// The bytes are the big-endian timestamp followed by the random part.
+    pub fn next_key(&mut self) -> Result<[u8; KEY_LENGTH], Error> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0)
+            .min(MAX_TIMESTAMP);
+
+        if timestamp > self.last_timestamp {
+            self.last_timestamp = timestamp;
+            self.last_random = random_part()?;
+        } else if self.last_random < MAX_RANDOM {
+            self.last_random += 1;
+        } else if self.last_timestamp < MAX_TIMESTAMP {
+            self.last_timestamp += 1;
+            self.last_random = random_part()?;
+        } else {
+            return Err(Error::LimitExceeded);
+        }
+
+        let mut key = [0u8; KEY_LENGTH];
+        key[..TIMESTAMP_LENGTH]
+            .copy_from_slice(&self.last_timestamp.to_be_bytes()[8 - TIMESTAMP_LENGTH..]);
+        key[TIMESTAMP_LENGTH..]
+            .copy_from_slice(&self.last_random.to_be_bytes()[16 - RANDOM_LENGTH..]);
+
+        Ok(key)
+    }
+
+    /// Return a new key as text.
+    ///
+    /// The key is encoded as 26 characters of base32 with the extended hex
+    /// alphabet (RFC 4648), which sorts in the same order as the bytes.
+    pub fn next_string(&mut self) -> Result<String, Error> {
+        Ok(data_encoding::BASE32HEX_NOPAD.encode(&self.next_key()?))
+    }
+}
+
+/// Return the time in milliseconds since the Unix epoch stored in a key.
+///
+/// Returns `None` if the key is shorter than [`KEY_LENGTH`].
+pub fn key_timestamp(key: &[u8]) -> Option<u64> {
+    if key.len() < KEY_LENGTH {
+        return None;
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[8 - TIMESTAMP_LENGTH..].copy_from_slice(&key[..TIMESTAMP_LENGTH]);
+
+    Some(u64::from_be_bytes(bytes))
+}
+
+fn random_part() -> Result<u128, Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes[16 - RANDOM_LENGTH..])
+        .map_err(|error| Error::Other(Box::new(error)))?;
+
+    Ok(u128::from_be_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_key_increasing() {
+        let mut generator = KeyGenerator::new();
+        let mut previous_key = generator.next_key().unwrap();
+
+        for _ in 0..10000 {
+            let key = generator.next_key().unwrap();
+            assert!(key > previous_key);
+            previous_key = key;
+        }
+    }
+
+    #[test]
+    fn test_next_key_overflow() {
+        let mut generator = KeyGenerator {
+            last_timestamp: MAX_TIMESTAMP - 1,
+            last_random: MAX_RANDOM,
+        };
+
+        let key = generator.next_key().unwrap();
+        assert_eq!(key_timestamp(&key), Some(MAX_TIMESTAMP));
+
+        generator.last_random = MAX_RANDOM;
+        assert!(matches!(generator.next_key(), Err(Error::LimitExceeded)));
+    }
+
+    #[test]
+    fn test_next_string() {
+        let mut generator = KeyGenerator::new();
+        let text_1 = generator.next_string().unwrap();
+        let text_2 = generator.next_string().unwrap();
+
+        assert_eq!(text_1.len(), 26);
+        assert!(text_1 < text_2);
+    }
+
+    #[test]
+    fn test_key_timestamp() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let key = KeyGenerator::new().next_key().unwrap();
+        let timestamp = key_timestamp(&key).unwrap();
+
+        assert!(timestamp >= now);
+        assert!(timestamp - now < 60_000);
+        assert_eq!(key_timestamp(b"short"), None);
+    }
+}
@@ -24,33 +24,58 @@
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 
+mod codec;
+pub mod collections;
+mod encryption;
 pub mod error;
 pub mod export;
 mod format;
+#[cfg(feature = "system")]
+pub mod keygen;
 mod lru;
 mod page;
+mod stats;
 mod system;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod tree;
 pub mod vfs;
 
 use std::{
-    fmt::Debug,
+    fmt::{Debug, Display},
     ops::{Bound, RangeBounds},
     path::{Path, PathBuf},
+    sync::Arc,
     time::{Duration, Instant},
 };
 
-pub use crate::error::Error;
-use crate::format::Format;
-use crate::page::{Metadata as PageMetadata, Page, PageOpenMode, PageTableOptions};
-use crate::tree::{Node, Tree, TreeCursor, TreeMetadata};
-use crate::vfs::{MemoryVfs, OsVfs, ReadOnlyVfs, Vfs, VfsSyncOption};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub use crate::encryption::EncryptionKey;
+pub use crate::error::{Error, ErrorKind};
+use crate::format::{Format, PageChecksum, PageCompression, PayloadCodec};
+use crate::page::{Metadata as PageMetadata, Page, PageFanout, PageOpenMode, PageTableOptions};
+pub use crate::stats::{
+    CleanupReport, DiskUsage, FsckIssue, FsckIssueKind, FsckReport, LatencyHistogram, LostPage,
+    OperationStats, PageDiskUsage, PageInfo, PageKind, PageSummary, SalvageReport, Stats,
+    TreeStats, TreeSummary,
+};
+use crate::system::Clock;
+use crate::tree::{ExistingKeyAction, Node, Tree, TreeCursor, TreeMetadata};
+use crate::vfs::{MemoryVfs, OsVfs, Vfs, VfsSyncOption};
 
 /// Type alias for an owned key-value pair.
 pub type KeyValuePair = (Vec<u8>, Vec<u8>);
 
 /// Database configuration options.
-#[derive(Debug, Clone)]
+///
+/// Options can be loaded from configuration files using serde. Fields that
+/// are missing take their default values, and unknown fields are an error.
+/// The encryption keys and the drop policy are not serialized and must be
+/// set by the application.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Options {
     /// Option when opening a database. Default: LoadOrCreate.
     pub open_mode: OpenMode,
@@ -61,6 +86,9 @@ pub struct Options {
     /// When a node is full, it is split into two and the tree is rebalanced.
     ///
     /// A page contains a single node and a page is stored on disk as one file.
+    /// Modifying a pair rewrites the whole file of its page, so a smaller
+    /// value reduces the bytes written for small updates when values are
+    /// large, at the cost of more files.
     ///
     /// This option shouldn't be changed without making performance and resource usage
     /// benchmarks.
@@ -71,8 +99,26 @@ pub struct Options {
     /// The cache is used to store frequently accessed pages for reducing disk operations.
     ///
     /// If memory usage is too high, consider decreasing this value first.
+    ///
+    /// Memory is not bounded for large pages. Pages are serialized into
+    /// the compressor and deserialized from the decompressor, but reading
+    /// or writing a page holds the whole file in memory because the file
+    /// system interface and encryption work on whole files. LZ4 compression
+    /// also holds the uncompressed page.
     pub page_cache_size: usize,
 
+    /// Maximum number of modified pages held in the page cache.
+    /// Default: None (limited only by the cache size).
+    ///
+    /// When the limit is exceeded, a modifying function such as put() or
+    /// remove() first writes the modified pages to files without committing
+    /// them, so the pages become clean in the cache. Like pages evicted from
+    /// the cache, the files become part of the database on the next flush.
+    /// This option bounds the work of a flush when automatic flush is
+    /// disabled. The limit is checked before each modification, so one
+    /// operation may exceed it by the pages it modifies.
+    pub max_dirty_pages: Option<usize>,
+
     /// Whether to use file locking to prevent corruption by multiple processes.
     /// Default: true.
     pub file_locking: bool,
@@ -97,13 +143,125 @@ pub struct Options {
     /// Number of modifications required for automatic flush to be considered.
     /// Default: 2048
     ///
-    /// When the threshold is reached after 300 seconds,
-    /// or the threshold × 2 is reached after 60 seconds,
+    /// When the threshold is reached after
+    /// [`Self::automatic_flush_long_interval`],
+    /// or the threshold × [`Self::automatic_flush_short_multiplier`]
+    /// is reached after [`Self::automatic_flush_short_interval`],
     /// a flush is scheduled to be performed on the next modification.
     pub automatic_flush_threshold: usize,
 
+    /// Minimum time since the last automatic flush before the threshold
+    /// triggers a flush. Default: 300 seconds.
+    ///
+    /// In configuration files, the value is a number of seconds, either
+    /// an integer or a float.
+    #[serde(with = "duration_seconds")]
+    pub automatic_flush_long_interval: Duration,
+
+    /// Minimum time since the last automatic flush before the threshold ×
+    /// multiplier triggers a flush. Default: 60 seconds.
+    ///
+    /// In configuration files, the value is a number of seconds, either
+    /// an integer or a float.
+    #[serde(with = "duration_seconds")]
+    pub automatic_flush_short_interval: Duration,
+
+    /// Multiplier of the threshold used with
+    /// [`Self::automatic_flush_short_interval`]. Default: 2.
+    pub automatic_flush_short_multiplier: usize,
+
     /// Compression level for each page. Default: Low.
     pub compression_level: CompressionLevel,
+
+    /// Compression algorithm for each page. Default: Zstd.
+    ///
+    /// The algorithm is only used when writing pages. Pages written with
+    /// any supported algorithm can be read regardless of this option.
+    pub compression_algorithm: CompressionAlgorithm,
+
+    /// Serialization format of pages when creating a database.
+    /// Default: MessagePack.
+    ///
+    /// The format is recorded in each file. When opening an existing database,
+    /// the format of the database is used instead of this option. Metadata
+    /// files are always MessagePack.
+    pub codec: Codec,
+
+    /// Checksum function for each page. Default: Crc32c.
+    ///
+    /// The function is recorded in each file, so files written with
+    /// different functions can be read regardless of this option.
+    pub checksum: Checksum,
+
+    /// Key for encrypting files. Default: None.
+    ///
+    /// When a key is provided, all files written by the database are
+    /// encrypted and authenticated, and all files read must be encrypted with
+    /// the same key. A database cannot be converted between encrypted and
+    /// unencrypted without export and import.
+    ///
+    /// Requires the `encryption` feature.
+    #[serde(skip)]
+    pub encryption_key: Option<EncryptionKey>,
+
+    /// Additional keys for decrypting files. Default: empty.
+    ///
+    /// Files that cannot be decrypted with [`Self::encryption_key`] are tried
+    /// with these keys. This option allows opening a database that is in the
+    /// middle of a key rotation by [`Database::rewrite_with_key()`].
+    #[serde(skip)]
+    pub previous_encryption_keys: Vec<EncryptionKey>,
+
+    /// Number of previous metadata generations to keep. Default: 1.
+    ///
+    /// Each flush creates a new generation. When greater than 1, page files
+    /// replaced by a flush are kept until their generation is discarded,
+    /// so earlier generations can be opened with
+    /// [`Database::open_at_generation()`]. Keeping generations uses
+    /// additional disk space for every page modified during that time.
+    pub metadata_generations: usize,
+
+    /// Number of flushes before a removed page can be reused. Default: 1.
+    ///
+    /// The default keeps the page of a removed node unused until the removal
+    /// has been flushed, so files of the removed page are not replaced before
+    /// the removal is persisted. A larger value keeps the page unused for
+    /// that many flushes. When 0, the page can be reused immediately by a
+    /// new node.
+    pub free_page_reuse_delay: usize,
+
+    /// Directory layout of page files when creating a database.
+    /// Default: Large.
+    ///
+    /// The layout is recorded in the metadata. When opening an existing
+    /// database, the layout of the database is used instead of this option.
+    pub directory_fanout: DirectoryFanout,
+
+    /// Action when the database is dropped without persisting its changes.
+    /// Default: Ignore.
+    ///
+    /// The policy applies when the final flush on drop fails, or when
+    /// automatic flush is disabled and there are changes that were not
+    /// flushed.
+    #[serde(skip)]
+    pub drop_policy: DropPolicy,
+
+    /// Clock for the intervals of automatic flushing.
+    /// Default: None (system clock).
+    ///
+    /// Requires the `testing` feature.
+    #[cfg(feature = "testing")]
+    #[serde(skip)]
+    pub clock: Option<testing::MockClock>,
+
+    /// Seed for generating the UUID of a new database and of
+    /// [`Database::regenerate_uuid()`] deterministically.
+    /// Default: None (random).
+    ///
+    /// Requires the `testing` feature.
+    #[cfg(feature = "testing")]
+    #[serde(skip)]
+    pub uuid_seed: Option<u64>,
 }
 
 impl Default for Options {
@@ -112,17 +270,38 @@ impl Default for Options {
             open_mode: OpenMode::default(),
             keys_per_node: 1024,
             page_cache_size: 64,
+            max_dirty_pages: None,
             file_locking: true,
             file_sync: SyncOption::default(),
             automatic_flush: true,
             automatic_flush_threshold: 2048,
+            automatic_flush_long_interval: Duration::from_secs(300),
+            automatic_flush_short_interval: Duration::from_secs(60),
+            automatic_flush_short_multiplier: 2,
             compression_level: CompressionLevel::default(),
+            compression_algorithm: CompressionAlgorithm::default(),
+            codec: Codec::default(),
+            checksum: Checksum::default(),
+            encryption_key: None,
+            previous_encryption_keys: Vec::new(),
+            metadata_generations: 1,
+            free_page_reuse_delay: 1,
+            directory_fanout: DirectoryFanout::default(),
+            drop_policy: DropPolicy::default(),
+            #[cfg(feature = "testing")]
+            clock: None,
+            #[cfg(feature = "testing")]
+            uuid_seed: None,
         }
     }
 }
 
 impl Options {
-    fn validate(&self) -> Result<(), Error> {
+    /// Check the options for values out of range.
+    ///
+    /// This function is called when opening a database and can be used to
+    /// check options loaded from a configuration file in advance.
+    pub fn validate(&self) -> Result<(), Error> {
         if self.keys_per_node < 2 {
             return Err(Error::InvalidConfig {
                 message: "required keys_per_node >= 2",
@@ -133,6 +312,56 @@ impl Options {
                 message: "required page_cache_size >= 1",
             });
         }
+        if self.max_dirty_pages == Some(0) {
+            return Err(Error::InvalidConfig {
+                message: "required max_dirty_pages >= 1",
+            });
+        }
+        if self.automatic_flush_short_multiplier < 1 {
+            return Err(Error::InvalidConfig {
+                message: "required automatic_flush_short_multiplier >= 1",
+            });
+        }
+        if self.metadata_generations < 1 {
+            return Err(Error::InvalidConfig {
+                message: "required metadata_generations >= 1",
+            });
+        }
+
+        if !PageFanout::from(self.directory_fanout).is_valid() {
+            return Err(Error::InvalidConfig {
+                message: "directory_fanout out of range",
+            });
+        }
+
+        #[cfg(not(feature = "codec_bincode"))]
+        if self.codec == Codec::Bincode {
+            return Err(Error::CodecUnavailable);
+        }
+
+        #[cfg(not(feature = "checksum_xxh3"))]
+        if self.checksum == Checksum::Xxh3 {
+            return Err(Error::ChecksumUnavailable);
+        }
+
+        #[cfg(not(feature = "checksum_crc64"))]
+        if self.checksum == Checksum::Crc64 {
+            return Err(Error::ChecksumUnavailable);
+        }
+
+        #[cfg(not(feature = "encryption"))]
+        if self.encryption_key.is_some() {
+            return Err(Error::EncryptionUnavailable);
+        }
+
+        #[cfg(feature = "zstd")]
+        if let CompressionLevel::Custom(level) = self.compression_level {
+            if !zstd::compression_level_range().contains(&level) {
+                return Err(Error::InvalidConfig {
+                    message: "compression_level out of range",
+                });
+            }
+        }
 
         Ok(())
     }
@@ -146,13 +375,28 @@ impl From<Options> for PageTableOptions {
             file_locking: options.file_locking,
             file_sync: options.file_sync.into(),
             keys_per_node: options.keys_per_node,
-            compression_level: options.compression_level.to_zstd(),
+            compression: options
+                .compression_algorithm
+                .to_page_compression(options.compression_level),
+            codec: options.codec.into(),
+            checksum: options.checksum.into(),
+            encryption_key: options.encryption_key,
+            previous_encryption_keys: options.previous_encryption_keys,
+            metadata_generations: options.metadata_generations,
+            generation: 0,
+            free_page_reuse_delay: options.free_page_reuse_delay,
+            fanout: options.directory_fanout.into(),
+            #[cfg(feature = "testing")]
+            uuid_seed: options.uuid_seed,
+            #[cfg(not(feature = "testing"))]
+            uuid_seed: None,
         }
     }
 }
 
 /// Database open modes.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum OpenMode {
     /// Open an existing database only if it exists.
     LoadOnly,
@@ -182,7 +426,8 @@ impl From<OpenMode> for PageOpenMode {
 }
 
 /// Database data compression level.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CompressionLevel {
     /// Disable compression.
     None,
@@ -206,6 +451,13 @@ pub enum CompressionLevel {
     ///
     /// Currently, this corresponds to Zstandard level 19.
     High,
+
+    /// Custom Zstandard level.
+    ///
+    /// Negative values are faster levels with lower compression ratios.
+    /// Valid values are determined by the Zstandard library.
+    /// The value is ignored by algorithms that do not support levels.
+    Custom(i32),
 }
 
 impl Default for CompressionLevel {
@@ -222,6 +474,208 @@ impl CompressionLevel {
             Self::Low => Some(3),
             Self::Medium => Some(9),
             Self::High => Some(19),
+            Self::Custom(level) => Some(level),
+        }
+    }
+}
+
+/// Database data compression algorithm.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionAlgorithm {
+    /// Zstandard compression.
+    ///
+    /// Requires the `compression` feature.
+    #[default]
+    Zstd,
+
+    /// LZ4 compression.
+    ///
+    /// LZ4 has lower CPU cost than Zstandard at the expense of lower
+    /// compression ratios. The compression level option is ignored except for
+    /// [`CompressionLevel::None`] which disables compression.
+    ///
+    /// Requires the `compression_lz4` feature.
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    fn to_page_compression(self, level: CompressionLevel) -> PageCompression {
+        match (self, level.to_zstd()) {
+            (_, None) => PageCompression::None,
+            (Self::Zstd, Some(level)) => PageCompression::Zstd(level),
+            (Self::Lz4, Some(_)) => PageCompression::Lz4,
+        }
+    }
+}
+
+/// Serialization format of database pages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    /// MessagePack format.
+    #[default]
+    MessagePack,
+
+    /// Bincode format.
+    ///
+    /// Bincode is more compact and faster to process than MessagePack,
+    /// but the files are not self-describing.
+    ///
+    /// Requires the `codec_bincode` feature.
+    Bincode,
+}
+
+impl From<Codec> for PayloadCodec {
+    fn from(option: Codec) -> Self {
+        match option {
+            Codec::MessagePack => PayloadCodec::MessagePack,
+            Codec::Bincode => PayloadCodec::Bincode,
+        }
+    }
+}
+
+/// Checksum function for detecting corrupted pages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Checksum {
+    /// 32-bit CRC-32C (Castagnoli).
+    #[default]
+    Crc32c,
+
+    /// 64-bit XXH3 hash.
+    ///
+    /// XXH3 is faster than CRC-32C for large pages.
+    ///
+    /// Requires the `checksum_xxh3` feature.
+    Xxh3,
+
+    /// 64-bit CRC-64/XZ.
+    ///
+    /// Requires the `checksum_crc64` feature.
+    Crc64,
+}
+
+impl From<Checksum> for PageChecksum {
+    fn from(option: Checksum) -> Self {
+        match option {
+            Checksum::Crc32c => PageChecksum::Crc32c,
+            Checksum::Xxh3 => PageChecksum::Xxh3,
+            Checksum::Crc64 => PageChecksum::Crc64,
+        }
+    }
+}
+
+/// Directory layout of page files.
+///
+/// Page files are grouped into nested directories by their page ID to avoid
+/// directories with too many files. Each directory at the bottom level
+/// contains the files of up to 256 pages for the preset layouts.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectoryFanout {
+    /// One directory level, suitable for databases of up to about 65 thousand pages.
+    Small,
+
+    /// Two directory levels, suitable for databases of up to about 16 million pages.
+    Medium,
+
+    /// Seven directory levels, suitable for billions of pages.
+    #[default]
+    Large,
+
+    /// Custom layout.
+    ///
+    /// The page ID, excluding its lowest `width` bits, is split into `depth`
+    /// directory levels of `width` bits each. The top level directory
+    /// contains any remaining bits. The depth must be at least 1, the width
+    /// must be between 1 and 16, and the depth × width must not exceed 64.
+    Custom {
+        /// Number of directory levels.
+        depth: u8,
+        /// Number of bits of the page ID per directory level.
+        width: u8,
+    },
+}
+
+/// Resolution of keys that exist in both databases when merging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeConflictPolicy {
+    /// Keep the existing value in the destination database.
+    KeepOurs,
+
+    /// Replace the existing value with the value from the source database.
+    KeepTheirs,
+
+    /// Stop the merge with [`Error::KeyConflict`].
+    Error,
+}
+
+/// Action when a database is dropped without persisting its changes.
+#[derive(Default, Clone)]
+pub enum DropPolicy {
+    /// Discard the changes silently.
+    #[default]
+    Ignore,
+
+    /// Call the given function with the reason.
+    ///
+    /// The function is called from `Drop` and should not panic.
+    Callback(Arc<dyn Fn(DropFailure<'_>) + Send + Sync>),
+
+    /// Print the reason to standard error and abort the process.
+    Abort,
+}
+
+impl Debug for DropPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ignore => f.write_str("Ignore"),
+            Self::Callback(_) => f.write_str("Callback(..)"),
+            Self::Abort => f.write_str("Abort"),
+        }
+    }
+}
+
+impl DropPolicy {
+    fn handle(&self, failure: DropFailure<'_>) {
+        match self {
+            Self::Ignore => {}
+            Self::Callback(callback) => callback(failure),
+            Self::Abort => {
+                eprintln!("grebedb: {}", failure);
+                std::process::abort();
+            }
+        }
+    }
+}
+
+/// Reason a database did not persist its changes when dropped.
+#[derive(Debug)]
+pub enum DropFailure<'a> {
+    /// The final flush failed with the given error.
+    FlushFailed(&'a Error),
+
+    /// Automatic flush is disabled and changes were not flushed.
+    UnflushedChanges,
+}
+
+impl Display for DropFailure<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FlushFailed(error) => write!(f, "flush on drop failed: {}", error),
+            Self::UnflushedChanges => f.write_str("unflushed changes discarded on drop"),
+        }
+    }
+}
+
+impl From<DirectoryFanout> for PageFanout {
+    fn from(option: DirectoryFanout) -> Self {
+        match option {
+            DirectoryFanout::Small => PageFanout { depth: 1, width: 8 },
+            DirectoryFanout::Medium => PageFanout { depth: 2, width: 8 },
+            DirectoryFanout::Large => PageFanout { depth: 7, width: 8 },
+            DirectoryFanout::Custom { depth, width } => PageFanout { depth, width },
         }
     }
 }
@@ -229,7 +683,8 @@ impl CompressionLevel {
 /// Level of file synchronization for files created by the database.
 ///
 /// These options are equivalent to [`vfs::VfsSyncOption`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SyncOption {
     /// Don't require any flushing and simply overwrite files.
     None,
@@ -262,47 +717,51 @@ impl From<SyncOption> for VfsSyncOption {
 }
 
 /// GrebeDB database interface.
-pub struct Database {
+///
+/// The type parameter is the virtual file system type. By default, it is a
+/// boxed [`Vfs`] trait object as used by [`Database::open()`]. Use
+/// [`Database::open_generic()`] to use a concrete type which avoids dynamic
+/// dispatch when accessing files.
+pub struct Database<F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
     options: Options,
-    tree: Tree,
+    tree: Tree<F>,
     flush_tracker: Option<FlushTracker>,
+    stats: Stats,
 }
 
 impl Database {
     /// Open a database using the given virtual file system and options.
     pub fn open(vfs: Box<dyn Vfs + Sync + Send>, options: Options) -> Result<Self, Error> {
-        options.validate()?;
-
-        let vfs: Box<dyn Vfs + Sync + Send> = if options.open_mode == OpenMode::ReadOnly {
-            Box::new(ReadOnlyVfs::new(vfs))
-        } else {
-            vfs
-        };
-
-        let mut tree = Tree::open(vfs, options.clone().into())?;
-
-        match options.open_mode {
-            OpenMode::CreateOnly | OpenMode::LoadOrCreate => {
-                tree.init_if_empty()?;
-                tree.upgrade()?;
-            }
-            OpenMode::LoadOnly => {
-                tree.upgrade()?;
-            }
-            _ => {}
-        }
+        Self::open_generic(vfs, options)
+    }
 
-        let flush_tracker = if options.automatic_flush && options.open_mode != OpenMode::ReadOnly {
-            Some(FlushTracker::new(options.automatic_flush_threshold))
-        } else {
-            None
+    /// Open a database as it was at an earlier flush.
+    ///
+    /// Generation 0 is the most recent flush, 1 is the flush before it,
+    /// and so on up to [`Options::metadata_generations`]. The database is
+    /// opened in read-only mode regardless of [`Options::open_mode`].
+    ///
+    /// Reading pages that were replaced after the generation requires that
+    /// the database was flushed with `metadata_generations` large enough to
+    /// keep the generation. Otherwise, an error is returned when the open
+    /// fails or when such a page is read.
+    ///
+    /// To roll back the database, export the contents of the opened database
+    /// and import them into a new database.
+    pub fn open_at_generation(
+        vfs: Box<dyn Vfs + Sync + Send>,
+        options: Options,
+        generation: usize,
+    ) -> Result<Self, Error> {
+        let options = Options {
+            open_mode: OpenMode::ReadOnly,
+            ..options
         };
 
-        Ok(Self {
-            options,
-            tree,
-            flush_tracker,
-        })
+        Self::open_generic_(vfs, options, generation)
     }
 
     /// Open a database in temporary memory.
@@ -319,14 +778,136 @@ impl Database {
     {
         Self::open(Box::new(OsVfs::new(root_path)), options)
     }
+}
+
+impl<F> Database<F>
+where
+    F: Vfs,
+{
+    /// Open a database using the given virtual file system type and options.
+    ///
+    /// Unlike [`Database::open()`], the file system is not boxed, so the
+    /// compiler can use static dispatch for file operations.
+    ///
+    /// In [`OpenMode::ReadOnly`], the file system is wrapped in
+    /// [`vfs::ReadOnlyVfs`] so that files are never modified.
+    pub fn open_generic(vfs: F, options: Options) -> Result<Self, Error> {
+        Self::open_generic_(vfs, options, 0)
+    }
+
+    fn open_generic_(vfs: F, options: Options, generation: usize) -> Result<Self, Error> {
+        options.validate()?;
+
+        let mut page_table_options: PageTableOptions = options.clone().into();
+        page_table_options.generation = generation;
+
+        let mut tree = Tree::open(vfs, page_table_options)?;
+
+        Self::prepare_tree(&mut tree, options.open_mode)?;
+
+        let flush_tracker = if options.automatic_flush && options.open_mode != OpenMode::ReadOnly {
+            Some(FlushTracker::new(&options))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            options,
+            tree,
+            flush_tracker,
+            stats: Stats::default(),
+        })
+    }
+
+    fn prepare_tree(tree: &mut Tree<F>, open_mode: OpenMode) -> Result<(), Error> {
+        match open_mode {
+            OpenMode::CreateOnly | OpenMode::LoadOrCreate => {
+                tree.init_if_empty()?;
+                tree.upgrade()?;
+            }
+            OpenMode::LoadOnly => {
+                tree.upgrade()?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
 
     /// Return database metadata information.
     pub fn metadata(&self) -> Metadata {
         Metadata {
+            uuid: self.tree.uuid(),
             tree_metadata: self.tree.metadata(),
         }
     }
 
+    /// Return the revision of the database.
+    ///
+    /// The revision is incremented when changes are flushed. A database
+    /// opened in read-only mode can detect changes flushed by another process
+    /// by calling [`Self::reopen()`] and comparing the revision.
+    pub fn revision(&self) -> u64 {
+        self.tree.revision()
+    }
+
+    pub(crate) fn uuid(&self) -> Uuid {
+        self.tree.uuid()
+    }
+
+    pub(crate) fn set_pinned(&mut self, pinned: bool) {
+        self.tree.set_pinned(pinned)
+    }
+
+    pub(crate) fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub(crate) fn set_last_export_revision(&mut self, revision: u64) -> Result<(), Error> {
+        self.tree.set_last_export_revision(revision)
+    }
+
+    pub(crate) fn set_import_progress(
+        &mut self,
+        progress: Option<(u64, u64)>,
+    ) -> Result<(), Error> {
+        self.tree.set_import_progress(progress)
+    }
+
+    pub(crate) fn set_import_source(&mut self, source: String) -> Result<(), Error> {
+        self.tree.set_import_source(source)
+    }
+
+    pub(crate) fn for_each_changed_pair<C>(
+        &mut self,
+        since_revision: u64,
+        callback: C,
+    ) -> Result<(), Error>
+    where
+        C: FnMut(&[u8], &[u8]) -> Result<(), Error>,
+    {
+        self.tree.for_each_changed_pair(since_revision, callback)
+    }
+
+    /// Return the application metadata previously stored with
+    /// [`Self::set_app_metadata()`].
+    pub fn get_app_metadata(&self) -> Option<&[u8]> {
+        self.metadata().app_metadata()
+    }
+
+    /// Store a small value in the database metadata.
+    ///
+    /// The value is intended for data such as a schema version or a
+    /// replication cursor and avoids reserving a key for it. Like other
+    /// modifications, it is persisted atomically on the next flush. The
+    /// value is written with every flush, so it should be kept small.
+    pub fn set_app_metadata<V>(&mut self, value: V) -> Result<(), Error>
+    where
+        V: Into<Vec<u8>>,
+    {
+        self.tree.set_app_metadata(value.into())
+    }
+
     /// Return whether the key exists.
     pub fn contains_key<K>(&mut self, key: K) -> Result<bool, Error>
     where
@@ -341,7 +922,7 @@ impl Database {
         K: AsRef<[u8]>,
     {
         let mut value = Vec::new();
-        if self.tree.get(key.as_ref(), &mut value)? {
+        if self.get_buf(key, &mut value)? {
             Ok(Some(value))
         } else {
             Ok(None)
@@ -356,7 +937,11 @@ impl Database {
     where
         K: AsRef<[u8]>,
     {
-        self.tree.get(key.as_ref(), value_destination)
+        let start_time = Instant::now();
+        let result = self.tree.get(key.as_ref(), value_destination);
+        self.stats.get.record(start_time.elapsed(), &result);
+
+        result
     }
 
     /// Store a key-value pair.
@@ -365,8 +950,27 @@ impl Database {
         K: Into<Vec<u8>>,
         V: Into<Vec<u8>>,
     {
-        self.maybe_flush(true)?;
-        self.tree.put(key.into(), value.into())
+        let start_time = Instant::now();
+        let result = self
+            .maybe_flush(true)
+            .and_then(|_| self.tree.put(key.into(), value.into()));
+        self.stats.put.record(start_time.elapsed(), &result);
+
+        result
+    }
+
+    // Store a key-value pair computed from the current value of the key.
+    pub(crate) fn update_value<U>(&mut self, key: Vec<u8>, update: U) -> Result<(), Error>
+    where
+        U: FnOnce(Option<&[u8]>) -> Result<Vec<u8>, Error>,
+    {
+        let start_time = Instant::now();
+        let result = self
+            .maybe_flush(true)
+            .and_then(|_| self.tree.update_value(key, update));
+        self.stats.put.record(start_time.elapsed(), &result);
+
+        result
     }
 
     /// Remove a key-value pair by its key.
@@ -376,30 +980,157 @@ impl Database {
     where
         K: AsRef<[u8]>,
     {
-        self.maybe_flush(true)?;
-        self.tree.remove(key.as_ref())
+        let start_time = Instant::now();
+        let result = self
+            .maybe_flush(true)
+            .and_then(|_| self.tree.remove(key.as_ref()));
+        self.stats.remove.record(start_time.elapsed(), &result);
+
+        result
     }
 
-    /// Return a cursor for iterating all the key-value pairs.
-    pub fn cursor(&mut self) -> Result<Cursor<'_>, Error> {
-        Ok(Cursor::new(&mut self.tree))
+    /// Remove all key-value pairs.
+    ///
+    /// The tree is reset to an empty root and the IDs of all other pages are
+    /// freed for reuse. Like other modifications, the removal is persisted
+    /// on the next flush and the files of the removed pages are deleted
+    /// at that time.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.tree.clear()
     }
 
-    /// Return a cursor for iterating all the key-value pairs within the given
-    /// range.
+    /// Insert all the key-value pairs of another database.
     ///
-    /// This method is equivalent of obtaining a cursor and calling
-    /// [`Cursor::seek()`] and [`Cursor::set_range()`]
-    pub fn cursor_range<K, R>(&mut self, range: R) -> Result<Cursor<'_>, Error>
+    /// The pairs are read in key order and inserted in batches, where the
+    /// pairs belonging to the same node are inserted together. The policy
+    /// determines what happens to keys that exist in both databases.
+    ///
+    /// With [`MergeConflictPolicy::Error`], [`Error::KeyConflict`] is returned
+    /// on the first conflicting key. Pairs before the conflicting key have
+    /// already been inserted at that point and can be discarded with
+    /// [`Self::reopen()`] if the database was not flushed.
+    pub fn merge_from<G>(
+        &mut self,
+        other: &mut Database<G>,
+        conflict_policy: MergeConflictPolicy,
+    ) -> Result<(), Error>
     where
-        K: AsRef<[u8]>,
-        R: RangeBounds<K>,
+        G: Vfs,
     {
-        let mut cursor = Cursor::new(&mut self.tree);
+        const BATCH_SIZE: usize = 1024;
 
-        match range.start_bound() {
-            Bound::Included(key) => {
-                cursor.seek(key)?;
+        let existing_key = match conflict_policy {
+            MergeConflictPolicy::KeepOurs => ExistingKeyAction::Keep,
+            MergeConflictPolicy::KeepTheirs => ExistingKeyAction::Replace,
+            MergeConflictPolicy::Error => ExistingKeyAction::Fail,
+        };
+        let mut cursor = other.cursor()?;
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+        loop {
+            let mut key = Vec::new();
+            let mut value = Vec::new();
+            let has_pair = cursor.next_buf(&mut key, &mut value)?;
+
+            if has_pair {
+                batch.push((key, value));
+            }
+
+            if batch.len() >= BATCH_SIZE || (!has_pair && !batch.is_empty()) {
+                if let Some(flush_tracker) = &mut self.flush_tracker {
+                    flush_tracker.add_modifications(batch.len());
+                }
+
+                self.maybe_flush(false)?;
+                self.tree.put_sorted(batch.drain(..), existing_key)?;
+            }
+
+            if !has_pair {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove multiple key-value pairs by their keys.
+    ///
+    /// The keys are sorted and removed grouped by the node that contains
+    /// them, so each affected node is modified only once. This function is
+    /// more efficient than calling [`Self::remove()`] for each key when
+    /// there are many keys. No error occurs if a key does not exist.
+    pub fn remove_many<I, K>(&mut self, keys: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<[u8]>,
+    {
+        let mut keys: Vec<Vec<u8>> = keys.into_iter().map(|key| key.as_ref().to_vec()).collect();
+        keys.sort_unstable();
+        keys.dedup();
+
+        self.maybe_flush(true)?;
+        self.tree.remove_many(&keys)
+    }
+
+    /// Remove all key-value pairs within the given range.
+    ///
+    /// The keys within the range are collected first and then removed like
+    /// [`Self::remove_many()`], so memory proportional to the keys in the
+    /// range is used. Returns the number of removed pairs.
+    pub fn remove_range<K, R>(&mut self, range: R) -> Result<u64, Error>
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        let mut cursor = self.cursor_range(range)?;
+        let mut keys = Vec::new();
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+
+        while cursor.next_buf(&mut key, &mut value)? {
+            keys.push(key.clone());
+        }
+
+        self.maybe_flush(true)?;
+        self.tree.remove_many(&keys)?;
+
+        Ok(keys.len() as u64)
+    }
+
+    /// Keep only the key-value pairs for which the predicate returns true.
+    ///
+    /// The leaf nodes are walked once in key order and the rejected pairs
+    /// are removed in place. Nodes that become empty are removed from the
+    /// tree. This function is more efficient than removing the keys
+    /// individually, such as when pruning expired entries.
+    pub fn retain<P>(&mut self, predicate: P) -> Result<(), Error>
+    where
+        P: FnMut(&[u8], &[u8]) -> bool,
+    {
+        self.maybe_flush(true)?;
+        self.tree.retain(predicate)
+    }
+
+    /// Return a cursor for iterating all the key-value pairs.
+    pub fn cursor(&mut self) -> Result<Cursor<'_, F>, Error> {
+        Ok(Cursor::new(&mut self.tree))
+    }
+
+    /// Return a cursor for iterating all the key-value pairs within the given
+    /// range.
+    ///
+    /// This method is equivalent of obtaining a cursor and calling
+    /// [`Cursor::seek()`] and [`Cursor::set_range()`]
+    pub fn cursor_range<K, R>(&mut self, range: R) -> Result<Cursor<'_, F>, Error>
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        let mut cursor = Cursor::new(&mut self.tree);
+
+        match range.start_bound() {
+            Bound::Included(key) => {
+                cursor.seek(key)?;
             }
             Bound::Excluded(key) => {
                 let mut key = key.as_ref().to_vec();
@@ -414,6 +1145,51 @@ impl Database {
         Ok(cursor)
     }
 
+    /// Return an iterator over the keys within the given range.
+    ///
+    /// Only the keys are copied from the nodes. Errors are reported by
+    /// [`Keys::error()`].
+    pub fn keys<K, R>(&mut self, range: R) -> Result<Keys<'_, F>, Error>
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        Ok(Keys {
+            cursor: self.cursor_range(range)?,
+        })
+    }
+
+    /// Return an iterator over the values, in key order, of the keys within
+    /// the given range.
+    ///
+    /// Only the values are copied from the nodes. Errors are reported by
+    /// [`Values::error()`].
+    pub fn values<K, R>(&mut self, range: R) -> Result<Values<'_, F>, Error>
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        Ok(Values {
+            cursor: self.cursor_range(range)?,
+        })
+    }
+
+    /// Return the estimated number of key-value pairs within the given range.
+    ///
+    /// The estimate is calculated from the key boundaries of the internal
+    /// nodes and the average number of pairs per leaf node, so the leaf
+    /// nodes are not scanned. It is suitable for purposes such as query
+    /// planning and progress bars, but it can be inaccurate, particularly
+    /// for small ranges or after many removals.
+    pub fn estimate_range_size<K, R>(&mut self, range: R) -> Result<u64, Error>
+    where
+        K: AsRef<[u8]>,
+        R: RangeBounds<K>,
+    {
+        let range = concrete_range(range);
+        self.tree.estimate_range_size(&slice_range(&range))
+    }
+
     /// Persist all modifications to the file system.
     ///
     /// Calling this function ensures that all changes pending, whether cached
@@ -424,7 +1200,68 @@ impl Database {
     ///
     /// For details about automatic flushing, see [`Options`].
     pub fn flush(&mut self) -> Result<(), Error> {
-        self.tree.flush()
+        let start_time = Instant::now();
+        let result = self.tree.flush();
+        self.stats.flush.record(start_time.elapsed(), &result);
+
+        result
+    }
+
+    /// Write the modified pages in the cache to files without committing
+    /// them.
+    ///
+    /// The written pages become part of the database on the next
+    /// [`Self::flush()`], which then only needs to sync the files and write
+    /// the metadata. Calling this function at idle times spreads out the cost
+    /// of a flush. Like other changes, the pages are discarded if the
+    /// database is not flushed.
+    ///
+    /// See also [`Options::max_dirty_pages`].
+    pub fn write_back_dirty(&mut self) -> Result<(), Error> {
+        self.tree.write_modified_pages()
+    }
+
+    /// Discard changes since the last successful flush and load the
+    /// database again.
+    ///
+    /// When an operation fails, the database refuses further operations
+    /// with [`Error::Closed`] to prevent further corruption. Once the cause
+    /// of the error, such as a full disk, is resolved, this function can be
+    /// used to resume using the database without opening it again.
+    pub fn reopen(&mut self) -> Result<(), Error> {
+        self.tree.reopen()?;
+
+        Self::prepare_tree(&mut self.tree, self.options.open_mode)?;
+
+        if self.flush_tracker.is_some() {
+            self.flush_tracker = Some(FlushTracker::new(&self.options));
+        }
+
+        Ok(())
+    }
+
+    /// Return operation and I/O statistics since the database was opened
+    /// or since the statistics were last reset.
+    ///
+    /// Automatic flushes are included in the flush statistics and in the
+    /// latency of the operation that triggered them.
+    pub fn stats(&self) -> Stats {
+        let mut stats = self.stats.clone();
+        stats.io = self.tree.io_counters();
+        stats
+    }
+
+    /// Reset all statistics to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+        self.tree.reset_io_counters();
+    }
+
+    /// Return a description of the error that closed the database.
+    ///
+    /// Returns `None` if the database is not closed.
+    pub fn last_error(&self) -> Option<&str> {
+        self.tree.last_error()
     }
 
     /// Check the database for internal consistency and data integrity.
@@ -434,6 +1271,9 @@ impl Database {
     ///
     /// The function returns an error on the first verification failure or
     /// other error.
+    ///
+    /// Pages recovered from torn writes while verifying are not errors but
+    /// are reported by [`Self::recovered_pages()`].
     pub fn verify<P>(&mut self, progress_callback: P) -> Result<(), Error>
     where
         P: FnMut(usize, usize),
@@ -441,9 +1281,305 @@ impl Database {
         self.tree.verify_tree(progress_callback)
     }
 
-    /// Print the tree for debugging purposes.
-    pub fn debug_print_tree(&mut self) -> Result<(), Error> {
-        self.tree.dump_tree()
+    /// Check the structure and the files of the database and return the
+    /// problems found.
+    ///
+    /// Unlike [`Self::verify()`], which stops at the first error, checking
+    /// continues after problems and collects them in a report: pages that
+    /// can't be read, page files that don't belong to the tree or weren't
+    /// committed, links between leaf nodes that don't match the tree, an
+    /// inconsistent list of unused page IDs, files with a different UUID,
+    /// and metadata backup files that disagree with the main file. The order
+    /// of keys is not checked, so use both functions for a complete check.
+    ///
+    /// All pages and page files are read. The files are compared with the
+    /// flushed state, so modifications that are not flushed may be reported
+    /// as problems. Most problems with files can be fixed with
+    /// [`Self::clean_up()`].
+    pub fn fsck(&mut self) -> Result<FsckReport, Error> {
+        self.tree.fsck()
+    }
+
+    /// Flush the database and delete the files of unused pages.
+    ///
+    /// Files of removed pages are deleted when the removal is committed, but
+    /// files may be left behind by an interrupted commit or by databases
+    /// written by earlier versions. This function reclaims the disk space of
+    /// pages that are currently unused.
+    pub fn purge_free_pages(&mut self) -> Result<(), Error> {
+        self.tree.purge_free_pages()
+    }
+
+    /// Repair the list of unused page IDs and flush the database.
+    ///
+    /// When a database is opened, unused page IDs that still belong to pages
+    /// are dropped from the list so they are not reused. This function
+    /// traverses the tree to also find pages that are unreachable but missing
+    /// from the list, such as those left by an interrupted operation.
+    /// The list is rebuilt from the reachable pages, the files of unreachable
+    /// pages are deleted, and the page ID counter is lowered past unused IDs
+    /// at the end to shrink the list.
+    pub fn reclaim_ids(&mut self) -> Result<(), Error> {
+        self.tree.reclaim_ids()
+    }
+
+    /// Flush the database and delete files left behind by interrupted
+    /// operations.
+    ///
+    /// The list of unused page IDs is repaired like [`Self::reclaim_ids()`]
+    /// and the files of unused pages are deleted like
+    /// [`Self::purge_free_pages()`]. In addition, files of page revisions
+    /// that were written but never committed are deleted, committed files
+    /// that were not renamed to their main filename are renamed, and empty
+    /// page directories are deleted.
+    ///
+    /// All pages are read, so the function takes time proportional to the
+    /// size of the database.
+    pub fn clean_up(&mut self) -> Result<CleanupReport, Error> {
+        self.tree.clean_up()
+    }
+
+    /// Rebuild the tree with densely packed nodes and flush the database.
+    ///
+    /// Nodes are filled up to [`Options::keys_per_node`], which reduces the
+    /// number of pages of a database that is only half full after random
+    /// insertions or after many removals. All pages are written to new
+    /// pages and the previous pages are freed, so the database temporarily
+    /// uses up to twice the disk space. Use [`Self::purge_free_pages()`]
+    /// afterwards to delete leftover files.
+    ///
+    /// Because nodes are full, inserting pairs afterwards splits nodes more
+    /// often, so the function is most useful for databases that are rarely
+    /// modified.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        self.compact_with_progress(|_, _| {})
+    }
+
+    /// Rebuild the tree like [`Self::compact()`] and call the callback
+    /// function with the number of leaf nodes processed and the total number
+    /// of leaf nodes.
+    pub fn compact_with_progress<P>(&mut self, progress_callback: P) -> Result<(), Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        if self.options.open_mode == OpenMode::ReadOnly {
+            return Err(Error::ReadOnly);
+        }
+
+        self.tree.compact(progress_callback)
+    }
+
+    /// Load the upper levels of the tree into the page cache.
+    ///
+    /// The root node is level 0 and nodes down to the given level are loaded.
+    /// Calling this function right after opening avoids the latency of
+    /// loading the internal nodes on the first accesses. Loading stops when
+    /// the page cache is full, so [`Options::page_cache_size`] limits the
+    /// number of pages loaded.
+    ///
+    /// Returns the number of pages loaded.
+    pub fn preload(&mut self, depth: usize) -> Result<usize, Error> {
+        self.tree.preload(depth)
+    }
+
+    /// Traverse the tree and return an overview of its structure.
+    ///
+    /// All pages are read, so the function takes time proportional to the
+    /// size of the database. Modifications that are not flushed are
+    /// included.
+    pub fn tree_stats(&mut self) -> Result<TreeStats, Error> {
+        self.tree.tree_stats()
+    }
+
+    /// Traverse the tree and return the size of the file of each page.
+    ///
+    /// The database is flushed first if it is writable. The sizes are of the
+    /// files of the latest flushed revision. All pages are read, so the
+    /// function takes time proportional to the size of the database.
+    pub fn disk_usage(&mut self) -> Result<DiskUsage, Error> {
+        self.tree.disk_usage()
+    }
+
+    /// Flush the database and save a checkpoint of it to a directory.
+    ///
+    /// The path is relative to the database's file system and the directory
+    /// must be empty or not exist. The checkpoint can be opened as a separate
+    /// database, such as with [`Database::open_path()`] using the directory of
+    /// the checkpoint. Previous generations are not included.
+    ///
+    /// Page files are saved with [`vfs::Vfs::link_file()`]. With [`OsVfs`],
+    /// files are hard-linked instead of copied on file systems that support
+    /// them, which makes checkpoints of large databases nearly instant and
+    /// only uses additional disk space for pages modified afterwards.
+    pub fn checkpoint(&mut self, path: &str) -> Result<(), Error> {
+        self.tree.checkpoint(path)
+    }
+
+    /// Read a copy of the files of the flushed database for a backup.
+    ///
+    /// The callback function is called with the path and the contents of
+    /// each page file of the tree, and then of the metadata file. Writing
+    /// the files to an empty directory produces a database of the same
+    /// revision and UUID as this database. Previous generations are not
+    /// included. If the database is not read-only, it is flushed first.
+    ///
+    /// The files are copied as stored, without decoding and encoding them
+    /// again. A database opened in [`OpenMode::ReadOnly`] without
+    /// [`Options::file_locking`] can be copied while another process
+    /// modifies it. If the other process flushes while the files are read
+    /// and the files of the revision are no longer available,
+    /// [`Error::ExternallyModified`] is returned and the copy can be retried
+    /// after [`Self::reopen()`].
+    pub fn backup_files<C>(&mut self, callback: C) -> Result<(), Error>
+    where
+        C: FnMut(&str, &[u8]) -> Result<(), Error>,
+    {
+        self.tree.read_committed_files(callback)
+    }
+
+    /// Flush the database and copy it to another file system.
+    ///
+    /// The copy is an independent database with its own UUID, such as for
+    /// migrating between [`OsVfs`] and [`MemoryVfs`]. The file system must
+    /// not contain a database. Pages are written again using the given
+    /// options, so the copy can use a different compression, encryption key,
+    /// or fanout. [`Options::open_mode`] is ignored. Previous generations are
+    /// not included.
+    pub fn clone_to<G>(&mut self, vfs: G, options: Options) -> Result<(), Error>
+    where
+        G: Vfs,
+    {
+        options.validate()?;
+
+        self.tree.clone_to(vfs, options.into())
+    }
+
+    /// Copy the pairs of all readable pages to a new database on another
+    /// file system.
+    ///
+    /// Unlike [`Self::clone_to()`], pages that cannot be read, such as
+    /// corrupted or missing page files, are skipped instead of returning an
+    /// error. The skipped pages are returned in the report with the key range
+    /// that they covered. If pages are lost, the remaining page files are
+    /// also searched for pairs in the lost key ranges that are no longer
+    /// reachable from the tree, such as the pairs of a lost internal node.
+    ///
+    /// The file system must not contain a database. The new database is
+    /// created with the given options and flushed. Application metadata is
+    /// not copied.
+    pub fn salvage_to<G>(&mut self, vfs: G, options: Options) -> Result<SalvageReport, Error>
+    where
+        G: Vfs,
+    {
+        let options = Options {
+            open_mode: OpenMode::CreateOnly,
+            ..options
+        };
+        let mut target = Database::open_generic(vfs, options)?;
+        let mut pair_count = 0;
+
+        let lost_pages = self.tree.salvage(|key, value| {
+            target.put(key, value)?;
+            pair_count += 1;
+
+            Ok(())
+        })?;
+
+        target.flush()?;
+
+        Ok(SalvageReport {
+            pair_count,
+            lost_pages,
+        })
+    }
+
+    /// Return the IDs of pages recovered from torn writes.
+    ///
+    /// When the newest file of a page fails its checksum, such as when a
+    /// write was interrupted, the older file of the page is used instead.
+    /// The pages are recorded since the database was opened.
+    pub fn recovered_pages(&self) -> Vec<u64> {
+        self.tree.recovered_pages()
+    }
+
+    /// Encrypt all files again using a new key.
+    ///
+    /// The database must already be encrypted. All pages are read and
+    /// written using the new key and the changes are flushed. The provided
+    /// callback function is called with the number of pages processed and the
+    /// estimated number of pages.
+    ///
+    /// Afterwards, every other file of the database that is still encrypted
+    /// with an old key, such as the page history, files of freed pages, and
+    /// older metadata files, is encrypted again with the new key. The old
+    /// key is not needed to read any file once this function returns.
+    ///
+    /// If the process is interrupted, some files may still be encrypted with
+    /// the old key. To continue, open the database with the new key and the
+    /// old key in [`Options::previous_encryption_keys`] and call this
+    /// function again.
+    pub fn rewrite_with_key<P>(
+        &mut self,
+        new_key: EncryptionKey,
+        progress_callback: P,
+    ) -> Result<(), Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        if self.options.open_mode == OpenMode::ReadOnly {
+            return Err(Error::ReadOnly);
+        }
+        if !cfg!(feature = "encryption") {
+            return Err(Error::EncryptionUnavailable);
+        }
+        if !self.tree.is_encrypted() {
+            return Err(Error::InvalidConfig {
+                message: "database not encrypted",
+            });
+        }
+
+        self.tree.set_encryption_key(new_key.clone());
+        self.options.encryption_key = Some(new_key);
+
+        self.tree.rewrite_tree(progress_callback)?;
+        self.tree.reencrypt_files()
+    }
+
+    /// Assign a new UUID to the database.
+    ///
+    /// Use this function on a copy of a database, such as a restored backup,
+    /// so it can be told apart from the original. All pages are read and
+    /// written with the new UUID and the changes are flushed. The provided
+    /// callback function is called with the number of pages processed and
+    /// the estimated number of pages.
+    ///
+    /// The UUID changes only when the metadata is saved at the end. If the
+    /// process is interrupted, the database keeps the old UUID.
+    pub fn regenerate_uuid<P>(&mut self, progress_callback: P) -> Result<(), Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        if self.options.open_mode == OpenMode::ReadOnly {
+            return Err(Error::ReadOnly);
+        }
+
+        self.tree.regenerate_uuid(progress_callback)
+    }
+
+    /// Write the tree as text to the given writer for debugging purposes.
+    ///
+    /// The format of the text is not stable. Use [`Database::tree_summary()`]
+    /// to inspect the structure of the tree programmatically.
+    pub fn debug_print_tree<W: std::io::Write>(&mut self, writer: W) -> Result<(), Error> {
+        self.tree.dump_tree(writer)
+    }
+
+    /// Traverse the tree and return the structure of its pages.
+    ///
+    /// All internal and leaf pages are read, so the function takes time
+    /// proportional to the size of the database.
+    pub fn tree_summary(&mut self) -> Result<TreeSummary, Error> {
+        self.tree.tree_summary()
     }
 
     fn maybe_flush(&mut self, increment: bool) -> Result<(), Error> {
@@ -457,35 +1593,69 @@ impl Database {
             }
         }
 
+        if let Some(max_dirty_pages) = self.options.max_dirty_pages {
+            if self.tree.modified_page_count() > max_dirty_pages {
+                self.write_back_dirty()?;
+            }
+        }
+
         Ok(())
     }
 }
 
-impl Drop for Database {
+impl<F> Drop for Database<F>
+where
+    F: Vfs,
+{
     fn drop(&mut self) {
-        if self.options.automatic_flush && self.options.open_mode != OpenMode::ReadOnly {
-            let _ = self.flush();
+        if self.options.open_mode == OpenMode::ReadOnly {
+            return;
+        }
+
+        if self.options.automatic_flush {
+            if let Err(error) = self.flush() {
+                self.options
+                    .drop_policy
+                    .handle(DropFailure::FlushFailed(&error));
+            }
+        } else if self.tree.has_uncommitted_changes() {
+            self.options
+                .drop_policy
+                .handle(DropFailure::UnflushedChanges);
         }
     }
 }
 
-impl Debug for Database {
+impl<F> Debug for Database<F>
+where
+    F: Vfs,
+{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Database {{ open_mode: {:?} }}", self.options.open_mode)
     }
 }
 
 /// Cursor for navigating key-value pairs in sorted order.
-pub struct Cursor<'a> {
-    tree: &'a mut Tree,
+///
+/// When used as an [`Iterator`], an error ends the iteration early and is
+/// kept for [`Self::error()`]. Use [`Self::try_for_each_pair()`] or
+/// [`Self::into_results()`] to handle errors without checking afterwards.
+pub struct Cursor<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    tree: &'a mut Tree<F>,
     tree_cursor: TreeCursor,
     error: Option<Error>,
     has_seeked: bool,
     range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
 }
 
-impl<'a> Cursor<'a> {
-    fn new(tree: &'a mut Tree) -> Self {
+impl<'a, F> Cursor<'a, F>
+where
+    F: Vfs,
+{
+    fn new(tree: &'a mut Tree<F>) -> Self {
         Self {
             tree,
             tree_cursor: TreeCursor::default(),
@@ -539,6 +1709,68 @@ impl<'a> Cursor<'a> {
     ///
     /// The vectors will be cleared and resized.
     pub fn next_buf(&mut self, key: &mut Vec<u8>, value: &mut Vec<u8>) -> Result<bool, Error> {
+        self.next_parts(Some(key), Some(value))
+    }
+
+    /// Call the given function with each remaining key-value pair.
+    ///
+    /// Iteration stops at the first error from the cursor or the function,
+    /// which is returned. Unlike the [`Iterator`] implementation, errors
+    /// can't be missed by forgetting to check [`Self::error()`]. The buffers
+    /// of the key and value are reused between calls.
+    pub fn try_for_each_pair<C, E>(&mut self, mut callback: C) -> Result<(), E>
+    where
+        C: FnMut(&[u8], &[u8]) -> Result<(), E>,
+        E: From<Error>,
+    {
+        let mut key_buffer = Vec::new();
+        let mut value_buffer = Vec::new();
+
+        while self.next_buf(&mut key_buffer, &mut value_buffer)? {
+            callback(&key_buffer, &value_buffer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Convert into an iterator that returns errors as items.
+    ///
+    /// Unlike the [`Iterator`] implementation of the cursor, which ends the
+    /// iteration and keeps the error for [`Self::error()`], errors can be
+    /// handled for each item such as with the `?` operator. The iteration
+    /// ends after the first error.
+    pub fn into_results(self) -> Results<'a, F> {
+        Results {
+            cursor: self,
+            failed: false,
+        }
+    }
+
+    /// Convert into a blocking stream that returns errors as items.
+    ///
+    /// The stream returns the same items as [`Self::into_results()`].
+    /// It is an adapter for APIs that take a stream and is not asynchronous:
+    /// polling it reads page files with blocking I/O on the calling thread
+    /// and never returns `Poll::Pending`. Consume it where blocking is
+    /// allowed, such as a thread for blocking work of the async runtime.
+    ///
+    /// Requires the `async` feature.
+    #[cfg(feature = "async")]
+    pub fn into_blocking_stream(self) -> BlockingStream<'a, F> {
+        BlockingStream {
+            results: self.into_results(),
+        }
+    }
+
+    pub(crate) fn set_pinned(&mut self, pinned: bool) {
+        self.tree.set_pinned(pinned)
+    }
+
+    fn next_parts(
+        &mut self,
+        key: Option<&mut Vec<u8>>,
+        value: Option<&mut Vec<u8>>,
+    ) -> Result<bool, Error> {
         if !self.has_seeked {
             self.has_seeked = true;
             self.tree.cursor_start(&mut self.tree_cursor, b"")?;
@@ -553,9 +1785,35 @@ impl<'a> Cursor<'a> {
             Ok(false)
         }
     }
+
+    fn next_part(&mut self, is_key: bool) -> Option<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let result = if is_key {
+            self.next_parts(Some(&mut buffer), None)
+        } else {
+            self.next_parts(None, Some(&mut buffer))
+        };
+
+        match result {
+            Ok(success) => {
+                if success {
+                    Some(buffer)
+                } else {
+                    None
+                }
+            }
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
 }
 
-impl<'a> Iterator for Cursor<'a> {
+impl<'a, F> Iterator for Cursor<'a, F>
+where
+    F: Vfs,
+{
     type Item = KeyValuePair;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -578,19 +1836,162 @@ impl<'a> Iterator for Cursor<'a> {
     }
 }
 
-impl<'a> Debug for Cursor<'a> {
+impl<'a, F> Debug for Cursor<'a, F>
+where
+    F: Vfs,
+{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "DatabaseCursor")
     }
 }
 
+/// Iterator over the keys of the database.
+///
+/// Values are not copied. Obtain one with [`Database::keys()`].
+#[derive(Debug)]
+pub struct Keys<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    cursor: Cursor<'a, F>,
+}
+
+impl<'a, F> Keys<'a, F>
+where
+    F: Vfs,
+{
+    /// Return the most recent error.
+    pub fn error(&self) -> Option<&Error> {
+        self.cursor.error()
+    }
+}
+
+impl<'a, F> Iterator for Keys<'a, F>
+where
+    F: Vfs,
+{
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor.next_part(true)
+    }
+}
+
+/// Iterator over the values of the database in key order.
+///
+/// Keys are not copied. Obtain one with [`Database::values()`].
+#[derive(Debug)]
+pub struct Values<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    cursor: Cursor<'a, F>,
+}
+
+impl<'a, F> Values<'a, F>
+where
+    F: Vfs,
+{
+    /// Return the most recent error.
+    pub fn error(&self) -> Option<&Error> {
+        self.cursor.error()
+    }
+}
+
+impl<'a, F> Iterator for Values<'a, F>
+where
+    F: Vfs,
+{
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor.next_part(false)
+    }
+}
+
+/// Iterator over the key-value pairs of the database with errors as items.
+///
+/// Obtain one with [`Cursor::into_results()`].
+#[derive(Debug)]
+pub struct Results<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    cursor: Cursor<'a, F>,
+    failed: bool,
+}
+
+impl<'a, F> Iterator for Results<'a, F>
+where
+    F: Vfs,
+{
+    type Item = Result<KeyValuePair, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let mut key_buffer = Vec::new();
+        let mut value_buffer = Vec::new();
+
+        match self.cursor.next_buf(&mut key_buffer, &mut value_buffer) {
+            Ok(true) => Some(Ok((key_buffer, value_buffer))),
+            Ok(false) => None,
+            Err(error) => {
+                self.failed = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Blocking stream over the key-value pairs of the database with errors as
+/// items.
+///
+/// Each poll reads the next pair on the calling thread and is always ready.
+/// Obtain one with [`Cursor::into_blocking_stream()`].
+///
+/// Requires the `async` feature.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct BlockingStream<'a, F = Box<dyn Vfs + Sync + Send>>
+where
+    F: Vfs,
+{
+    results: Results<'a, F>,
+}
+
+#[cfg(feature = "async")]
+impl<'a, F> futures_core::Stream for BlockingStream<'a, F>
+where
+    F: Vfs,
+{
+    type Item = Result<KeyValuePair, Error>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        _context: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        std::task::Poll::Ready(self.get_mut().results.next())
+    }
+}
+
 #[derive(Debug)]
 /// Additional non-critical information associated with the database.
 pub struct Metadata<'a> {
+    uuid: Uuid,
     tree_metadata: Option<&'a TreeMetadata>,
 }
 
 impl<'a> Metadata<'a> {
+    /// Return the UUID of the database as a hyphenated string.
+    ///
+    /// The UUID is generated when the database is created.
+    pub fn uuid(&self) -> String {
+        self.uuid.to_string()
+    }
+
     /// Return the approximate number of key-value pairs in the database.
     pub fn key_value_count(&self) -> u64 {
         if let Some(meta) = self.tree_metadata {
@@ -599,20 +2000,77 @@ impl<'a> Metadata<'a> {
             0
         }
     }
+
+    /// Return the revision recorded by the most recent
+    /// [`export::export_incremental()`].
+    pub fn last_export_revision(&self) -> Option<u64> {
+        self.tree_metadata
+            .and_then(|meta| meta.last_export_revision)
+    }
+
+    /// Return the number of pairs applied and the input byte offset of an
+    /// unfinished [`export::import()`].
+    ///
+    /// The value is removed when the import completes. See
+    /// [`export::import_resume()`].
+    pub fn import_progress(&self) -> Option<(u64, u64)> {
+        self.tree_metadata.and_then(|meta| meta.import_progress)
+    }
+
+    /// Return the [`Options::keys_per_node`] that the database was most
+    /// recently modified with.
+    ///
+    /// Returns `None` for databases created by versions that did not save
+    /// the value.
+    pub fn keys_per_node(&self) -> Option<usize> {
+        self.tree_metadata.and_then(|meta| meta.keys_per_node)
+    }
+
+    /// Return the UUID of the database that the most recently imported
+    /// export file was created from.
+    pub fn import_source(&self) -> Option<&'a str> {
+        self.tree_metadata
+            .and_then(|meta| meta.import_source.as_deref())
+    }
+
+    /// Return the application metadata.
+    ///
+    /// See [`Database::set_app_metadata()`].
+    pub fn app_metadata(&self) -> Option<&'a [u8]> {
+        self.tree_metadata
+            .and_then(|meta| meta.app_metadata.as_deref())
+    }
 }
 
 struct FlushTracker {
+    clock: Clock,
     base_threshold: usize,
+    long_interval: Duration,
+    short_interval: Duration,
+    short_multiplier: usize,
     modification_count: usize,
     last_flush_time: Instant,
 }
 
 impl FlushTracker {
-    pub fn new(base_threshold: usize) -> Self {
+    pub fn new(options: &Options) -> Self {
+        #[cfg(feature = "testing")]
+        let clock = match &options.clock {
+            Some(clock) => Clock::Mock(clock.clone()),
+            None => Clock::System,
+        };
+        #[cfg(not(feature = "testing"))]
+        let clock = Clock::System;
+        let last_flush_time = clock.now();
+
         Self {
-            base_threshold,
+            clock,
+            base_threshold: options.automatic_flush_threshold,
+            long_interval: options.automatic_flush_long_interval,
+            short_interval: options.automatic_flush_short_interval,
+            short_multiplier: options.automatic_flush_short_multiplier,
             modification_count: 0,
-            last_flush_time: Instant::now(),
+            last_flush_time,
         }
     }
 
@@ -620,15 +2078,22 @@ impl FlushTracker {
         self.modification_count += 1;
     }
 
+    pub fn add_modifications(&mut self, count: usize) {
+        self.modification_count += count;
+    }
+
     pub fn check_should_flush(&mut self) -> bool {
-        let level_long = self.modification_count >= self.base_threshold
-            && self.last_flush_time.elapsed() >= Duration::from_secs(300);
-        let level_short = self.modification_count >= self.base_threshold * 2
-            && self.last_flush_time.elapsed() >= Duration::from_secs(60);
+        let now = self.clock.now();
+        let elapsed = now.saturating_duration_since(self.last_flush_time);
+        let level_long =
+            self.modification_count >= self.base_threshold && elapsed >= self.long_interval;
+        let level_short = self.modification_count
+            >= self.base_threshold.saturating_mul(self.short_multiplier)
+            && elapsed >= self.short_interval;
 
         if level_long || level_short {
             self.modification_count = 0;
-            self.last_flush_time = Instant::now();
+            self.last_flush_time = now;
             true
         } else {
             false
@@ -636,24 +2101,146 @@ impl FlushTracker {
     }
 }
 
+/// Options for [`debug_format_page()`].
+#[derive(Debug, Clone, Default)]
+pub struct DebugPrintOptions {
+    /// Whether to format the file as a JSON object instead of the Rust debug
+    /// format.
+    ///
+    /// Keys and values of pages are encoded as hex.
+    ///
+    /// Default: false.
+    pub json: bool,
+
+    /// Maximum number of bytes of each key and value of a page to include.
+    ///
+    /// In JSON, truncated keys and values end with `..`.
+    ///
+    /// Default: None (no limit).
+    pub truncate: Option<usize>,
+
+    /// Whether to include only the header of a page: the UUID, page ID,
+    /// revision, deleted flag, node kind, and number of keys.
+    ///
+    /// Default: false.
+    pub header_only: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct PageHeader {
+    uuid: Uuid,
+    id: u64,
+    revision: u64,
+    deleted: bool,
+    kind: Option<&'static str>,
+    key_count: usize,
+}
+
 /// Print the page contents for debugging purposes.
 pub fn debug_print_page(path: &Path) -> Result<(), Error> {
-    let mut format = Format::default();
-    let mut vfs = ReadOnlyVfs::new(Box::new(OsVfs::new(path.parent().unwrap())));
+    eprintln!(
+        "{}",
+        debug_format_page(path, &DebugPrintOptions::default())?
+    );
 
-    let filename = path.file_name().unwrap().to_str().unwrap();
+    Ok(())
+}
 
-    if filename.contains("meta") {
-        let payload: PageMetadata<TreeMetadata> = format.read_file(&mut vfs, filename)?;
+/// Decode a page file by its path.
+///
+/// The file is read without checking that it belongs to the same database
+/// as the other files in its directory, so the function can be used by
+/// tools for examining damaged databases. Metadata files are not pages and
+/// return [`Error::InvalidFileFormat`].
+pub fn inspect_page(path: &Path) -> Result<PageInfo, Error> {
+    if is_metadata_filename(path) {
+        return Err(Error::InvalidFileFormat {
+            path: path.to_string_lossy().into_owned(),
+            message: "metadata file is not a page",
+        });
+    }
 
-        eprintln!("{:?}", payload);
-    } else {
-        let payload: Page<Node> = format.read_file(&mut vfs, filename)?;
+    Ok(read_page_file(path)?.into())
+}
 
-        eprintln!("{:?}", payload);
+/// Format the contents of a page or metadata file for debugging purposes.
+///
+/// Files are read without checking that they belong to the same database,
+/// so the function can be used for triaging damaged pages.
+pub fn debug_format_page(path: &Path, options: &DebugPrintOptions) -> Result<String, Error> {
+    if is_metadata_filename(path) {
+        let (filename, data) = read_host_file(path)?;
+        let payload: PageMetadata<TreeMetadata> = Format::default().decode_data(&filename, data)?;
+
+        return if options.json {
+            Ok(serde_json::to_string(&payload)?)
+        } else {
+            Ok(format!("{:?}", payload))
+        };
     }
 
-    Ok(())
+    let page = read_page_file(path)?;
+    let header = PageHeader {
+        uuid: page.uuid,
+        id: page.id,
+        revision: page.revision,
+        deleted: page.deleted,
+        kind: page.content.as_ref().map(Node::kind_name),
+        key_count: page.content.as_ref().map(Node::key_count).unwrap_or(0),
+    };
+
+    match (options.header_only, options.json) {
+        (true, true) => Ok(serde_json::to_string(&header)?),
+        (true, false) => Ok(format!("{:?}", header)),
+        (false, true) => {
+            let mut value = serde_json::to_value(&header)?;
+
+            if let Some(node) = &page.content {
+                value["node"] = node.debug_json(options.truncate);
+            }
+
+            Ok(value.to_string())
+        }
+        (false, false) => {
+            let page = match options.truncate {
+                Some(length) => Page {
+                    content: page.content.map(|node| node.truncated(length)),
+                    ..page
+                },
+                None => page,
+            };
+
+            Ok(format!("{:?}", page))
+        }
+    }
+}
+
+fn is_metadata_filename(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.contains("meta"))
+        .unwrap_or(false)
+}
+
+// Read a file given by the user, such as a copy of a damaged page. The
+// filename is not checked like paths within a database because the copy may
+// have been renamed.
+fn read_host_file(path: &Path) -> Result<(String, Vec<u8>), Error> {
+    let filename = path
+        .file_name()
+        .and_then(|filename| filename.to_str())
+        .ok_or_else(|| Error::InvalidPath {
+            path: path.to_string_lossy().into_owned(),
+            message: "not a path to a file",
+        })?;
+
+    Ok((filename.to_string(), std::fs::read(path)?))
+}
+
+fn read_page_file(path: &Path) -> Result<Page<Node>, Error> {
+    let (filename, data) = read_host_file(path)?;
+
+    Format::default().decode_data(&filename, data)
 }
 
 fn concrete_range<K, R>(range: R) -> (Bound<Vec<u8>>, Bound<Vec<u8>>)
@@ -689,3 +2276,66 @@ fn slice_range<'a>(
     };
     (start_bound, end_bound)
 }
+
+// Serializes a duration as a number of seconds: an integer if it is whole,
+// otherwise a float.
+mod duration_seconds {
+    use std::{fmt, time::Duration};
+
+    use serde::{de, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if duration.subsec_nanos() == 0 {
+            serializer.serialize_u64(duration.as_secs())
+        } else {
+            serializer.serialize_f64(duration.as_secs_f64())
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(SecondsVisitor)
+    }
+
+    struct SecondsVisitor;
+
+    impl<'de> de::Visitor<'de> for SecondsVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a non-negative number of seconds")
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Duration, E>
+        where
+            E: de::Error,
+        {
+            Ok(Duration::from_secs(value))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Duration, E>
+        where
+            E: de::Error,
+        {
+            u64::try_from(value)
+                .map(Duration::from_secs)
+                .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Duration, E>
+        where
+            E: de::Error,
+        {
+            if value.is_finite() && value >= 0.0 && value <= u64::MAX as f64 {
+                Ok(Duration::from_secs_f64(value))
+            } else {
+                Err(E::invalid_value(de::Unexpected::Float(value), &self))
+            }
+        }
+    }
+}
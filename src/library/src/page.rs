@@ -1,5 +1,5 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     ops::{Deref, DerefMut},
 };
 
@@ -7,11 +7,13 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
+    encryption::EncryptionKey,
     error::Error,
-    format::Format,
+    format::{ContentHash, Format, PageChecksum, PageCompression, PayloadCodec},
     lru::LruVec,
+    stats::{CleanupReport, FsckIssueKind, FsckReport, IoCounters},
     system::UuidGenerator,
-    vfs::{Vfs, VfsSyncOption},
+    vfs::{MemoryVfs, OpenModeVfs, Vfs, VfsSyncOption},
 };
 
 const LOCK_FILENAME: &str = "grebedb_lock.lock";
@@ -23,6 +25,12 @@ const METADATA_COPY_FILENAME: &str = "grebedb_meta_copy.grebedb";
 pub type PageId = u64;
 pub type RevisionId = u64;
 
+// A decoded page and the contents of its file
+type PageFileData<T> = (Page<T>, Vec<u8>);
+
+// Path, page ID, and revision flag of a page file
+type PageFileEntry = (String, PageId, Option<RevisionFlag>);
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Page<T> {
     pub uuid: Uuid, // should match metadata
@@ -40,12 +48,23 @@ pub struct Metadata<M> {
     pub free_id_list: Vec<PageId>,
     pub root_id: Option<PageId>,
     pub auxiliary: Option<M>,
+    #[serde(default)]
+    pub generations: Vec<RevisionId>, // revisions of previous generations, newest first
+    #[serde(default)]
+    pub history: Vec<(RevisionId, PageId)>, // replaced page files kept for previous generations
+    #[serde(default)]
+    pub pending_free_id_list: Vec<(u64, PageId)>, // commits remaining until ID is freed
+    #[serde(default)]
+    pub fanout: PageFanout,
+    #[serde(default)]
+    pub codec: PayloadCodec, // codec of the page files
 }
 
 struct PageCache<T> {
     lru: LruVec<PageId>,
     cached_pages: HashMap<PageId, Page<T>>,
     modified_pages: HashSet<PageId>, // pages in cache not yet written to disk
+    stored_hashes: HashMap<PageId, ContentHash>, // hash of page payloads as stored on disk
 }
 
 impl<T> PageCache<T> {
@@ -56,6 +75,7 @@ impl<T> PageCache<T> {
             lru: LruVec::new(capacity),
             cached_pages: HashMap::with_capacity(capacity + 1), // +1 due to statement order
             modified_pages: HashSet::with_capacity(capacity + 1),
+            stored_hashes: HashMap::with_capacity(capacity + 1),
         }
     }
 
@@ -76,7 +96,7 @@ impl<T> PageCache<T> {
         self.cached_pages.get(&page_id)
     }
 
-    pub fn _peek(&self, page_id: PageId) -> Option<&Page<T>> {
+    pub fn peek(&self, page_id: PageId) -> Option<&Page<T>> {
         self.cached_pages.get(&page_id)
     }
 
@@ -86,6 +106,18 @@ impl<T> PageCache<T> {
         self.cached_pages.get_mut(&page_id)
     }
 
+    pub fn stored_hash(&self, page_id: PageId) -> Option<ContentHash> {
+        self.stored_hashes.get(&page_id).cloned()
+    }
+
+    pub fn set_stored_hash(&mut self, page_id: PageId, hash: Option<ContentHash>) {
+        if let Some(hash) = hash {
+            self.stored_hashes.insert(page_id, hash);
+        } else {
+            self.stored_hashes.remove(&page_id);
+        }
+    }
+
     pub fn set_page_revision(&mut self, page_id: PageId, revision: RevisionId) {
         let mut page = self.cached_pages.get_mut(&page_id).unwrap();
         page.revision = revision;
@@ -95,15 +127,18 @@ impl<T> PageCache<T> {
     pub fn put_touched(&mut self, page_id: PageId, page: Page<T>) -> Option<EvictedPage<T>> {
         self.cached_pages.insert(page_id, page);
         self.modified_pages.insert(page_id);
+        self.stored_hashes.remove(&page_id);
 
         if let Some(evicted_page_id) = self.lru.insert(page_id) {
             let modified = self.modified_pages.remove(&evicted_page_id);
+            let stored_hash = self.stored_hashes.remove(&evicted_page_id);
             let page = self.cached_pages.remove(&evicted_page_id).unwrap();
 
             Some(EvictedPage {
                 id: evicted_page_id,
                 page,
                 modified,
+                stored_hash,
             })
         } else {
             None
@@ -125,12 +160,14 @@ struct EvictedPage<T> {
     id: PageId,
     page: Page<T>,
     modified: bool,
+    stored_hash: Option<ContentHash>,
 }
 
 #[derive(Default)]
 struct FileTracker {
     pub pending_sync: HashSet<PageId>, // files written but not fsync()-ed
     pub pending_promotion: HashSet<PageId>, // files not renamed to the main filename
+    pub pending_removal: HashSet<PageId>, // removed pages with files not yet deleted
 }
 
 #[derive(Default)]
@@ -141,6 +178,7 @@ struct CounterTracker {
     root_id: Option<PageId>,
     id_counter: PageId, // current allocated page ID counter
     free_id_list: VecDeque<PageId>,
+    pending_free_id_list: VecDeque<(u64, PageId)>, // IDs not yet reusable
 }
 
 impl CounterTracker {
@@ -148,6 +186,10 @@ impl CounterTracker {
         self.dirty
     }
 
+    pub fn set_dirty(&mut self) {
+        self.dirty = true;
+    }
+
     pub fn unset_dirty(&mut self) {
         self.dirty = false;
     }
@@ -177,24 +219,44 @@ impl CounterTracker {
         &self.free_id_list
     }
 
+    pub fn pending_free_id_list(&self) -> &VecDeque<(u64, PageId)> {
+        &self.pending_free_id_list
+    }
+
     pub fn restore(
         &mut self,
         revision: RevisionId,
         root_id: Option<PageId>,
         id_counter: PageId,
         free_id_list: &[PageId],
+        pending_free_id_list: &[(u64, PageId)],
     ) {
         assert!(self.revision == 0);
         assert!(self.revision_on_persistence == 0);
         assert!(self.root_id == None);
         assert!(self.id_counter == 0);
         assert!(self.free_id_list.is_empty());
+        assert!(self.pending_free_id_list.is_empty());
 
         self.revision = revision;
         self.revision_on_persistence = revision;
         self.root_id = root_id;
         self.id_counter = id_counter;
         self.free_id_list.extend(free_id_list);
+        self.pending_free_id_list.extend(pending_free_id_list);
+    }
+
+    // Move the counters past the IDs and revisions used by a lost commit
+    pub fn advance(&mut self, id_counter: PageId, revision: RevisionId) {
+        if id_counter > self.id_counter {
+            self.id_counter = id_counter;
+            self.dirty = true;
+        }
+
+        if revision > self.revision {
+            self.revision = revision;
+            self.dirty = true;
+        }
     }
 
     pub fn new_page_id(&mut self) -> PageId {
@@ -208,10 +270,87 @@ impl CounterTracker {
         }
     }
 
-    pub fn free_page_id(&mut self, page_id: PageId) {
+    pub fn free_page_id(&mut self, page_id: PageId, reuse_delay: u64) {
+        self.dirty = true;
+
+        if reuse_delay == 0 {
+            self.free_id_list.push_back(page_id);
+        } else {
+            self.pending_free_id_list.push_back((reuse_delay, page_id));
+        }
+    }
+
+    // Count down the pending IDs for a commit and free the ones that are due
+    pub fn release_pending_free_ids(&mut self) {
+        for (commits_remaining, page_id) in std::mem::take(&mut self.pending_free_id_list) {
+            if commits_remaining <= 1 {
+                self.free_id_list.push_back(page_id);
+            } else {
+                self.pending_free_id_list
+                    .push_back((commits_remaining - 1, page_id));
+            }
+        }
+    }
+
+    // Drop duplicate IDs, IDs that were never allocated, and IDs of pages
+    // still in use
+    pub fn remove_invalid_free_ids(&mut self, used_ids: &HashSet<PageId>) {
+        let id_counter = self.id_counter;
+        let mut seen = HashSet::new();
+        let mut is_valid = |page_id: PageId| {
+            page_id != 0
+                && page_id <= id_counter
+                && !used_ids.contains(&page_id)
+                && seen.insert(page_id)
+        };
+
+        let count = self.free_id_list.len() + self.pending_free_id_list.len();
+
+        self.pending_free_id_list
+            .retain(|(_, page_id)| is_valid(*page_id));
+        self.free_id_list.retain(|page_id| is_valid(*page_id));
+
+        if count != self.free_id_list.len() + self.pending_free_id_list.len() {
+            self.dirty = true;
+        }
+    }
+
+    // Rebuild the free ID list from the IDs in use, shrinking the ID counter
+    // past unused IDs at the end. Returns the IDs that were leaked or are
+    // past the counter.
+    pub fn reclaim_ids(&mut self, used_ids: &HashSet<PageId>) -> Vec<PageId> {
         self.dirty = true;
 
-        self.free_id_list.push_back(page_id);
+        let id_counter = self.id_counter;
+        let old_free_ids: HashSet<PageId> = self.free_id_list.iter().cloned().collect();
+
+        self.pending_free_id_list
+            .retain(|(_, page_id)| !used_ids.contains(page_id));
+
+        let pending_ids: HashSet<PageId> = self
+            .pending_free_id_list
+            .iter()
+            .map(|(_, page_id)| *page_id)
+            .collect();
+        let is_free =
+            |page_id: PageId| !used_ids.contains(&page_id) && !pending_ids.contains(&page_id);
+
+        while self.id_counter > 0 && is_free(self.id_counter) {
+            self.id_counter -= 1;
+        }
+
+        self.free_id_list = (1..=self.id_counter)
+            .filter(|page_id| is_free(*page_id))
+            .collect();
+
+        let free_ids: HashSet<PageId> = self.free_id_list.iter().cloned().collect();
+
+        (1..=id_counter)
+            .filter(|page_id| {
+                *page_id > self.id_counter
+                    || (free_ids.contains(page_id) && !old_free_ids.contains(page_id))
+            })
+            .collect()
     }
 
     pub fn increment_revision(&mut self) {
@@ -224,12 +363,73 @@ impl CounterTracker {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum RevisionFlag {
     Current,
     New,
     NewUnsync,
 }
 
+// Directory layout of page files. The page ID is split into `depth`
+// directory levels of `width` bits each, excluding the lowest `width` bits.
+// The top level contains any remaining bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageFanout {
+    pub depth: u8,
+    pub width: u8,
+}
+
+impl Default for PageFanout {
+    fn default() -> Self {
+        Self { depth: 7, width: 8 }
+    }
+}
+
+impl PageFanout {
+    pub fn is_valid(&self) -> bool {
+        self.depth >= 1
+            && (1..=16).contains(&self.width)
+            && self.depth as u32 * self.width as u32 <= 64
+    }
+
+    fn make_path(&self, page_id: PageId, revision_flag: RevisionFlag) -> String {
+        format!(
+            "{}/{}",
+            self.split_number(page_id),
+            make_filename(page_id, revision_flag)
+        )
+    }
+
+    // Replaced page file that was current before the given revision
+    fn make_history_path(&self, page_id: PageId, revision: RevisionId) -> String {
+        format!(
+            "{}/grebedb_{:016x}_r{:016x}.grebedb",
+            self.split_number(page_id),
+            page_id,
+            revision
+        )
+    }
+
+    fn split_number(&self, id: u64) -> String {
+        let bits = self.width as u32;
+        let mask = (1u64 << bits) - 1;
+        let digits = (bits as usize - 1) / 4 + 1;
+        let mut parts = Vec::with_capacity(self.depth as usize);
+
+        for level in (1..=self.depth as u32).rev() {
+            let mut part = id.checked_shr(bits * level).unwrap_or(0);
+
+            if level != self.depth as u32 {
+                part &= mask;
+            }
+
+            parts.push(format!("{:0digits$x}", part, digits = digits));
+        }
+
+        parts.join("/")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PageTableOptions {
     pub open_mode: PageOpenMode,
@@ -237,7 +437,16 @@ pub struct PageTableOptions {
     pub keys_per_node: usize,
     pub file_locking: bool,
     pub file_sync: VfsSyncOption,
-    pub compression_level: Option<i32>,
+    pub compression: PageCompression,
+    pub codec: PayloadCodec,
+    pub checksum: PageChecksum,
+    pub encryption_key: Option<EncryptionKey>,
+    pub previous_encryption_keys: Vec<EncryptionKey>,
+    pub metadata_generations: usize,
+    pub generation: usize, // previous generation to open, 0 is the latest
+    pub free_page_reuse_delay: usize,
+    pub fanout: PageFanout, // used when creating a database
+    pub uuid_seed: Option<u64>,
 }
 
 impl Default for PageTableOptions {
@@ -248,7 +457,16 @@ impl Default for PageTableOptions {
             keys_per_node: 1024,
             file_locking: true,
             file_sync: VfsSyncOption::Data,
-            compression_level: Some(3),
+            compression: PageCompression::Zstd(3),
+            codec: PayloadCodec::MessagePack,
+            checksum: PageChecksum::Crc32c,
+            encryption_key: None,
+            previous_encryption_keys: Vec::new(),
+            metadata_generations: 1,
+            generation: 0,
+            free_page_reuse_delay: 1,
+            fanout: PageFanout::default(),
+            uuid_seed: None,
         }
     }
 }
@@ -267,36 +485,45 @@ impl Default for PageOpenMode {
     }
 }
 
-pub struct PageTable<T, M = ()>
+pub struct PageTable<T, M = (), V = Box<dyn Vfs + Sync + Send>>
 where
     T: Serialize + DeserializeOwned,
     M: Serialize + DeserializeOwned + Clone,
+    V: Vfs,
 {
     options: PageTableOptions,
-    vfs: Box<dyn Vfs + Sync + Send>,
+    vfs: OpenModeVfs<V>,
     format: Format,
     page_cache: PageCache<T>,
     file_tracker: FileTracker,
     counter_tracker: CounterTracker,
     uuid_generator: UuidGenerator,
     uuid: Uuid,
+    previous_uuid: Option<Uuid>, // pages not yet rewritten after regenerating the UUID
     closed: bool,
+    last_error: Option<String>, // description of the error that closed the table
+    metadata_damaged: bool,     // main metadata file was unreadable when loaded
+    generation_revisions: Vec<RevisionId>,
+    page_history: Vec<(RevisionId, PageId)>,
+    recovered_pages: BTreeSet<PageId>, // pages read from an older file due to a torn write
+    fanout: PageFanout,
     auxiliary_metadata: Option<M>,
+    pinned: bool, // read-only table keeps reading its revision from history files
+    cache_hits: u64,
+    cache_misses: u64,
 }
 
-impl<T, M> PageTable<T, M>
+impl<T, M, V> PageTable<T, M, V>
 where
     T: Serialize + DeserializeOwned,
     M: Serialize + DeserializeOwned + Clone,
+    V: Vfs,
 {
-    pub fn open(
-        mut vfs: Box<dyn Vfs + Sync + Send>,
-        options: PageTableOptions,
-    ) -> Result<Self, Error> {
+    pub fn open(mut vfs: V, options: PageTableOptions) -> Result<Self, Error> {
         if matches!(
             options.open_mode,
             PageOpenMode::LoadOnly | PageOpenMode::ReadOnly
-        ) && !Self::metadata_file_exists(vfs.as_ref())?
+        ) && !Self::metadata_file_exists(&vfs)?
         {
             return Err(Error::InvalidFileFormat {
                 path: "(directory contents)".to_string(),
@@ -308,27 +535,44 @@ where
             vfs.lock(LOCK_FILENAME)?;
         }
 
-        let metadata_file_exists = Self::metadata_file_exists(vfs.as_ref())?;
+        let metadata_file_exists = Self::metadata_file_exists(&vfs)?;
 
         let mut format = Format::default();
-        format.set_compression_level(options.compression_level);
+        format.set_compression(options.compression);
+        format.set_codec(options.codec);
+        format.set_checksum(options.checksum);
+        format.set_encryption_key(options.encryption_key.clone());
+        format.set_previous_encryption_keys(options.previous_encryption_keys.clone());
+
+        let read_only = options.open_mode == PageOpenMode::ReadOnly;
 
         let mut table = Self {
             options: options.clone(),
-            vfs,
+            vfs: OpenModeVfs::new(vfs, read_only),
             format,
             page_cache: PageCache::new(options.page_cache_size),
             uuid: Uuid::nil(),
+            previous_uuid: None,
             file_tracker: FileTracker::default(),
             counter_tracker: CounterTracker::default(),
-            uuid_generator: UuidGenerator::new(),
+            uuid_generator: UuidGenerator::new(options.uuid_seed),
             closed: false,
+            last_error: None,
+            metadata_damaged: false,
+            generation_revisions: Vec::new(),
+            page_history: Vec::new(),
+            recovered_pages: BTreeSet::new(),
+            fanout: options.fanout,
             auxiliary_metadata: None,
+            pinned: false,
+            cache_hits: 0,
+            cache_misses: 0,
         };
 
         match options.open_mode {
             PageOpenMode::LoadOnly | PageOpenMode::ReadOnly => {
                 table.load_and_restore_metadata()?;
+                table.validate_free_id_list()?;
             }
             PageOpenMode::CreateOnly => {
                 table.save_new_metadata()?;
@@ -336,6 +580,7 @@ where
             PageOpenMode::LoadOrCreate => {
                 if metadata_file_exists {
                     table.load_and_restore_metadata()?;
+                    table.validate_free_id_list()?;
                 } else {
                     table.save_new_metadata()?;
                 }
@@ -345,16 +590,123 @@ where
         Ok(table)
     }
 
-    fn metadata_file_exists(vfs: &dyn Vfs) -> Result<bool, Error> {
+    fn metadata_file_exists(vfs: &V) -> Result<bool, Error> {
         Ok(vfs.exists(METADATA_FILENAME)?
             || vfs.exists(METADATA_COPY_FILENAME)?
             || vfs.exists(METADATA_OLD_FILENAME)?)
     }
 
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    pub fn io_counters(&self) -> IoCounters {
+        IoCounters {
+            cache_hits: self.cache_hits,
+            cache_misses: self.cache_misses,
+            ..self.format.io_counters()
+        }
+    }
+
+    pub fn reset_io_counters(&mut self) {
+        self.format.reset_io_counters();
+        self.cache_hits = 0;
+        self.cache_misses = 0;
+    }
+
+    // Discard the in-memory state and load the committed state again
+    pub fn reopen(&mut self) -> Result<(), Error> {
+        self.closed = true;
+        self.page_cache = PageCache::new(self.options.page_cache_size);
+        self.file_tracker = FileTracker::default();
+        self.counter_tracker = CounterTracker::default();
+        self.uuid = Uuid::nil();
+        self.metadata_damaged = false;
+        self.generation_revisions.clear();
+        self.page_history.clear();
+        self.fanout = self.options.fanout;
+        self.auxiliary_metadata = None;
+        self.format.clear_dir_cache();
+
+        let result = self
+            .load_and_restore_metadata()
+            .and_then(|_| self.validate_free_id_list());
+
+        match &result {
+            Ok(_) => {
+                self.closed = false;
+                self.last_error = None;
+            }
+            Err(error) => self.close(error),
+        }
+
+        result
+    }
+
+    pub fn revision(&self) -> RevisionId {
+        self.counter_tracker.revision()
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    // Pages with the old UUID are still read until they are written again
+    // with touch() and committed
+    pub fn regenerate_uuid(&mut self) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        self.previous_uuid = Some(self.uuid);
+        self.uuid = self.uuid_generator.new_uuid();
+        self.counter_tracker.set_dirty();
+
+        Ok(())
+    }
+
+    fn is_own_uuid(&self, uuid: Uuid) -> bool {
+        uuid == self.uuid || Some(uuid) == self.previous_uuid
+    }
+
+    // While pinned, pages replaced by a writer after the table was loaded are
+    // read from the history files kept for previous generations instead of
+    // reporting an external modification. Only read-only tables are pinned.
+    pub fn set_pinned(&mut self, pinned: bool) {
+        self.pinned = pinned && self.options.open_mode == PageOpenMode::ReadOnly;
+    }
+
     pub fn root_id(&self) -> Option<PageId> {
         self.counter_tracker.root_id()
     }
 
+    pub fn id_counter(&self) -> PageId {
+        self.counter_tracker.id_counter()
+    }
+
+    // Includes IDs that are not yet reusable
+    pub fn free_page_ids(&self) -> HashSet<PageId> {
+        let mut page_ids: HashSet<PageId> = self
+            .counter_tracker
+            .free_id_list()
+            .iter()
+            .cloned()
+            .collect();
+        page_ids.extend(
+            self.counter_tracker
+                .pending_free_id_list()
+                .iter()
+                .map(|(_, page_id)| *page_id),
+        );
+
+        page_ids
+    }
+
+    // Includes IDs that are not yet reusable
+    pub fn free_page_count(&self) -> usize {
+        self.counter_tracker.free_id_list().len()
+            + self.counter_tracker.pending_free_id_list().len()
+    }
+
     pub fn set_root_id(&mut self, value: Option<PageId>) {
         self.counter_tracker.set_root_id(value);
     }
@@ -375,209 +727,1241 @@ where
         self.auxiliary_metadata = value;
     }
 
-    pub fn get(&mut self, page_id: PageId) -> Result<Option<&T>, Error> {
+    // Modify the auxiliary metadata such that it is saved on the next commit
+    // even if no pages were modified.
+    pub fn update_auxiliary_metadata(&mut self) -> Result<Option<&mut M>, Error> {
         self.check_if_closed()?;
+        self.check_if_read_only()?;
 
-        self.get_(page_id)
-    }
-
-    fn get_(&mut self, page_id: PageId) -> Result<Option<&T>, Error> {
-        self.check_page_id_counter_consistency(page_id)?;
+        self.counter_tracker.set_dirty();
 
-        if !self.page_cache.contains_page(page_id) {
-            self.load_page_into_cache(page_id)?;
-        }
+        Ok(self.auxiliary_metadata.as_mut())
+    }
 
-        if let Some(page) = self.page_cache.get_touched(page_id) {
-            if let Some(content) = &page.content {
-                Ok(Some(content))
-            } else {
-                Ok(None)
+    pub fn set_encryption_key(&mut self, value: EncryptionKey) {
+        if let Some(old_key) = self.options.encryption_key.replace(value.clone()) {
+            if !self.options.previous_encryption_keys.contains(&old_key) {
+                self.options.previous_encryption_keys.push(old_key);
             }
-        } else {
-            Ok(None)
         }
+
+        self.format.set_encryption_key(Some(value));
+        self.format
+            .set_previous_encryption_keys(self.options.previous_encryption_keys.clone());
     }
 
-    pub fn put(&mut self, page_id: PageId, content: T) -> Result<(), Error> {
+    pub fn is_encrypted(&self) -> bool {
+        self.options.encryption_key.is_some()
+    }
+
+    pub fn recovered_pages(&self) -> &BTreeSet<PageId> {
+        &self.recovered_pages
+    }
+
+    // Mark the page as modified so it is written out on the next commit
+    pub fn touch(&mut self, page_id: PageId) -> Result<(), Error> {
         self.check_if_closed()?;
         self.check_if_read_only()?;
 
-        let result = self.put_(page_id, content);
+        self.update_(page_id)?;
 
-        if result.is_err() {
-            self.closed = true;
+        if let Some(page) = self.page_cache.get_touched_mut(page_id) {
+            page.uuid = self.uuid;
         }
 
-        result
+        // Don't let the write be skipped because the content is unchanged
+        self.page_cache.set_stored_hash(page_id, None);
+
+        Ok(())
     }
 
-    fn put_(&mut self, page_id: PageId, content: T) -> Result<(), Error> {
-        self.check_page_id_counter_consistency(page_id)?;
+    // Write the metadata files again without changing the revision
+    pub fn rewrite_metadata(&mut self) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
 
-        let page = Page {
-            uuid: self.uuid,
-            id: page_id,
-            revision: self.counter_tracker.revision(),
-            deleted: false,
-            content: Some(content),
-        };
+        let result = self.save_metadata();
 
-        if let Some(evicted_page_info) = self.page_cache.put_touched(page_id, page) {
-            self.maybe_save_evicted_page(evicted_page_info)?;
+        if let Err(error) = &result {
+            self.close(error);
         }
 
-        Ok(())
+        result
     }
 
-    pub fn update(&mut self, page_id: PageId) -> Result<Option<PageUpdateGuard<T>>, Error> {
+    // Encrypt every database file again that is still encrypted with a
+    // previous key. This includes files that aren't reachable from the
+    // current metadata such as the page history, revision files, files of
+    // free pages, and the older metadata files.
+    pub fn reencrypt_files(&mut self) -> Result<(), Error> {
         self.check_if_closed()?;
         self.check_if_read_only()?;
 
-        self.update_(page_id)
+        let result = self.reencrypt_files_();
+
+        if let Err(error) = &result {
+            self.close(error);
+        }
+
+        result
     }
 
-    fn update_(&mut self, page_id: PageId) -> Result<Option<PageUpdateGuard<T>>, Error> {
-        self.check_page_id_counter_consistency(page_id)?;
+    fn reencrypt_files_(&mut self) -> Result<(), Error> {
+        let mut paths: Vec<String> = self
+            .vfs
+            .read_dir("")?
+            .into_iter()
+            .filter(|filename| {
+                filename.starts_with("grebedb_meta") && filename.ends_with(".grebedb")
+            })
+            .collect();
+        paths.sort_unstable();
+        paths.extend(self.list_page_files()?.into_iter().map(|(path, _, _)| path));
 
-        if !self.page_cache.contains_page(page_id) {
-            self.load_page_into_cache(page_id)?;
+        for path in paths {
+            self.format
+                .reencrypt_file(&mut self.vfs, &path, self.options.file_sync)?;
         }
 
-        if let Some(page) = self.page_cache.get_touched_mut(page_id) {
-            if page.content.is_some() {
-                Ok(Some(PageUpdateGuard::new(page)))
+        Ok(())
+    }
+
+    // Return the path, page ID, and revision flag of every page file in the
+    // page directories. The revision flag is none for files in the page
+    // history.
+    fn list_page_files(&self) -> Result<Vec<PageFileEntry>, Error> {
+        let mut entries = Vec::new();
+        self.list_page_files_in_dir("", self.fanout.depth, &mut entries)?;
+
+        Ok(entries)
+    }
+
+    fn list_page_files_in_dir(
+        &self,
+        path: &str,
+        depth: u8,
+        entries: &mut Vec<PageFileEntry>,
+    ) -> Result<(), Error> {
+        let mut filenames = self.vfs.read_dir(path)?;
+        filenames.sort_unstable();
+
+        for filename in filenames {
+            let file_path = if path.is_empty() {
+                filename.clone()
             } else {
-                Ok(None)
+                format!("{}/{}", path, filename)
+            };
+
+            if depth > 0 {
+                if !filename.is_empty()
+                    && filename.chars().all(|c| c.is_ascii_hexdigit())
+                    && self.vfs.is_dir(&file_path)?
+                {
+                    self.list_page_files_in_dir(&file_path, depth - 1, entries)?;
+                }
+            } else if let Some((page_id, revision_flag)) = parse_page_filename(&filename) {
+                entries.push((file_path, page_id, revision_flag));
             }
-        } else {
-            Ok(None)
         }
+
+        Ok(())
     }
 
-    pub fn remove(&mut self, page_id: PageId) -> Result<(), Error> {
+    // Delete the files of pages in the free ID list
+    pub fn purge_free_pages(&mut self) -> Result<(), Error> {
         self.check_if_closed()?;
         self.check_if_read_only()?;
 
-        let result = self.remove_(page_id);
+        // Only IDs that are free in the committed state are safe to purge
+        self.commit()?;
+
+        let result = self.purge_free_pages_();
 
-        if result.is_err() {
-            self.closed = true;
+        if let Err(error) = &result {
+            self.close(error);
         }
 
         result
     }
 
-    fn remove_(&mut self, page_id: PageId) -> Result<(), Error> {
-        self.check_page_id_counter_consistency(page_id)?;
-
-        let page = Page {
-            uuid: self.uuid,
-            id: page_id,
-            revision: self.counter_tracker.revision(),
-            deleted: true,
-            content: None,
-        };
+    fn purge_free_pages_(&mut self) -> Result<(), Error> {
+        let page_ids: Vec<PageId> = self
+            .counter_tracker
+            .free_id_list()
+            .iter()
+            .cloned()
+            .collect();
 
-        if let Some(evicted_page_info) = self.page_cache.put_touched(page_id, page) {
-            self.maybe_save_evicted_page(evicted_page_info)?;
+        for page_id in page_ids {
+            self.remove_page_files(page_id)?;
         }
 
-        self.counter_tracker.free_page_id(page_id);
+        self.format.clear_dir_cache();
 
         Ok(())
     }
 
-    pub fn commit(&mut self) -> Result<(), Error> {
+    // Rebuild the free ID list given the IDs of all reachable pages
+    pub fn reclaim_ids(&mut self, used_ids: &HashSet<PageId>) -> Result<(), Error> {
         self.check_if_closed()?;
         self.check_if_read_only()?;
 
-        let result = self.commit_();
+        let result = self.reclaim_ids_(used_ids);
 
-        if result.is_err() {
-            self.closed = true;
+        if let Err(error) = &result {
+            self.close(error);
         }
 
         result
     }
 
-    fn commit_(&mut self) -> Result<(), Error> {
-        if !self.is_anything_modified() {
-            return Ok(());
-        }
+    fn reclaim_ids_(&mut self, used_ids: &HashSet<PageId>) -> Result<(), Error> {
+        let page_ids = self.counter_tracker.reclaim_ids(used_ids);
+        self.commit_()?;
 
-        self.counter_tracker.increment_revision();
+        // Files of leaked pages still contain content which would prevent
+        // their IDs from being reused when the database is opened again
+        for page_id in page_ids {
+            self.remove_page_files(page_id)?;
+        }
 
-        self.save_all_modified_pages()?;
-        self.sync_and_rename_pending_page_files()?;
-        self.file_tracker.pending_sync.clear();
-        self.save_metadata()?;
-        self.commit_counters();
-        self.promote_page_filenames()?;
-        self.file_tracker.pending_promotion.clear();
-        self.page_cache.clear_modified_pages();
+        self.format.clear_dir_cache();
 
         Ok(())
     }
 
-    fn is_anything_modified(&self) -> bool {
-        self.counter_tracker.is_dirty() || !self.page_cache.modified_pages().is_empty()
-    }
-
-    fn load_and_restore_metadata(&mut self) -> Result<(), Error> {
-        let metadata: Metadata<M> = self
-            .format
-            .read_file(self.vfs.as_mut(), METADATA_FILENAME)?;
-
-        self.uuid = metadata.uuid;
+    // Delete page files with revisions that were not committed and promote
+    // the filenames of committed files
+    pub fn clean_up_revision_files(&mut self) -> Result<CleanupReport, Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
 
-        self.counter_tracker.restore(
-            metadata.revision,
-            metadata.root_id,
-            metadata.id_counter,
-            &metadata.free_id_list,
-        );
+        self.commit()?;
 
-        self.auxiliary_metadata = metadata.auxiliary;
+        let result = self.clean_up_revision_files_();
 
-        // TODO: the copy backup file could be read if the main metadata file
-        // is unreadable
+        if let Err(error) = &result {
+            self.close(error);
+        }
 
-        Ok(())
+        result
     }
 
-    fn save_new_metadata(&mut self) -> Result<(), Error> {
-        self.uuid = self.uuid_generator.new_uuid();
+    fn clean_up_revision_files_(&mut self) -> Result<CleanupReport, Error> {
+        let mut report = CleanupReport::default();
+        let revision = self.counter_tracker.revision();
 
-        // We check for the backup file too in case the main file disappears
-        if self.vfs.exists(METADATA_FILENAME)?
-            || self.vfs.exists(METADATA_COPY_FILENAME)?
-            || self.vfs.exists(METADATA_OLD_FILENAME)?
-        {
-            return Err(Error::InvalidMetadata {
-                message: "database already exists",
-            });
-        }
+        for page_id in 1..=self.counter_tracker.id_counter() {
+            // Files with delayed sync are renamed before the metadata is
+            // saved, so a remaining file was never committed
+            let path_2 = self.fanout.make_path(page_id, RevisionFlag::NewUnsync);
 
-        self.save_metadata()?;
+            if self.vfs.exists(&path_2)? {
+                self.vfs.remove_file(&path_2)?;
+                report.removed_files += 1;
+            }
 
-        Ok(())
-    }
+            let path_1 = self.fanout.make_path(page_id, RevisionFlag::New);
 
-    fn load_page(
+            match self.load_page(page_id, RevisionFlag::New) {
+                Ok(Some(page)) if page.revision <= revision => {
+                    self.file_tracker.pending_promotion.insert(page_id);
+                }
+                Ok(Some(_)) | Err(Error::BadChecksum { .. }) => {
+                    self.vfs.remove_file(&path_1)?;
+                    report.removed_files += 1;
+                }
+                Ok(None) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        report.promoted_files = self.file_tracker.pending_promotion.len() as u64;
+        self.promote_page_filenames()?;
+        self.file_tracker.pending_promotion.clear();
+
+        Ok(report)
+    }
+
+    // Rebuild the free ID list given the IDs of all reachable pages, and
+    // delete the files of unused pages and empty directories
+    pub fn clean_up_unused_pages(
+        &mut self,
+        used_ids: &HashSet<PageId>,
+        report: &mut CleanupReport,
+    ) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        let result = self.clean_up_unused_pages_(used_ids, report);
+
+        if let Err(error) = &result {
+            self.close(error);
+        }
+
+        result
+    }
+
+    fn clean_up_unused_pages_(
+        &mut self,
+        used_ids: &HashSet<PageId>,
+        report: &mut CleanupReport,
+    ) -> Result<(), Error> {
+        let mut page_ids = self.counter_tracker.reclaim_ids(used_ids);
+        report.reclaimed_ids = page_ids.len() as u64;
+        self.commit_()?;
+
+        page_ids.extend(self.counter_tracker.free_id_list().iter().cloned());
+
+        for page_id in page_ids {
+            report.removed_files += self.remove_page_files(page_id)?;
+        }
+
+        report.removed_dirs = self.remove_empty_page_dirs("", self.fanout.depth)?;
+        self.format.clear_dir_cache();
+
+        Ok(())
+    }
+
+    // Check the metadata backup files, the free ID list, and the page files
+    // given the IDs of all reachable pages
+    pub fn fsck_files(
+        &mut self,
+        used_ids: &HashSet<PageId>,
+        report: &mut FsckReport,
+    ) -> Result<(), Error> {
+        self.check_if_closed()?;
+
+        self.fsck_metadata_files(report)?;
+        self.fsck_free_id_list(used_ids, report);
+        self.fsck_page_files(used_ids, report)?;
+
+        Ok(())
+    }
+
+    fn fsck_metadata_files(&mut self, report: &mut FsckReport) -> Result<(), Error> {
+        let metadata: Metadata<M> = match self.load_metadata_file(METADATA_FILENAME) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                report.push(
+                    FsckIssueKind::MetadataDivergence,
+                    None,
+                    Some(METADATA_FILENAME.to_string()),
+                    format!("main file could not be read: {}", error),
+                );
+                return Ok(());
+            }
+        };
+
+        for path in [METADATA_COPY_FILENAME, METADATA_OLD_FILENAME] {
+            if !self.vfs.exists(path)? {
+                continue;
+            }
+
+            let backup: Metadata<M> = match self.load_metadata_file(path) {
+                Ok(backup) => backup,
+                Err(error) => {
+                    report.push(
+                        FsckIssueKind::MetadataDivergence,
+                        None,
+                        Some(path.to_string()),
+                        format!("backup file could not be read: {}", error),
+                    );
+                    continue;
+                }
+            };
+
+            let (kind, message) = if backup.uuid != metadata.uuid {
+                (
+                    FsckIssueKind::UuidMismatch,
+                    format!("UUID {} instead of {}", backup.uuid, metadata.uuid),
+                )
+            } else if path == METADATA_OLD_FILENAME {
+                // The previous file is at most the same revision as the main file
+                if backup.revision <= metadata.revision {
+                    continue;
+                }
+
+                (
+                    FsckIssueKind::MetadataDivergence,
+                    format!(
+                        "revision {} is newer than revision {} of the main file",
+                        backup.revision, metadata.revision
+                    ),
+                )
+            } else if backup.revision != metadata.revision {
+                (
+                    FsckIssueKind::MetadataDivergence,
+                    format!(
+                        "revision {} instead of revision {} of the main file",
+                        backup.revision, metadata.revision
+                    ),
+                )
+            } else if backup.root_id != metadata.root_id
+                || backup.id_counter != metadata.id_counter
+                || backup.free_id_list != metadata.free_id_list
+            {
+                (
+                    FsckIssueKind::MetadataDivergence,
+                    "page IDs differ from the main file of the same revision".to_string(),
+                )
+            } else {
+                continue;
+            };
+
+            report.push(kind, None, Some(path.to_string()), message);
+        }
+
+        Ok(())
+    }
+
+    fn fsck_free_id_list(&self, used_ids: &HashSet<PageId>, report: &mut FsckReport) {
+        let id_counter = self.counter_tracker.id_counter();
+        let mut listed_ids = HashSet::new();
+        let free_ids = self.counter_tracker.free_id_list().iter().cloned().chain(
+            self.counter_tracker
+                .pending_free_id_list()
+                .iter()
+                .map(|(_, page_id)| *page_id),
+        );
+
+        for page_id in free_ids {
+            let message = if !listed_ids.insert(page_id) {
+                "listed as unused more than once"
+            } else if used_ids.contains(&page_id) {
+                "in use but listed as unused"
+            } else if page_id == 0 || page_id > id_counter {
+                "listed as unused but beyond the page ID counter"
+            } else {
+                continue;
+            };
+
+            report.push(
+                FsckIssueKind::FreeListInconsistency,
+                Some(page_id),
+                None,
+                message.to_string(),
+            );
+        }
+
+        let mut used_ids: Vec<PageId> = used_ids.iter().cloned().collect();
+        used_ids.sort_unstable();
+
+        for &page_id in &used_ids {
+            if page_id > id_counter {
+                report.push(
+                    FsckIssueKind::FreeListInconsistency,
+                    Some(page_id),
+                    None,
+                    "in use but beyond the page ID counter".to_string(),
+                );
+            }
+        }
+
+        for page_id in 1..=id_counter {
+            if used_ids.binary_search(&page_id).is_err() && !listed_ids.contains(&page_id) {
+                report.push(
+                    FsckIssueKind::FreeListInconsistency,
+                    Some(page_id),
+                    None,
+                    "neither in use nor listed as unused".to_string(),
+                );
+            }
+        }
+    }
+
+    fn fsck_page_files(
+        &mut self,
+        used_ids: &HashSet<PageId>,
+        report: &mut FsckReport,
+    ) -> Result<(), Error> {
+        let free_ids = self.free_page_ids();
+
+        for (path, page_id, revision_flag) in self.list_page_files()? {
+            self.fsck_page_file(page_id, revision_flag, &path, used_ids, &free_ids, report);
+        }
+
+        Ok(())
+    }
+
+    // The revision flag is none for files in the page history
+    fn fsck_page_file(
+        &mut self,
+        page_id: PageId,
+        revision_flag: Option<RevisionFlag>,
+        path: &str,
+        used_ids: &HashSet<PageId>,
+        free_ids: &HashSet<PageId>,
+        report: &mut FsckReport,
+    ) {
+        report.file_count += 1;
+
+        // Unreadable files of pages in the tree are reported when traversing
+        // the tree, and unreadable files of other pages are orphans anyway
+        let page: Option<Page<T>> = self.format.read_file(&mut self.vfs, path).ok();
+
+        if let Some(page) = &page {
+            if page.uuid != self.uuid {
+                report.push(
+                    FsckIssueKind::UuidMismatch,
+                    Some(page_id),
+                    Some(path.to_string()),
+                    format!("UUID {} instead of {}", page.uuid, self.uuid),
+                );
+                return;
+            }
+        }
+
+        let is_uncommitted = match revision_flag {
+            Some(RevisionFlag::NewUnsync) => true,
+            Some(RevisionFlag::New) => matches!(
+                page,
+                Some(page) if page.revision > self.counter_tracker.revision()
+            ),
+            _ => false,
+        };
+
+        // Files of removed pages are kept until the ID is reused or the free
+        // pages are purged, and databases of older versions never deleted them
+        let message = match revision_flag {
+            None => return,
+            Some(_) if is_uncommitted => "revision was not committed",
+            Some(_) if free_ids.contains(&page_id) => return,
+            Some(_) if !used_ids.contains(&page_id) => "page is not part of the tree",
+            Some(_) => return,
+        };
+
+        report.push(
+            FsckIssueKind::OrphanPageFile,
+            Some(page_id),
+            Some(path.to_string()),
+            message.to_string(),
+        );
+    }
+
+    // Delete empty directories named like those of page files and return
+    // the number deleted
+    fn remove_empty_page_dirs(&mut self, path: &str, depth: u8) -> Result<u64, Error> {
+        let mut count = 0;
+
+        for filename in self.vfs.read_dir(path)? {
+            if filename.is_empty() || !filename.chars().all(|c| c.is_ascii_hexdigit()) {
+                continue;
+            }
+
+            let dir_path = if path.is_empty() {
+                filename
+            } else {
+                format!("{}/{}", path, filename)
+            };
+
+            if !self.vfs.is_dir(&dir_path)? {
+                continue;
+            }
+
+            if depth > 1 {
+                count += self.remove_empty_page_dirs(&dir_path, depth - 1)?;
+            }
+
+            if self.vfs.read_dir(&dir_path)?.is_empty() {
+                self.vfs.remove_dir(&dir_path)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    // Save the committed files to a directory. Page files are only replaced
+    // by renaming, so they can be linked instead of copied.
+    pub fn checkpoint(&mut self, path: &str) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        self.commit()?;
+
+        if self.vfs.exists(path)? && !self.vfs.read_dir(path)?.is_empty() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "checkpoint directory not empty",
+            )));
+        }
+
+        let mut created_dirs = HashSet::new();
+
+        for page_id in 1..=self.counter_tracker.id_counter() {
+            for revision_flag in [RevisionFlag::Current, RevisionFlag::New] {
+                let source_path = self.fanout.make_path(page_id, revision_flag);
+
+                if !self.vfs.exists(&source_path)? {
+                    continue;
+                }
+
+                let dir_path = format!("{}/{}", path, self.fanout.split_number(page_id));
+
+                if created_dirs.insert(dir_path.clone()) {
+                    self.vfs.create_dir_all(&dir_path)?;
+                }
+
+                let destination_path = format!("{}/{}", path, source_path);
+
+                if revision_flag == RevisionFlag::Current {
+                    self.vfs
+                        .link_file(&source_path, &destination_path, self.options.file_sync)?;
+                } else {
+                    // An unpromoted file may be overwritten in place
+                    let data = self.vfs.read(&source_path)?;
+                    self.vfs
+                        .write(&destination_path, &data, self.options.file_sync)?;
+                }
+            }
+        }
+
+        self.vfs.create_dir_all(path)?;
+
+        // Metadata files may be overwritten in place. The main file is
+        // written last so the checkpoint isn't a database until complete.
+        for filename in [METADATA_COPY_FILENAME, METADATA_FILENAME] {
+            if self.vfs.exists(filename)? {
+                let data = self.vfs.read(filename)?;
+                self.vfs.write(
+                    &format!("{}/{}", path, filename),
+                    &data,
+                    self.options.file_sync,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Pass the file of each given page at the committed revision and then
+    // the metadata to the callback. Page files are passed with the filenames
+    // of current files and previous generations are left out of the
+    // metadata. Files are read as they are stored, so another process may
+    // commit while they are read, which returns an error if detected.
+    pub fn read_committed_files<C>(
+        &mut self,
+        page_ids: &[PageId],
+        mut callback: C,
+    ) -> Result<(), Error>
+    where
+        C: FnMut(&str, &[u8]) -> Result<(), Error>,
+    {
+        self.check_if_closed()?;
+
+        if self.options.open_mode != PageOpenMode::ReadOnly {
+            self.commit()?;
+        }
+
+        for &page_id in page_ids {
+            let (_page, data) = self.read_committed_page_file(page_id)?;
+
+            callback(
+                &self.fanout.make_path(page_id, RevisionFlag::Current),
+                &data,
+            )?;
+        }
+
+        let mut metadata = self.make_metadata();
+        metadata.generations.clear();
+        metadata.history.clear();
+
+        let mut vfs = MemoryVfs::new();
+        self.format.write_metadata_file(
+            &mut vfs,
+            METADATA_FILENAME,
+            metadata,
+            VfsSyncOption::None,
+        )?;
+
+        callback(METADATA_FILENAME, &vfs.read(METADATA_FILENAME)?)
+    }
+
+    // Return the size of the committed file of each given page and the size
+    // of its payload before compression. The payload size is of the page
+    // serialized again with the current codec.
+    pub fn committed_page_sizes(&mut self, page_ids: &[PageId]) -> Result<Vec<(u64, u64)>, Error> {
+        self.check_if_closed()?;
+
+        if self.options.open_mode != PageOpenMode::ReadOnly {
+            self.commit()?;
+        }
+
+        let mut sizes = Vec::with_capacity(page_ids.len());
+
+        for &page_id in page_ids {
+            let (page, data) = self.read_committed_page_file(page_id)?;
+            let payload_size = self.format.content_hash(&page)?.length();
+
+            sizes.push((data.len() as u64, payload_size));
+        }
+
+        Ok(sizes)
+    }
+
+    pub fn metadata_file_size(&mut self) -> Result<u64, Error> {
+        Ok(self.vfs.read(METADATA_FILENAME)?.len() as u64)
+    }
+
+    // The newest file with a revision that is not newer than the committed
+    // revision is the committed file
+    fn read_committed_page_file(&mut self, page_id: PageId) -> Result<PageFileData<T>, Error> {
+        for revision_flag in [RevisionFlag::New, RevisionFlag::Current] {
+            let path = self.fanout.make_path(page_id, revision_flag);
+
+            if let Some(page_and_data) = self.read_committed_file(&path, revision_flag)? {
+                return Ok(page_and_data);
+            }
+        }
+
+        // The committed file may have been moved to the history
+        if let Some(path) = self.find_page_history_path(page_id)? {
+            if let Some(page_and_data) = self.read_committed_file(&path, RevisionFlag::Current)? {
+                return Ok(page_and_data);
+            }
+        }
+
+        self.check_external_modification()?;
+
+        Err(Error::InvalidPageData {
+            page: page_id,
+            message: "missing page",
+        })
+    }
+
+    fn read_committed_file(
+        &mut self,
+        path: &str,
+        revision_flag: RevisionFlag,
+    ) -> Result<Option<PageFileData<T>>, Error> {
+        if !self.vfs.exists(path)? {
+            return Ok(None);
+        }
+
+        match self
+            .format
+            .read_file_and_data::<Page<T>, _>(&mut self.vfs, path)
+        {
+            Ok((page, data))
+                if page.revision <= self.counter_tracker.revision()
+                    && self.is_own_uuid(page.uuid) =>
+            {
+                Ok(Some((page, data)))
+            }
+            Ok(_) => Ok(None),
+            // A newer file that was torn by an interrupted write
+            Err(Error::BadChecksum { .. }) if revision_flag == RevisionFlag::New => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    // Copy the committed pages to a new page table using the options of the
+    // new table. Pages are decoded and encoded again, so the copy can use a
+    // different compression, codec, or encryption key.
+    pub fn clone_to<W: Vfs>(&mut self, vfs: W, options: PageTableOptions) -> Result<(), Error>
+    where
+        T: Clone,
+    {
+        self.check_if_closed()?;
+
+        if self.options.open_mode != PageOpenMode::ReadOnly {
+            self.commit()?;
+        }
+
+        let mut target = PageTable::<T, M, W>::open(
+            vfs,
+            PageTableOptions {
+                open_mode: PageOpenMode::CreateOnly,
+                ..options
+            },
+        )?;
+
+        // The copy has no previous generations, so pending IDs are free now
+        let mut free_id_list = self.counter_tracker.free_id_list().clone();
+        free_id_list.extend(
+            self.counter_tracker
+                .pending_free_id_list()
+                .iter()
+                .map(|(_, page_id)| *page_id),
+        );
+        let free_id_list: Vec<PageId> = free_id_list.into_iter().collect();
+
+        target.counter_tracker.restore(
+            0,
+            None,
+            self.counter_tracker.id_counter(),
+            &free_id_list,
+            &[],
+        );
+        target
+            .counter_tracker
+            .set_root_id(self.counter_tracker.root_id());
+        target.auxiliary_metadata = self.auxiliary_metadata.clone();
+
+        for page_id in 1..=self.counter_tracker.id_counter() {
+            if let Some(content) = self.get_(page_id)? {
+                target.put(page_id, content.clone())?;
+            }
+        }
+
+        target.commit()?;
+
+        Ok(())
+    }
+
+    pub fn get(&mut self, page_id: PageId) -> Result<Option<&T>, Error> {
+        self.check_if_closed()?;
+
+        self.get_(page_id)
+    }
+
+    fn get_(&mut self, page_id: PageId) -> Result<Option<&T>, Error> {
+        self.check_page_id_counter_consistency(page_id)?;
+
+        self.ensure_page_cached(page_id)?;
+
+        if let Some(page) = self.page_cache.get_touched(page_id) {
+            if let Some(content) = &page.content {
+                Ok(Some(content))
+            } else {
+                Ok(None)
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Revision of the most recent save of the page, which must be in the cache
+    pub fn page_revision(&self, page_id: PageId) -> Option<RevisionId> {
+        self.page_cache.peek(page_id).map(|page| page.revision)
+    }
+
+    pub fn put(&mut self, page_id: PageId, content: T) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        let result = self.put_(page_id, content);
+
+        if let Err(error) = &result {
+            self.close(error);
+        }
+
+        result
+    }
+
+    fn put_(&mut self, page_id: PageId, content: T) -> Result<(), Error> {
+        self.check_page_id_counter_consistency(page_id)?;
+
+        let page = Page {
+            uuid: self.uuid,
+            id: page_id,
+            revision: self.counter_tracker.revision(),
+            deleted: false,
+            content: Some(content),
+        };
+
+        self.file_tracker.pending_removal.remove(&page_id);
+
+        if let Some(evicted_page_info) = self.page_cache.put_touched(page_id, page) {
+            self.maybe_save_evicted_page(evicted_page_info)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, page_id: PageId) -> Result<Option<PageUpdateGuard<T>>, Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        self.update_(page_id)
+    }
+
+    fn update_(&mut self, page_id: PageId) -> Result<Option<PageUpdateGuard<T>>, Error> {
+        self.check_page_id_counter_consistency(page_id)?;
+
+        self.ensure_page_cached(page_id)?;
+
+        if let Some(page) = self.page_cache.get_touched_mut(page_id) {
+            if page.content.is_some() {
+                Ok(Some(PageUpdateGuard::new(page)))
+            } else {
+                Ok(None)
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn remove(&mut self, page_id: PageId) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        let result = self.remove_(page_id);
+
+        if let Err(error) = &result {
+            self.close(error);
+        }
+
+        result
+    }
+
+    fn remove_(&mut self, page_id: PageId) -> Result<(), Error> {
+        self.check_page_id_counter_consistency(page_id)?;
+
+        let page = Page {
+            uuid: self.uuid,
+            id: page_id,
+            revision: self.counter_tracker.revision(),
+            deleted: true,
+            content: None,
+        };
+
+        if let Some(evicted_page_info) = self.page_cache.put_touched(page_id, page) {
+            self.maybe_save_evicted_page(evicted_page_info)?;
+        }
+
+        self.counter_tracker
+            .free_page_id(page_id, self.options.free_page_reuse_delay as u64);
+        self.file_tracker.pending_removal.insert(page_id);
+
+        Ok(())
+    }
+
+    pub fn commit(&mut self) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        let result = self.commit_();
+
+        if let Err(error) = &result {
+            self.close(error);
+        }
+
+        result
+    }
+
+    fn commit_(&mut self) -> Result<(), Error> {
+        if !self.is_anything_modified() {
+            return Ok(());
+        }
+
+        self.counter_tracker.increment_revision();
+
+        self.save_all_modified_pages()?;
+        self.sync_and_rename_pending_page_files()?;
+        self.file_tracker.pending_sync.clear();
+        self.counter_tracker.release_pending_free_ids();
+        self.save_metadata()?;
+        self.commit_counters();
+        self.promote_page_filenames()?;
+        self.file_tracker.pending_promotion.clear();
+        self.remove_deleted_page_files()?;
+        self.page_cache.clear_modified_pages();
+        self.remove_expired_page_history()?;
+
+        Ok(())
+    }
+
+    pub fn page_cache_size(&self) -> usize {
+        self.options.page_cache_size
+    }
+
+    pub fn has_uncommitted_changes(&self) -> bool {
+        self.is_anything_modified()
+    }
+
+    // Hint the file system to start reading the file of a page that is
+    // expected to be loaded soon, such as the next leaf of a cursor
+    pub fn prefetch(&self, page_id: PageId) {
+        if self.page_cache.peek(page_id).is_some() {
+            return;
+        }
+
+        let revision_flag = if self.file_tracker.pending_sync.contains(&page_id) {
+            RevisionFlag::NewUnsync
+        } else if self.file_tracker.pending_promotion.contains(&page_id) {
+            RevisionFlag::New
+        } else {
+            RevisionFlag::Current
+        };
+
+        let _ = self
+            .vfs
+            .will_read(&self.fanout.make_path(page_id, revision_flag));
+    }
+
+    pub fn modified_page_count(&self) -> usize {
+        self.page_cache.modified_pages().len()
+    }
+
+    // Write the modified pages in the cache like evicted pages so they are
+    // included in the next commit without being held in memory as modified
+    pub fn write_modified_pages(&mut self) -> Result<(), Error> {
+        self.check_if_closed()?;
+        self.check_if_read_only()?;
+
+        let result = self.write_modified_pages_();
+
+        if let Err(error) = &result {
+            self.close(error);
+        }
+
+        result
+    }
+
+    fn write_modified_pages_(&mut self) -> Result<(), Error> {
+        self.counter_tracker.increment_revision();
+        self.save_all_modified_pages()?;
+        self.page_cache.clear_modified_pages();
+
+        Ok(())
+    }
+
+    fn is_anything_modified(&self) -> bool {
+        self.counter_tracker.is_dirty() || !self.page_cache.modified_pages().is_empty()
+    }
+
+    fn load_and_restore_metadata(&mut self) -> Result<(), Error> {
+        if self.options.generation > 0 {
+            return self.load_and_restore_metadata_generation();
+        }
+
+        let error = match self.load_metadata_file(METADATA_FILENAME) {
+            Ok(metadata) => {
+                self.restore_metadata(metadata);
+                return Ok(());
+            }
+            Err(error) => error,
+        };
+
+        // The copy is written last and is normally the same revision as the
+        // main file. The previous file is one revision behind and is only
+        // usable if the page files of the last commit weren't promoted.
+        for path in [METADATA_COPY_FILENAME, METADATA_OLD_FILENAME] {
+            if let Ok(metadata) = self.load_metadata_file(path) {
+                self.restore_metadata(metadata);
+
+                if self.is_restored_metadata_consistent() && self.discard_lost_commit_files()? {
+                    self.metadata_damaged = true;
+                    return Ok(());
+                }
+
+                self.counter_tracker = CounterTracker::default();
+                self.file_tracker = FileTracker::default();
+            }
+        }
+
+        Err(error)
+    }
+
+    fn load_and_restore_metadata_generation(&mut self) -> Result<(), Error> {
+        let path = make_metadata_generation_path(self.options.generation);
+
+        if !self.vfs.exists(&path)? {
+            return Err(Error::InvalidMetadata {
+                message: "metadata generation not found",
+            });
+        }
+
+        let metadata = self.load_metadata_file(&path)?;
+        self.restore_metadata(metadata);
+
+        if !self.is_restored_metadata_consistent() {
+            return Err(Error::InvalidMetadata {
+                message: "pages of metadata generation not available",
+            });
+        }
+
+        Ok(())
+    }
+
+    fn restore_metadata(&mut self, metadata: Metadata<M>) {
+        self.uuid = metadata.uuid;
+
+        self.counter_tracker.restore(
+            metadata.revision,
+            metadata.root_id,
+            metadata.id_counter,
+            &metadata.free_id_list,
+            &metadata.pending_free_id_list,
+        );
+
+        self.auxiliary_metadata = metadata.auxiliary;
+        self.generation_revisions = metadata.generations;
+        self.page_history = metadata.history;
+        self.fanout = metadata.fanout;
+
+        // Continue using the codec chosen when the database was created
+        self.format.set_codec(metadata.codec);
+    }
+
+    // Check the root page can be loaded at the restored revision. Other pages
+    // that are too new will still be reported as missing when they are read.
+    fn is_restored_metadata_consistent(&mut self) -> bool {
+        match self.counter_tracker.root_id() {
+            Some(root_id) => matches!(self.load_latest_known_page(root_id), Ok(Some(_))),
+            None => true,
+        }
+    }
+
+    // After falling back to older metadata, files written by the lost commit
+    // have revisions newer than the restored revision. New commits would
+    // reach those revisions and make the files look committed, so they are
+    // deleted, and the counters are moved past the IDs of the remaining
+    // files and every revision found. A newer current file of a page that
+    // was allocated in the restored revision means the lost commit was
+    // promoted and replaced a page of the restored tree, so false is
+    // returned.
+    fn discard_lost_commit_files(&mut self) -> Result<bool, Error> {
+        let restored_revision = self.counter_tracker.revision();
+        let restored_id_counter = self.counter_tracker.id_counter();
+        let free_ids: HashSet<PageId> = self
+            .counter_tracker
+            .free_id_list()
+            .iter()
+            .cloned()
+            .chain(
+                self.counter_tracker
+                    .pending_free_id_list()
+                    .iter()
+                    .map(|(_, page_id)| *page_id),
+            )
+            .collect();
+        let mut max_page_id = 0;
+        let mut max_revision = restored_revision;
+        let mut lost_paths = Vec::new();
+
+        for (path, page_id, revision_flag) in self.list_page_files()? {
+            // Unreadable files are handled when the page is loaded
+            let page: Option<Page<T>> = match revision_flag {
+                Some(_) => self.format.read_file(&mut self.vfs, &path).ok(),
+                None => None,
+            };
+
+            match page {
+                Some(page) if page.revision > restored_revision => {
+                    let is_new_page = page_id > restored_id_counter || free_ids.contains(&page_id);
+
+                    if revision_flag == Some(RevisionFlag::Current) && !is_new_page {
+                        return Ok(false);
+                    }
+
+                    max_revision = max_revision.max(page.revision);
+                    lost_paths.push(path);
+                }
+                _ => max_page_id = max_page_id.max(page_id),
+            }
+        }
+
+        if self.options.open_mode != PageOpenMode::ReadOnly {
+            for path in lost_paths {
+                self.vfs.remove_file(&path)?;
+            }
+
+            self.counter_tracker.advance(max_page_id, max_revision);
+        }
+
+        Ok(true)
+    }
+
+    // Reusing a free ID that still has a live page would overwrite the page,
+    // so such IDs are dropped from the list. Allocated IDs missing from the
+    // list require a tree traversal and are only found by reclaim_ids().
+    fn validate_free_id_list(&mut self) -> Result<(), Error> {
+        if self.options.generation > 0 {
+            return Ok(());
+        }
+
+        let page_ids: Vec<PageId> = self
+            .counter_tracker
+            .free_id_list()
+            .iter()
+            .cloned()
+            .chain(
+                self.counter_tracker
+                    .pending_free_id_list()
+                    .iter()
+                    .map(|(_, page_id)| *page_id),
+            )
+            .filter(|page_id| *page_id <= self.counter_tracker.id_counter())
+            .collect();
+        let mut used_ids = HashSet::new();
+
+        for page_id in page_ids {
+            if let Some((page, _)) = self.load_latest_known_page(page_id)? {
+                if !page.deleted && page.content.is_some() {
+                    used_ids.insert(page_id);
+                }
+            }
+        }
+
+        self.counter_tracker.remove_invalid_free_ids(&used_ids);
+
+        Ok(())
+    }
+
+    // A read-only handle doesn't hold the database steady, so compare the
+    // metadata on disk with the metadata restored when opened
+    fn check_external_modification(&mut self) -> Result<(), Error> {
+        if self.options.open_mode != PageOpenMode::ReadOnly || self.options.generation > 0 {
+            return Ok(());
+        }
+
+        let metadata: Metadata<M> = match self.load_metadata_file(METADATA_FILENAME) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(()),
+        };
+
+        if metadata.uuid != self.uuid
+            || metadata.revision != self.counter_tracker.revision_on_persistence()
+        {
+            Err(Error::ExternallyModified)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn load_metadata_file(&mut self, path: &str) -> Result<Metadata<M>, Error> {
+        self.format.read_file(&mut self.vfs, path)
+    }
+
+    fn save_new_metadata(&mut self) -> Result<(), Error> {
+        self.uuid = self.uuid_generator.new_uuid();
+
+        // We check for the backup file too in case the main file disappears
+        if self.vfs.exists(METADATA_FILENAME)?
+            || self.vfs.exists(METADATA_COPY_FILENAME)?
+            || self.vfs.exists(METADATA_OLD_FILENAME)?
+        {
+            return Err(Error::InvalidMetadata {
+                message: "database already exists",
+            });
+        }
+
+        self.save_metadata()?;
+
+        Ok(())
+    }
+
+    fn load_page(
         &mut self,
         page_id: PageId,
         revision_flag: RevisionFlag,
     ) -> Result<Option<Page<T>>, Error> {
-        let path = make_path(page_id, revision_flag);
+        let path = self.fanout.make_path(page_id, revision_flag);
 
-        if !self.vfs.exists(&path)? {
+        self.load_page_file(page_id, &path)
+    }
+
+    fn load_page_file(&mut self, page_id: PageId, path: &str) -> Result<Option<Page<T>>, Error> {
+        if !self.vfs.exists(path)? {
             return Ok(None);
         }
 
-        let page: Page<T> = self.format.read_file(self.vfs.as_mut(), &path)?;
+        let page: Page<T> = self.format.read_file(&mut self.vfs, path)?;
 
-        if !self.uuid.is_nil() && page.uuid != self.uuid {
+        if !self.uuid.is_nil() && !self.is_own_uuid(page.uuid) {
             return Err(Error::InvalidPageData {
                 page: page_id,
                 message: "wrong UUID",
@@ -594,32 +1978,45 @@ where
         Ok(Some(page))
     }
 
-    fn load_latest_known_page(&mut self, page_id: PageId) -> Result<Option<Page<T>>, Error> {
+    // Returns the page and whether a file with a newer, uncommitted revision
+    // was ignored
+    fn load_latest_known_page(
+        &mut self,
+        page_id: PageId,
+    ) -> Result<Option<(Page<T>, bool)>, Error> {
+        let mut superseded = false;
+
         if self.file_tracker.pending_sync.contains(&page_id) {
-            let page_2 = self.load_page(page_id, RevisionFlag::NewUnsync)?;
+            let page_2 = self.load_newer_page(page_id, RevisionFlag::NewUnsync, &mut superseded)?;
 
             if let Some(page) = page_2 {
                 if page.revision <= self.counter_tracker.revision() {
-                    return Ok(Some(page));
+                    return Ok(Some((page, superseded)));
                 }
+
+                superseded = true;
             }
         }
 
-        let page_1 = self.load_page(page_id, RevisionFlag::New)?;
+        let page_1 = self.load_newer_page(page_id, RevisionFlag::New, &mut superseded)?;
 
         if let Some(page) = page_1 {
             if page.revision <= self.counter_tracker.revision() {
                 self.maybe_queue_page_for_filename_promotion(&page);
 
-                return Ok(Some(page));
+                return Ok(Some((page, superseded)));
             }
+
+            superseded = true;
         }
 
         let page_0 = self.load_page(page_id, RevisionFlag::Current)?;
 
         if let Some(page) = page_0 {
             if page.revision <= self.counter_tracker.revision() {
-                return Ok(Some(page));
+                return Ok(Some((page, superseded)));
+            } else if let Some(page) = self.load_page_from_history(page_id)? {
+                return Ok(Some((page, true)));
             } else {
                 return Err(Error::InvalidPageData {
                     page: page_id,
@@ -628,21 +2025,125 @@ where
             }
         }
 
+        // The page may have been purged after the restored generation
+        if let Some(page) = self.load_page_from_history(page_id)? {
+            return Ok(Some((page, true)));
+        }
+
+        Ok(None)
+    }
+
+    // A newer file that fails its checksum was likely torn by an interrupted
+    // write, so it is discarded in favor of the older file
+    fn load_newer_page(
+        &mut self,
+        page_id: PageId,
+        revision_flag: RevisionFlag,
+        superseded: &mut bool,
+    ) -> Result<Option<Page<T>>, Error> {
+        match self.load_page(page_id, revision_flag) {
+            Err(Error::BadChecksum { .. }) => {
+                self.recovered_pages.insert(page_id);
+                *superseded = true;
+                Ok(None)
+            }
+            result => result,
+        }
+    }
+
+    // Find the page replaced by the earliest generation after the
+    // restored revision
+    fn load_page_from_history(&mut self, page_id: PageId) -> Result<Option<Page<T>>, Error> {
+        if self.options.generation == 0 && !self.pinned {
+            return Ok(None);
+        }
+
+        if let Some(path) = self.find_page_history_path(page_id)? {
+            if let Some(page) = self.load_page_file(page_id, &path)? {
+                if page.revision <= self.counter_tracker.revision() {
+                    return Ok(Some(page));
+                }
+            }
+        }
+
         Ok(None)
     }
 
+    fn find_page_history_path(&mut self, page_id: PageId) -> Result<Option<String>, Error> {
+        let dir_path = self.fanout.split_number(page_id);
+
+        if !self.vfs.exists(&dir_path)? {
+            return Ok(None);
+        }
+
+        let prefix = format!("grebedb_{:016x}_r", page_id);
+        let current_revision = self.counter_tracker.revision();
+        let revision = self
+            .vfs
+            .read_dir(&dir_path)?
+            .iter()
+            .filter_map(|filename| filename.strip_prefix(&prefix)?.strip_suffix(".grebedb"))
+            .filter_map(|revision| RevisionId::from_str_radix(revision, 16).ok())
+            .filter(|revision| *revision > current_revision)
+            .min();
+
+        Ok(revision.map(|revision| self.fanout.make_history_path(page_id, revision)))
+    }
+
+    fn ensure_page_cached(&mut self, page_id: PageId) -> Result<(), Error> {
+        if self.page_cache.contains_page(page_id) {
+            self.cache_hits += 1;
+        } else {
+            self.cache_misses += 1;
+            self.load_page_into_cache(page_id)?;
+        }
+
+        Ok(())
+    }
+
     fn load_page_into_cache(&mut self, page_id: PageId) -> Result<bool, Error> {
-        let page = self.load_latest_known_page(page_id)?;
+        let page = self.load_latest_known_page(page_id);
+
+        // The returned page is from the file read last unless it was superseded
+        let payload_hash = self.format.last_payload_hash();
+
+        let page = match page {
+            Ok(page) => page,
+            Err(error) => {
+                self.check_external_modification()?;
+                return Err(error);
+            }
+        };
+
+        // A missing, removed, or newer page can mean a writer committed
+        let unexpected = match &page {
+            Some((page, superseded)) => (*superseded && !self.pinned) || page.deleted,
+            None => true,
+        };
 
-        if let Some(page) = page {
+        if unexpected {
+            self.check_external_modification()?;
+        }
+
+        if let Some((page, superseded)) = page {
             if page.deleted || page.content.is_none() {
                 return Ok(false);
             }
 
+            // A superseded file must be overwritten, so the page is only
+            // eligible for skipping its write if it is the newest file.
+            let stored_hash = if self.options.open_mode != PageOpenMode::ReadOnly && !superseded {
+                payload_hash
+            } else {
+                None
+            };
+
             if let Some(evicted_page_info) = self.page_cache.put_touched(page_id, page) {
                 self.maybe_save_evicted_page(evicted_page_info)?;
             }
 
+            self.page_cache.set_stored_hash(page_id, stored_hash);
+
             Ok(true)
         } else {
             Ok(false)
@@ -652,7 +2153,8 @@ where
     fn save_page(&mut self, page_id: PageId, page: &Page<T>) -> Result<(), Error> {
         self.check_if_read_only()?;
 
-        if self.options.file_sync == VfsSyncOption::None {
+        // Replaced pages are only kept when promoting the filename
+        if self.options.file_sync == VfsSyncOption::None && self.options.metadata_generations <= 1 {
             self.save_page_by_overwrite(page_id, page)?;
         } else {
             self.save_page_with_delayed_sync(page_id, page)?;
@@ -668,9 +2170,9 @@ where
     }
 
     fn save_page_by_overwrite(&mut self, page_id: PageId, page: &Page<T>) -> Result<(), Error> {
-        let path_1 = make_path(page_id, RevisionFlag::New);
+        let path_1 = self.fanout.make_path(page_id, RevisionFlag::New);
         self.format
-            .write_file(self.vfs.as_mut(), &path_1, page, VfsSyncOption::None)?;
+            .write_file(&mut self.vfs, &path_1, page, VfsSyncOption::None)?;
         Ok(())
     }
 
@@ -679,10 +2181,10 @@ where
         page_id: PageId,
         page: &Page<T>,
     ) -> Result<(), Error> {
-        let path_2 = make_path(page_id, RevisionFlag::NewUnsync);
+        let path_2 = self.fanout.make_path(page_id, RevisionFlag::NewUnsync);
 
         self.format
-            .write_file(self.vfs.as_mut(), &path_2, page, VfsSyncOption::None)?;
+            .write_file(&mut self.vfs, &path_2, page, VfsSyncOption::None)?;
 
         self.file_tracker.pending_sync.insert(page_id);
 
@@ -690,15 +2192,11 @@ where
     }
 
     fn _save_page_by_atomic(&mut self, page_id: PageId, page: &Page<T>) -> Result<(), Error> {
-        let path_1 = make_path(page_id, RevisionFlag::New);
+        let path_1 = self.fanout.make_path(page_id, RevisionFlag::New);
         let path_1_temp = format!("{}.tmp", &path_1);
 
-        self.format.write_file(
-            self.vfs.as_mut(),
-            &path_1_temp,
-            page,
-            self.options.file_sync,
-        )?;
+        self.format
+            .write_file(&mut self.vfs, &path_1_temp, page, self.options.file_sync)?;
 
         self.vfs.rename_file(&path_1_temp, &path_1)?;
         self.file_tracker.pending_promotion.insert(page_id);
@@ -726,36 +2224,38 @@ where
     fn save_metadata(&mut self) -> Result<(), Error> {
         self.check_if_read_only()?;
 
-        let metadata = Metadata {
-            uuid: self.uuid,
-            revision: self.counter_tracker.revision(),
-            id_counter: self.counter_tracker.id_counter(),
-            root_id: self.counter_tracker.root_id(),
-            free_id_list: self
-                .counter_tracker
-                .free_id_list()
-                .iter()
-                .cloned()
-                .collect(),
-            auxiliary: self.auxiliary_metadata.clone(),
-        };
+        let new_generation =
+            self.counter_tracker.revision() != self.counter_tracker.revision_on_persistence();
+
+        if new_generation {
+            self.add_generation()?;
+        }
+
+        let metadata = self.make_metadata();
+
+        // Don't overwrite a good backup with the damaged main file. When
+        // keeping generations, the previous file must be a different revision.
+        if !self.metadata_damaged
+            && (new_generation || self.options.metadata_generations <= 1)
+            && self.vfs.exists(METADATA_FILENAME)?
+        {
+            self.rotate_metadata_generations()?;
 
-        if self.vfs.exists(METADATA_FILENAME)? {
             let data = self.vfs.read(METADATA_FILENAME)?;
             self.vfs
                 .write(METADATA_OLD_FILENAME, &data, self.options.file_sync)?;
         }
 
         if self.options.file_sync == VfsSyncOption::None {
-            self.format.write_file(
-                self.vfs.as_mut(),
+            self.format.write_metadata_file(
+                &mut self.vfs,
                 METADATA_FILENAME,
                 metadata.clone(),
                 self.options.file_sync,
             )?;
         } else {
-            self.format.write_file(
-                self.vfs.as_mut(),
+            self.format.write_metadata_file(
+                &mut self.vfs,
                 METADATA_NEW_FILENAME,
                 metadata.clone(),
                 self.options.file_sync,
@@ -765,18 +2265,124 @@ where
                 .rename_file(METADATA_NEW_FILENAME, METADATA_FILENAME)?;
         }
 
-        self.format.write_file(
-            self.vfs.as_mut(),
+        self.format.write_metadata_file(
+            &mut self.vfs,
             METADATA_COPY_FILENAME,
             metadata,
             self.options.file_sync,
         )?;
 
+        self.metadata_damaged = false;
+
+        Ok(())
+    }
+
+    fn make_metadata(&self) -> Metadata<M> {
+        Metadata {
+            uuid: self.uuid,
+            revision: self.counter_tracker.revision(),
+            id_counter: self.counter_tracker.id_counter(),
+            root_id: self.counter_tracker.root_id(),
+            free_id_list: self
+                .counter_tracker
+                .free_id_list()
+                .iter()
+                .cloned()
+                .collect(),
+            auxiliary: self.auxiliary_metadata.clone(),
+            generations: self.generation_revisions.clone(),
+            history: self.page_history.clone(),
+            pending_free_id_list: self
+                .counter_tracker
+                .pending_free_id_list()
+                .iter()
+                .cloned()
+                .collect(),
+            fanout: self.fanout,
+            codec: self.format.codec(),
+        }
+    }
+
+    fn add_generation(&mut self) -> Result<(), Error> {
+        let revision = self.counter_tracker.revision();
+
+        // The previous generation file isn't replaced when the main file is damaged
+        if !self.metadata_damaged {
+            self.generation_revisions
+                .insert(0, self.counter_tracker.revision_on_persistence());
+            self.generation_revisions
+                .truncate(self.options.metadata_generations);
+        }
+
+        // Record the files that will be replaced by promotion so they can be
+        // removed even if the process stops before the next commit
+        if self.options.metadata_generations > 1 {
+            let page_ids: Vec<PageId> = self
+                .file_tracker
+                .pending_promotion
+                .iter()
+                .cloned()
+                .collect();
+
+            for page_id in page_ids {
+                if self
+                    .vfs
+                    .exists(&self.fanout.make_path(page_id, RevisionFlag::Current))?
+                {
+                    self.page_history.push((revision, page_id));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rotate_metadata_generations(&mut self) -> Result<(), Error> {
+        for generation in (1..self.options.metadata_generations).rev() {
+            let path = make_metadata_generation_path(generation);
+
+            if self.vfs.exists(&path)? {
+                self.vfs
+                    .rename_file(&path, &make_metadata_generation_path(generation + 1))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Remove replaced page files that are older than the oldest generation
+    fn remove_expired_page_history(&mut self) -> Result<(), Error> {
+        let oldest_revision = match self.generation_revisions.last() {
+            Some(revision) => *revision,
+            None => return Ok(()),
+        };
+
+        let (expired, kept): (Vec<_>, Vec<_>) = self
+            .page_history
+            .iter()
+            .partition(|(revision, _page_id)| *revision <= oldest_revision);
+
+        self.page_history = kept;
+
+        for (revision, page_id) in expired {
+            let path = self.fanout.make_history_path(page_id, revision);
+
+            if self.vfs.exists(&path)? {
+                self.vfs.remove_file(&path)?;
+            }
+        }
+
         Ok(())
     }
 
     fn maybe_save_evicted_page(&mut self, evicted_page_info: EvictedPage<T>) -> Result<(), Error> {
         if self.options.open_mode != PageOpenMode::ReadOnly && evicted_page_info.modified {
+            if let Some(stored_hash) = evicted_page_info.stored_hash {
+                if self.format.content_hash(&evicted_page_info.page)? == stored_hash {
+                    return Ok(());
+                }
+            }
+
             self.save_evicted_page(evicted_page_info.id, evicted_page_info.page)?;
         }
 
@@ -796,15 +2402,37 @@ where
         let page_ids: Vec<PageId> = self.page_cache.modified_pages().iter().cloned().collect();
 
         for page_id in page_ids {
+            let content_hash = self.cached_page_content_hash(page_id)?;
+
+            if content_hash.is_some() && content_hash == self.page_cache.stored_hash(page_id) {
+                continue;
+            }
+
             self.page_cache
                 .set_page_revision(page_id, self.counter_tracker.revision());
 
             self.save_page_from_cache(page_id)?;
+
+            if content_hash.is_some() {
+                self.page_cache
+                    .set_stored_hash(page_id, self.format.last_payload_hash());
+            }
         }
 
         Ok(())
     }
 
+    // Deleted pages don't have a hash so they are always written
+    fn cached_page_content_hash(&mut self, page_id: PageId) -> Result<Option<ContentHash>, Error> {
+        let page = self.page_cache.peek(page_id).unwrap();
+
+        if page.deleted {
+            Ok(None)
+        } else {
+            Ok(Some(self.format.content_hash(page)?))
+        }
+    }
+
     fn sync_and_rename_pending_page_files(&mut self) -> Result<(), Error> {
         let page_ids: Vec<PageId> = self.file_tracker.pending_sync.iter().cloned().collect();
 
@@ -819,7 +2447,7 @@ where
     }
 
     fn sync_pending_page_file(&mut self, page_id: PageId) -> Result<(), Error> {
-        let path_2 = make_path(page_id, RevisionFlag::NewUnsync);
+        let path_2 = self.fanout.make_path(page_id, RevisionFlag::NewUnsync);
 
         self.vfs.sync_file(&path_2, self.options.file_sync)?;
 
@@ -827,8 +2455,8 @@ where
     }
 
     fn rename_pending_page_file(&mut self, page_id: PageId) -> Result<(), Error> {
-        let path_1 = make_path(page_id, RevisionFlag::New);
-        let path_2 = make_path(page_id, RevisionFlag::NewUnsync);
+        let path_1 = self.fanout.make_path(page_id, RevisionFlag::New);
+        let path_2 = self.fanout.make_path(page_id, RevisionFlag::NewUnsync);
 
         self.vfs.rename_file(&path_2, &path_1)?;
         self.file_tracker.pending_promotion.insert(page_id);
@@ -841,14 +2469,69 @@ where
 
         assert!(self.file_tracker.pending_sync.is_empty());
 
-        let path_0 = make_path(page_id, RevisionFlag::Current);
-        let path_1 = make_path(page_id, RevisionFlag::New);
+        let path_0 = self.fanout.make_path(page_id, RevisionFlag::Current);
+        let path_1 = self.fanout.make_path(page_id, RevisionFlag::New);
+
+        if self.options.metadata_generations > 1 && self.vfs.exists(&path_0)? {
+            let history_path = self
+                .fanout
+                .make_history_path(page_id, self.counter_tracker.revision());
+            self.vfs.rename_file(&path_0, &history_path)?;
+        }
 
         self.vfs.rename_file(&path_1, &path_0)?;
 
         Ok(())
     }
 
+    // Delete the files of pages removed by the commit. This must be done
+    // after the metadata is saved so the tombstone files are no longer needed.
+    fn remove_deleted_page_files(&mut self) -> Result<(), Error> {
+        if self.file_tracker.pending_removal.is_empty() {
+            return Ok(());
+        }
+
+        let page_ids: Vec<PageId> = self.file_tracker.pending_removal.drain().collect();
+
+        for page_id in page_ids {
+            self.remove_page_files(page_id)?;
+        }
+
+        self.format.clear_dir_cache();
+
+        Ok(())
+    }
+
+    // Previous generations don't need these files because a removed page
+    // has no content. Replaced files with content are moved to the history
+    // when their filename is promoted.
+    //
+    // Returns the number of files deleted.
+    fn remove_page_files(&mut self, page_id: PageId) -> Result<u64, Error> {
+        let mut count = 0;
+
+        for revision_flag in [
+            RevisionFlag::Current,
+            RevisionFlag::New,
+            RevisionFlag::NewUnsync,
+        ] {
+            let path = self.fanout.make_path(page_id, revision_flag);
+
+            if self.vfs.exists(&path)? {
+                self.vfs.remove_file(&path)?;
+                count += 1;
+            }
+        }
+
+        let dir_path = self.fanout.split_number(page_id);
+
+        if self.vfs.exists(&dir_path)? {
+            self.vfs.remove_empty_dir_all(&dir_path)?;
+        }
+
+        Ok(count)
+    }
+
     fn maybe_queue_page_for_filename_promotion(&mut self, page: &Page<T>) {
         if self.options.open_mode != PageOpenMode::ReadOnly
             && page.revision <= self.counter_tracker.revision_on_persistence()
@@ -880,6 +2563,12 @@ where
         Ok(())
     }
 
+    // Refuse further operations to prevent further corruption
+    fn close(&mut self, error: &Error) {
+        self.closed = true;
+        self.last_error = Some(error.to_string());
+    }
+
     fn check_if_closed(&self) -> Result<(), Error> {
         if self.closed {
             Err(Error::Closed)
@@ -908,10 +2597,11 @@ where
     }
 }
 
-impl<T, M> Drop for PageTable<T, M>
+impl<T, M, V> Drop for PageTable<T, M, V>
 where
     T: Serialize + DeserializeOwned,
     M: Serialize + DeserializeOwned + Clone,
+    V: Vfs,
 {
     fn drop(&mut self) {
         if self.options.file_locking {
@@ -957,14 +2647,6 @@ impl<'a, T> Drop for PageUpdateGuard<'a, T> {
     }
 }
 
-fn make_path(page_id: PageId, revision_flag: RevisionFlag) -> String {
-    format!(
-        "{}/{}",
-        split_number(page_id),
-        make_filename(page_id, revision_flag)
-    )
-}
-
 fn make_filename(page_id: PageId, revision_flag: RevisionFlag) -> String {
     format!(
         "grebedb_{:016x}_{}.grebedb",
@@ -977,34 +2659,86 @@ fn make_filename(page_id: PageId, revision_flag: RevisionFlag) -> String {
     )
 }
 
-fn split_number(mut id: u64) -> String {
-    let mut parts = [0u64; 8];
-    let bits = 8;
-    let mask = 0xff;
+// Return the page ID and the revision flag of a page filename, or none as
+// the flag for a file in the page history
+fn parse_page_filename(filename: &str) -> Option<(PageId, Option<RevisionFlag>)> {
+    let name = filename
+        .strip_prefix("grebedb_")?
+        .strip_suffix(".grebedb")?;
+    let (page_id, suffix) = name.split_once('_')?;
 
-    for index in (0..bits).rev() {
-        parts[index] = id & mask;
-        id >>= bits;
+    if page_id.len() != 16 {
+        return None;
     }
 
-    format!(
-        "{:02x}/{:02x}/{:02x}/{:02x}/{:02x}/{:02x}/{:02x}",
-        parts[0], parts[1], parts[2], parts[3], parts[4], parts[5], parts[6]
-    )
+    let page_id = PageId::from_str_radix(page_id, 16).ok()?;
+    let revision_flag = match suffix {
+        "0" => Some(RevisionFlag::Current),
+        "1" => Some(RevisionFlag::New),
+        "2" => Some(RevisionFlag::NewUnsync),
+        _ => {
+            let revision = suffix.strip_prefix('r')?;
+
+            if revision.len() != 16 || RevisionId::from_str_radix(revision, 16).is_err() {
+                return None;
+            }
+
+            None
+        }
+    };
+
+    Some((page_id, revision_flag))
+}
+
+fn make_metadata_generation_path(generation: usize) -> String {
+    if generation <= 1 {
+        METADATA_OLD_FILENAME.to_string()
+    } else {
+        format!("grebedb_meta_prev_{}.grebedb", generation)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::vfs::MemoryVfs;
-
     use super::*;
 
     #[test]
     fn test_split_number() {
-        assert_eq!(&split_number(0), "00/00/00/00/00/00/00");
-        assert_eq!(&split_number(1), "00/00/00/00/00/00/00");
-        assert_eq!(&split_number(0xaabb_ccdd), "00/00/00/00/aa/bb/cc");
-        assert_eq!(&split_number(0xaabb_ccdd_1122_3344), "aa/bb/cc/dd/11/22/33");
+        let fanout = PageFanout::default();
+
+        assert_eq!(&fanout.split_number(0), "00/00/00/00/00/00/00");
+        assert_eq!(&fanout.split_number(1), "00/00/00/00/00/00/00");
+        assert_eq!(&fanout.split_number(0xaabb_ccdd), "00/00/00/00/aa/bb/cc");
+        assert_eq!(
+            &fanout.split_number(0xaabb_ccdd_1122_3344),
+            "aa/bb/cc/dd/11/22/33"
+        );
+    }
+
+    #[test]
+    fn test_split_number_custom_fanout() {
+        let fanout = PageFanout { depth: 1, width: 8 };
+
+        assert_eq!(&fanout.split_number(1), "00");
+        assert_eq!(&fanout.split_number(0xaabb_ccdd), "aabbcc");
+
+        let fanout = PageFanout {
+            depth: 2,
+            width: 12,
+        };
+
+        assert_eq!(&fanout.split_number(0xaabb_ccdd), "0aa/bbc");
+        assert_eq!(&fanout.split_number(0x1_2345_6789), "123/456");
+
+        let fanout = PageFanout {
+            depth: 4,
+            width: 16,
+        };
+
+        assert_eq!(
+            &fanout.split_number(0xaabb_ccdd_1122_3344),
+            "0000/aabb/ccdd/1122"
+        );
     }
 
     #[test]
@@ -1027,6 +2761,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_page_filename() {
+        assert_eq!(
+            parse_page_filename("grebedb_0000000000bc614e_0.grebedb"),
+            Some((12345678, Some(RevisionFlag::Current)))
+        );
+        assert_eq!(
+            parse_page_filename("grebedb_00000000aabbccdd_2.grebedb"),
+            Some((0xaabb_ccdd, Some(RevisionFlag::NewUnsync)))
+        );
+        assert_eq!(
+            parse_page_filename("grebedb_0000000000000001_r0000000000000010.grebedb"),
+            Some((1, None))
+        );
+        assert_eq!(parse_page_filename("grebedb_meta.grebedb"), None);
+        assert_eq!(
+            parse_page_filename("grebedb_0000000000000001_3.grebedb"),
+            None
+        );
+        assert_eq!(parse_page_filename("grebedb_1_0.grebedb"), None);
+    }
+
     #[test]
     fn test_page_table_create_load() {
         let vfs = MemoryVfs::new();
@@ -1040,42 +2796,159 @@ mod tests {
 
         let page_id = page_table.new_page_id();
         page_table.put(page_id, 789).unwrap();
-
+
+        page_table.commit().unwrap();
+
+        drop(page_table);
+
+        let options = PageTableOptions {
+            open_mode: PageOpenMode::LoadOnly,
+            ..Default::default()
+        };
+
+        let mut page_table = PageTable::<i32>::open(Box::new(vfs), options).unwrap();
+
+        let content = page_table.get(page_id).unwrap();
+        assert_eq!(content.cloned(), Some(789));
+    }
+
+    #[test]
+    fn test_page_table_create_load_exists() {
+        let vfs = MemoryVfs::new();
+
+        let options = PageTableOptions {
+            open_mode: PageOpenMode::LoadOnly,
+            ..Default::default()
+        };
+
+        assert!(PageTable::<()>::open(Box::new(vfs.clone()), options).is_err());
+
+        let options = PageTableOptions {
+            open_mode: PageOpenMode::CreateOnly,
+            ..Default::default()
+        };
+
+        let _page_table = PageTable::<()>::open(Box::new(vfs.clone()), options).unwrap();
+
+        let _page_table =
+            PageTable::<()>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+    }
+
+    #[test]
+    fn test_page_table_read_only_vfs() {
+        let vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        let page_id = page_table.new_page_id();
+        page_table.put(page_id, 789).unwrap();
         page_table.commit().unwrap();
-
         drop(page_table);
 
         let options = PageTableOptions {
-            open_mode: PageOpenMode::LoadOnly,
+            open_mode: PageOpenMode::ReadOnly,
             ..Default::default()
         };
+        let mut page_table = PageTable::<i32>::open(Box::new(vfs.clone()), options).unwrap();
 
-        let mut page_table = PageTable::<i32>::open(Box::new(vfs), options).unwrap();
-
-        let content = page_table.get(page_id).unwrap();
-        assert_eq!(content.cloned(), Some(789));
+        // Internal functions that don't check the open mode can't write either
+        assert!(matches!(
+            page_table.remove_page_files(page_id),
+            Err(Error::ReadOnly)
+        ));
+        assert!(vfs
+            .exists(&PageFanout::default().make_path(page_id, RevisionFlag::Current))
+            .unwrap());
+        assert_eq!(page_table.get(page_id).unwrap().cloned(), Some(789));
     }
 
+    #[cfg(feature = "encryption")]
     #[test]
-    fn test_page_table_create_load_exists() {
-        let vfs = MemoryVfs::new();
+    fn test_page_table_reencrypt_files() {
+        fn list_files(vfs: &MemoryVfs, path: &str, files: &mut Vec<String>) {
+            for filename in vfs.read_dir(path).unwrap() {
+                let child_path = if path.is_empty() {
+                    filename
+                } else {
+                    format!("{}/{}", path, filename)
+                };
+
+                if vfs.is_dir(&child_path).unwrap() {
+                    list_files(vfs, &child_path, files);
+                } else {
+                    files.push(child_path);
+                }
+            }
+        }
 
+        let old_key = EncryptionKey::new([1u8; 32]);
+        let new_key = EncryptionKey::new([2u8; 32]);
+        let vfs = MemoryVfs::new();
         let options = PageTableOptions {
-            open_mode: PageOpenMode::LoadOnly,
+            encryption_key: Some(old_key.clone()),
+            metadata_generations: 3,
             ..Default::default()
         };
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), options.clone()).unwrap();
 
-        assert!(PageTable::<()>::open(Box::new(vfs.clone()), options).is_err());
+        let page_id = page_table.new_page_id();
+        let page_id_2 = page_table.new_page_id();
+        page_table.put(page_id, 123).unwrap();
+        page_table.put(page_id_2, 456).unwrap();
+        page_table.set_root_id(Some(page_id));
+        page_table.commit().unwrap();
 
-        let options = PageTableOptions {
-            open_mode: PageOpenMode::CreateOnly,
-            ..Default::default()
-        };
+        // Leave history files, older metadata generations, and the files of
+        // a freed page behind
+        page_table.put(page_id, 789).unwrap();
+        page_table.remove(page_id_2).unwrap();
+        page_table.commit().unwrap();
 
-        let _page_table = PageTable::<()>::open(Box::new(vfs.clone()), options).unwrap();
+        page_table.set_encryption_key(new_key.clone());
+        page_table.touch(page_id).unwrap();
+        page_table.commit().unwrap();
+        page_table.rewrite_metadata().unwrap();
+        page_table.reencrypt_files().unwrap();
+        drop(page_table);
 
-        let _page_table =
-            PageTable::<()>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+        let mut files = Vec::new();
+        list_files(&vfs, "", &mut files);
+        assert!(files.iter().any(|path| path.contains("_prev_")));
+        assert!(files.iter().any(|path| path.contains("_r")));
+
+        let mut old_format = Format::default();
+        old_format.set_encryption_key(Some(old_key));
+        let mut new_format = Format::default();
+        new_format.set_encryption_key(Some(new_key.clone()));
+        let mut file_vfs = vfs.clone();
+
+        for path in files {
+            if path.starts_with("grebedb_meta") {
+                assert!(matches!(
+                    old_format.read_file::<Metadata<()>, _>(&mut file_vfs, &path),
+                    Err(Error::DecryptionFailed { .. })
+                ));
+                new_format
+                    .read_file::<Metadata<()>, _>(&mut file_vfs, &path)
+                    .unwrap();
+            } else if path.ends_with(".grebedb") {
+                assert!(matches!(
+                    old_format.read_file::<Page<i32>, _>(&mut file_vfs, &path),
+                    Err(Error::DecryptionFailed { .. })
+                ));
+                new_format
+                    .read_file::<Page<i32>, _>(&mut file_vfs, &path)
+                    .unwrap();
+            }
+        }
+
+        let options = PageTableOptions {
+            encryption_key: Some(new_key),
+            ..options
+        };
+        let mut page_table = PageTable::<i32>::open(Box::new(vfs), options).unwrap();
+        assert_eq!(page_table.get(page_id).unwrap().cloned(), Some(789));
     }
 
     #[test]
@@ -1127,6 +3000,147 @@ mod tests {
         assert_eq!(content.cloned(), Some(123));
     }
 
+    #[test]
+    fn test_page_table_skip_unchanged() {
+        let vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        let page_id = page_table.new_page_id();
+        page_table.put(page_id, 789).unwrap();
+        page_table.commit().unwrap();
+
+        drop(page_table);
+
+        let path = PageFanout::default().make_path(page_id, RevisionFlag::Current);
+        let data = vfs.read(&path).unwrap();
+
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        {
+            let mut guard = page_table.update(page_id).unwrap().unwrap();
+            *guard = 789;
+        }
+
+        page_table.commit().unwrap();
+
+        assert_eq!(vfs.read(&path).unwrap(), data);
+
+        {
+            let mut guard = page_table.update(page_id).unwrap().unwrap();
+            *guard = 123;
+        }
+
+        page_table.commit().unwrap();
+
+        assert_ne!(vfs.read(&path).unwrap(), data);
+
+        // The hash of the written file is kept for the next commit
+        let data = vfs.read(&path).unwrap();
+
+        {
+            let mut guard = page_table.update(page_id).unwrap().unwrap();
+            *guard = 123;
+        }
+
+        page_table.commit().unwrap();
+
+        assert_eq!(vfs.read(&path).unwrap(), data);
+
+        drop(page_table);
+
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+
+        let content = page_table.get(page_id).unwrap();
+        assert_eq!(content.cloned(), Some(123));
+    }
+
+    #[test]
+    fn test_page_table_previous_metadata_fallback() {
+        let mut vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        let page_id = page_table.new_page_id();
+        page_table.put(page_id, 789).unwrap();
+        page_table.set_root_id(Some(page_id));
+        page_table.commit().unwrap();
+
+        let page_id_2 = page_table.new_page_id();
+        page_table.put(page_id_2, 123).unwrap();
+        page_table.commit().unwrap();
+
+        drop(page_table);
+
+        vfs.remove_file(METADATA_FILENAME).unwrap();
+        vfs.remove_file(METADATA_COPY_FILENAME).unwrap();
+
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+
+        assert_eq!(page_table.root_id(), Some(page_id));
+        assert_eq!(page_table.get(page_id).unwrap().cloned(), Some(789));
+        assert!(page_table.get(page_id_2).is_err());
+
+        page_table.commit().unwrap();
+    }
+
+    #[test]
+    fn test_page_table_previous_metadata_fallback_lost_files() {
+        let mut vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        let page_id = page_table.new_page_id();
+        let page_id_2 = page_table.new_page_id();
+        page_table.put(page_id, 789).unwrap();
+        page_table.put(page_id_2, 123).unwrap();
+        page_table.set_root_id(Some(page_id));
+        page_table.commit().unwrap();
+
+        let current_path = PageFanout::default().make_path(page_id_2, RevisionFlag::Current);
+        let new_path = PageFanout::default().make_path(page_id_2, RevisionFlag::New);
+        let committed_data = vfs.read(&current_path).unwrap();
+
+        page_table.put(page_id_2, 456).unwrap();
+        let page_id_3 = page_table.new_page_id();
+        page_table.put(page_id_3, 999).unwrap();
+        page_table.commit().unwrap();
+
+        drop(page_table);
+
+        // Simulate the second commit stopping before its files were promoted
+        vfs.rename_file(&current_path, &new_path).unwrap();
+        vfs.write(&current_path, &committed_data, VfsSyncOption::None)
+            .unwrap();
+        vfs.remove_file(METADATA_FILENAME).unwrap();
+        vfs.remove_file(METADATA_COPY_FILENAME).unwrap();
+
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        assert_eq!(page_table.get(page_id_2).unwrap().cloned(), Some(123));
+        assert!(!vfs.exists(&new_path).unwrap());
+
+        page_table.put(page_id, 790).unwrap();
+        let page_id_4 = page_table.new_page_id();
+        page_table.put(page_id_4, 111).unwrap();
+        page_table.commit().unwrap();
+        page_table.put(page_id, 791).unwrap();
+        page_table.commit().unwrap();
+
+        drop(page_table);
+
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+
+        assert_eq!(page_table.get(page_id).unwrap().cloned(), Some(791));
+        assert_eq!(page_table.get(page_id_2).unwrap().cloned(), Some(123));
+        assert_eq!(page_table.get(page_id_4).unwrap().cloned(), Some(111));
+    }
+
     #[test]
     fn test_page_table_many_on_single_page() {
         let vfs = MemoryVfs::new();
@@ -1173,8 +3187,11 @@ mod tests {
     #[test]
     fn test_page_table_remove() {
         let vfs = MemoryVfs::new();
-        let mut page_table =
-            PageTable::<i32>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+        let options = PageTableOptions {
+            free_page_reuse_delay: 0,
+            ..Default::default()
+        };
+        let mut page_table = PageTable::<i32>::open(Box::new(vfs), options).unwrap();
 
         let page_id = page_table.new_page_id();
         let page_id_2 = page_table.new_page_id();
@@ -1195,4 +3212,167 @@ mod tests {
         assert_eq!(page_table.get(page_id_3).unwrap(), None);
         assert_eq!(page_table.get(page_id_2).unwrap().cloned(), Some(456));
     }
+
+    #[test]
+    fn test_page_table_default_free_page_reuse_delay() {
+        let vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+
+        let page_id = page_table.new_page_id();
+        page_table.put(page_id, 123).unwrap();
+        page_table.commit().unwrap();
+
+        page_table.remove(page_id).unwrap();
+        let page_id_2 = page_table.new_page_id();
+        assert_ne!(page_id_2, page_id);
+        page_table.put(page_id_2, 456).unwrap();
+
+        page_table.commit().unwrap();
+        assert_eq!(page_table.new_page_id(), page_id);
+    }
+
+    #[test]
+    fn test_page_table_free_page_reuse_delay() {
+        let vfs = MemoryVfs::new();
+        let options = PageTableOptions {
+            free_page_reuse_delay: 2,
+            ..Default::default()
+        };
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), options.clone()).unwrap();
+
+        let page_id = page_table.new_page_id();
+        page_table.put(page_id, 123).unwrap();
+        page_table.commit().unwrap();
+
+        page_table.remove(page_id).unwrap();
+        assert_ne!(page_table.new_page_id(), page_id);
+
+        page_table.commit().unwrap();
+        drop(page_table);
+
+        let mut page_table = PageTable::<i32>::open(Box::new(vfs), options).unwrap();
+        assert_ne!(page_table.new_page_id(), page_id);
+
+        page_table.commit().unwrap();
+        assert_eq!(page_table.new_page_id(), page_id);
+    }
+
+    #[test]
+    fn test_page_table_remove_deletes_files() {
+        let vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        let page_id = page_table.new_page_id();
+        let page_id_2 = page_table.new_page_id();
+        page_table.put(page_id, 123).unwrap();
+        page_table.put(page_id_2, 456).unwrap();
+        page_table.commit().unwrap();
+
+        let path = PageFanout::default().make_path(page_id, RevisionFlag::Current);
+        assert!(vfs.exists(&path).unwrap());
+
+        page_table.remove(page_id).unwrap();
+        page_table.commit().unwrap();
+
+        assert!(!vfs.exists(&path).unwrap());
+        assert!(vfs
+            .exists(&PageFanout::default().make_path(page_id_2, RevisionFlag::Current))
+            .unwrap());
+
+        // a reused ID must keep its new file
+        let page_id_3 = page_table.new_page_id();
+        assert_eq!(page_id_3, page_id);
+        page_table.remove(page_id_2).unwrap();
+        page_table.put(page_id_3, 789).unwrap();
+        page_table.commit().unwrap();
+
+        assert!(vfs.exists(&path).unwrap());
+        assert!(!vfs
+            .exists(&PageFanout::default().make_path(page_id_2, RevisionFlag::Current))
+            .unwrap());
+        assert_eq!(page_table.get(page_id_3).unwrap().cloned(), Some(789));
+
+        // empty directories are pruned
+        page_table.remove(page_id_3).unwrap();
+        page_table.commit().unwrap();
+
+        assert!(!vfs
+            .exists(&PageFanout::default().split_number(page_id))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_page_table_free_id_list_validation() {
+        let vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        let page_id_1 = page_table.new_page_id();
+        let page_id_2 = page_table.new_page_id();
+        page_table.put(page_id_1, 123).unwrap();
+        page_table.put(page_id_2, 456).unwrap();
+        page_table.commit().unwrap();
+
+        page_table.remove(page_id_2).unwrap();
+        page_table.commit().unwrap();
+
+        // live page, duplicate, and never allocated IDs
+        let free_id_list = &mut page_table.counter_tracker.free_id_list;
+        free_id_list.extend([page_id_1, page_id_2, 100]);
+        page_table.counter_tracker.dirty = true;
+        page_table.commit().unwrap();
+        drop(page_table);
+
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+
+        assert_eq!(
+            page_table.counter_tracker.free_id_list(),
+            &VecDeque::from(vec![page_id_2])
+        );
+        assert_eq!(page_table.new_page_id(), page_id_2);
+        assert_eq!(page_table.new_page_id(), page_id_2 + 1);
+        assert_eq!(page_table.get(page_id_1).unwrap().cloned(), Some(123));
+    }
+
+    #[test]
+    fn test_page_table_reclaim_ids() {
+        let vfs = MemoryVfs::new();
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs.clone()), PageTableOptions::default()).unwrap();
+
+        for value in 1..=5 {
+            let page_id = page_table.new_page_id();
+            assert_eq!(page_id, value);
+            page_table.put(page_id, value as i32).unwrap();
+        }
+
+        page_table.remove(2).unwrap();
+        page_table.commit().unwrap();
+
+        // pages 3 and 5 are unreachable and not in the free list
+        let used_ids = HashSet::from([1, 4]);
+        page_table.reclaim_ids(&used_ids).unwrap();
+
+        assert_eq!(page_table.counter_tracker.id_counter(), 4);
+        assert_eq!(
+            page_table.counter_tracker.free_id_list(),
+            &VecDeque::from(vec![2, 3])
+        );
+        assert!(!vfs
+            .exists(&PageFanout::default().make_path(5, RevisionFlag::Current))
+            .unwrap());
+        drop(page_table);
+
+        let mut page_table =
+            PageTable::<i32>::open(Box::new(vfs), PageTableOptions::default()).unwrap();
+
+        assert_eq!(page_table.new_page_id(), 2);
+        assert_eq!(page_table.new_page_id(), 3);
+        assert_eq!(page_table.new_page_id(), 5);
+        assert_eq!(page_table.get(4).unwrap().cloned(), Some(4));
+    }
 }
@@ -0,0 +1,714 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+// Bucket upper bounds are 1 µs, 2 µs, 4 µs, ... 2^25 µs (about 33.5 s)
+// followed by an unbounded bucket.
+const BOUNDED_BUCKET_COUNT: usize = 26;
+
+/// Operation and I/O statistics of a database.
+///
+/// Counters start at zero when the database is opened and can be reset with
+/// [`crate::Database::reset_stats()`]. The values are plain numbers so that
+/// they can be exported to monitoring systems such as Prometheus by the
+/// application.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub(crate) get: OperationStats,
+    pub(crate) put: OperationStats,
+    pub(crate) remove: OperationStats,
+    pub(crate) flush: OperationStats,
+    pub(crate) io: IoCounters,
+}
+
+impl Stats {
+    /// Return statistics of retrieving values.
+    pub fn get(&self) -> &OperationStats {
+        &self.get
+    }
+
+    /// Return statistics of storing key-value pairs.
+    pub fn put(&self) -> &OperationStats {
+        &self.put
+    }
+
+    /// Return statistics of removing key-value pairs.
+    pub fn remove(&self) -> &OperationStats {
+        &self.remove
+    }
+
+    /// Return statistics of flushing.
+    pub fn flush(&self) -> &OperationStats {
+        &self.flush
+    }
+
+    /// Return the number of page files read, including metadata.
+    pub fn page_reads(&self) -> u64 {
+        self.io.page_reads
+    }
+
+    /// Return the number of page files written, including metadata.
+    pub fn page_writes(&self) -> u64 {
+        self.io.page_writes
+    }
+
+    /// Return the number of uncompressed bytes passed to the compressor.
+    pub fn bytes_compressed(&self) -> u64 {
+        self.io.bytes_compressed
+    }
+
+    /// Return the number of uncompressed bytes produced by the decompressor.
+    pub fn bytes_decompressed(&self) -> u64 {
+        self.io.bytes_decompressed
+    }
+
+    /// Return the number of page accesses found in the page cache.
+    pub fn cache_hits(&self) -> u64 {
+        self.io.cache_hits
+    }
+
+    /// Return the number of page accesses that loaded the page into the
+    /// page cache.
+    pub fn cache_misses(&self) -> u64 {
+        self.io.cache_misses
+    }
+}
+
+/// Statistics of a single type of database operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OperationStats {
+    errors: u64,
+    latency: LatencyHistogram,
+}
+
+impl OperationStats {
+    /// Return the number of calls, including calls that returned an error.
+    pub fn count(&self) -> u64 {
+        self.latency.count()
+    }
+
+    /// Return the number of calls that returned an error.
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Return the latency distribution of the calls.
+    pub fn latency(&self) -> &LatencyHistogram {
+        &self.latency
+    }
+
+    pub(crate) fn record<T, E>(&mut self, latency: Duration, result: &Result<T, E>) {
+        if result.is_err() {
+            self.errors += 1;
+        }
+
+        self.latency.record(latency);
+    }
+}
+
+/// Histogram of operation latencies.
+///
+/// Buckets have upper bounds of powers of 2 microseconds, from 1 µs to about
+/// 33 seconds, plus a final unbounded bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; BOUNDED_BUCKET_COUNT + 1],
+    sum: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; BOUNDED_BUCKET_COUNT + 1],
+            sum: Duration::ZERO,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Return the number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Return the sum of all recorded latencies.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Return the upper bound and cumulative count of each bucket.
+    ///
+    /// Like Prometheus histogram buckets, each count includes all latencies
+    /// less than or equal to the bound. The last bucket has no bound and its
+    /// count is equal to [`Self::count()`].
+    pub fn buckets(&self) -> Vec<(Option<Duration>, u64)> {
+        let mut cumulative_count = 0;
+
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(index, count)| {
+                cumulative_count += count;
+
+                let bound = if index < BOUNDED_BUCKET_COUNT {
+                    Some(Duration::from_micros(1 << index))
+                } else {
+                    None
+                };
+
+                (bound, cumulative_count)
+            })
+            .collect()
+    }
+
+    pub(crate) fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros();
+        let index = if micros <= 1 {
+            0
+        } else {
+            // Position of the smallest power of 2 that is >= micros
+            (128 - (micros - 1).leading_zeros()) as usize
+        };
+
+        self.counts[index.min(BOUNDED_BUCKET_COUNT)] += 1;
+        self.sum += latency;
+    }
+}
+
+/// Overview of the structure of a database.
+///
+/// Returned by [`crate::Database::tree_stats()`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TreeStats {
+    pub(crate) level_page_counts: Vec<u64>,
+    pub(crate) key_count: u64,
+    pub(crate) key_capacity: u64,
+    pub(crate) free_page_count: u64,
+    pub(crate) uuid: String,
+    pub(crate) revision: u64,
+}
+
+impl TreeStats {
+    /// Return the number of levels of the tree.
+    pub fn height(&self) -> usize {
+        self.level_page_counts.len()
+    }
+
+    /// Return the number of pages in each level, starting with the root.
+    pub fn level_page_counts(&self) -> &[u64] {
+        &self.level_page_counts
+    }
+
+    /// Return the number of pages in the tree.
+    pub fn page_count(&self) -> u64 {
+        self.level_page_counts.iter().sum()
+    }
+
+    /// Return the average fill factor of the pages.
+    ///
+    /// The value is the number of keys in all nodes divided by the maximum
+    /// number of keys of all nodes. It is between 0.0 and 1.0.
+    ///
+    /// The maximum number of keys of a node is the
+    /// [`crate::Options::keys_per_node`] that the database was last modified
+    /// with, as saved in the metadata. For databases without the saved
+    /// value, it is estimated from the largest node.
+    pub fn fill_factor(&self) -> f64 {
+        if self.key_capacity > 0 {
+            self.key_count as f64 / self.key_capacity as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Return the number of unused page IDs available for reuse, including
+    /// IDs waiting for the free page reuse delay.
+    pub fn free_page_count(&self) -> u64 {
+        self.free_page_count
+    }
+
+    /// Return the UUID of the database as text.
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    /// Return the revision of the database.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+/// Disk usage of the pages of a database.
+///
+/// Returned by [`crate::Database::disk_usage()`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiskUsage {
+    pub(crate) pages: Vec<PageDiskUsage>,
+    pub(crate) metadata_size: u64,
+}
+
+impl DiskUsage {
+    /// Return the usage of each page in breadth-first order of the tree.
+    pub fn pages(&self) -> &[PageDiskUsage] {
+        &self.pages
+    }
+
+    /// Return the size in bytes of the metadata file.
+    pub fn metadata_size(&self) -> u64 {
+        self.metadata_size
+    }
+
+    /// Return the total size in bytes of the page files and metadata file.
+    ///
+    /// Files of previous generations, the history, and leftover files are
+    /// not included.
+    pub fn total_size(&self) -> u64 {
+        self.pages.iter().map(|page| page.file_size).sum::<u64>() + self.metadata_size
+    }
+}
+
+/// Disk usage of a page.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageDiskUsage {
+    pub(crate) page_id: u64,
+    pub(crate) level: usize,
+    pub(crate) key_count: u64,
+    pub(crate) file_size: u64,
+    pub(crate) payload_size: u64,
+    pub(crate) largest_value_size: u64,
+}
+
+impl PageDiskUsage {
+    /// Return the page ID.
+    pub fn page_id(&self) -> u64 {
+        self.page_id
+    }
+
+    /// Return the level of the page in the tree, starting with 0 for the root.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Return the number of keys in the page.
+    pub fn key_count(&self) -> u64 {
+        self.key_count
+    }
+
+    /// Return the size in bytes of the page file.
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Return the size in bytes of the page before compression.
+    pub fn payload_size(&self) -> u64 {
+        self.payload_size
+    }
+
+    /// Return the size in bytes of the largest value in a leaf page, or 0
+    /// for internal pages.
+    pub fn largest_value_size(&self) -> u64 {
+        self.largest_value_size
+    }
+
+    /// Return the payload size divided by the file size.
+    ///
+    /// Values greater than 1.0 mean the page was compressed.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.file_size > 0 {
+            self.payload_size as f64 / self.file_size as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Structure of the tree of a database.
+///
+/// Returned by [`crate::Database::tree_summary()`]. The summary implements
+/// serde's `Serialize` so it can be written as JSON by tools and compared in
+/// tests.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TreeSummary {
+    pub(crate) root_id: Option<u64>,
+    pub(crate) pages: Vec<PageSummary>,
+}
+
+impl TreeSummary {
+    /// Return the page ID of the root node, or None if the tree is empty.
+    pub fn root_id(&self) -> Option<u64> {
+        self.root_id
+    }
+
+    /// Return the summary of each page in breadth-first order of the tree.
+    pub fn pages(&self) -> &[PageSummary] {
+        &self.pages
+    }
+}
+
+/// Summary of a node in a [`TreeSummary`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageSummary {
+    pub(crate) page_id: u64,
+    pub(crate) level: usize,
+    pub(crate) kind: PageKind,
+    pub(crate) key_count: u64,
+    pub(crate) children: Vec<u64>,
+    pub(crate) next_leaf: Option<u64>,
+}
+
+impl PageSummary {
+    /// Return the page ID.
+    pub fn page_id(&self) -> u64 {
+        self.page_id
+    }
+
+    /// Return the level of the page in the tree, starting with 0 for the root.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    /// Return the kind of node in the page.
+    pub fn kind(&self) -> PageKind {
+        self.kind
+    }
+
+    /// Return the number of keys in the page.
+    pub fn key_count(&self) -> u64 {
+        self.key_count
+    }
+
+    /// Return the page IDs of the children of an internal node.
+    pub fn children(&self) -> &[u64] {
+        &self.children
+    }
+
+    /// Return the page ID of the next leaf node, if the page is a leaf node
+    /// that is not the last.
+    pub fn next_leaf(&self) -> Option<u64> {
+        self.next_leaf
+    }
+}
+
+/// Decoded contents of a page file.
+///
+/// Returned by [`crate::inspect_page()`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageInfo {
+    pub(crate) uuid: Uuid,
+    pub(crate) page_id: u64,
+    pub(crate) revision: u64,
+    pub(crate) deleted: bool,
+    pub(crate) kind: Option<PageKind>,
+    pub(crate) keys: Vec<Vec<u8>>,
+    pub(crate) values: Vec<Vec<u8>>,
+    pub(crate) children: Vec<u64>,
+    pub(crate) next_leaf: Option<u64>,
+}
+
+impl PageInfo {
+    /// Return the UUID of the database that wrote the page.
+    pub fn uuid(&self) -> String {
+        self.uuid.to_string()
+    }
+
+    /// Return the page ID.
+    pub fn page_id(&self) -> u64 {
+        self.page_id
+    }
+
+    /// Return the revision of the database when the page was written.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Return whether the page was marked as deleted.
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    /// Return the kind of node in the page, or None if the page has no node.
+    pub fn kind(&self) -> Option<PageKind> {
+        self.kind
+    }
+
+    /// Return the keys of the node.
+    pub fn keys(&self) -> &[Vec<u8>] {
+        &self.keys
+    }
+
+    /// Return the values of a leaf node in the same order as the keys.
+    pub fn values(&self) -> &[Vec<u8>] {
+        &self.values
+    }
+
+    /// Return the page IDs of the children of an internal node.
+    pub fn children(&self) -> &[u64] {
+        &self.children
+    }
+
+    /// Return the page ID of the next leaf node, if the page is a leaf node
+    /// that is not the last.
+    pub fn next_leaf(&self) -> Option<u64> {
+        self.next_leaf
+    }
+}
+
+/// Kind of node stored in a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageKind {
+    /// Root of a tree without key-value pairs.
+    EmptyRoot,
+    /// Node with keys and child pages.
+    Internal,
+    /// Node with key-value pairs.
+    Leaf,
+}
+
+/// Result of cleaning up the files of a database.
+///
+/// Returned by [`crate::Database::clean_up()`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CleanupReport {
+    pub(crate) reclaimed_ids: u64,
+    pub(crate) removed_files: u64,
+    pub(crate) promoted_files: u64,
+    pub(crate) removed_dirs: u64,
+}
+
+impl CleanupReport {
+    /// Return the number of IDs of unreachable pages that were missing from
+    /// the list of unused page IDs, or that were released by lowering the
+    /// page ID counter.
+    pub fn reclaimed_ids(&self) -> u64 {
+        self.reclaimed_ids
+    }
+
+    /// Return the number of page files deleted because they are unused or
+    /// were not committed.
+    pub fn removed_files(&self) -> u64 {
+        self.removed_files
+    }
+
+    /// Return the number of committed page files renamed to their main
+    /// filename.
+    pub fn promoted_files(&self) -> u64 {
+        self.promoted_files
+    }
+
+    /// Return the number of empty page directories deleted.
+    pub fn removed_dirs(&self) -> u64 {
+        self.removed_dirs
+    }
+}
+
+/// Result of checking the structure and files of a database.
+///
+/// Returned by [`crate::Database::fsck()`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FsckReport {
+    pub(crate) page_count: u64,
+    pub(crate) file_count: u64,
+    pub(crate) issues: Vec<FsckIssue>,
+}
+
+impl FsckReport {
+    /// Return the number of pages in the tree.
+    pub fn page_count(&self) -> u64 {
+        self.page_count
+    }
+
+    /// Return the number of page files checked.
+    pub fn file_count(&self) -> u64 {
+        self.file_count
+    }
+
+    /// Return the problems found.
+    pub fn issues(&self) -> &[FsckIssue] {
+        &self.issues
+    }
+
+    /// Return whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        kind: FsckIssueKind,
+        page_id: Option<u64>,
+        path: Option<String>,
+        message: String,
+    ) {
+        self.issues.push(FsckIssue {
+            kind,
+            page_id,
+            path,
+            message,
+        });
+    }
+}
+
+/// Problem found while checking a database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsckIssue {
+    pub(crate) kind: FsckIssueKind,
+    pub(crate) page_id: Option<u64>,
+    pub(crate) path: Option<String>,
+    pub(crate) message: String,
+}
+
+impl FsckIssue {
+    /// Return the category of the problem.
+    pub fn kind(&self) -> FsckIssueKind {
+        self.kind
+    }
+
+    /// Return the ID of the page involved, if any.
+    pub fn page_id(&self) -> Option<u64> {
+        self.page_id
+    }
+
+    /// Return the path of the file involved, if any.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Return the description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Category of a problem found while checking a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FsckIssueKind {
+    /// Page of the tree that could not be read or that is referenced more
+    /// than once.
+    InvalidPage,
+
+    /// Page file that does not belong to a page of the tree or that
+    /// contains a revision that was not committed.
+    ///
+    /// Committed files of unused pages are not reported. They are deleted by
+    /// [`crate::Database::purge_free_pages()`].
+    OrphanPageFile,
+
+    /// Link of a leaf node to the next leaf node that does not match the
+    /// order of the tree.
+    DanglingNextLeaf,
+
+    /// Page ID that is in use but listed as unused, listed more than once,
+    /// beyond the page ID counter, or neither in use nor listed as unused.
+    FreeListInconsistency,
+
+    /// File with a UUID that is different from the database.
+    UuidMismatch,
+
+    /// Metadata backup file that does not agree with the main metadata file.
+    MetadataDivergence,
+}
+
+/// Result of copying the readable pairs of a damaged database.
+///
+/// Returned by [`crate::Database::salvage_to()`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SalvageReport {
+    pub(crate) pair_count: u64,
+    pub(crate) lost_pages: Vec<LostPage>,
+}
+
+impl SalvageReport {
+    /// Return the number of pairs copied.
+    pub fn pair_count(&self) -> u64 {
+        self.pair_count
+    }
+
+    /// Return the pages that could not be read, in key order.
+    pub fn lost_pages(&self) -> &[LostPage] {
+        &self.lost_pages
+    }
+}
+
+/// Page that could not be read while salvaging a database.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LostPage {
+    pub(crate) page_id: u64,
+    pub(crate) start_key: Option<Vec<u8>>,
+    pub(crate) end_key: Option<Vec<u8>>,
+    pub(crate) message: String,
+}
+
+impl LostPage {
+    /// Return the ID of the page.
+    pub fn page_id(&self) -> u64 {
+        self.page_id
+    }
+
+    /// Return the inclusive lower bound of the keys that were stored in the
+    /// page, or `None` if unbounded.
+    pub fn start_key(&self) -> Option<&[u8]> {
+        self.start_key.as_deref()
+    }
+
+    /// Return the exclusive upper bound of the keys that were stored in the
+    /// page, or `None` if unbounded.
+    pub fn end_key(&self) -> Option<&[u8]> {
+        self.end_key.as_deref()
+    }
+
+    /// Return the description of the error that occurred reading the page.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub(crate) fn contains_key(&self, key: &[u8]) -> bool {
+        self.start_key
+            .as_deref()
+            .is_none_or(|start_key| key >= start_key)
+            && self.end_key.as_deref().is_none_or(|end_key| key < end_key)
+    }
+}
+
+/// Counters of page file and compression activity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct IoCounters {
+    pub page_reads: u64,
+    pub page_writes: u64,
+    pub bytes_compressed: u64,
+    pub bytes_decompressed: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+
+        histogram.record(Duration::from_nanos(500));
+        histogram.record(Duration::from_micros(1));
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_micros(4));
+        histogram.record(Duration::from_secs(100));
+
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(
+            histogram.sum(),
+            Duration::from_secs(100) + Duration::from_nanos(8500)
+        );
+
+        let buckets = histogram.buckets();
+
+        assert_eq!(buckets.len(), BOUNDED_BUCKET_COUNT + 1);
+        assert_eq!(buckets[0], (Some(Duration::from_micros(1)), 2));
+        assert_eq!(buckets[1], (Some(Duration::from_micros(2)), 2));
+        assert_eq!(buckets[2], (Some(Duration::from_micros(4)), 4));
+        assert_eq!(buckets[BOUNDED_BUCKET_COUNT - 1].1, 4);
+        assert_eq!(buckets[BOUNDED_BUCKET_COUNT], (None, 5));
+    }
+}
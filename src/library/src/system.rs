@@ -1,19 +1,70 @@
+use std::time::Instant;
+
 use uuid::Uuid;
 
-pub struct UuidGenerator {}
+#[cfg(feature = "testing")]
+use crate::testing::MockClock;
+
+pub struct UuidGenerator {
+    seed_state: Option<u64>,
+}
 
 impl UuidGenerator {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(seed: Option<u64>) -> Self {
+        Self { seed_state: seed }
+    }
+
+    pub fn new_uuid(&mut self) -> Uuid {
+        match &mut self.seed_state {
+            Some(state) => seeded_uuid(state),
+            None => Self::system_uuid(),
+        }
     }
 
     #[cfg(feature = "system")]
-    pub fn new_uuid(&self) -> Uuid {
+    fn system_uuid() -> Uuid {
         Uuid::new_v4()
     }
 
     #[cfg(not(feature = "system"))]
-    pub fn new_uuid(&self) -> Uuid {
+    fn system_uuid() -> Uuid {
         Uuid::nil()
     }
 }
+
+// Version 4 UUID from the SplitMix64 sequence of the state
+fn seeded_uuid(state: &mut u64) -> Uuid {
+    let mut bytes = [0u8; 16];
+
+    for chunk in bytes.chunks_mut(8) {
+        *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = *state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^= value >> 31;
+
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+
+    uuid::Builder::from_bytes(bytes)
+        .set_variant(uuid::Variant::RFC4122)
+        .set_version(uuid::Version::Random)
+        .build()
+}
+
+#[derive(Clone)]
+pub enum Clock {
+    System,
+    #[cfg(feature = "testing")]
+    Mock(MockClock),
+}
+
+impl Clock {
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::System => Instant::now(),
+            #[cfg(feature = "testing")]
+            Clock::Mock(clock) => clock.now(),
+        }
+    }
+}
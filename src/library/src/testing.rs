@@ -0,0 +1,60 @@
+//! Helpers for deterministic tests.
+//!
+//! This module requires the `testing` feature. The helpers are injected
+//! with [`Options::clock`](crate::Options::clock) and
+//! [`Options::uuid_seed`](crate::Options::uuid_seed).
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Clock that only moves forward when advanced manually.
+///
+/// Clones share the same time, so a test can keep a clone to advance the
+/// clock of a database that uses it for automatic flushing.
+#[derive(Clone)]
+pub struct MockClock {
+    start: Instant,
+    offset: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    /// Create a clock that starts at the current time.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            offset: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    /// Move the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        *self.offset.lock().unwrap() += duration;
+    }
+
+    /// Return the time elapsed since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.offset.lock().unwrap()
+    }
+
+    /// Return the current time of the clock.
+    pub fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for MockClock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockClock")
+            .field("elapsed", &self.elapsed())
+            .finish()
+    }
+}
@@ -1,16 +1,40 @@
-use std::{collections::VecDeque, fmt::Debug, ops::RangeBounds};
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Debug,
+    io::Write,
+    ops::{Bound, RangeBounds},
+};
 
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::{
+    encryption::EncryptionKey,
     error::Error,
-    page::{PageId, PageTable, PageTableOptions, PageUpdateGuard},
+    page::{Page, PageId, PageTable, PageTableOptions, PageUpdateGuard},
+    stats::{
+        CleanupReport, DiskUsage, FsckIssueKind, FsckReport, IoCounters, LostPage, PageDiskUsage,
+        PageInfo, PageKind, PageSummary, TreeStats, TreeSummary,
+    },
     vfs::Vfs,
 };
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TreeMetadata {
     pub key_value_count: u64,
+    #[serde(default)]
+    pub app_metadata: Option<Vec<u8>>,
+    #[serde(default)]
+    pub last_export_revision: Option<u64>,
+    // Pairs applied and input byte offset of an unfinished import
+    #[serde(default)]
+    pub import_progress: Option<(u64, u64)>,
+    // Database UUID of the export file of the most recent import
+    #[serde(default)]
+    pub import_source: Option<String>,
+    // Maximum number of keys per node used by the most recent writer
+    #[serde(default)]
+    pub keys_per_node: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,7 +46,7 @@ pub enum Node {
 }
 
 impl Node {
-    fn _internal(&self, page_id: PageId) -> Result<&InternalNode, Error> {
+    fn internal(&self, page_id: PageId) -> Result<&InternalNode, Error> {
         if let Self::Internal(internal_node) = self {
             Ok(internal_node)
         } else {
@@ -65,6 +89,109 @@ impl Node {
             })
         }
     }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Self::EmptyRoot => "empty_root",
+            Self::Internal(_) => "internal",
+            Self::Leaf(_) => "leaf",
+        }
+    }
+
+    pub fn key_count(&self) -> usize {
+        match self {
+            Self::EmptyRoot => 0,
+            Self::Internal(internal_node) => internal_node.keys.len(),
+            Self::Leaf(leaf_node) => leaf_node.keys.len(),
+        }
+    }
+
+    // Copy of the node with the keys and values cut to the given length for
+    // debug printing
+    pub fn truncated(&self, length: usize) -> Self {
+        let truncate = |items: &[Vec<u8>]| -> Vec<Vec<u8>> {
+            items
+                .iter()
+                .map(|item| item[..item.len().min(length)].to_vec())
+                .collect()
+        };
+
+        match self {
+            Self::EmptyRoot => Self::EmptyRoot,
+            Self::Internal(internal_node) => Self::Internal(InternalNode {
+                keys: truncate(&internal_node.keys),
+                children: internal_node.children.clone(),
+            }),
+            Self::Leaf(leaf_node) => Self::Leaf(LeafNode {
+                keys: truncate(&leaf_node.keys),
+                values: truncate(&leaf_node.values),
+                next_leaf: leaf_node.next_leaf,
+            }),
+        }
+    }
+
+    // Contents of the node with the keys and values as hex for debug printing.
+    // Truncated keys and values end with "..".
+    pub fn debug_json(&self, truncate: Option<usize>) -> serde_json::Value {
+        let encode = |items: &[Vec<u8>]| -> Vec<String> {
+            items
+                .iter()
+                .map(|item| match truncate {
+                    Some(length) if item.len() > length => {
+                        format!("{}..", data_encoding::HEXUPPER.encode(&item[..length]))
+                    }
+                    _ => data_encoding::HEXUPPER.encode(item),
+                })
+                .collect()
+        };
+
+        match self {
+            Self::EmptyRoot => serde_json::json!({}),
+            Self::Internal(internal_node) => serde_json::json!({
+                "keys": encode(&internal_node.keys),
+                "children": internal_node.children,
+            }),
+            Self::Leaf(leaf_node) => serde_json::json!({
+                "keys": encode(&leaf_node.keys),
+                "values": encode(&leaf_node.values),
+                "next_leaf": leaf_node.next_leaf,
+            }),
+        }
+    }
+}
+
+impl From<Page<Node>> for PageInfo {
+    fn from(page: Page<Node>) -> Self {
+        let mut info = PageInfo {
+            uuid: page.uuid,
+            page_id: page.id,
+            revision: page.revision,
+            deleted: page.deleted,
+            kind: None,
+            keys: Vec::new(),
+            values: Vec::new(),
+            children: Vec::new(),
+            next_leaf: None,
+        };
+
+        match page.content {
+            None => {}
+            Some(Node::EmptyRoot) => info.kind = Some(PageKind::EmptyRoot),
+            Some(Node::Internal(internal_node)) => {
+                info.kind = Some(PageKind::Internal);
+                info.keys = internal_node.keys;
+                info.children = internal_node.children;
+            }
+            Some(Node::Leaf(leaf_node)) => {
+                info.kind = Some(PageKind::Leaf);
+                info.keys = leaf_node.keys;
+                info.values = leaf_node.values;
+                info.next_leaf = leaf_node.next_leaf;
+            }
+        }
+
+        info
+    }
 }
 
 #[derive(Default, Clone, Serialize, Deserialize)]
@@ -258,6 +385,18 @@ impl LeafNode {
         self.keys.first().map(|item| item.as_slice())
     }
 
+    pub fn last_key(&self) -> Option<&[u8]> {
+        self.keys.last().map(|item| item.as_slice())
+    }
+
+    pub fn largest_value_len(&self) -> usize {
+        self.values
+            .iter()
+            .map(|value| value.len())
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn next_leaf(&self) -> Option<PageId> {
         self.next_leaf
     }
@@ -345,6 +484,16 @@ impl LeafNode {
         }
     }
 
+    // Keep only the key-value pairs with a true value at the same index
+    pub fn retain_by_mask(&mut self, mask: &[bool]) {
+        assert!(self.keys.len() == mask.len());
+
+        let mut mask_iter = mask.iter();
+        self.keys.retain(|_| *mask_iter.next().unwrap());
+        let mut mask_iter = mask.iter();
+        self.values.retain(|_| *mask_iter.next().unwrap());
+    }
+
     pub fn split(&mut self) -> LeafNode {
         assert!(self.keys.len() >= 2);
         assert!(self.keys.len() == self.values.len());
@@ -379,16 +528,19 @@ impl Debug for LeafNode {
     }
 }
 
-pub struct Tree {
-    page_table: PageTable<Node, TreeMetadata>,
+pub struct Tree<V>
+where
+    V: Vfs,
+{
+    page_table: PageTable<Node, TreeMetadata, V>,
     keys_per_node: usize,
 }
 
-impl Tree {
-    pub fn open(
-        vfs: Box<dyn Vfs + Sync + Send>,
-        page_table_options: PageTableOptions,
-    ) -> Result<Self, Error> {
+impl<V> Tree<V>
+where
+    V: Vfs,
+{
+    pub fn open(vfs: V, page_table_options: PageTableOptions) -> Result<Self, Error> {
         assert!(page_table_options.keys_per_node >= 2);
 
         Ok(Self {
@@ -415,6 +567,11 @@ impl Tree {
                 .set_auxiliary_metadata(Some(TreeMetadata::default()))
         }
 
+        // Saved with the next commit so that it describes the modified nodes
+        if let Some(meta) = self.page_table.auxiliary_metadata_mut() {
+            meta.keys_per_node = Some(self.keys_per_node);
+        }
+
         Ok(())
     }
 
@@ -422,6 +579,14 @@ impl Tree {
         self.page_table.auxiliary_metadata()
     }
 
+    pub fn set_app_metadata(&mut self, value: Vec<u8>) -> Result<(), Error> {
+        if let Some(meta) = self.page_table.update_auxiliary_metadata()? {
+            meta.app_metadata = Some(value);
+        }
+
+        Ok(())
+    }
+
     pub fn contains_key(&mut self, key: &[u8]) -> Result<bool, Error> {
         let page_id = match self.find_leaf_node(key, None)? {
             Some(page_id) => page_id,
@@ -483,6 +648,146 @@ impl Tree {
         Ok(())
     }
 
+    // Replace the value of a key using the current value, if any, with a
+    // single traversal of the tree. Nothing is modified if the function fails.
+    pub fn update_value<U>(&mut self, key: Vec<u8>, update: U) -> Result<(), Error>
+    where
+        U: FnOnce(Option<&[u8]>) -> Result<Vec<u8>, Error>,
+    {
+        let keys_per_node = self.keys_per_node;
+        let mut node_path = Vec::new();
+
+        if let Some(page_id) = self.find_leaf_node(&key, Some(&mut node_path))? {
+            let value = update(self.read_node(page_id)?.leaf(page_id)?.find_value(&key))?;
+
+            let (num_keys, replaced) = {
+                let mut leaf_node_ = self.edit_node(page_id)?;
+                let leaf_node = leaf_node_.leaf_mut(page_id)?;
+
+                let replaced = leaf_node.insert(key, value);
+                (leaf_node.len(), replaced)
+            };
+
+            if !replaced {
+                self.increment_key_value_count();
+            }
+
+            if num_keys > keys_per_node {
+                self.split_leaf_node(page_id, &mut node_path)?;
+            }
+        } else {
+            let value = update(None)?;
+            self.increment_key_value_count();
+            self.add_new_root_leaf_node(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    // Insert pairs sorted by key, inserting consecutive pairs that belong to
+    // the same leaf node with a single edit. The leaf node is split when
+    // full, like with individual puts.
+    pub fn put_sorted<I>(&mut self, pairs: I, existing_key: ExistingKeyAction) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        let keys_per_node = self.keys_per_node;
+        let mut pairs = pairs.into_iter().peekable();
+
+        while let Some((key, value)) = pairs.next() {
+            let mut node_path = Vec::new();
+
+            let page_id = match self.find_leaf_node(&key, Some(&mut node_path))? {
+                Some(page_id) => page_id,
+                None => {
+                    self.increment_key_value_count();
+                    self.add_new_root_leaf_node(key, value)?;
+                    continue;
+                }
+            };
+
+            let right_key = self.leaf_right_key(&node_path, &key)?;
+            let mut conflict_key = None;
+
+            let (num_keys, inserted_count) = {
+                let mut leaf_node_ = self.edit_node(page_id)?;
+                let leaf_node = leaf_node_.leaf_mut(page_id)?;
+                let mut inserted_count = 0;
+                let mut current_pair = Some((key, value));
+
+                while let Some((key, value)) = current_pair.take() {
+                    let exists = leaf_node.find_value(&key).is_some();
+
+                    match (exists, existing_key) {
+                        (true, ExistingKeyAction::Keep) => {}
+                        (true, ExistingKeyAction::Fail) => {
+                            conflict_key = Some(key);
+                            break;
+                        }
+                        _ => {
+                            if !leaf_node.insert(key, value) {
+                                inserted_count += 1;
+                            }
+                        }
+                    }
+
+                    if leaf_node.len() > keys_per_node {
+                        break;
+                    }
+
+                    if let Some((next_key, _)) = pairs.peek() {
+                        let is_same_leaf = match &right_key {
+                            Some(right_key) => next_key < right_key,
+                            None => true,
+                        };
+
+                        if is_same_leaf {
+                            current_pair = pairs.next();
+                        }
+                    }
+                }
+
+                (leaf_node.len(), inserted_count)
+            };
+
+            if let Some(meta) = self.page_table.auxiliary_metadata_mut() {
+                meta.key_value_count += inserted_count;
+            }
+
+            if num_keys > keys_per_node {
+                self.split_leaf_node(page_id, &mut node_path)?;
+            }
+
+            if let Some(key) = conflict_key {
+                return Err(Error::KeyConflict { key });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Return the exclusive upper bound of the keys that are routed to the
+    // leaf node found with the key and path
+    fn leaf_right_key(
+        &mut self,
+        node_path: &[PageId],
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, Error> {
+        for &page_id in node_path.iter().rev() {
+            let internal_node = self.read_node(page_id)?.internal(page_id)?;
+            let index = match internal_node.search(key) {
+                Ok(index) => index + 1,
+                Err(index) => index,
+            };
+
+            if index < internal_node.keys_len() {
+                return Ok(Some(internal_node.keys()[index].clone()));
+            }
+        }
+
+        Ok(None)
+    }
+
     pub fn remove(&mut self, key: &[u8]) -> Result<(), Error> {
         let mut node_path = Vec::new();
 
@@ -517,11 +822,147 @@ impl Tree {
         Ok(())
     }
 
+    // Remove all pages except the root which is reset to an empty root
+    pub fn clear(&mut self) -> Result<(), Error> {
+        let root_id = match self.page_table.root_id() {
+            Some(page_id) => page_id,
+            None => return Ok(()),
+        };
+        let mut page_queue = VecDeque::new();
+
+        page_queue.push_back(root_id);
+
+        while let Some(page_id) = page_queue.pop_front() {
+            if let Node::Internal(internal_node) = self.read_node(page_id)? {
+                page_queue.extend(internal_node.children());
+            }
+
+            if page_id != root_id {
+                self.page_table.remove(page_id)?;
+            }
+        }
+
+        self.page_table.put(root_id, Node::EmptyRoot)?;
+
+        if let Some(meta) = self.page_table.auxiliary_metadata_mut() {
+            meta.key_value_count = 0;
+        }
+
+        Ok(())
+    }
+
+    // Remove the sorted and deduplicated keys, editing each leaf node once.
+    // The keys that are at most the last key of the leaf node found for the
+    // first key all belong to the same leaf node since a leaf node covers a
+    // contiguous range of keys.
+    pub fn remove_many(&mut self, keys: &[Vec<u8>]) -> Result<(), Error> {
+        let mut index = 0;
+
+        while index < keys.len() {
+            let mut node_path = Vec::new();
+
+            let page_id = match self.find_leaf_node(&keys[index], Some(&mut node_path))? {
+                Some(page_id) => page_id,
+                None => return Ok(()),
+            };
+
+            let leaf_node = self.read_node(page_id)?.leaf(page_id)?;
+            let group_len = match leaf_node.last_key() {
+                Some(last_key) => keys[index..]
+                    .iter()
+                    .take_while(|key| key.as_slice() <= last_key)
+                    .count()
+                    .max(1),
+                None => 1,
+            };
+            let group = &keys[index..index + group_len];
+            index += group_len;
+
+            if !group.iter().any(|key| leaf_node.find_value(key).is_some()) {
+                continue;
+            }
+
+            let (num_keys, removed_count) = {
+                let mut leaf_node_ = self.edit_node(page_id)?;
+                let leaf_node = leaf_node_.leaf_mut(page_id)?;
+                let removed_count = group.iter().filter(|key| leaf_node.remove_key(key)).count();
+
+                (leaf_node.len(), removed_count)
+            };
+
+            if let Some(meta) = self.page_table.auxiliary_metadata_mut() {
+                meta.key_value_count = meta.key_value_count.saturating_sub(removed_count as u64);
+            }
+
+            if num_keys == 0 {
+                self.remove_leaf_node(page_id, &mut node_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Walk the leaf nodes once and remove pairs rejected by the predicate.
+    // Leaf nodes are only modified when they contain rejected pairs.
+    pub fn retain<P>(&mut self, mut predicate: P) -> Result<(), Error>
+    where
+        P: FnMut(&[u8], &[u8]) -> bool,
+    {
+        let mut next_page_id = self.find_leaf_node(&[], None)?;
+
+        while let Some(page_id) = next_page_id {
+            let leaf_node = self.read_node(page_id)?.leaf(page_id)?;
+            let mask: Vec<bool> = leaf_node
+                .keys
+                .iter()
+                .zip(&leaf_node.values)
+                .map(|(key, value)| predicate(key, value))
+                .collect();
+            let first_key = leaf_node.first_key().map(|key| key.to_vec());
+            next_page_id = leaf_node.next_leaf();
+
+            let removed_count = mask.iter().filter(|keep| !**keep).count();
+
+            if removed_count == 0 {
+                continue;
+            }
+
+            let num_keys = {
+                let mut leaf_node_ = self.edit_node(page_id)?;
+                let leaf_node = leaf_node_.leaf_mut(page_id)?;
+
+                leaf_node.retain_by_mask(&mask);
+                leaf_node.len()
+            };
+
+            if let Some(meta) = self.page_table.auxiliary_metadata_mut() {
+                meta.key_value_count = meta.key_value_count.saturating_sub(removed_count as u64);
+            }
+
+            if num_keys == 0 {
+                let mut node_path = Vec::new();
+                let first_key = first_key.unwrap_or_default();
+
+                if self.find_leaf_node(&first_key, Some(&mut node_path))? != Some(page_id) {
+                    return Err(Error::InvalidPageData {
+                        page: page_id,
+                        message: "leaf node not reachable",
+                    });
+                }
+
+                self.remove_leaf_node(page_id, &mut node_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn cursor_start(&mut self, cursor: &mut TreeCursor, start_key: &[u8]) -> Result<(), Error> {
         match self.find_leaf_node(start_key, None)? {
             Some(page_id) => {
                 let leaf_node = self.read_node(page_id)?.leaf(page_id)?.clone();
                 cursor.key_index = leaf_node.find_index(start_key);
+                self.prefetch_next_leaf(&leaf_node);
                 cursor.leaf_node = Some(leaf_node);
             }
             None => {
@@ -532,11 +973,13 @@ impl Tree {
         Ok(())
     }
 
+    // Buffers that are not given are skipped so that the unused part of the
+    // pair is not copied
     pub fn cursor_next<R>(
         &mut self,
         cursor: &mut TreeCursor,
-        key_buffer: &mut Vec<u8>,
-        value_buffer: &mut Vec<u8>,
+        key_buffer: Option<&mut Vec<u8>>,
+        value_buffer: Option<&mut Vec<u8>>,
         range: &R,
     ) -> Result<bool, Error>
     where
@@ -553,10 +996,15 @@ impl Tree {
 
             cursor.key_index += 1;
 
-            key_buffer.resize(key.len(), 0);
-            key_buffer.copy_from_slice(key);
-            value_buffer.resize(value.len(), 0);
-            value_buffer.copy_from_slice(value);
+            if let Some(key_buffer) = key_buffer {
+                key_buffer.resize(key.len(), 0);
+                key_buffer.copy_from_slice(key);
+            }
+
+            if let Some(value_buffer) = value_buffer {
+                value_buffer.resize(value.len(), 0);
+                value_buffer.copy_from_slice(value);
+            }
 
             Ok(true)
         } else {
@@ -573,6 +1021,7 @@ impl Tree {
                 match leaf_node.next_leaf() {
                     Some(page_id) => {
                         let next_leaf_node = self.read_node(page_id)?.leaf(page_id)?.clone();
+                        self.prefetch_next_leaf(&next_leaf_node);
                         cursor.leaf_node = Some(next_leaf_node);
                     }
                     None => {
@@ -587,88 +1036,887 @@ impl Tree {
         Ok(())
     }
 
+    // Start reading the next leaf while the pairs of the current leaf are
+    // consumed by a sequential scan
+    fn prefetch_next_leaf(&self, leaf_node: &LeafNode) {
+        if let Some(page_id) = leaf_node.next_leaf() {
+            self.page_table.prefetch(page_id);
+        }
+    }
+
     pub fn flush(&mut self) -> Result<(), Error> {
         self.page_table.commit()
     }
 
-    pub fn verify_tree<P>(&mut self, mut progress_callback: P) -> Result<(), Error>
-    where
-        P: FnMut(usize, usize),
-    {
-        let page_id = if let Some(page_id) = self.page_table.root_id() {
-            page_id
-        } else {
-            return Err(Error::InvalidMetadata {
-                message: "missing root page ID",
-            });
-        };
-        let mut current = 0usize;
-        let mut total = 0usize;
-        let mut page_queue = VecDeque::<(u64, Option<Vec<u8>>, Option<Vec<u8>>)>::new();
-
-        page_queue.push_back((page_id, None, None));
-        total += 1;
+    pub fn revision(&self) -> u64 {
+        self.page_table.revision()
+    }
 
-        while let Some((page_id, left_key, right_key)) = page_queue.pop_front() {
-            let node = self.read_node(page_id)?;
+    pub fn uuid(&self) -> Uuid {
+        self.page_table.uuid()
+    }
 
-            current += 1;
-            progress_callback(current, total);
+    pub fn set_pinned(&mut self, pinned: bool) {
+        self.page_table.set_pinned(pinned)
+    }
 
-            match node {
-                Node::EmptyRoot => {}
-                Node::Internal(internal_node) => {
-                    if let Some(message) = internal_node
-                        .verify_with_parent_keys(left_key.as_deref(), right_key.as_deref())
-                    {
-                        return Err(Error::InvalidPageData {
-                            page: page_id,
-                            message,
-                        });
-                    }
+    pub fn set_last_export_revision(&mut self, revision: u64) -> Result<(), Error> {
+        if let Some(meta) = self.page_table.update_auxiliary_metadata()? {
+            meta.last_export_revision = Some(revision);
+        }
 
-                    for (index, page_id) in internal_node.children().iter().enumerate() {
-                        let left_key = if index > 0 {
-                            internal_node.keys().get(index - 1).cloned()
-                        } else {
-                            None
-                        };
-                        let right_key = internal_node.keys().get(index).cloned();
+        Ok(())
+    }
 
-                        page_queue.push_back((*page_id, left_key, right_key));
-                        total += 1;
-                    }
-                }
-                Node::Leaf(leaf_node) => {
-                    if let Some(message) =
-                        leaf_node.verify_with_parent_keys(left_key.as_deref(), right_key.as_deref())
-                    {
-                        return Err(Error::InvalidPageData {
-                            page: page_id,
-                            message,
-                        });
-                    }
-                }
-            }
+    pub fn set_import_progress(&mut self, progress: Option<(u64, u64)>) -> Result<(), Error> {
+        if let Some(meta) = self.page_table.update_auxiliary_metadata()? {
+            meta.import_progress = progress;
         }
 
         Ok(())
     }
 
-    pub fn dump_tree(&mut self) -> Result<(), Error> {
-        let page_id = self.page_table.root_id().unwrap();
-        let mut page_queue = VecDeque::new();
+    pub fn set_import_source(&mut self, source: String) -> Result<(), Error> {
+        if let Some(meta) = self.page_table.update_auxiliary_metadata()? {
+            meta.import_source = Some(source);
+        }
 
-        page_queue.push_back((page_id, 0));
+        Ok(())
+    }
 
-        eprintln!("Root page: {}", page_id);
+    // Call the function with the pairs of leaf nodes saved after the given
+    // revision, in key order. Unsaved modifications are not included.
+    pub fn for_each_changed_pair<C>(
+        &mut self,
+        since_revision: u64,
+        mut callback: C,
+    ) -> Result<(), Error>
+    where
+        C: FnMut(&[u8], &[u8]) -> Result<(), Error>,
+    {
+        let mut next_page_id = self.find_leaf_node(b"", None)?;
 
-        while let Some((page_id, height)) = page_queue.pop_front() {
-            let node = self.read_node(page_id)?;
+        while let Some(page_id) = next_page_id {
+            self.read_node(page_id)?;
+            let revision = self.page_table.page_revision(page_id).unwrap_or(u64::MAX);
+            let leaf_node = self.read_node(page_id)?.leaf(page_id)?;
 
-            eprintln!("Page {}: {} {:?}", page_id, height, &node);
+            if revision > since_revision {
+                for index in 0..leaf_node.len() {
+                    let (key, value) = leaf_node.get(index);
+                    callback(key, value)?;
+                }
+            }
 
-            match node {
+            next_page_id = leaf_node.next_leaf();
+        }
+
+        Ok(())
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.page_table.is_encrypted()
+    }
+
+    pub fn purge_free_pages(&mut self) -> Result<(), Error> {
+        self.page_table.purge_free_pages()
+    }
+
+    pub fn checkpoint(&mut self, path: &str) -> Result<(), Error> {
+        self.page_table.checkpoint(path)
+    }
+
+    pub fn clone_to<W: Vfs>(&mut self, vfs: W, options: PageTableOptions) -> Result<(), Error> {
+        self.page_table.clone_to(vfs, options)
+    }
+
+    pub fn last_error(&self) -> Option<&str> {
+        self.page_table.last_error()
+    }
+
+    pub fn reopen(&mut self) -> Result<(), Error> {
+        self.page_table.reopen()
+    }
+
+    pub fn has_uncommitted_changes(&self) -> bool {
+        self.page_table.has_uncommitted_changes()
+    }
+
+    pub fn modified_page_count(&self) -> usize {
+        self.page_table.modified_page_count()
+    }
+
+    pub fn write_modified_pages(&mut self) -> Result<(), Error> {
+        self.page_table.write_modified_pages()
+    }
+
+    pub fn io_counters(&self) -> IoCounters {
+        self.page_table.io_counters()
+    }
+
+    pub fn reset_io_counters(&mut self) {
+        self.page_table.reset_io_counters();
+    }
+
+    pub fn recovered_pages(&self) -> Vec<PageId> {
+        self.page_table.recovered_pages().iter().cloned().collect()
+    }
+
+    pub fn set_encryption_key(&mut self, value: EncryptionKey) {
+        self.page_table.set_encryption_key(value);
+    }
+
+    pub fn reencrypt_files(&mut self) -> Result<(), Error> {
+        self.page_table.reencrypt_files()
+    }
+
+    // Write every page in the tree with a new UUID
+    pub fn regenerate_uuid<P>(&mut self, progress_callback: P) -> Result<(), Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        self.page_table.regenerate_uuid()?;
+
+        if self.page_table.root_id().is_some() {
+            self.rewrite_tree(progress_callback)
+        } else {
+            self.page_table.commit()?;
+            self.page_table.rewrite_metadata()
+        }
+    }
+
+    // Write every page in the tree and the metadata again
+    pub fn rewrite_tree<P>(&mut self, mut progress_callback: P) -> Result<(), Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        let page_id = if let Some(page_id) = self.page_table.root_id() {
+            page_id
+        } else {
+            return Err(Error::InvalidMetadata {
+                message: "missing root page ID",
+            });
+        };
+        let mut current = 0usize;
+        let mut total = 1usize;
+        let mut page_queue = VecDeque::new();
+
+        page_queue.push_back(page_id);
+
+        while let Some(page_id) = page_queue.pop_front() {
+            self.page_table.touch(page_id)?;
+
+            if let Node::Internal(internal_node) = self.read_node(page_id)? {
+                page_queue.extend(internal_node.children());
+                total += internal_node.children().len();
+            }
+
+            current += 1;
+            progress_callback(current, total);
+        }
+
+        self.page_table.commit()?;
+
+        // Save again so the previous metadata copy is also rewritten
+        self.page_table.rewrite_metadata()
+    }
+
+    // Rebuild the free page ID list from the pages reachable from the root
+    pub fn reclaim_ids(&mut self) -> Result<(), Error> {
+        let used_ids = self.reachable_page_ids()?;
+
+        self.page_table.reclaim_ids(&used_ids)
+    }
+
+    pub fn read_committed_files<C>(&mut self, callback: C) -> Result<(), Error>
+    where
+        C: FnMut(&str, &[u8]) -> Result<(), Error>,
+    {
+        let mut page_ids: Vec<PageId> = self.reachable_page_ids()?.into_iter().collect();
+        page_ids.sort_unstable();
+
+        self.page_table.read_committed_files(&page_ids, callback)
+    }
+
+    // Revision files are cleaned up first because traversing would load the
+    // pages of the files and handle them without counting them
+    pub fn clean_up(&mut self) -> Result<CleanupReport, Error> {
+        let mut report = self.page_table.clean_up_revision_files()?;
+        let used_ids = self.reachable_page_ids()?;
+
+        self.page_table
+            .clean_up_unused_pages(&used_ids, &mut report)?;
+
+        Ok(report)
+    }
+
+    fn reachable_page_ids(&mut self) -> Result<HashSet<PageId>, Error> {
+        let mut used_ids = HashSet::new();
+        let mut page_queue = VecDeque::new();
+
+        if let Some(page_id) = self.page_table.root_id() {
+            page_queue.push_back(page_id);
+        }
+
+        while let Some(page_id) = page_queue.pop_front() {
+            if !used_ids.insert(page_id) {
+                return Err(Error::InvalidPageData {
+                    page: page_id,
+                    message: "page referenced more than once",
+                });
+            }
+
+            if let Node::Internal(internal_node) = self.read_node(page_id)? {
+                page_queue.extend(internal_node.children());
+            }
+        }
+
+        Ok(used_ids)
+    }
+
+    // Build a new tree of full nodes from the pairs in key order, replace the
+    // old tree, and commit. The last two nodes of each level share their
+    // items evenly so that no node is nearly empty.
+    pub fn compact<P>(&mut self, progress_callback: P) -> Result<(), Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        let mut old_page_ids = Vec::new();
+        let mut leaf_ids = Vec::new();
+        let mut page_stack = Vec::new();
+        let mut has_pairs = false;
+
+        if let Some(page_id) = self.page_table.root_id() {
+            page_stack.push(page_id);
+        }
+
+        while let Some(page_id) = page_stack.pop() {
+            old_page_ids.push(page_id);
+
+            match self.read_node(page_id)? {
+                Node::EmptyRoot => {}
+                Node::Internal(internal_node) => {
+                    page_stack.extend(internal_node.children().iter().rev());
+                }
+                Node::Leaf(leaf_node) => {
+                    has_pairs |= leaf_node.len() > 0;
+                    leaf_ids.push(page_id);
+                }
+            }
+        }
+
+        if !has_pairs {
+            return Ok(());
+        }
+
+        let mut entries = self.compact_leaf_nodes(&leaf_ids, progress_callback)?;
+
+        while entries.len() > 1 {
+            entries = self.compact_internal_nodes(entries)?;
+        }
+
+        self.page_table.set_root_id(Some(entries[0].1));
+
+        for page_id in old_page_ids {
+            self.page_table.remove(page_id)?;
+        }
+
+        self.page_table.commit()
+    }
+
+    // Returns the first key and page ID of the new leaf nodes
+    fn compact_leaf_nodes<P>(
+        &mut self,
+        leaf_ids: &[PageId],
+        mut progress_callback: P,
+    ) -> Result<Vec<(Vec<u8>, PageId)>, Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        let keys_per_node = self.keys_per_node;
+        let mut entries = Vec::new();
+        let mut previous_node: Option<(PageId, LeafNode)> = None;
+        let mut current_node = LeafNode::default();
+        let mut current_id = self.page_table.new_page_id();
+
+        for (index, &leaf_id) in leaf_ids.iter().enumerate() {
+            progress_callback(index, leaf_ids.len());
+
+            let leaf_node = self.read_node(leaf_id)?.leaf(leaf_id)?.clone();
+
+            for (key, value) in leaf_node.keys.into_iter().zip(leaf_node.values) {
+                if current_node.len() == keys_per_node {
+                    let next_id = self.page_table.new_page_id();
+                    current_node.set_next_leaf(Some(next_id));
+
+                    let node = std::mem::take(&mut current_node);
+
+                    if let Some((page_id, node)) = previous_node.replace((current_id, node)) {
+                        entries.push((node.keys[0].clone(), page_id));
+                        self.page_table.put(page_id, Node::Leaf(node))?;
+                    }
+
+                    current_id = next_id;
+                }
+
+                current_node.keys.push(key);
+                current_node.values.push(value);
+            }
+        }
+
+        if let Some((page_id, mut node)) = previous_node {
+            let total = node.len() + current_node.len();
+
+            if current_node.len() < total / 2 {
+                let moved_keys = node.keys.split_off(total - total / 2);
+                let moved_values = node.values.split_off(total - total / 2);
+
+                current_node.keys.splice(0..0, moved_keys);
+                current_node.values.splice(0..0, moved_values);
+            }
+
+            entries.push((node.keys[0].clone(), page_id));
+            self.page_table.put(page_id, Node::Leaf(node))?;
+        }
+
+        entries.push((current_node.keys[0].clone(), current_id));
+        self.page_table.put(current_id, Node::Leaf(current_node))?;
+
+        progress_callback(leaf_ids.len(), leaf_ids.len());
+
+        Ok(entries)
+    }
+
+    // Returns the first key and page ID of the new parent nodes
+    fn compact_internal_nodes(
+        &mut self,
+        entries: Vec<(Vec<u8>, PageId)>,
+    ) -> Result<Vec<(Vec<u8>, PageId)>, Error> {
+        let max_children = self.keys_per_node + 1;
+        let entry_count = entries.len();
+        let node_count = entry_count.div_ceil(max_children);
+        let mut parent_entries = Vec::with_capacity(node_count);
+        let mut entries = entries.into_iter();
+
+        for index in 0..node_count {
+            let child_count =
+                entry_count * (index + 1) / node_count - entry_count * index / node_count;
+            let mut keys = Vec::with_capacity(child_count - 1);
+            let mut children = Vec::with_capacity(child_count);
+            let mut first_key = None;
+
+            for (key, page_id) in entries.by_ref().take(child_count) {
+                if first_key.is_none() {
+                    first_key = Some(key);
+                } else {
+                    keys.push(key);
+                }
+
+                children.push(page_id);
+            }
+
+            let page_id = self.page_table.new_page_id();
+            self.page_table
+                .put(page_id, Node::Internal(InternalNode::new(keys, children)))?;
+            parent_entries.push((first_key.unwrap(), page_id));
+        }
+
+        Ok(parent_entries)
+    }
+
+    // Visit the pairs of the readable leaf nodes and return the pages that
+    // could not be read with the key range given by their parents. If pages
+    // are lost, leaf nodes not reachable from the tree with keys in the lost
+    // ranges, such as children of a lost internal node, are visited too.
+    pub fn salvage<C>(&mut self, mut visitor: C) -> Result<Vec<LostPage>, Error>
+    where
+        C: FnMut(Vec<u8>, Vec<u8>) -> Result<(), Error>,
+    {
+        let mut lost_pages = Vec::new();
+        let mut visited_ids = HashSet::new();
+        let mut page_stack = Vec::new();
+
+        if let Some(page_id) = self.page_table.root_id() {
+            page_stack.push((page_id, None, None));
+        }
+
+        while let Some((page_id, start_key, end_key)) = page_stack.pop() {
+            visited_ids.insert(page_id);
+
+            let node = match self.read_node(page_id) {
+                Ok(node) => node.clone(),
+                Err(error) => {
+                    lost_pages.push(LostPage {
+                        page_id,
+                        start_key,
+                        end_key,
+                        message: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            match node {
+                Node::EmptyRoot => {}
+                Node::Internal(internal_node) => {
+                    for (index, &child_id) in internal_node.children().iter().enumerate().rev() {
+                        let child_start_key = if index > 0 {
+                            internal_node.keys().get(index - 1).cloned()
+                        } else {
+                            start_key.clone()
+                        };
+                        let child_end_key = internal_node
+                            .keys()
+                            .get(index)
+                            .cloned()
+                            .or_else(|| end_key.clone());
+
+                        page_stack.push((child_id, child_start_key, child_end_key));
+                    }
+                }
+                Node::Leaf(leaf_node) => {
+                    for (key, value) in leaf_node.keys.into_iter().zip(leaf_node.values) {
+                        visitor(key, value)?;
+                    }
+                }
+            }
+        }
+
+        if !lost_pages.is_empty() {
+            self.salvage_unreachable_leaf_nodes(&visited_ids, &lost_pages, &mut visitor)?;
+        }
+
+        Ok(lost_pages)
+    }
+
+    fn salvage_unreachable_leaf_nodes<C>(
+        &mut self,
+        visited_ids: &HashSet<PageId>,
+        lost_pages: &[LostPage],
+        visitor: &mut C,
+    ) -> Result<(), Error>
+    where
+        C: FnMut(Vec<u8>, Vec<u8>) -> Result<(), Error>,
+    {
+        let free_ids = self.page_table.free_page_ids();
+
+        for page_id in 1..=self.page_table.id_counter() {
+            if visited_ids.contains(&page_id) || free_ids.contains(&page_id) {
+                continue;
+            }
+
+            let leaf_node = match self.read_node(page_id) {
+                Ok(Node::Leaf(leaf_node)) => leaf_node.clone(),
+                _ => continue,
+            };
+
+            for (key, value) in leaf_node.keys.into_iter().zip(leaf_node.values) {
+                if lost_pages
+                    .iter()
+                    .any(|lost_page| lost_page.contains_key(&key))
+                {
+                    visitor(key, value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Estimate the number of pairs in the range from the key boundaries of
+    // the internal nodes. Only the leftmost leaf node and the leaf nodes
+    // containing the bounds of the range are read.
+    pub fn estimate_range_size<R>(&mut self, range: &R) -> Result<u64, Error>
+    where
+        R: RangeBounds<[u8]>,
+    {
+        let root_id = match self.page_table.root_id() {
+            Some(page_id) => page_id,
+            None => return Ok(0),
+        };
+
+        match self.read_node(root_id)? {
+            Node::EmptyRoot => return Ok(0),
+            Node::Leaf(leaf_node) => {
+                return Ok(leaf_node
+                    .keys
+                    .iter()
+                    .filter(|key| range.contains(key.as_slice()))
+                    .count() as u64);
+            }
+            Node::Internal(_) => {}
+        }
+
+        // All leaf nodes are at the same depth
+        let mut leaf_depth = 0;
+        let mut page_id = root_id;
+
+        while let Node::Internal(internal_node) = self.read_node(page_id)? {
+            page_id = internal_node.children()[0];
+            leaf_depth += 1;
+        }
+
+        let mut leaf_count = 0u64;
+        let mut full_leaf_count = 0u64;
+        let mut partial_leaf_ids = Vec::new();
+        let mut page_queue = VecDeque::<(PageId, usize, Option<Vec<u8>>, Option<Vec<u8>>)>::new();
+
+        page_queue.push_back((root_id, 0, None, None));
+
+        while let Some((page_id, depth, left_key, right_key)) = page_queue.pop_front() {
+            let internal_node = self.read_node(page_id)?.internal(page_id)?;
+            let keys = internal_node.keys();
+
+            for (index, &child_id) in internal_node.children().iter().enumerate() {
+                let child_left_key = if index == 0 {
+                    left_key.as_deref()
+                } else {
+                    Some(keys[index - 1].as_slice())
+                };
+                let child_right_key = if index < keys.len() {
+                    Some(keys[index].as_slice())
+                } else {
+                    right_key.as_deref()
+                };
+
+                if depth + 1 == leaf_depth {
+                    leaf_count += 1;
+
+                    match range_overlap(range, child_left_key, child_right_key) {
+                        RangeOverlap::Disjoint => {}
+                        RangeOverlap::Partial => partial_leaf_ids.push(child_id),
+                        RangeOverlap::Full => full_leaf_count += 1,
+                    }
+                } else {
+                    page_queue.push_back((
+                        child_id,
+                        depth + 1,
+                        child_left_key.map(|key| key.to_vec()),
+                        child_right_key.map(|key| key.to_vec()),
+                    ));
+                }
+            }
+        }
+
+        let key_value_count = self
+            .page_table
+            .auxiliary_metadata()
+            .map(|meta| meta.key_value_count)
+            .unwrap_or_default();
+        let average_fill = key_value_count as f64 / leaf_count.max(1) as f64;
+        let mut estimate = (full_leaf_count as f64 * average_fill).round() as u64;
+
+        // At most two leaf nodes contain the bounds so count them exactly
+        for page_id in partial_leaf_ids {
+            let leaf_node = self.read_node(page_id)?.leaf(page_id)?;
+
+            estimate += leaf_node
+                .keys
+                .iter()
+                .filter(|key| range.contains(key.as_slice()))
+                .count() as u64;
+        }
+
+        Ok(estimate)
+    }
+
+    // Load the upper levels of the tree breadth-first, stopping before the
+    // page cache would evict the loaded pages
+    pub fn preload(&mut self, depth: usize) -> Result<usize, Error> {
+        let capacity = self.page_table.page_cache_size();
+        let mut count = 0;
+        let mut page_queue = VecDeque::new();
+
+        if let Some(page_id) = self.page_table.root_id() {
+            page_queue.push_back((page_id, 0));
+        }
+
+        while let Some((page_id, level)) = page_queue.pop_front() {
+            if count >= capacity {
+                break;
+            }
+
+            count += 1;
+
+            if let Node::Internal(internal_node) = self.read_node(page_id)? {
+                if level < depth {
+                    page_queue.extend(
+                        internal_node
+                            .children()
+                            .iter()
+                            .map(|&child_id| (child_id, level + 1)),
+                    );
+                }
+            }
+        }
+
+        Ok(count)
+    }
+
+    pub fn tree_stats(&mut self) -> Result<TreeStats, Error> {
+        let mut stats = TreeStats {
+            free_page_count: self.page_table.free_page_count() as u64,
+            uuid: self.page_table.uuid().to_string(),
+            revision: self.page_table.revision(),
+            ..Default::default()
+        };
+        let mut page_queue = VecDeque::new();
+        let mut key_counts = Vec::new();
+
+        if let Some(page_id) = self.page_table.root_id() {
+            page_queue.push_back((page_id, 0));
+        }
+
+        while let Some((page_id, level)) = page_queue.pop_front() {
+            let key_count = match self.read_node(page_id)? {
+                Node::EmptyRoot => 0,
+                Node::Internal(internal_node) => {
+                    page_queue.extend(
+                        internal_node
+                            .children()
+                            .iter()
+                            .map(|&child_id| (child_id, level + 1)),
+                    );
+                    internal_node.keys_len()
+                }
+                Node::Leaf(leaf_node) => leaf_node.len(),
+            };
+
+            if stats.level_page_counts.len() <= level {
+                stats.level_page_counts.push(0);
+            }
+
+            stats.level_page_counts[level] += 1;
+            stats.key_count += key_count as u64;
+            key_counts.push(key_count);
+        }
+
+        // The node size of databases written before it was saved is
+        // estimated from the largest node because nodes are split when full
+        let keys_per_node = match self.metadata().and_then(|meta| meta.keys_per_node) {
+            Some(keys_per_node) => keys_per_node,
+            None if key_counts.len() > 1 => key_counts.iter().copied().max().unwrap_or(0),
+            None => self.keys_per_node,
+        };
+
+        stats.key_capacity = key_counts
+            .iter()
+            .map(|&key_count| key_count.max(keys_per_node) as u64)
+            .sum();
+
+        Ok(stats)
+    }
+
+    pub fn disk_usage(&mut self) -> Result<DiskUsage, Error> {
+        let mut pages = Vec::new();
+        let mut page_queue = VecDeque::new();
+
+        if let Some(page_id) = self.page_table.root_id() {
+            page_queue.push_back((page_id, 0));
+        }
+
+        while let Some((page_id, level)) = page_queue.pop_front() {
+            let (key_count, largest_value_size) = match self.read_node(page_id)? {
+                Node::EmptyRoot => (0, 0),
+                Node::Internal(internal_node) => {
+                    page_queue.extend(
+                        internal_node
+                            .children()
+                            .iter()
+                            .map(|&child_id| (child_id, level + 1)),
+                    );
+                    (internal_node.keys_len(), 0)
+                }
+                Node::Leaf(leaf_node) => (leaf_node.len(), leaf_node.largest_value_len()),
+            };
+
+            pages.push(PageDiskUsage {
+                page_id,
+                level,
+                key_count: key_count as u64,
+                largest_value_size: largest_value_size as u64,
+                ..Default::default()
+            });
+        }
+
+        let page_ids: Vec<PageId> = pages.iter().map(|page| page.page_id).collect();
+        let sizes = self.page_table.committed_page_sizes(&page_ids)?;
+
+        for (page, (file_size, payload_size)) in pages.iter_mut().zip(sizes) {
+            page.file_size = file_size;
+            page.payload_size = payload_size;
+        }
+
+        Ok(DiskUsage {
+            pages,
+            metadata_size: self.page_table.metadata_file_size()?,
+        })
+    }
+
+    // Traverse the tree without stopping at errors and check the links of
+    // the leaf nodes and the files
+    pub fn fsck(&mut self) -> Result<FsckReport, Error> {
+        let mut report = FsckReport::default();
+        let mut used_ids = HashSet::new();
+        let mut leaf_links = Vec::new();
+        let mut page_stack = Vec::new();
+
+        if let Some(page_id) = self.page_table.root_id() {
+            page_stack.push(page_id);
+        }
+
+        // Children are visited in key order so the leaf nodes are in the
+        // order of their links
+        while let Some(page_id) = page_stack.pop() {
+            if !used_ids.insert(page_id) {
+                report.push(
+                    FsckIssueKind::InvalidPage,
+                    Some(page_id),
+                    None,
+                    "page referenced more than once".to_string(),
+                );
+                continue;
+            }
+
+            report.page_count += 1;
+
+            match self.read_node(page_id) {
+                Ok(Node::EmptyRoot) => {}
+                Ok(Node::Internal(internal_node)) => {
+                    page_stack.extend(internal_node.children().iter().rev());
+                }
+                Ok(Node::Leaf(leaf_node)) => leaf_links.push((page_id, leaf_node.next_leaf())),
+                Err(error) => report.push(
+                    FsckIssueKind::InvalidPage,
+                    Some(page_id),
+                    None,
+                    error.to_string(),
+                ),
+            }
+        }
+
+        // The expected links are unknown if leaf nodes are missing
+        let complete = report.is_ok();
+        let leaf_ids: HashSet<PageId> = leaf_links.iter().map(|(page_id, _)| *page_id).collect();
+
+        for (index, &(page_id, next_leaf)) in leaf_links.iter().enumerate() {
+            let expected = leaf_links.get(index + 1).map(|(page_id, _)| *page_id);
+
+            let message = match next_leaf {
+                _ if next_leaf == expected => continue,
+                Some(next_leaf) if !leaf_ids.contains(&next_leaf) => {
+                    format!(
+                        "next leaf page {} is not a leaf node of the tree",
+                        next_leaf
+                    )
+                }
+                _ if !complete => continue,
+                Some(next_leaf) => match expected {
+                    Some(expected) => {
+                        format!("next leaf page {} instead of {}", next_leaf, expected)
+                    }
+                    None => format!("next leaf page {} after the last leaf node", next_leaf),
+                },
+                None => format!("missing link to next leaf page {}", expected.unwrap()),
+            };
+
+            report.push(
+                FsckIssueKind::DanglingNextLeaf,
+                Some(page_id),
+                None,
+                message,
+            );
+        }
+
+        self.page_table.fsck_files(&used_ids, &mut report)?;
+
+        Ok(report)
+    }
+
+    pub fn verify_tree<P>(&mut self, mut progress_callback: P) -> Result<(), Error>
+    where
+        P: FnMut(usize, usize),
+    {
+        let page_id = if let Some(page_id) = self.page_table.root_id() {
+            page_id
+        } else {
+            return Err(Error::InvalidMetadata {
+                message: "missing root page ID",
+            });
+        };
+        let mut current = 0usize;
+        let mut total = 0usize;
+        let mut page_queue = VecDeque::<(u64, Option<Vec<u8>>, Option<Vec<u8>>)>::new();
+
+        page_queue.push_back((page_id, None, None));
+        total += 1;
+
+        while let Some((page_id, left_key, right_key)) = page_queue.pop_front() {
+            let node = self.read_node(page_id)?;
+
+            current += 1;
+            progress_callback(current, total);
+
+            match node {
+                Node::EmptyRoot => {}
+                Node::Internal(internal_node) => {
+                    if let Some(message) = internal_node
+                        .verify_with_parent_keys(left_key.as_deref(), right_key.as_deref())
+                    {
+                        return Err(Error::InvalidPageData {
+                            page: page_id,
+                            message,
+                        });
+                    }
+
+                    for (index, page_id) in internal_node.children().iter().enumerate() {
+                        let left_key = if index > 0 {
+                            internal_node.keys().get(index - 1).cloned()
+                        } else {
+                            None
+                        };
+                        let right_key = internal_node.keys().get(index).cloned();
+
+                        page_queue.push_back((*page_id, left_key, right_key));
+                        total += 1;
+                    }
+                }
+                Node::Leaf(leaf_node) => {
+                    if let Some(message) =
+                        leaf_node.verify_with_parent_keys(left_key.as_deref(), right_key.as_deref())
+                    {
+                        return Err(Error::InvalidPageData {
+                            page: page_id,
+                            message,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn dump_tree<W: Write>(&mut self, mut writer: W) -> Result<(), Error> {
+        let page_id = match self.page_table.root_id() {
+            Some(page_id) => page_id,
+            None => {
+                writeln!(writer, "Root page: none")?;
+                return Ok(());
+            }
+        };
+        let mut page_queue = VecDeque::new();
+
+        page_queue.push_back((page_id, 0));
+
+        writeln!(writer, "Root page: {}", page_id)?;
+
+        while let Some((page_id, height)) = page_queue.pop_front() {
+            let node = self.read_node(page_id)?;
+
+            writeln!(writer, "Page {}: {} {:?}", page_id, height, &node)?;
+
+            match node {
                 Node::EmptyRoot => {}
                 Node::Internal(internal_node) => {
                     for page_id in internal_node.children() {
@@ -682,6 +1930,42 @@ impl Tree {
         Ok(())
     }
 
+    pub fn tree_summary(&mut self) -> Result<TreeSummary, Error> {
+        let root_id = self.page_table.root_id();
+        let mut pages = Vec::new();
+        let mut page_queue = VecDeque::new();
+
+        if let Some(page_id) = root_id {
+            page_queue.push_back((page_id, 0));
+        }
+
+        while let Some((page_id, level)) = page_queue.pop_front() {
+            let node = self.read_node(page_id)?;
+            let key_count = node.key_count() as u64;
+
+            let (kind, children, next_leaf) = match node {
+                Node::EmptyRoot => (PageKind::EmptyRoot, Vec::new(), None),
+                Node::Internal(internal_node) => {
+                    let children = internal_node.children().to_vec();
+                    page_queue.extend(children.iter().map(|&child_id| (child_id, level + 1)));
+                    (PageKind::Internal, children, None)
+                }
+                Node::Leaf(leaf_node) => (PageKind::Leaf, Vec::new(), leaf_node.next_leaf),
+            };
+
+            pages.push(PageSummary {
+                page_id,
+                level,
+                kind,
+                key_count,
+                children,
+                next_leaf,
+            });
+        }
+
+        Ok(TreeSummary { root_id, pages })
+    }
+
     // Find a leaf node
     //
     // Path is the list of parents to the leaf node. Path won't include the leaf.
@@ -919,11 +2203,19 @@ impl Tree {
         leaf_node_id: PageId,
         node_path: &mut Vec<PageId>,
     ) -> Result<(), Error> {
-        if let Some(parent_id) = node_path.pop() {
-            // When the leaf node is a child of an internal node
-            let adjacent_leafs =
-                self.remove_child_from_internal_node(parent_id, leaf_node_id, node_path)?;
-            self.join_leaf_nodes(adjacent_leafs.0, adjacent_leafs.1)?;
+        if let Some(&parent_id) = node_path.last() {
+            // When the leaf node is a child of an internal node.
+            // The previous leaf may be under a different parent node, so it
+            // is found from the path instead of the siblings in the parent.
+            let previous_leaf_id = self.find_previous_leaf_node(leaf_node_id, node_path)?;
+            let next_leaf_id = self
+                .read_node(leaf_node_id)?
+                .leaf(leaf_node_id)?
+                .next_leaf();
+            node_path.pop();
+
+            self.remove_child_from_internal_node(parent_id, leaf_node_id, node_path)?;
+            self.join_leaf_nodes(previous_leaf_id, next_leaf_id)?;
             self.page_table.remove(leaf_node_id)?;
         } else {
             // When the leaf node was also the root node
@@ -934,12 +2226,49 @@ impl Tree {
         Ok(())
     }
 
+    // Find the rightmost leaf node of the closest subtree on the left side
+    // of the given node
+    fn find_previous_leaf_node(
+        &mut self,
+        page_id: PageId,
+        node_path: &[PageId],
+    ) -> Result<Option<PageId>, Error> {
+        let mut child_id = page_id;
+
+        for &parent_id in node_path.iter().rev() {
+            let internal_node = self.read_node(parent_id)?.internal(parent_id)?;
+            let children = internal_node.children();
+            let index = children.iter().position(|&id| id == child_id);
+
+            match index {
+                Some(index) if index > 0 => {
+                    let mut page_id = children[index - 1];
+
+                    for _ in 0..u16::MAX {
+                        match self.read_node(page_id)? {
+                            Node::Internal(internal_node) => {
+                                page_id = *internal_node.children().last().unwrap();
+                            }
+                            Node::Leaf(_) => return Ok(Some(page_id)),
+                            Node::EmptyRoot => return Ok(None),
+                        }
+                    }
+
+                    return Err(Error::LimitExceeded);
+                }
+                _ => child_id = parent_id,
+            }
+        }
+
+        Ok(None)
+    }
+
     fn remove_child_from_internal_node(
         &mut self,
         internal_node_id: PageId,
         child_node_id: PageId,
         node_path: &mut Vec<PageId>,
-    ) -> Result<(Option<PageId>, Option<PageId>), Error> {
+    ) -> Result<(), Error> {
         let mut internal_node_ = self.edit_node(internal_node_id)?;
         let internal_node = internal_node_.internal_mut(internal_node_id)?;
 
@@ -961,13 +2290,12 @@ impl Tree {
                 assert_eq!(self.page_table.root_id(), Some(internal_node_id));
                 self.page_table.put(internal_node_id, Node::EmptyRoot)?;
             }
-
-            Ok((None, None))
         } else {
             // Lazy remove the child node, allowing underflow (traditional B+tree invariants violated)
-            let adjacent_nodes = internal_node.remove_child(child_node_id);
-            Ok(adjacent_nodes)
+            internal_node.remove_child(child_node_id);
         }
+
+        Ok(())
     }
 
     fn join_leaf_nodes(
@@ -1004,12 +2332,58 @@ impl Tree {
     }
 }
 
+// What to do when a key being inserted already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExistingKeyAction {
+    Replace,
+    Keep,
+    Fail,
+}
+
 #[derive(Default)]
 pub struct TreeCursor {
     leaf_node: Option<LeafNode>,
     key_index: usize,
 }
 
+enum RangeOverlap {
+    Disjoint,
+    Partial,
+    Full,
+}
+
+// Return how a node with keys within [left_key, right_key) overlaps the range
+fn range_overlap<R>(range: &R, left_key: Option<&[u8]>, right_key: Option<&[u8]>) -> RangeOverlap
+where
+    R: RangeBounds<[u8]>,
+{
+    let start_within = match (range.start_bound(), right_key) {
+        (Bound::Included(key) | Bound::Excluded(key), Some(right_key)) if key >= right_key => {
+            return RangeOverlap::Disjoint
+        }
+        (Bound::Included(key) | Bound::Excluded(key), _) => match left_key {
+            Some(left_key) => key > left_key,
+            None => true,
+        },
+        (Bound::Unbounded, _) => false,
+    };
+    let end_within = match (range.end_bound(), left_key) {
+        (Bound::Included(key), Some(left_key)) if key < left_key => return RangeOverlap::Disjoint,
+        (Bound::Excluded(key), Some(left_key)) if key <= left_key => return RangeOverlap::Disjoint,
+        (Bound::Included(key) | Bound::Excluded(key), _) => match right_key {
+            Some(right_key) => key < right_key,
+            None => true,
+        },
+        (Bound::Unbounded, _) => false,
+    };
+
+    if start_within || end_within {
+        RangeOverlap::Partial
+    } else {
+        RangeOverlap::Full
+    }
+}
+
 fn is_sorted<T>(data: &[T]) -> bool
 where
     T: Ord,
@@ -1185,7 +2559,8 @@ mod tests {
 
     #[test]
     fn test_verify_internal_node_bad_key_sort() {
-        let mut node = InternalNode::new(vec![b"key100".to_vec(), b"key200".to_vec()], vec![4, 8, 3]);
+        let mut node =
+            InternalNode::new(vec![b"key100".to_vec(), b"key200".to_vec()], vec![4, 8, 3]);
         node.keys.reverse();
 
         assert!(node.verify_with_parent_keys(None, None).is_some());
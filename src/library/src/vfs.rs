@@ -1,6 +1,15 @@
 //! Virtual file system interface for database storage.
 
-use std::{fmt::Debug, io::Write, path::PathBuf};
+mod pack;
+mod segment;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::{
+    fmt::Debug,
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
 
 #[cfg(feature = "fslock")]
 use std::collections::HashMap;
@@ -10,6 +19,11 @@ use vfs::{MemoryFS, VfsFileType, VfsPath};
 
 use crate::error::Error;
 
+pub use self::pack::{PackVfs, PackWriter};
+pub use self::segment::SegmentVfs;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteVfs;
+
 /// Represents a virtual file system.
 ///
 /// File paths are characters within pattern `[a-z0-9._]` in Unix style
@@ -18,7 +32,9 @@ use crate::error::Error;
 ///
 /// Implementations are not expected to support directory traversal notations
 /// or handling redundant slashes. Implementations can return an error in
-/// those cases.
+/// those cases. [`MemoryVfs`] and [`OsVfs`] check paths with
+/// [`normalize_path()`] and return [`Error::InvalidPath`] for paths that
+/// don't follow these rules.
 pub trait Vfs {
     /// Lock the file preventing other processes from accessing it.
     ///
@@ -114,6 +130,202 @@ pub trait Vfs {
 
     /// Return whether the path exists.
     fn exists(&self, path: &str) -> Result<bool, Error>;
+
+    /// Append the contents to the end of a file.
+    ///
+    /// The file will be created if it does not exist.
+    ///
+    /// The default implementation reads the entire file and writes it back.
+    fn append(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        let mut buffer = if self.exists(path)? {
+            self.read(path)?
+        } else {
+            Vec::new()
+        };
+        buffer.extend_from_slice(data);
+        self.write(path, &buffer, sync_option)
+    }
+
+    /// Read a portion of a file starting at the given offset.
+    ///
+    /// If the file is shorter than the requested length, an error is returned.
+    ///
+    /// The default implementation reads the entire file.
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        let data = self.read(path)?;
+        let start = offset as usize;
+
+        match data.get(start..start.saturating_add(length)) {
+            Some(range) if range.len() == length => Ok(range.to_vec()),
+            _ => Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "range beyond end of file",
+            ))),
+        }
+    }
+
+    /// Hint that a file will be read soon.
+    ///
+    /// Implementations may start loading the file in the background, such as
+    /// into the operating system's page cache, so that a later read is
+    /// faster. Errors are ignored by the caller.
+    ///
+    /// The default implementation does nothing.
+    fn will_read(&self, path: &str) -> Result<(), Error> {
+        let _ = path;
+        Ok(())
+    }
+
+    /// Create a file at the new path with the contents of the old path.
+    ///
+    /// The new file may share its storage with the old file, such as a hard
+    /// link, so neither file should be modified in place afterwards.
+    ///
+    /// The default implementation copies the file.
+    fn link_file(
+        &mut self,
+        old_path: &str,
+        new_path: &str,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        let data = self.read(old_path)?;
+        self.write(new_path, &data, sync_option)
+    }
+}
+
+impl<T> Vfs for Box<T>
+where
+    T: Vfs + ?Sized,
+{
+    fn lock(&mut self, path: &str) -> Result<(), Error> {
+        self.as_mut().lock(path)
+    }
+
+    fn unlock(&mut self, path: &str) -> Result<(), Error> {
+        self.as_mut().unlock(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.as_ref().read(path)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        self.as_mut().write(path, data, sync_option)
+    }
+
+    fn sync_file(&mut self, path: &str, sync_option: VfsSyncOption) -> Result<(), Error> {
+        self.as_mut().sync_file(path, sync_option)
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), Error> {
+        self.as_mut().remove_file(path)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        self.as_ref().read_dir(path)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), Error> {
+        self.as_mut().create_dir(path)
+    }
+
+    fn create_dir_all(&mut self, path: &str) -> Result<(), Error> {
+        self.as_mut().create_dir_all(path)
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
+        self.as_mut().remove_dir(path)
+    }
+
+    fn remove_empty_dir_all(&mut self, path: &str) -> Result<(), Error> {
+        self.as_mut().remove_empty_dir_all(path)
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        self.as_mut().rename_file(old_path, new_path)
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        self.as_ref().is_dir(path)
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        self.as_ref().exists(path)
+    }
+
+    fn append(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        self.as_mut().append(path, data, sync_option)
+    }
+
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        self.as_ref().read_range(path, offset, length)
+    }
+
+    fn will_read(&self, path: &str) -> Result<(), Error> {
+        self.as_ref().will_read(path)
+    }
+
+    fn link_file(
+        &mut self,
+        old_path: &str,
+        new_path: &str,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        self.as_mut().link_file(old_path, new_path, sync_option)
+    }
+}
+
+// Device names that can't be used as filenames on Windows, even with an
+// extension
+const RESERVED_NAMES: [&str; 22] = [
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Check a path for the rules of [`Vfs`] and return it in normal form.
+///
+/// Backslashes are treated as directory separators, and redundant,
+/// leading, and trailing separators are removed. The empty path refers to
+/// the root directory. An error is returned for components that are `.` or
+/// `..`, contain characters outside of `[a-z0-9._]`, end with a dot, or are
+/// device names reserved on Windows such as `con` or `nul.txt`.
+pub fn normalize_path(path: &str) -> Result<String, Error> {
+    let invalid = |message| Error::InvalidPath {
+        path: path.to_string(),
+        message,
+    };
+    let mut components = Vec::new();
+
+    for component in path.split(['/', '\\']) {
+        if component.is_empty() {
+            continue;
+        }
+
+        if component == "." || component == ".." {
+            return Err(invalid("directory traversal not allowed"));
+        }
+
+        if !component
+            .chars()
+            .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '_'))
+        {
+            return Err(invalid("character not allowed"));
+        }
+
+        if component.ends_with('.') {
+            return Err(invalid("name ends with a dot"));
+        }
+
+        let stem = component.split('.').next().unwrap_or_default();
+
+        if RESERVED_NAMES.contains(&stem) {
+            return Err(invalid("reserved device name"));
+        }
+
+        components.push(component);
+    }
+
+    Ok(components.join("/"))
 }
 
 /// File system synchronization options for synchronizing data to disk.
@@ -148,6 +360,34 @@ impl MemoryVfs {
             vfs: VfsPath::new(MemoryFS::default()),
         }
     }
+
+    /// Copy all files and directories to another file system.
+    ///
+    /// This can be used to build a database in memory and then persist it
+    /// to disk at the end. The database should be flushed and closed
+    /// before the contents are copied. Existing files in the destination
+    /// with the same path are overwritten.
+    pub fn dump_to(
+        &self,
+        destination: &mut dyn Vfs,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        copy_dir_all(self, destination, "", sync_option)
+    }
+
+    /// Copy all files and directories from another file system into this one.
+    ///
+    /// This can be used to seed an in-memory database from one on disk.
+    /// Existing files with the same path are overwritten.
+    pub fn load_from(&mut self, source: &dyn Vfs) -> Result<(), Error> {
+        copy_dir_all(source, self, "", VfsSyncOption::None)
+    }
+}
+
+impl MemoryVfs {
+    fn join(&self, path: &str) -> Result<VfsPath, Error> {
+        Ok(self.vfs.join(&normalize_path(path)?)?)
+    }
 }
 
 impl Default for MemoryVfs {
@@ -172,14 +412,14 @@ impl Vfs for MemoryVfs {
     }
 
     fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
-        let mut file = self.vfs.join(path)?.open_file()?;
+        let mut file = self.join(path)?.open_file()?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)?;
         Ok(buffer)
     }
 
     fn write(&mut self, path: &str, data: &[u8], _sync_option: VfsSyncOption) -> Result<(), Error> {
-        let mut file = self.vfs.join(path)?.create_file()?;
+        let mut file = self.join(path)?.create_file()?;
         file.write_all(data)?;
         Ok(())
     }
@@ -189,14 +429,14 @@ impl Vfs for MemoryVfs {
     }
 
     fn remove_file(&mut self, path: &str) -> Result<(), Error> {
-        self.vfs.join(path)?.remove_file()?;
+        self.join(path)?.remove_file()?;
         Ok(())
     }
 
     fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
         let mut filenames = Vec::new();
 
-        for sub_path in self.vfs.join(path)?.read_dir()? {
+        for sub_path in self.join(path)?.read_dir()? {
             filenames.push(sub_path.filename());
         }
 
@@ -204,12 +444,12 @@ impl Vfs for MemoryVfs {
     }
 
     fn create_dir(&mut self, path: &str) -> Result<(), Error> {
-        self.vfs.join(path)?.create_dir()?;
+        self.join(path)?.create_dir()?;
         Ok(())
     }
 
     fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
-        self.vfs.join(path)?.remove_dir()?;
+        self.join(path)?.remove_dir()?;
         Ok(())
     }
 
@@ -218,20 +458,38 @@ impl Vfs for MemoryVfs {
             self.remove_file(new_path)?;
         }
 
-        self.vfs
-            .join(old_path)?
-            .move_file(&self.vfs.join(new_path)?)?;
+        self.join(old_path)?.move_file(&self.join(new_path)?)?;
 
         Ok(())
     }
 
     fn is_dir(&self, path: &str) -> Result<bool, Error> {
-        let metadata = self.vfs.join(path)?.metadata()?;
+        let metadata = self.join(path)?.metadata()?;
         Ok(matches!(metadata.file_type, VfsFileType::Directory))
     }
 
     fn exists(&self, path: &str) -> Result<bool, Error> {
-        Ok(self.vfs.join(path)?.exists()?)
+        Ok(self.join(path)?.exists()?)
+    }
+
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        let path = self.join(path)?;
+
+        // The in-memory file doesn't support reading past its end
+        if offset.saturating_add(length as u64) > path.metadata()?.len {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "range beyond end of file",
+            )));
+        }
+
+        let mut file = path.open_file()?;
+        let mut buffer = vec![0u8; length];
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buffer)?;
+
+        Ok(buffer)
     }
 }
 
@@ -263,6 +521,19 @@ impl OsVfs {
     }
 }
 
+impl OsVfs {
+    // The empty path is the root itself, which can also be a file
+    fn join(&self, path: &str) -> Result<PathBuf, Error> {
+        let path = normalize_path(path)?;
+
+        if path.is_empty() {
+            Ok(self.root.clone())
+        } else {
+            Ok(self.root.join(path))
+        }
+    }
+}
+
 impl Debug for OsVfs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "OsVfs {{ path: {:?} }}", &self.root)
@@ -272,11 +543,11 @@ impl Debug for OsVfs {
 impl Vfs for OsVfs {
     #[cfg(feature = "fslock")]
     fn lock(&mut self, path: &str) -> Result<(), Error> {
-        let mut lock = fslock::LockFile::open(self.root.join(path).as_path())?;
+        let mut lock = fslock::LockFile::open(self.join(path)?.as_path())?;
         if !lock.try_lock()? {
             return Err(Error::Locked);
         }
-        self.locks.insert(self.root.join(path), lock);
+        self.locks.insert(self.join(path)?, lock);
 
         Ok(())
     }
@@ -287,7 +558,7 @@ impl Vfs for OsVfs {
 
     #[cfg(feature = "fslock")]
     fn unlock(&mut self, path: &str) -> Result<(), Error> {
-        if let Some(mut lock) = self.locks.remove(&self.root.join(path)) {
+        if let Some(mut lock) = self.locks.remove(&self.join(path)?) {
             lock.unlock()?;
         } else {
             return Err(Error::Io(std::io::Error::new(
@@ -305,21 +576,21 @@ impl Vfs for OsVfs {
     }
 
     fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
-        Ok(std::fs::read(self.root.join(path))?)
+        Ok(std::fs::read(self.join(path)?)?)
     }
 
     fn write(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
         match sync_option {
-            VfsSyncOption::None => Ok(std::fs::write(self.root.join(path), data)?),
+            VfsSyncOption::None => Ok(std::fs::write(self.join(path)?, data)?),
             VfsSyncOption::Data => {
-                let mut file = std::fs::File::create(self.root.join(path))?;
+                let mut file = std::fs::File::create(self.join(path)?)?;
                 file.write_all(data)?;
                 file.sync_data()?;
 
                 Ok(())
             }
             VfsSyncOption::All => {
-                let mut file = std::fs::File::create(self.root.join(path))?;
+                let mut file = std::fs::File::create(self.join(path)?)?;
                 file.write_all(data)?;
                 file.sync_all()?;
 
@@ -331,7 +602,7 @@ impl Vfs for OsVfs {
     fn sync_file(&mut self, path: &str, sync_option: VfsSyncOption) -> Result<(), Error> {
         let file = std::fs::OpenOptions::new()
             .append(true)
-            .open(self.root.join(path))?;
+            .open(self.join(path)?)?;
 
         match sync_option {
             VfsSyncOption::None => {}
@@ -347,11 +618,11 @@ impl Vfs for OsVfs {
     }
 
     fn remove_file(&mut self, path: &str) -> Result<(), Error> {
-        Ok(std::fs::remove_file(self.root.join(path))?)
+        Ok(std::fs::remove_file(self.join(path)?)?)
     }
 
     fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
-        let dir = std::fs::read_dir(self.root.join(path))?;
+        let dir = std::fs::read_dir(self.join(path)?)?;
         let mut filenames = Vec::new();
 
         for entry in dir {
@@ -366,49 +637,142 @@ impl Vfs for OsVfs {
     }
 
     fn create_dir(&mut self, path: &str) -> Result<(), Error> {
-        std::fs::create_dir(self.root.join(path))?;
+        std::fs::create_dir(self.join(path)?)?;
         Ok(())
     }
 
     fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
-        std::fs::remove_dir(self.root.join(path))?;
+        std::fs::remove_dir(self.join(path)?)?;
         Ok(())
     }
 
     fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<(), Error> {
-        std::fs::rename(self.root.join(old_path), self.root.join(new_path))?;
+        std::fs::rename(self.join(old_path)?, self.join(new_path)?)?;
         Ok(())
     }
 
     fn is_dir(&self, path: &str) -> Result<bool, Error> {
-        let metadata = std::fs::metadata(self.root.join(path))?;
+        let metadata = std::fs::metadata(self.join(path)?)?;
 
         Ok(metadata.is_dir())
     }
 
     fn exists(&self, path: &str) -> Result<bool, Error> {
-        Ok(self.root.join(path).exists())
+        Ok(self.join(path)?.exists())
+    }
+
+    fn append(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.join(path)?)?;
+        file.write_all(data)?;
+
+        match sync_option {
+            VfsSyncOption::None => {}
+            VfsSyncOption::Data => {
+                file.sync_data()?;
+            }
+            VfsSyncOption::All => {
+                file.sync_all()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        let mut file = std::fs::File::open(self.join(path)?)?;
+        let mut buffer = vec![0u8; length];
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    #[cfg(all(feature = "read_ahead", target_os = "linux"))]
+    fn will_read(&self, path: &str) -> Result<(), Error> {
+        use nix::fcntl::{posix_fadvise, PosixFadviseAdvice};
+        use std::os::unix::io::AsRawFd;
+
+        let file = std::fs::File::open(self.join(path)?)?;
+
+        // The page cache of the file is kept after it is closed
+        posix_fadvise(
+            file.as_raw_fd(),
+            0,
+            0,
+            PosixFadviseAdvice::POSIX_FADV_WILLNEED,
+        )
+        .map_err(std::io::Error::from)?;
+
+        Ok(())
+    }
+
+    fn link_file(
+        &mut self,
+        old_path: &str,
+        new_path: &str,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        let old_path = self.join(old_path)?;
+        let new_path = self.join(new_path)?;
+
+        match std::fs::hard_link(&old_path, &new_path) {
+            Ok(_) => return Ok(()),
+            Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(error.into())
+            }
+            // Hard links are not supported by every platform and file system
+            Err(_) => {}
+        }
+
+        std::fs::copy(&old_path, &new_path)?;
+
+        let file = std::fs::OpenOptions::new().append(true).open(&new_path)?;
+
+        match sync_option {
+            VfsSyncOption::None => {}
+            VfsSyncOption::Data => {
+                file.sync_data()?;
+            }
+            VfsSyncOption::All => {
+                file.sync_all()?;
+            }
+        }
+
+        Ok(())
     }
 }
 
 /// Wrapper that allows only read operations.
-pub struct ReadOnlyVfs {
-    inner: Box<dyn Vfs + Sync + Send>,
+pub struct ReadOnlyVfs<V = Box<dyn Vfs + Sync + Send>>
+where
+    V: Vfs,
+{
+    inner: V,
 }
 
-impl ReadOnlyVfs {
+impl<V> ReadOnlyVfs<V>
+where
+    V: Vfs,
+{
     /// Wrap a VFS.
-    pub fn new(inner: Box<dyn Vfs + Sync + Send>) -> Self {
+    pub fn new(inner: V) -> Self {
         Self { inner }
     }
 
     /// Return the wrapped VFS.
-    pub fn into_inner(self) -> Box<dyn Vfs + Sync + Send> {
+    pub fn into_inner(self) -> V {
         self.inner
     }
 }
 
-impl Vfs for ReadOnlyVfs {
+impl<V> Vfs for ReadOnlyVfs<V>
+where
+    V: Vfs,
+{
     fn lock(&mut self, path: &str) -> Result<(), Error> {
         self.inner.lock(path)
     }
@@ -461,14 +825,229 @@ impl Vfs for ReadOnlyVfs {
     fn exists(&self, path: &str) -> Result<bool, Error> {
         self.inner.exists(path)
     }
+
+    fn append(
+        &mut self,
+        _path: &str,
+        _data: &[u8],
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        self.inner.read_range(path, offset, length)
+    }
+
+    fn will_read(&self, path: &str) -> Result<(), Error> {
+        self.inner.will_read(path)
+    }
+
+    fn link_file(
+        &mut self,
+        _old_path: &str,
+        _new_path: &str,
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
 }
 
-impl Debug for ReadOnlyVfs {
+impl<V> Debug for ReadOnlyVfs<V>
+where
+    V: Vfs,
+{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "ReadOnlyVfs")
     }
 }
 
+// File system of a page table, which is wrapped when opened in read-only
+// mode so that no code path can modify the files
+pub(crate) enum OpenModeVfs<V>
+where
+    V: Vfs,
+{
+    Writable(V),
+    ReadOnly(ReadOnlyVfs<V>),
+}
+
+impl<V> OpenModeVfs<V>
+where
+    V: Vfs,
+{
+    pub fn new(vfs: V, read_only: bool) -> Self {
+        if read_only {
+            Self::ReadOnly(ReadOnlyVfs::new(vfs))
+        } else {
+            Self::Writable(vfs)
+        }
+    }
+}
+
+impl<V> Vfs for OpenModeVfs<V>
+where
+    V: Vfs,
+{
+    fn lock(&mut self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.lock(path),
+            Self::ReadOnly(vfs) => vfs.lock(path),
+        }
+    }
+
+    fn unlock(&mut self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.unlock(path),
+            Self::ReadOnly(vfs) => vfs.unlock(path),
+        }
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Writable(vfs) => vfs.read(path),
+            Self::ReadOnly(vfs) => vfs.read(path),
+        }
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.write(path, data, sync_option),
+            Self::ReadOnly(vfs) => vfs.write(path, data, sync_option),
+        }
+    }
+
+    fn sync_file(&mut self, path: &str, sync_option: VfsSyncOption) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.sync_file(path, sync_option),
+            Self::ReadOnly(vfs) => vfs.sync_file(path, sync_option),
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.remove_file(path),
+            Self::ReadOnly(vfs) => vfs.remove_file(path),
+        }
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        match self {
+            Self::Writable(vfs) => vfs.read_dir(path),
+            Self::ReadOnly(vfs) => vfs.read_dir(path),
+        }
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.create_dir(path),
+            Self::ReadOnly(vfs) => vfs.create_dir(path),
+        }
+    }
+
+    fn create_dir_all(&mut self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.create_dir_all(path),
+            Self::ReadOnly(vfs) => vfs.create_dir_all(path),
+        }
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.remove_dir(path),
+            Self::ReadOnly(vfs) => vfs.remove_dir(path),
+        }
+    }
+
+    fn remove_empty_dir_all(&mut self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.remove_empty_dir_all(path),
+            Self::ReadOnly(vfs) => vfs.remove_empty_dir_all(path),
+        }
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.rename_file(old_path, new_path),
+            Self::ReadOnly(vfs) => vfs.rename_file(old_path, new_path),
+        }
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        match self {
+            Self::Writable(vfs) => vfs.is_dir(path),
+            Self::ReadOnly(vfs) => vfs.is_dir(path),
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        match self {
+            Self::Writable(vfs) => vfs.exists(path),
+            Self::ReadOnly(vfs) => vfs.exists(path),
+        }
+    }
+
+    fn append(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.append(path, data, sync_option),
+            Self::ReadOnly(vfs) => vfs.append(path, data, sync_option),
+        }
+    }
+
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Writable(vfs) => vfs.read_range(path, offset, length),
+            Self::ReadOnly(vfs) => vfs.read_range(path, offset, length),
+        }
+    }
+
+    fn will_read(&self, path: &str) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.will_read(path),
+            Self::ReadOnly(vfs) => vfs.will_read(path),
+        }
+    }
+
+    fn link_file(
+        &mut self,
+        old_path: &str,
+        new_path: &str,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Writable(vfs) => vfs.link_file(old_path, new_path, sync_option),
+            Self::ReadOnly(vfs) => vfs.link_file(old_path, new_path, sync_option),
+        }
+    }
+}
+
+fn copy_dir_all(
+    source: &dyn Vfs,
+    destination: &mut dyn Vfs,
+    dir_path: &str,
+    sync_option: VfsSyncOption,
+) -> Result<(), Error> {
+    let mut filenames = source.read_dir(dir_path)?;
+    filenames.sort_unstable();
+
+    for filename in filenames {
+        let path = RelativePath::new(dir_path).join(&filename);
+
+        if source.is_dir(path.as_str())? {
+            if !destination.exists(path.as_str())? {
+                destination.create_dir(path.as_str())?;
+            }
+
+            copy_dir_all(source, destination, path.as_str(), sync_option)?;
+        } else {
+            let data = source.read(path.as_str())?;
+            destination.write(path.as_str(), &data, sync_option)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -490,4 +1069,60 @@ mod tests {
         vfs.remove_empty_dir_all("a/b/c").unwrap();
         assert!(!vfs.exists("a/b/c").unwrap());
     }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("").unwrap(), "");
+        assert_eq!(normalize_path("a/b_1.grebedb").unwrap(), "a/b_1.grebedb");
+        assert_eq!(normalize_path("/a//b/").unwrap(), "a/b");
+        assert_eq!(normalize_path("a\\b").unwrap(), "a/b");
+        assert_eq!(normalize_path(".tmp").unwrap(), ".tmp");
+        assert_eq!(normalize_path("console").unwrap(), "console");
+
+        for path in [
+            "a/../b",
+            "./a",
+            "A",
+            "a-b",
+            "a b",
+            "a:b",
+            "a.",
+            "con",
+            "a/nul.txt",
+            "com1",
+        ] {
+            assert!(
+                matches!(normalize_path(path), Err(Error::InvalidPath { .. })),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_path() {
+        let mut vfs = MemoryVfs::new();
+
+        assert!(matches!(
+            vfs.write("../a", b"", VfsSyncOption::None),
+            Err(Error::InvalidPath { .. })
+        ));
+        assert!(matches!(
+            vfs.create_dir("aux"),
+            Err(Error::InvalidPath { .. })
+        ));
+
+        vfs.write("a//b", b"hello", VfsSyncOption::None)
+            .unwrap_err();
+        vfs.create_dir("a").unwrap();
+        vfs.write("a//b", b"hello", VfsSyncOption::None).unwrap();
+        assert_eq!(vfs.read("a/b").unwrap(), b"hello");
+
+        vfs.rename_file("/a//b", "a/c").unwrap();
+        assert_eq!(vfs.read("a/c").unwrap(), b"hello");
+        assert!(matches!(
+            vfs.rename_file("a/../a/c", "a/d"),
+            Err(Error::InvalidPath { .. })
+        ));
+    }
 }
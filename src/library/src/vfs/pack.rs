@@ -0,0 +1,468 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryInto,
+    fmt::Debug,
+    io::{Seek, SeekFrom, Write},
+};
+
+use relative_path::RelativePath;
+
+use super::{Vfs, VfsSyncOption};
+use crate::error::Error;
+
+const PACK_MAGIC_BYTES: [u8; 8] = [0xFE, b'G', b'r', b'e', b'b', b'e', b'P', b'k'];
+
+// magic (8) + index offset (8) + index length (8) + index crc32c (4)
+const PACK_HEADER_SIZE: usize = 28;
+// offset (8) + length (8) + crc32c (4)
+const INDEX_ENTRY_FOOTER_SIZE: usize = 20;
+const MAX_INDEX_SIZE: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+struct PackEntry {
+    offset: u64,
+    length: u64,
+    crc: u32,
+}
+
+/// Read-only file system of the files contained in a single pack file.
+///
+/// A pack file is created with [`PackWriter`] and is useful for
+/// distributing a finished database as a single file. The database should be
+/// opened using [`crate::OpenMode::ReadOnly`].
+///
+/// Files are read from the pack file on demand using
+/// [`Vfs::read_range()`] of the underlying file system. Operations that
+/// modify files return [`Error::ReadOnly`]. Locking always succeeds because
+/// the contents cannot change.
+pub struct PackVfs<V>
+where
+    V: Vfs,
+{
+    inner: V,
+    path: String,
+    files: BTreeMap<String, PackEntry>,
+    dirs: BTreeSet<String>,
+}
+
+impl<V> PackVfs<V>
+where
+    V: Vfs,
+{
+    /// Open the pack file at the given path in the file system.
+    ///
+    /// The index of the pack file is read into memory.
+    pub fn open(inner: V, path: &str) -> Result<Self, Error> {
+        let mut vfs = Self {
+            inner,
+            path: path.to_string(),
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+        };
+
+        vfs.load_index()?;
+
+        Ok(vfs)
+    }
+
+    /// Return the number of files in the pack.
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Copy all files and directories to another file system.
+    ///
+    /// Existing files in the destination with the same path are overwritten.
+    pub fn unpack_to(
+        &self,
+        destination: &mut dyn Vfs,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        super::copy_dir_all(self, destination, "", sync_option)
+    }
+
+    /// Return the wrapped file system.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    fn load_index(&mut self) -> Result<(), Error> {
+        let header = match self.inner.read_range(&self.path, 0, PACK_HEADER_SIZE) {
+            Ok(header) => header,
+            Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(self.make_format_error("not a pack file"));
+            }
+            Err(error) => return Err(error),
+        };
+
+        if header[0..PACK_MAGIC_BYTES.len()] != PACK_MAGIC_BYTES {
+            return Err(self.make_format_error("not a pack file"));
+        }
+
+        let index_offset = u64::from_be_bytes(header[8..16].try_into().unwrap());
+        let index_length = u64::from_be_bytes(header[16..24].try_into().unwrap());
+        let index_crc = u32::from_be_bytes(header[24..28].try_into().unwrap());
+
+        if index_length > MAX_INDEX_SIZE {
+            return Err(self.make_format_error("pack index too large"));
+        }
+
+        let index = self
+            .inner
+            .read_range(&self.path, index_offset, index_length as usize)?;
+
+        if crc32c::crc32c(&index) != index_crc {
+            return Err(Error::BadChecksum {
+                path: self.path.clone(),
+            });
+        }
+
+        let mut offset = 0;
+
+        while offset < index.len() {
+            let (path, entry, next_offset) = parse_index_entry(&index, offset)
+                .ok_or_else(|| self.make_format_error("bad pack index entry"))?;
+
+            let mut parent = RelativePath::new(&path).parent();
+
+            while let Some(dir_path) = parent.filter(|dir_path| !dir_path.as_str().is_empty()) {
+                self.dirs.insert(dir_path.to_string());
+                parent = dir_path.parent();
+            }
+
+            self.files.insert(path, entry);
+            offset = next_offset;
+        }
+
+        Ok(())
+    }
+
+    fn make_format_error(&self, message: &'static str) -> Error {
+        Error::InvalidFileFormat {
+            path: self.path.clone(),
+            message,
+        }
+    }
+}
+
+impl<V> Vfs for PackVfs<V>
+where
+    V: Vfs,
+{
+    fn lock(&mut self, _path: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn unlock(&mut self, _path: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let entry = self.files.get(path).ok_or_else(make_not_found_error)?;
+        let data = self
+            .inner
+            .read_range(&self.path, entry.offset, entry.length as usize)?;
+
+        if crc32c::crc32c(&data) != entry.crc {
+            return Err(Error::BadChecksum {
+                path: path.to_string(),
+            });
+        }
+
+        Ok(data)
+    }
+
+    fn write(
+        &mut self,
+        _path: &str,
+        _data: &[u8],
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn sync_file(&mut self, _path: &str, _sync_option: VfsSyncOption) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn remove_file(&mut self, _path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        if !path.is_empty() && !self.dirs.contains(path) {
+            return Err(make_not_found_error());
+        }
+
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path)
+        };
+        let mut filenames = Vec::new();
+
+        let children = self
+            .dirs
+            .range(prefix.clone()..)
+            .chain(self.files.range(prefix.clone()..).map(|(key, _)| key));
+
+        for child in children {
+            if !child.starts_with(&prefix) {
+                continue;
+            }
+
+            let filename = &child[prefix.len()..];
+
+            if !filename.is_empty() && !filename.contains('/') {
+                filenames.push(filename.to_string());
+            }
+        }
+
+        Ok(filenames)
+    }
+
+    fn create_dir(&mut self, _path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn remove_dir(&mut self, _path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn rename_file(&mut self, _old_path: &str, _new_path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        if path.is_empty() || self.dirs.contains(path) {
+            Ok(true)
+        } else if self.files.contains_key(path) {
+            Ok(false)
+        } else {
+            Err(make_not_found_error())
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        Ok(path.is_empty() || self.dirs.contains(path) || self.files.contains_key(path))
+    }
+
+    fn append(
+        &mut self,
+        _path: &str,
+        _data: &[u8],
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn link_file(
+        &mut self,
+        _old_path: &str,
+        _new_path: &str,
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+}
+
+impl<V> Debug for PackVfs<V>
+where
+    V: Vfs,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PackVfs {{ path: {:?}, files: {} }}",
+            self.path,
+            self.files.len()
+        )
+    }
+}
+
+/// Writes files into a pack file that can be opened with [`PackVfs`].
+///
+/// The contents of the files are written as they are added and the index is
+/// written by [`Self::finish()`].
+pub struct PackWriter<W>
+where
+    W: Write + Seek,
+{
+    writer: W,
+    start_position: u64,
+    offset: u64,
+    index: Vec<u8>,
+    paths: BTreeSet<String>,
+}
+
+impl<W> PackWriter<W>
+where
+    W: Write + Seek,
+{
+    /// Start a pack file using the given writer.
+    ///
+    /// The writer should be positioned at the start of an empty file because
+    /// the offsets in the index are relative to the start of the file.
+    pub fn new(mut writer: W) -> Result<Self, Error> {
+        let start_position = writer.stream_position()?;
+        writer.write_all(&[0u8; PACK_HEADER_SIZE])?;
+
+        Ok(Self {
+            writer,
+            start_position,
+            offset: PACK_HEADER_SIZE as u64,
+            index: Vec::new(),
+            paths: BTreeSet::new(),
+        })
+    }
+
+    /// Add a file with the given path and contents.
+    ///
+    /// Directories are created implicitly from the path. Returns an error if
+    /// the path was already added.
+    pub fn add_file(&mut self, path: &str, data: &[u8]) -> Result<(), Error> {
+        if !self.paths.insert(path.to_string()) {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "path already exists",
+            )));
+        }
+
+        self.writer.write_all(data)?;
+
+        self.index
+            .extend_from_slice(&(path.len() as u32).to_be_bytes());
+        self.index.extend_from_slice(path.as_bytes());
+        self.index.extend_from_slice(&self.offset.to_be_bytes());
+        self.index
+            .extend_from_slice(&(data.len() as u64).to_be_bytes());
+        self.index
+            .extend_from_slice(&crc32c::crc32c(data).to_be_bytes());
+
+        self.offset += data.len() as u64;
+
+        Ok(())
+    }
+
+    /// Add all files of another file system.
+    pub fn add_vfs(&mut self, source: &dyn Vfs) -> Result<(), Error> {
+        self.add_dir_all(source, "")
+    }
+
+    /// Write the index and header and return the writer.
+    ///
+    /// The writer is not flushed.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let index_offset = self.offset;
+
+        self.writer.write_all(&self.index)?;
+
+        let mut header = Vec::with_capacity(PACK_HEADER_SIZE);
+        header.extend_from_slice(&PACK_MAGIC_BYTES);
+        header.extend_from_slice(&index_offset.to_be_bytes());
+        header.extend_from_slice(&(self.index.len() as u64).to_be_bytes());
+        header.extend_from_slice(&crc32c::crc32c(&self.index).to_be_bytes());
+
+        let end_position = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.start_position))?;
+        self.writer.write_all(&header)?;
+        self.writer.seek(SeekFrom::Start(end_position))?;
+
+        Ok(self.writer)
+    }
+
+    fn add_dir_all(&mut self, source: &dyn Vfs, dir_path: &str) -> Result<(), Error> {
+        let mut filenames = source.read_dir(dir_path)?;
+        filenames.sort_unstable();
+
+        for filename in filenames {
+            let path = RelativePath::new(dir_path).join(&filename);
+
+            if source.is_dir(path.as_str())? {
+                self.add_dir_all(source, path.as_str())?;
+            } else {
+                let data = source.read(path.as_str())?;
+                self.add_file(path.as_str(), &data)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_index_entry(index: &[u8], start: usize) -> Option<(String, PackEntry, usize)> {
+    let path_length = u32::from_be_bytes(index.get(start..start + 4)?.try_into().ok()?) as usize;
+    let path_start = start + 4;
+    let path_end = path_start.checked_add(path_length)?;
+    let path = std::str::from_utf8(index.get(path_start..path_end)?).ok()?;
+    let footer = index.get(path_end..path_end + INDEX_ENTRY_FOOTER_SIZE)?;
+
+    let entry = PackEntry {
+        offset: u64::from_be_bytes(footer[0..8].try_into().ok()?),
+        length: u64::from_be_bytes(footer[8..16].try_into().ok()?),
+        crc: u32::from_be_bytes(footer[16..20].try_into().ok()?),
+    };
+
+    Some((path.to_string(), entry, path_end + INDEX_ENTRY_FOOTER_SIZE))
+}
+
+fn make_not_found_error() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "file not found",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::vfs::MemoryVfs;
+
+    #[test]
+    fn test_pack_vfs() {
+        let mut writer = PackWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.add_file("a/b/file1", b"hello").unwrap();
+        writer.add_file("a/file2", b"world").unwrap();
+        writer.add_file("file3", b"").unwrap();
+        assert!(writer.add_file("file3", b"again").is_err());
+        let data = writer.finish().unwrap().into_inner();
+
+        let mut memory_vfs = MemoryVfs::new();
+        memory_vfs
+            .write("my.grebepack", &data, VfsSyncOption::None)
+            .unwrap();
+
+        let mut vfs = PackVfs::open(memory_vfs.clone(), "my.grebepack").unwrap();
+
+        assert_eq!(vfs.file_count(), 3);
+        assert_eq!(vfs.read("a/b/file1").unwrap(), b"hello");
+        assert_eq!(vfs.read("a/file2").unwrap(), b"world");
+        assert_eq!(vfs.read("file3").unwrap(), b"");
+        assert!(vfs.is_dir("a/b").unwrap());
+        assert!(!vfs.is_dir("a/file2").unwrap());
+        assert!(!vfs.exists("a/c").unwrap());
+
+        let mut filenames = vfs.read_dir("a").unwrap();
+        filenames.sort();
+        assert_eq!(filenames, vec!["b".to_string(), "file2".to_string()]);
+
+        assert!(matches!(
+            vfs.write("file4", b"", VfsSyncOption::None),
+            Err(Error::ReadOnly)
+        ));
+
+        let mut corrupt_data = data;
+        corrupt_data[PACK_HEADER_SIZE] ^= 0xff;
+        memory_vfs
+            .write("my.grebepack", &corrupt_data, VfsSyncOption::None)
+            .unwrap();
+
+        let vfs = PackVfs::open(memory_vfs, "my.grebepack").unwrap();
+        assert!(matches!(
+            vfs.read("a/b/file1"),
+            Err(Error::BadChecksum { .. })
+        ));
+    }
+}
@@ -0,0 +1,851 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    convert::TryInto,
+    fmt::Debug,
+};
+
+use relative_path::RelativePath;
+
+use super::{Vfs, VfsSyncOption};
+use crate::error::Error;
+
+const SEGMENT_MAGIC_BYTES: [u8; 8] = [0xFE, b'G', b'r', b'e', b'b', b'e', b'S', b'g'];
+const SEGMENT_FILENAME_PREFIX: &str = "grebedb_segment_";
+const SEGMENT_FILENAME_SUFFIX: &str = ".grebedb_seg";
+const DEFAULT_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+const RECORD_WRITE: u8 = 0x01;
+const RECORD_REMOVE_FILE: u8 = 0x02;
+const RECORD_RENAME_FILE: u8 = 0x03;
+const RECORD_CREATE_DIR: u8 = 0x04;
+const RECORD_REMOVE_DIR: u8 = 0x05;
+const RECORD_COMPACTION: u8 = 0x06;
+
+// tag (1) + path length (4)
+const RECORD_PATH_HEADER_SIZE: usize = 5;
+// data length (8)
+const RECORD_DATA_HEADER_SIZE: usize = 8;
+// crc32c (4)
+const RECORD_FOOTER_SIZE: usize = 4;
+
+type SegmentId = u64;
+
+#[derive(Debug, Clone, Copy)]
+struct SegmentEntry {
+    segment: SegmentId,
+    offset: u64,
+    length: u64,
+}
+
+/// File system that packs files into a small number of append-only
+/// segment files stored in another file system.
+///
+/// Each file operation is appended as a record to the current segment file.
+/// When the segment file exceeds the segment size (default 64 MB), a new
+/// segment file is started. An index of file locations is kept in memory and
+/// rebuilt by reading the segments one at a time when the file system is
+/// opened.
+///
+/// This reduces the number of files and renames on the underlying file system
+/// at the expense of space used by superseded records. Use [`Self::compact()`]
+/// to rewrite live files into new segments and delete the old ones.
+///
+/// Locking is passed through to the underlying file system.
+pub struct SegmentVfs<V>
+where
+    V: Vfs,
+{
+    inner: V,
+    segment_size: u64,
+    files: BTreeMap<String, SegmentEntry>,
+    dirs: BTreeSet<String>,
+    segments: BTreeSet<SegmentId>,
+    current_segment: SegmentId,
+    current_segment_size: u64,
+    unsynced_segments: HashSet<SegmentId>,
+}
+
+impl<V> SegmentVfs<V>
+where
+    V: Vfs,
+{
+    /// Open the segment files in the given file system.
+    ///
+    /// The segment files since the most recent compaction are read to
+    /// rebuild the index. Older segment files left by an interrupted
+    /// compaction are deleted.
+    pub fn open(inner: V) -> Result<Self, Error> {
+        let mut vfs = Self {
+            inner,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+            segments: BTreeSet::new(),
+            current_segment: 0,
+            current_segment_size: 0,
+            unsynced_segments: HashSet::new(),
+        };
+
+        vfs.load_segments()?;
+
+        Ok(vfs)
+    }
+
+    /// Set the size in bytes at which a new segment file is started.
+    ///
+    /// Default: 64 MB.
+    pub fn set_segment_size(&mut self, value: u64) {
+        self.segment_size = value;
+    }
+
+    /// Return the number of segment files.
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Return the wrapped file system.
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+
+    /// Rewrite all live files into new segment files and delete the old
+    /// segment files.
+    ///
+    /// The new segments are synchronized using the given option before the
+    /// old segments are deleted. If the process is interrupted before the
+    /// new segments are complete, the old segments continue to be used.
+    /// Otherwise, the remaining old segments are deleted when the file system
+    /// is opened again.
+    pub fn compact(&mut self, sync_option: VfsSyncOption) -> Result<(), Error> {
+        let old_segments: Vec<SegmentId> = self.segments.iter().cloned().collect();
+
+        self.start_new_segment()?;
+        let first_segment = self.current_segment;
+
+        let dirs: Vec<String> = self.dirs.iter().cloned().collect();
+
+        for path in dirs {
+            self.append_record(RECORD_CREATE_DIR, &path, &[])?;
+        }
+
+        let paths: Vec<String> = self.files.keys().cloned().collect();
+
+        for path in paths {
+            let data = self.read(&path)?;
+            let entry = self.append_record(RECORD_WRITE, &path, &data)?;
+            self.files.insert(path, entry);
+        }
+
+        self.append_record(RECORD_COMPACTION, "", &first_segment.to_be_bytes())?;
+        self.sync_all_segments(sync_option)?;
+
+        for segment in old_segments {
+            self.inner.remove_file(&make_segment_filename(segment))?;
+            self.segments.remove(&segment);
+            self.unsynced_segments.remove(&segment);
+        }
+
+        Ok(())
+    }
+
+    fn load_segments(&mut self) -> Result<(), Error> {
+        let mut segments = Vec::new();
+
+        for filename in self.inner.read_dir("")? {
+            if let Some(segment) = parse_segment_filename(&filename) {
+                segments.push(segment);
+            }
+        }
+
+        segments.sort_unstable();
+
+        let first_segment = self.find_first_segment(&segments)?.unwrap_or(0);
+        let mut torn = false;
+
+        for segment in &segments {
+            if *segment < first_segment {
+                // Left by a compaction interrupted before deleting them
+                self.inner.remove_file(&make_segment_filename(*segment))?;
+                continue;
+            }
+
+            // Only one segment is held in memory at a time
+            let data = self.inner.read(&make_segment_filename(*segment))?;
+            let mut records = RecordIter::new(&data);
+
+            for record in &mut records {
+                self.replay_record(*segment, &record)?;
+            }
+
+            self.segments.insert(*segment);
+            torn = records.torn;
+            self.current_segment_size = records.offset as u64;
+        }
+
+        if let Some(segment) = segments.last() {
+            self.current_segment = *segment;
+        }
+
+        if torn {
+            // Don't append after a partially written record
+            self.start_new_segment()?;
+        }
+
+        Ok(())
+    }
+
+    // Return the first segment of the most recent completed compaction by
+    // walking the record headers of the segments from the newest one
+    fn find_first_segment(&self, segments: &[SegmentId]) -> Result<Option<SegmentId>, Error> {
+        for segment in segments.iter().rev() {
+            if let Some(first_segment) = self.find_compaction_record(*segment)? {
+                return Ok(Some(first_segment));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Return the value of the last compaction record in the segment. Only
+    // the headers and the compaction records are read.
+    fn find_compaction_record(&self, segment: SegmentId) -> Result<Option<SegmentId>, Error> {
+        let filename = make_segment_filename(segment);
+        let mut offset = SEGMENT_MAGIC_BYTES.len() as u64;
+        let mut first_segment = None;
+
+        match self.read_segment_range(&filename, 0, SEGMENT_MAGIC_BYTES.len())? {
+            Some(magic) if magic == SEGMENT_MAGIC_BYTES => {}
+            _ => return Ok(None),
+        }
+
+        while let Some(header) =
+            self.read_segment_range(&filename, offset, RECORD_PATH_HEADER_SIZE)?
+        {
+            let tag = header[0];
+            let path_length = u32::from_be_bytes(header[1..].try_into().unwrap()) as u64;
+            let length_offset = offset + RECORD_PATH_HEADER_SIZE as u64 + path_length;
+
+            let data_length =
+                match self.read_segment_range(&filename, length_offset, RECORD_DATA_HEADER_SIZE)? {
+                    Some(bytes) => u64::from_be_bytes(bytes.try_into().unwrap()),
+                    None => break,
+                };
+            let record_length = match (length_offset + RECORD_DATA_HEADER_SIZE as u64)
+                .checked_add(data_length)
+                .and_then(|end| end.checked_add(RECORD_FOOTER_SIZE as u64))
+            {
+                Some(end) => end - offset,
+                None => break,
+            };
+
+            if tag == RECORD_COMPACTION {
+                // The record has an empty path and the segment ID as data,
+                // so it is small enough to read whole and check
+                let data = if path_length == 0 && data_length == 8 {
+                    self.read_segment_range(&filename, offset, record_length as usize)?
+                } else {
+                    None
+                };
+                let mut records = RecordIter {
+                    data: data.as_deref().unwrap_or_default(),
+                    offset: 0,
+                    torn: false,
+                };
+
+                match records.next() {
+                    Some(record) => first_segment = Some(parse_compaction_record(record.data)?),
+                    None => break,
+                }
+            }
+
+            offset += record_length;
+        }
+
+        Ok(first_segment)
+    }
+
+    // Returns None if the range is past the end of the segment
+    fn read_segment_range(
+        &self,
+        filename: &str,
+        offset: u64,
+        length: usize,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        match self.inner.read_range(filename, offset, length) {
+            Ok(data) => Ok(Some(data)),
+            Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn replay_record(&mut self, segment: SegmentId, record: &Record) -> Result<(), Error> {
+        let path = record.path.to_string();
+
+        match record.tag {
+            RECORD_WRITE => {
+                let entry = SegmentEntry {
+                    segment,
+                    offset: record.data_offset as u64,
+                    length: record.data.len() as u64,
+                };
+                self.files.insert(path, entry);
+            }
+            RECORD_REMOVE_FILE => {
+                self.files.remove(&path);
+            }
+            RECORD_RENAME_FILE => {
+                let new_path =
+                    std::str::from_utf8(record.data).map_err(|_| Error::InvalidFileFormat {
+                        path: make_segment_filename(segment),
+                        message: "bad segment record path",
+                    })?;
+
+                if let Some(entry) = self.files.remove(&path) {
+                    self.files.insert(new_path.to_string(), entry);
+                }
+            }
+            RECORD_CREATE_DIR => {
+                self.dirs.insert(path);
+            }
+            RECORD_REMOVE_DIR => {
+                self.dirs.remove(&path);
+            }
+            RECORD_COMPACTION => {}
+            _ => {
+                return Err(Error::InvalidFileFormat {
+                    path: make_segment_filename(segment),
+                    message: "unknown segment record type",
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn start_new_segment(&mut self) -> Result<(), Error> {
+        if !self.segments.is_empty() {
+            self.current_segment += 1;
+        }
+
+        let filename = make_segment_filename(self.current_segment);
+
+        self.inner
+            .write(&filename, &SEGMENT_MAGIC_BYTES, VfsSyncOption::None)?;
+        self.segments.insert(self.current_segment);
+        self.unsynced_segments.insert(self.current_segment);
+        self.current_segment_size = SEGMENT_MAGIC_BYTES.len() as u64;
+
+        Ok(())
+    }
+
+    fn append_record(&mut self, tag: u8, path: &str, data: &[u8]) -> Result<SegmentEntry, Error> {
+        if self.segments.is_empty() || self.current_segment_size >= self.segment_size {
+            self.start_new_segment()?;
+        }
+
+        let record = encode_record(tag, path, data);
+        let data_offset = self.current_segment_size
+            + (RECORD_PATH_HEADER_SIZE + path.len() + RECORD_DATA_HEADER_SIZE) as u64;
+
+        self.inner.append(
+            &make_segment_filename(self.current_segment),
+            &record,
+            VfsSyncOption::None,
+        )?;
+
+        self.current_segment_size += record.len() as u64;
+        self.unsynced_segments.insert(self.current_segment);
+
+        Ok(SegmentEntry {
+            segment: self.current_segment,
+            offset: data_offset,
+            length: data.len() as u64,
+        })
+    }
+
+    fn sync_segment(
+        &mut self,
+        segment: SegmentId,
+        sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        if sync_option != VfsSyncOption::None && self.unsynced_segments.remove(&segment) {
+            self.inner
+                .sync_file(&make_segment_filename(segment), sync_option)?;
+        }
+
+        Ok(())
+    }
+
+    fn sync_all_segments(&mut self, sync_option: VfsSyncOption) -> Result<(), Error> {
+        let segments: Vec<SegmentId> = self.unsynced_segments.iter().cloned().collect();
+
+        for segment in segments {
+            self.sync_segment(segment, sync_option)?;
+        }
+
+        Ok(())
+    }
+
+    fn has_parent_dir(&self, path: &str) -> bool {
+        match RelativePath::new(path).parent() {
+            Some(parent) => parent.as_str().is_empty() || self.dirs.contains(parent.as_str()),
+            None => true,
+        }
+    }
+}
+
+impl<V> Vfs for SegmentVfs<V>
+where
+    V: Vfs,
+{
+    fn lock(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.lock(path)
+    }
+
+    fn unlock(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.unlock(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self.files.get(path) {
+            Some(entry) => self.inner.read_range(
+                &make_segment_filename(entry.segment),
+                entry.offset,
+                entry.length as usize,
+            ),
+            None => Err(make_not_found_error()),
+        }
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        if !self.has_parent_dir(path) {
+            return Err(make_not_found_error());
+        }
+
+        let entry = self.append_record(RECORD_WRITE, path, data)?;
+        self.files.insert(path.to_string(), entry);
+        self.sync_segment(entry.segment, sync_option)?;
+
+        Ok(())
+    }
+
+    fn sync_file(&mut self, path: &str, sync_option: VfsSyncOption) -> Result<(), Error> {
+        match self.files.get(path) {
+            Some(entry) => {
+                let segment = entry.segment;
+                self.sync_segment(segment, sync_option)
+            }
+            None => Err(make_not_found_error()),
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), Error> {
+        if !self.files.contains_key(path) {
+            return Err(make_not_found_error());
+        }
+
+        self.append_record(RECORD_REMOVE_FILE, path, &[])?;
+        self.files.remove(path);
+
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        if !path.is_empty() && !self.dirs.contains(path) {
+            return Err(make_not_found_error());
+        }
+
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path)
+        };
+        let mut filenames = Vec::new();
+
+        let children = self
+            .dirs
+            .range(prefix.clone()..)
+            .chain(self.files.range(prefix.clone()..).map(|(key, _)| key));
+
+        for child in children {
+            if !child.starts_with(&prefix) {
+                continue;
+            }
+
+            let filename = &child[prefix.len()..];
+
+            if !filename.is_empty() && !filename.contains('/') {
+                filenames.push(filename.to_string());
+            }
+        }
+
+        Ok(filenames)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), Error> {
+        if self.exists(path)? {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "path already exists",
+            )));
+        }
+        if !self.has_parent_dir(path) {
+            return Err(make_not_found_error());
+        }
+
+        self.append_record(RECORD_CREATE_DIR, path, &[])?;
+        self.dirs.insert(path.to_string());
+
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
+        if !self.read_dir(path)?.is_empty() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "directory not empty",
+            )));
+        }
+
+        self.append_record(RECORD_REMOVE_DIR, path, &[])?;
+        self.dirs.remove(path);
+
+        Ok(())
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        if !self.files.contains_key(old_path) || !self.has_parent_dir(new_path) {
+            return Err(make_not_found_error());
+        }
+
+        self.append_record(RECORD_RENAME_FILE, old_path, new_path.as_bytes())?;
+
+        let entry = self.files.remove(old_path).unwrap();
+        self.files.insert(new_path.to_string(), entry);
+
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        if path.is_empty() || self.dirs.contains(path) {
+            Ok(true)
+        } else if self.files.contains_key(path) {
+            Ok(false)
+        } else {
+            Err(make_not_found_error())
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        Ok(path.is_empty() || self.dirs.contains(path) || self.files.contains_key(path))
+    }
+}
+
+impl<V> Debug for SegmentVfs<V>
+where
+    V: Vfs,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SegmentVfs {{ segments: {}, files: {} }}",
+            self.segments.len(),
+            self.files.len()
+        )
+    }
+}
+
+struct Record<'a> {
+    tag: u8,
+    path: &'a str,
+    data: &'a [u8],
+    data_offset: usize,
+}
+
+// Iterates records in a segment, stopping at the end or at a partially
+// written (torn) record.
+struct RecordIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    torn: bool,
+}
+
+impl<'a> RecordIter<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let valid_magic = data.get(0..SEGMENT_MAGIC_BYTES.len()) == Some(&SEGMENT_MAGIC_BYTES[..]);
+
+        Self {
+            data: if valid_magic { data } else { &[] },
+            offset: SEGMENT_MAGIC_BYTES.len(),
+            torn: !valid_magic,
+        }
+    }
+
+    fn parse_record(&self) -> Option<(Record<'a>, usize)> {
+        let data = self.data;
+        let start = self.offset;
+
+        let tag = *data.get(start)?;
+        let path_length =
+            u32::from_be_bytes(data.get(start + 1..start + 5)?.try_into().ok()?) as usize;
+        let path_start = start + RECORD_PATH_HEADER_SIZE;
+        let path = data.get(path_start..path_start.checked_add(path_length)?)?;
+
+        let length_start = path_start + path_length;
+        let data_length = u64::from_be_bytes(
+            data.get(length_start..length_start + RECORD_DATA_HEADER_SIZE)?
+                .try_into()
+                .ok()?,
+        ) as usize;
+        let data_start = length_start + RECORD_DATA_HEADER_SIZE;
+        let data_end = data_start.checked_add(data_length)?;
+        let record_data = data.get(data_start..data_end)?;
+
+        let crc = u32::from_be_bytes(
+            data.get(data_end..data_end + RECORD_FOOTER_SIZE)?
+                .try_into()
+                .ok()?,
+        );
+
+        if crc != crc32c::crc32c(&data[start..data_end]) {
+            return None;
+        }
+
+        let path = std::str::from_utf8(path).ok()?;
+
+        Some((
+            Record {
+                tag,
+                path,
+                data: record_data,
+                data_offset: data_start,
+            },
+            data_end + RECORD_FOOTER_SIZE,
+        ))
+    }
+}
+
+impl<'a> Iterator for RecordIter<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.torn || self.offset >= self.data.len() {
+            return None;
+        }
+
+        match self.parse_record() {
+            Some((record, next_offset)) => {
+                self.offset = next_offset;
+                Some(record)
+            }
+            None => {
+                self.torn = true;
+                None
+            }
+        }
+    }
+}
+
+fn encode_record(tag: u8, path: &str, data: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(
+        RECORD_PATH_HEADER_SIZE
+            + path.len()
+            + RECORD_DATA_HEADER_SIZE
+            + data.len()
+            + RECORD_FOOTER_SIZE,
+    );
+
+    record.push(tag);
+    record.extend_from_slice(&(path.len() as u32).to_be_bytes());
+    record.extend_from_slice(path.as_bytes());
+    record.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    record.extend_from_slice(data);
+
+    let crc = crc32c::crc32c(&record);
+    record.extend_from_slice(&crc.to_be_bytes());
+
+    record
+}
+
+fn parse_compaction_record(data: &[u8]) -> Result<SegmentId, Error> {
+    match data.try_into() {
+        Ok(bytes) => Ok(SegmentId::from_be_bytes(bytes)),
+        Err(_) => Err(Error::InvalidFileFormat {
+            path: "(segment)".to_string(),
+            message: "bad compaction record",
+        }),
+    }
+}
+
+fn make_segment_filename(segment: SegmentId) -> String {
+    format!(
+        "{}{:016x}{}",
+        SEGMENT_FILENAME_PREFIX, segment, SEGMENT_FILENAME_SUFFIX
+    )
+}
+
+fn parse_segment_filename(filename: &str) -> Option<SegmentId> {
+    let hex = filename
+        .strip_prefix(SEGMENT_FILENAME_PREFIX)?
+        .strip_suffix(SEGMENT_FILENAME_SUFFIX)?;
+
+    SegmentId::from_str_radix(hex, 16).ok()
+}
+
+fn make_not_found_error() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "file not found",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryVfs;
+
+    #[test]
+    fn test_segment_filename() {
+        assert_eq!(
+            make_segment_filename(0xab),
+            "grebedb_segment_00000000000000ab.grebedb_seg"
+        );
+        assert_eq!(
+            parse_segment_filename("grebedb_segment_00000000000000ab.grebedb_seg"),
+            Some(0xab)
+        );
+        assert_eq!(parse_segment_filename("grebedb_meta.grebedb"), None);
+    }
+
+    #[test]
+    fn test_segment_vfs_reopen() {
+        let memory_vfs = MemoryVfs::new();
+        let mut vfs = SegmentVfs::open(memory_vfs.clone()).unwrap();
+        vfs.set_segment_size(64);
+
+        vfs.create_dir_all("a/b").unwrap();
+        vfs.write("a/b/file1", b"hello", VfsSyncOption::Data)
+            .unwrap();
+        vfs.write("a/b/file2", b"world", VfsSyncOption::None)
+            .unwrap();
+        vfs.write("a/b/file1", b"hello world", VfsSyncOption::None)
+            .unwrap();
+        vfs.rename_file("a/b/file2", "a/file3").unwrap();
+        vfs.write("file4", b"removed", VfsSyncOption::None).unwrap();
+        vfs.remove_file("file4").unwrap();
+
+        assert!(vfs.segment_count() > 1);
+
+        let check = |vfs: &SegmentVfs<MemoryVfs>| {
+            assert_eq!(vfs.read("a/b/file1").unwrap(), b"hello world");
+            assert_eq!(vfs.read("a/file3").unwrap(), b"world");
+            assert!(!vfs.exists("a/b/file2").unwrap());
+            assert!(!vfs.exists("file4").unwrap());
+            assert!(vfs.is_dir("a/b").unwrap());
+
+            let mut filenames = vfs.read_dir("a").unwrap();
+            filenames.sort();
+            assert_eq!(filenames, vec!["b".to_string(), "file3".to_string()]);
+        };
+
+        check(&vfs);
+
+        let vfs = SegmentVfs::open(memory_vfs).unwrap();
+        check(&vfs);
+    }
+
+    #[test]
+    fn test_segment_vfs_compact() {
+        let memory_vfs = MemoryVfs::new();
+        let mut vfs = SegmentVfs::open(memory_vfs.clone()).unwrap();
+        vfs.set_segment_size(64);
+
+        for num in 0..20 {
+            vfs.write(
+                "my_file",
+                format!("hello {}", num).as_bytes(),
+                VfsSyncOption::None,
+            )
+            .unwrap();
+        }
+        vfs.write("removed", b"data", VfsSyncOption::None).unwrap();
+        vfs.remove_file("removed").unwrap();
+
+        let count = vfs.segment_count();
+        vfs.compact(VfsSyncOption::Data).unwrap();
+        assert!(vfs.segment_count() < count);
+        assert_eq!(vfs.read("my_file").unwrap(), b"hello 19");
+
+        let mut vfs = SegmentVfs::open(memory_vfs).unwrap();
+        assert_eq!(vfs.read("my_file").unwrap(), b"hello 19");
+        assert!(!vfs.exists("removed").unwrap());
+
+        vfs.write("my_file", b"after", VfsSyncOption::None).unwrap();
+        assert_eq!(vfs.read("my_file").unwrap(), b"after");
+    }
+
+    #[test]
+    fn test_segment_vfs_interrupted_compaction() {
+        let mut memory_vfs = MemoryVfs::new();
+        let mut vfs = SegmentVfs::open(memory_vfs.clone()).unwrap();
+        vfs.set_segment_size(64);
+
+        for num in 0..20 {
+            vfs.write(
+                "my_file",
+                format!("hello {}", num).as_bytes(),
+                VfsSyncOption::None,
+            )
+            .unwrap();
+        }
+
+        let old_segments: Vec<SegmentId> = vfs.segments.iter().cloned().collect();
+        vfs.compact(VfsSyncOption::Data).unwrap();
+        drop(vfs);
+
+        // Old segments that weren't deleted before the process stopped
+        for segment in &old_segments {
+            let mut data = SEGMENT_MAGIC_BYTES.to_vec();
+            data.extend_from_slice(&encode_record(RECORD_WRITE, "stale_file", b"stale"));
+            memory_vfs
+                .write(&make_segment_filename(*segment), &data, VfsSyncOption::None)
+                .unwrap();
+        }
+
+        let vfs = SegmentVfs::open(memory_vfs.clone()).unwrap();
+        assert_eq!(vfs.read("my_file").unwrap(), b"hello 19");
+        assert!(!vfs.exists("stale_file").unwrap());
+
+        for segment in &old_segments {
+            assert!(!memory_vfs.exists(&make_segment_filename(*segment)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_segment_vfs_torn_record() {
+        let mut memory_vfs = MemoryVfs::new();
+        let mut vfs = SegmentVfs::open(memory_vfs.clone()).unwrap();
+
+        vfs.write("file1", b"hello", VfsSyncOption::None).unwrap();
+        drop(vfs);
+
+        let partial_record = encode_record(RECORD_WRITE, "file2", b"world");
+        memory_vfs
+            .append(
+                &make_segment_filename(0),
+                &partial_record[..partial_record.len() - 2],
+                VfsSyncOption::None,
+            )
+            .unwrap();
+
+        let mut vfs = SegmentVfs::open(memory_vfs.clone()).unwrap();
+        assert_eq!(vfs.read("file1").unwrap(), b"hello");
+        assert!(!vfs.exists("file2").unwrap());
+
+        vfs.write("file3", b"new", VfsSyncOption::None).unwrap();
+        drop(vfs);
+
+        let vfs = SegmentVfs::open(memory_vfs).unwrap();
+        assert_eq!(vfs.read("file3").unwrap(), b"new");
+    }
+}
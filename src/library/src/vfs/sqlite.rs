@@ -0,0 +1,471 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension};
+
+use super::{normalize_path, Vfs, VfsSyncOption};
+use crate::error::Error;
+
+const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS grebedb_files (
+    path TEXT PRIMARY KEY NOT NULL,
+    data BLOB
+)";
+
+/// File system that stores files as rows in a single SQLite database file.
+///
+/// Each path is a row in the `grebedb_files` table. Files have their
+/// contents in the `data` column as a blob while directories have a null
+/// value. This is useful for keeping a database in a single file on
+/// platforms where SQLite is already used as a container format.
+///
+/// Every write is committed in its own SQLite transaction, so durability
+/// follows the settings of the SQLite connection and [`VfsSyncOption`] is
+/// not used. Locking a path takes an exclusive lock on the whole SQLite file
+/// which is held until all paths are unlocked.
+pub struct SqliteVfs {
+    connection: Mutex<Connection>,
+    path: Option<PathBuf>,
+    locks: BTreeSet<String>,
+}
+
+impl SqliteVfs {
+    /// Open or create the SQLite database file at the given path.
+    pub fn open<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let connection = Connection::open(path.as_ref()).map_err(map_sqlite_error)?;
+        let mut vfs = Self::from_connection(connection)?;
+        vfs.path = Some(path.as_ref().to_path_buf());
+
+        Ok(vfs)
+    }
+
+    /// Use an existing SQLite connection.
+    ///
+    /// The table is created if it doesn't exist.
+    pub fn from_connection(connection: Connection) -> Result<Self, Error> {
+        // Another process holding the lock should fail instead of waiting
+        connection
+            .busy_timeout(Duration::ZERO)
+            .map_err(map_sqlite_error)?;
+        connection
+            .execute(CREATE_TABLE_SQL, [])
+            .map_err(map_sqlite_error)?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            path: None,
+            locks: BTreeSet::new(),
+        })
+    }
+
+    /// Return the SQLite connection.
+    pub fn into_inner(self) -> Connection {
+        self.connection
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Returns None if the path doesn't exist, Some(None) for a directory,
+    // and Some(Some(length)) for a file
+    fn entry(&self, path: &str) -> Result<Option<Option<u64>>, Error> {
+        if path.is_empty() {
+            return Ok(Some(None));
+        }
+
+        self.connection()
+            .query_row(
+                "SELECT length(data) FROM grebedb_files WHERE path = ?1",
+                params![path],
+                |row| row.get::<_, Option<u64>>(0),
+            )
+            .optional()
+            .map_err(map_sqlite_error)
+    }
+
+    fn has_parent_dir(&self, path: &str) -> Result<bool, Error> {
+        let parent = match path.rsplit_once('/') {
+            Some((parent, _)) => parent,
+            None => "",
+        };
+
+        Ok(matches!(self.entry(parent)?, Some(None)))
+    }
+
+    fn set_locking_mode(&self, mode: &str) -> Result<(), Error> {
+        self.connection()
+            .query_row(
+                &format!("PRAGMA locking_mode = {}", mode),
+                [],
+                |_row| Ok(()),
+            )
+            .map_err(map_sqlite_error)
+    }
+}
+
+impl Vfs for SqliteVfs {
+    fn lock(&mut self, path: &str) -> Result<(), Error> {
+        let path = normalize_path(path)?;
+
+        if self.locks.contains(&path) {
+            return Err(Error::Locked);
+        }
+
+        if self.locks.is_empty() {
+            // The exclusive lock is kept after the transaction ends in this mode
+            self.set_locking_mode("EXCLUSIVE")?;
+
+            let result = self
+                .connection()
+                .execute_batch("BEGIN EXCLUSIVE; COMMIT;")
+                .map_err(map_sqlite_error);
+
+            if let Err(error) = result {
+                self.set_locking_mode("NORMAL")?;
+                return Err(error);
+            }
+        }
+
+        self.locks.insert(path);
+
+        Ok(())
+    }
+
+    fn unlock(&mut self, path: &str) -> Result<(), Error> {
+        let path = normalize_path(path)?;
+
+        if !self.locks.remove(&path) {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "file not locked",
+            )));
+        }
+
+        if self.locks.is_empty() {
+            self.set_locking_mode("NORMAL")?;
+
+            // The lock is released on the next access to the file
+            self.connection()
+                .execute_batch("SELECT 1 FROM grebedb_files LIMIT 1")
+                .map_err(map_sqlite_error)?;
+        }
+
+        Ok(())
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let path = normalize_path(path)?;
+
+        let data = self
+            .connection()
+            .query_row(
+                "SELECT data FROM grebedb_files WHERE path = ?1",
+                params![path],
+                |row| row.get::<_, Option<Vec<u8>>>(0),
+            )
+            .optional()
+            .map_err(map_sqlite_error)?;
+
+        match data {
+            Some(Some(data)) => Ok(data),
+            Some(None) => Err(make_is_dir_error()),
+            None => Err(make_not_found_error()),
+        }
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], _sync_option: VfsSyncOption) -> Result<(), Error> {
+        let path = normalize_path(path)?;
+
+        match self.entry(&path)? {
+            Some(None) => return Err(make_is_dir_error()),
+            Some(Some(_)) => {}
+            None => {
+                if !self.has_parent_dir(&path)? {
+                    return Err(make_not_found_error());
+                }
+            }
+        }
+
+        self.connection()
+            .execute(
+                "INSERT OR REPLACE INTO grebedb_files (path, data) VALUES (?1, ?2)",
+                params![path, data],
+            )
+            .map_err(map_sqlite_error)?;
+
+        Ok(())
+    }
+
+    fn sync_file(&mut self, path: &str, _sync_option: VfsSyncOption) -> Result<(), Error> {
+        let path = normalize_path(path)?;
+
+        match self.entry(&path)? {
+            Some(_) => Ok(()),
+            None => Err(make_not_found_error()),
+        }
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), Error> {
+        let path = normalize_path(path)?;
+
+        let count = self
+            .connection()
+            .execute(
+                "DELETE FROM grebedb_files WHERE path = ?1 AND data IS NOT NULL",
+                params![path],
+            )
+            .map_err(map_sqlite_error)?;
+
+        if count == 0 {
+            return Err(make_not_found_error());
+        }
+
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        let path = normalize_path(path)?;
+
+        match self.entry(&path)? {
+            Some(None) => {}
+            Some(Some(_)) => {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "not a directory",
+                )))
+            }
+            None => return Err(make_not_found_error()),
+        }
+
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path)
+        };
+        // Paths only contain characters that sort before DEL
+        let end = format!("{}\u{7f}", prefix);
+
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare("SELECT path FROM grebedb_files WHERE path >= ?1 AND path < ?2")
+            .map_err(map_sqlite_error)?;
+        let rows = statement
+            .query_map(params![prefix, end], |row| row.get::<_, String>(0))
+            .map_err(map_sqlite_error)?;
+        let mut filenames = Vec::new();
+
+        for child in rows {
+            let child = child.map_err(map_sqlite_error)?;
+            let filename = &child[prefix.len()..];
+
+            if !filename.is_empty() && !filename.contains('/') {
+                filenames.push(filename.to_string());
+            }
+        }
+
+        Ok(filenames)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), Error> {
+        let path = normalize_path(path)?;
+
+        if self.entry(&path)?.is_some() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "path already exists",
+            )));
+        }
+        if !self.has_parent_dir(&path)? {
+            return Err(make_not_found_error());
+        }
+
+        self.connection()
+            .execute(
+                "INSERT INTO grebedb_files (path, data) VALUES (?1, NULL)",
+                params![path],
+            )
+            .map_err(map_sqlite_error)?;
+
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
+        if !self.read_dir(path)?.is_empty() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "directory not empty",
+            )));
+        }
+
+        let path = normalize_path(path)?;
+
+        if path.is_empty() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "cannot remove root directory",
+            )));
+        }
+
+        self.connection()
+            .execute("DELETE FROM grebedb_files WHERE path = ?1", params![path])
+            .map_err(map_sqlite_error)?;
+
+        Ok(())
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        let old_path = normalize_path(old_path)?;
+        let new_path = normalize_path(new_path)?;
+
+        if !matches!(self.entry(&old_path)?, Some(Some(_))) || !self.has_parent_dir(&new_path)? {
+            return Err(make_not_found_error());
+        }
+        if matches!(self.entry(&new_path)?, Some(None)) {
+            return Err(make_is_dir_error());
+        }
+
+        let connection = self
+            .connection
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let transaction = connection.transaction().map_err(map_sqlite_error)?;
+
+        transaction
+            .execute(
+                "DELETE FROM grebedb_files WHERE path = ?1",
+                params![new_path],
+            )
+            .map_err(map_sqlite_error)?;
+        transaction
+            .execute(
+                "UPDATE grebedb_files SET path = ?2 WHERE path = ?1",
+                params![old_path, new_path],
+            )
+            .map_err(map_sqlite_error)?;
+        transaction.commit().map_err(map_sqlite_error)?;
+
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        let path = normalize_path(path)?;
+
+        match self.entry(&path)? {
+            Some(length) => Ok(length.is_none()),
+            None => Err(make_not_found_error()),
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        let path = normalize_path(path)?;
+
+        Ok(self.entry(&path)?.is_some())
+    }
+
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        let path = normalize_path(path)?;
+
+        let file_length = match self.entry(&path)? {
+            Some(Some(file_length)) => file_length,
+            Some(None) => return Err(make_is_dir_error()),
+            None => return Err(make_not_found_error()),
+        };
+
+        if offset.saturating_add(length as u64) > file_length {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "range past end of file",
+            )));
+        }
+
+        // Blob positions in SQL start at 1
+        self.connection()
+            .query_row(
+                "SELECT substr(data, ?2, ?3) FROM grebedb_files WHERE path = ?1",
+                params![path, offset + 1, length as u64],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .map_err(map_sqlite_error)
+    }
+}
+
+impl Debug for SqliteVfs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SqliteVfs {{ path: {:?} }}", self.path)
+    }
+}
+
+fn map_sqlite_error(error: rusqlite::Error) -> Error {
+    match error.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => Error::Locked,
+        _ => Error::Other(Box::new(error)),
+    }
+}
+
+fn make_not_found_error() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "file not found",
+    ))
+}
+
+fn make_is_dir_error() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "path is a directory",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqlite_vfs() {
+        let mut vfs = SqliteVfs::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+
+        vfs.create_dir_all("a/b").unwrap();
+        vfs.write("a/b/file1", b"hello", VfsSyncOption::None)
+            .unwrap();
+        vfs.write("a/file2", b"world", VfsSyncOption::None).unwrap();
+        assert!(vfs.write("c/file3", b"", VfsSyncOption::None).is_err());
+
+        assert_eq!(vfs.read("a/b/file1").unwrap(), b"hello");
+        assert_eq!(vfs.read_range("a/file2", 1, 3).unwrap(), b"orl");
+        assert!(vfs.read_range("a/file2", 3, 3).is_err());
+        assert!(vfs.is_dir("a/b").unwrap());
+        assert!(!vfs.is_dir("a/file2").unwrap());
+        assert!(!vfs.exists("a/c").unwrap());
+
+        let mut filenames = vfs.read_dir("a").unwrap();
+        filenames.sort();
+        assert_eq!(filenames, vec!["b".to_string(), "file2".to_string()]);
+        assert_eq!(vfs.read_dir("").unwrap(), vec!["a".to_string()]);
+
+        vfs.rename_file("a/file2", "a/b/file1").unwrap();
+        assert_eq!(vfs.read("a/b/file1").unwrap(), b"world");
+        assert!(!vfs.exists("a/file2").unwrap());
+
+        assert!(vfs.remove_dir("a/b").is_err());
+        vfs.remove_file("a/b/file1").unwrap();
+        vfs.remove_dir("a/b").unwrap();
+        assert!(!vfs.exists("a/b").unwrap());
+
+        assert!(matches!(
+            vfs.read("../file"),
+            Err(Error::InvalidPath { .. })
+        ));
+    }
+}
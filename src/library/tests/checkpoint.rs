@@ -0,0 +1,291 @@
+mod common;
+
+use std::ops::Range;
+
+use grebedb::{
+    vfs::{MemoryVfs, OsVfs, Vfs, VfsSyncOption},
+    CompressionLevel, Database, Error, OpenMode, Options,
+};
+
+fn fill_database(db: &mut Database, range: Range<u32>) -> Result<(), Error> {
+    for num in range {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_checkpoint_memory() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    fill_database(&mut db, 0..500)?;
+    db.checkpoint("checkpoint")?;
+
+    assert!(db.checkpoint("checkpoint").is_err());
+
+    fill_database(&mut db, 500..1000)?;
+    db.flush()?;
+
+    let mut checkpoint_vfs = MemoryVfs::new();
+
+    for path in common::list_files(&vfs, "checkpoint") {
+        let new_path = path.strip_prefix("checkpoint/").unwrap();
+
+        if let Some((dir_path, _)) = new_path.rsplit_once('/') {
+            checkpoint_vfs.create_dir_all(dir_path)?;
+        }
+
+        checkpoint_vfs.write(new_path, &vfs.read(&path)?, VfsSyncOption::None)?;
+    }
+
+    let mut checkpoint_db = Database::open(Box::new(checkpoint_vfs), options)?;
+
+    assert_eq!(checkpoint_db.metadata().key_value_count(), 500);
+    assert!(checkpoint_db.contains_key("key:0000")?);
+    assert!(!checkpoint_db.contains_key(format!("key:{:04x}", 500))?);
+    checkpoint_db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_checkpoint_os() -> anyhow::Result<()> {
+    let temp_dir = common::make_tempdir();
+    let options = Options {
+        keys_per_node: 16,
+        file_locking: false,
+        ..Default::default()
+    };
+    let mut db = Database::open_path(temp_dir.path(), options.clone())?;
+
+    fill_database(&mut db, 0..500)?;
+    db.checkpoint("checkpoint")?;
+
+    // Files replaced by later writes don't affect the checkpoint
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "new value")?;
+    }
+    fill_database(&mut db, 500..1000)?;
+    db.flush()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let vfs = OsVfs::new(temp_dir.path());
+        let linked = common::list_files(&vfs, "checkpoint")
+            .iter()
+            .filter(|path| path.ends_with("_0.grebedb"))
+            .any(|path| {
+                std::fs::metadata(temp_dir.path().join(path))
+                    .map(|metadata| metadata.nlink() > 1)
+                    .unwrap_or(false)
+            });
+
+        assert!(linked);
+    }
+
+    let mut checkpoint_db = Database::open_path(temp_dir.path().join("checkpoint"), options)?;
+
+    assert_eq!(checkpoint_db.metadata().key_value_count(), 500);
+    assert_eq!(
+        checkpoint_db.get("key:0000")?,
+        Some(b"hello world".to_vec())
+    );
+    checkpoint_db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_clone_to() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options.clone())?;
+
+    fill_database(&mut db, 0..1000)?;
+
+    for num in (0..1000).step_by(3) {
+        db.remove(format!("key:{:04x}", num))?;
+    }
+
+    let clone_vfs = MemoryVfs::new();
+    let clone_options = Options {
+        compression_level: CompressionLevel::None,
+        ..options.clone()
+    };
+    db.clone_to(clone_vfs.clone(), clone_options.clone())?;
+
+    assert!(db
+        .clone_to(clone_vfs.clone(), clone_options.clone())
+        .is_err());
+
+    fill_database(&mut db, 1000..1100)?;
+    db.flush()?;
+
+    let mut clone_db = Database::open(Box::new(clone_vfs), clone_options)?;
+
+    assert_eq!(clone_db.metadata().key_value_count(), 666);
+    assert!(!clone_db.contains_key("key:0000")?);
+    assert_eq!(clone_db.get("key:0001")?, Some(b"hello world".to_vec()));
+    assert!(!clone_db.contains_key(format!("key:{:04x}", 1000))?);
+    clone_db.verify(|_, _| {})?;
+
+    // The copy is writable and reuses the freed page IDs
+    fill_database(&mut clone_db, 2000..2500)?;
+    clone_db.flush()?;
+    clone_db.verify(|_, _| {})?;
+
+    assert_eq!(clone_db.metadata().key_value_count(), 1166);
+    assert_eq!(db.metadata().key_value_count(), 766);
+
+    Ok(())
+}
+
+#[test]
+fn test_salvage_to() -> anyhow::Result<()> {
+    let mut vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 8,
+        compression_level: CompressionLevel::None,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    fill_database(&mut db, 0..500)?;
+    db.flush()?;
+    drop(db);
+
+    for path in common::list_files(&vfs, "") {
+        if path.contains("grebedb_0000000000000003_") {
+            vfs.remove_file(&path)?;
+        } else if path.contains("grebedb_0000000000000007_") {
+            vfs.write(&path, b"garbage", VfsSyncOption::None)?;
+        }
+    }
+
+    let mut db = Database::open(Box::new(vfs), options.clone())?;
+    assert!(db.verify(|_, _| {}).is_err());
+
+    let salvage_vfs = MemoryVfs::new();
+    let report = db.salvage_to(salvage_vfs.clone(), options.clone())?;
+
+    assert!(!report.lost_pages().is_empty());
+
+    for lost_page in report.lost_pages() {
+        assert!([3, 7].contains(&lost_page.page_id()));
+        assert!(!lost_page.message().is_empty());
+    }
+
+    assert!(db.salvage_to(salvage_vfs.clone(), options.clone()).is_err());
+
+    let mut salvage_db = Database::open(Box::new(salvage_vfs), options)?;
+    salvage_db.verify(|_, _| {})?;
+
+    assert_eq!(salvage_db.metadata().key_value_count(), report.pair_count());
+    assert!(report.pair_count() < 500);
+
+    for num in 0..500 {
+        let key = format!("key:{:04x}", num);
+        let lost = report.lost_pages().iter().any(|lost_page| {
+            lost_page
+                .start_key()
+                .is_none_or(|start_key| key.as_bytes() >= start_key)
+                && lost_page
+                    .end_key()
+                    .is_none_or(|end_key| key.as_bytes() < end_key)
+        });
+
+        if !lost {
+            assert_eq!(salvage_db.get(&key)?, Some(b"hello world".to_vec()));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_link_file_default() -> anyhow::Result<()> {
+    let mut vfs = MemoryVfs::new();
+
+    vfs.write("a", b"hello", VfsSyncOption::None)?;
+    vfs.link_file("a", "b", VfsSyncOption::None)?;
+
+    assert_eq!(vfs.read("b")?, b"hello");
+
+    Ok(())
+}
+
+fn restore_backup(db: &mut Database) -> Result<MemoryVfs, Error> {
+    let mut backup_vfs = MemoryVfs::new();
+
+    db.backup_files(|path, data| {
+        if let Some((dir_path, _)) = path.rsplit_once('/') {
+            backup_vfs.create_dir_all(dir_path)?;
+        }
+
+        backup_vfs.write(path, data, VfsSyncOption::None)
+    })?;
+
+    Ok(backup_vfs)
+}
+
+#[test]
+fn test_backup_files() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    fill_database(&mut db, 0..500)?;
+    db.flush()?;
+
+    let reader_options = Options {
+        open_mode: OpenMode::ReadOnly,
+        file_locking: false,
+        ..options.clone()
+    };
+    let mut reader_db = Database::open(Box::new(vfs.clone()), reader_options)?;
+
+    fill_database(&mut db, 500..1000)?;
+    db.flush()?;
+
+    // The files of the opened revision were replaced
+    match restore_backup(&mut reader_db) {
+        Ok(backup_vfs) => {
+            let mut backup_db = Database::open(Box::new(backup_vfs), options.clone())?;
+            assert_eq!(backup_db.metadata().key_value_count(), 500);
+            backup_db.verify(|_, _| {})?;
+        }
+        Err(Error::ExternallyModified) => {}
+        Err(error) => return Err(error.into()),
+    }
+
+    reader_db.reopen()?;
+    let backup_vfs = restore_backup(&mut reader_db)?;
+
+    assert_eq!(
+        common::list_files(&backup_vfs, "").len(),
+        common::list_files(&vfs, "").len() - 2
+    );
+
+    let mut backup_db = Database::open(Box::new(backup_vfs), options)?;
+
+    assert_eq!(backup_db.metadata().key_value_count(), 1000);
+    assert_eq!(backup_db.metadata().uuid(), db.metadata().uuid());
+    assert_eq!(backup_db.revision(), db.revision());
+    assert!(backup_db.contains_key("key:0000")?);
+    backup_db.verify(|_, _| {})?;
+
+    Ok(())
+}
@@ -14,6 +14,28 @@ pub fn make_tempdir() -> TempDir {
         .unwrap()
 }
 
+/// Return the paths of all files in the directory and its subdirectories.
+#[allow(dead_code)]
+pub fn list_files(vfs: &dyn Vfs, path: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+
+    for filename in vfs.read_dir(path).unwrap() {
+        let file_path = if path.is_empty() {
+            filename
+        } else {
+            format!("{}/{}", path, filename)
+        };
+
+        if vfs.is_dir(&file_path).unwrap() {
+            paths.extend(list_files(vfs, &file_path));
+        } else {
+            paths.push(file_path);
+        }
+    }
+
+    paths
+}
+
 #[macro_export]
 macro_rules! matrix_test {
     ($fn_name:ident) => {
@@ -0,0 +1,54 @@
+use grebedb::{collections::Counter, vfs::MemoryVfs, Database, Error, Options};
+
+#[test]
+fn test_counter() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut counter = Counter::new(Database::open(Box::new(vfs.clone()), options.clone())?);
+
+    for round in 1..=3 {
+        for num in 0..200 {
+            assert_eq!(
+                counter.increment(format!("key:{:04x}", num), num)?,
+                num * round
+            );
+        }
+    }
+
+    assert_eq!(counter.increment("key:0001", -10)?, -7);
+    assert_eq!(counter.get("key:0002")?, 6);
+    assert_eq!(counter.get("missing")?, 0);
+    assert_eq!(counter.database().metadata().key_value_count(), 200);
+    counter.flush()?;
+
+    // Unflushed increments are discarded
+    counter.increment("key:0002", 100)?;
+    drop(counter);
+
+    let mut counter = Counter::new(Database::open(Box::new(vfs), options)?);
+
+    assert_eq!(counter.get("key:0002")?, 6);
+    assert_eq!(counter.increment("key:0003", i64::MAX)?, i64::MIN + 8);
+
+    let mut db = counter.into_inner();
+    db.put("text", "hello")?;
+    db.verify(|_, _| {})?;
+
+    let mut counter = Counter::new(db);
+
+    assert!(matches!(
+        counter.increment("text", 1),
+        Err(Error::InvalidValue { .. })
+    ));
+    assert!(matches!(
+        counter.get("text"),
+        Err(Error::InvalidValue { .. })
+    ));
+    assert_eq!(counter.into_inner().get("text")?, Some(b"hello".to_vec()));
+
+    Ok(())
+}
@@ -1,6 +1,11 @@
 mod common;
 
-use grebedb::{Database, Error};
+use std::sync::{Arc, Mutex};
+
+use grebedb::{
+    vfs::{MemoryVfs, Vfs, VfsSyncOption},
+    Database, Error, Options,
+};
 
 fn cursor_sequential(mut database: Database) -> Result<(), Error> {
     for num in 0..10000 {
@@ -77,6 +82,34 @@ fn cursor_next_buf(mut database: Database) -> Result<(), Error> {
     Ok(())
 }
 
+fn cursor_try_for_each_pair(mut database: Database) -> Result<(), Error> {
+    for num in 0..500 {
+        let key = format!("{:08x}", num);
+        let value = format!("hello world {}", num);
+
+        database.put(key, value)?;
+    }
+
+    let mut cursor = database.cursor()?;
+    let mut count = 0;
+
+    cursor.try_for_each_pair(|key, value| {
+        assert_eq!(key, format!("{:08x}", count).as_bytes());
+        assert_eq!(value, format!("hello world {}", count).as_bytes());
+        count += 1;
+
+        Ok::<_, Error>(())
+    })?;
+
+    assert_eq!(count, 500);
+
+    let mut cursor = database.cursor()?;
+    let result = cursor.try_for_each_pair(|_key, _value| Err(Error::ReadOnly));
+    assert!(matches!(result, Err(Error::ReadOnly)));
+
+    Ok(())
+}
+
 fn cursor_removed_items(mut database: Database) -> Result<(), Error> {
     for num in 0..10000 {
         let key = format!("{:08x}", num);
@@ -143,8 +176,179 @@ fn cursor_range(mut database: Database) -> Result<(), Error> {
     Ok(())
 }
 
+fn keys_values(mut database: Database) -> Result<(), Error> {
+    for num in 0..1000 {
+        let key = format!("{:08x}", num);
+        let value = format!("hello world {}", num);
+
+        database.put(key, value)?;
+    }
+
+    let keys: Vec<Vec<u8>> = database.keys("00000010".."00000020")?.collect();
+    let values: Vec<Vec<u8>> = database.values("00000010".."00000020")?.collect();
+
+    assert_eq!(keys.len(), 16);
+    assert_eq!(values.len(), 16);
+
+    for (index, num) in (0x10..0x20).enumerate() {
+        assert_eq!(keys[index], format!("{:08x}", num).as_bytes());
+        assert_eq!(values[index], format!("hello world {}", num).as_bytes());
+    }
+
+    assert_eq!(database.keys::<&str, _>(..)?.count(), 1000);
+    assert_eq!(
+        database.values("000003e0"..)?.last(),
+        Some(b"hello world 999".to_vec())
+    );
+
+    Ok(())
+}
+
 matrix_test!(cursor_sequential);
 matrix_test!(cursor_iter_manual);
 matrix_test!(cursor_next_buf);
+matrix_test!(cursor_try_for_each_pair);
 matrix_test!(cursor_range);
 matrix_test!(cursor_removed_items);
+matrix_test!(keys_values);
+
+// Records the paths given to will_read()
+#[derive(Clone, Default)]
+struct ReadAheadVfs {
+    inner: MemoryVfs,
+    hinted_paths: Arc<Mutex<Vec<String>>>,
+}
+
+impl Vfs for ReadAheadVfs {
+    fn lock(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.lock(path)
+    }
+
+    fn unlock(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.unlock(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.inner.read(path)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8], sync_option: VfsSyncOption) -> Result<(), Error> {
+        self.inner.write(path, data, sync_option)
+    }
+
+    fn sync_file(&mut self, path: &str, sync_option: VfsSyncOption) -> Result<(), Error> {
+        self.inner.sync_file(path, sync_option)
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.remove_file(path)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.create_dir(path)
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.remove_dir(path)
+    }
+
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        self.inner.rename_file(old_path, new_path)
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        self.inner.is_dir(path)
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        self.inner.exists(path)
+    }
+
+    fn will_read(&self, path: &str) -> Result<(), Error> {
+        self.hinted_paths.lock().unwrap().push(path.to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_cursor_read_ahead() -> anyhow::Result<()> {
+    let vfs = ReadAheadVfs::default();
+    let options = Options {
+        keys_per_node: 8,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options)?;
+
+    for num in 0..200 {
+        database.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    database.flush()?;
+    vfs.hinted_paths.lock().unwrap().clear();
+
+    assert_eq!(database.cursor()?.count(), 200);
+
+    let hinted_paths = vfs.hinted_paths.lock().unwrap();
+
+    assert!(hinted_paths.len() > 10);
+
+    for path in hinted_paths.iter() {
+        assert!(vfs.inner.exists(path)?, "{}", path);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_cursor_into_results() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 8,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..200 {
+        database.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    database.flush()?;
+
+    let pairs = database
+        .cursor()?
+        .into_results()
+        .collect::<Result<Vec<_>, Error>>()?;
+    assert_eq!(pairs.len(), 200);
+
+    drop(database);
+
+    // The page with the largest ID is a leaf created by the last split
+    let mut vfs_mut = vfs.clone();
+    let page_paths: Vec<String> = common::list_files(&vfs, "")
+        .into_iter()
+        .filter(|path| path.contains('/'))
+        .collect();
+    let last_page_prefix = page_paths.iter().max().unwrap().rsplit_once('_').unwrap().0;
+
+    for path in page_paths
+        .iter()
+        .filter(|path| path.starts_with(last_page_prefix))
+    {
+        vfs_mut.remove_file(path)?;
+    }
+
+    let mut database = Database::open(Box::new(vfs), options)?;
+    let results: Vec<_> = database.cursor()?.into_results().collect();
+
+    assert!(results.len() < 200);
+    assert!(results.last().unwrap().is_err());
+    assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+
+    Ok(())
+}
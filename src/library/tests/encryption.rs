@@ -0,0 +1,170 @@
+#![cfg(feature = "encryption")]
+
+use grebedb::{vfs::MemoryVfs, CompressionLevel, Database, EncryptionKey, Error, Options};
+
+fn collect_files(vfs: &MemoryVfs, path: &str, files: &mut Vec<Vec<u8>>) -> anyhow::Result<()> {
+    use grebedb::vfs::Vfs;
+
+    for filename in vfs.read_dir(path)? {
+        let child_path = if path.is_empty() {
+            filename
+        } else {
+            format!("{}/{}", path, filename)
+        };
+
+        if vfs.is_dir(&child_path)? {
+            collect_files(vfs, &child_path, files)?;
+        } else {
+            files.push(vfs.read(&child_path)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_encryption() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        compression_level: CompressionLevel::None,
+        encryption_key: Some(EncryptionKey::new([1u8; 32])),
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..100 {
+        db.put(format!("key:{:04x}", num), format!("secret value {}", num))?;
+    }
+
+    db.flush()?;
+    drop(db);
+
+    let mut files = Vec::new();
+    collect_files(&vfs, "", &mut files)?;
+
+    assert!(files.len() > 1);
+
+    for data in files {
+        assert!(!data.windows(12).any(|window| window == b"secret value"));
+    }
+
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+    assert_eq!(db.get("key:0000")?, Some(b"secret value 0".to_vec()));
+    db.verify(|_, _| {})?;
+    drop(db);
+
+    let result = Database::open(Box::new(vfs.clone()), Options::default());
+    assert!(result.is_err());
+
+    let options = Options {
+        encryption_key: Some(EncryptionKey::new([2u8; 32])),
+        ..Default::default()
+    };
+    let result = Database::open(Box::new(vfs), options);
+    assert!(matches!(result, Err(Error::DecryptionFailed { .. })));
+
+    Ok(())
+}
+
+#[test]
+fn test_rewrite_with_key() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let old_key = EncryptionKey::new([1u8; 32]);
+    let new_key = EncryptionKey::new([2u8; 32]);
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        automatic_flush: false,
+        metadata_generations: 3,
+        encryption_key: Some(old_key.clone()),
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), format!("secret value {}", num))?;
+    }
+
+    db.flush()?;
+
+    for num in 0..100 {
+        db.remove(format!("key:{:04x}", num))?;
+    }
+
+    db.flush()?;
+
+    let mut last_progress = (0, 0);
+    db.rewrite_with_key(new_key.clone(), |current, total| {
+        last_progress = (current, total)
+    })?;
+
+    assert!(last_progress.0 > 1);
+    assert_eq!(last_progress.0, last_progress.1);
+
+    assert_eq!(db.get("key:0064")?, Some(b"secret value 100".to_vec()));
+    drop(db);
+
+    let result = Database::open(Box::new(vfs.clone()), options.clone());
+    assert!(matches!(result, Err(Error::DecryptionFailed { .. })));
+
+    // Older generations are encrypted with the new key too
+    let result = Database::open_at_generation(Box::new(vfs.clone()), options, 1);
+    assert!(matches!(result, Err(Error::DecryptionFailed { .. })));
+
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        automatic_flush: false,
+        metadata_generations: 3,
+        encryption_key: Some(new_key),
+        ..Default::default()
+    };
+    let mut db = Database::open_at_generation(Box::new(vfs.clone()), options.clone(), 2)?;
+    assert_eq!(db.get("key:0000")?, Some(b"secret value 0".to_vec()));
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+    assert_eq!(db.get("key:0000")?, None);
+
+    for num in 100..500 {
+        assert_eq!(
+            db.get(format!("key:{:04x}", num))?,
+            Some(format!("secret value {}", num).into_bytes())
+        );
+    }
+
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_previous_encryption_keys() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let old_key = EncryptionKey::new([1u8; 32]);
+    let new_key = EncryptionKey::new([2u8; 32]);
+    let options = Options {
+        encryption_key: Some(old_key.clone()),
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+    db.put("my key", "hello world")?;
+    db.flush()?;
+    drop(db);
+
+    let options = Options {
+        encryption_key: Some(new_key),
+        previous_encryption_keys: vec![old_key],
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    assert_eq!(db.get("my key")?, Some(b"hello world".to_vec()));
+
+    db.put("my key 2", "hello world 2")?;
+    db.flush()?;
+
+    Ok(())
+}
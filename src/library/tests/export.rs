@@ -1,6 +1,15 @@
+mod common;
+
 use std::io::BufReader;
 
-use grebedb::{Database, Options};
+use grebedb::{
+    export::{
+        ByteEncoding, CompressedWriter, CsvOptions, DecompressedReader, Delimiter,
+        ExportCompression, ExportOptions, ImportOptions,
+    },
+    vfs::MemoryVfs,
+    Database, OpenMode, Options,
+};
 
 #[test]
 fn test_export() {
@@ -27,3 +36,797 @@ fn test_export() {
     assert_eq!(database.get("key2").unwrap(), Some(b"value2".to_vec()));
     assert_eq!(database.get("key3").unwrap(), Some(b"value3".to_vec()));
 }
+
+#[test]
+fn test_export_binary() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..100 {
+        database.put(format!("key:{:04x}", num), vec![num as u8; 100])?;
+    }
+
+    let mut json_file = Vec::new();
+    grebedb::export::export(&mut database, &mut json_file, |_| {})?;
+
+    let mut file = Vec::new();
+    grebedb::export::export_binary(&mut database, &mut file, |_| {})?;
+
+    assert!(file.len() < json_file.len() * 2 / 3);
+
+    let mut database = Database::open_memory(Options::default())?;
+    let mut count = 0;
+    grebedb::export::import(
+        &mut database,
+        &mut BufReader::new(std::io::Cursor::new(&file)),
+        |progress| count = progress.rows(),
+    )?;
+
+    assert_eq!(count, 100);
+    assert_eq!(database.metadata().key_value_count(), 100);
+    assert_eq!(database.get("key:0063")?, Some(vec![99u8; 100]));
+
+    // Missing footer
+    let mut database = Database::open_memory(Options::default())?;
+    let result = grebedb::export::import(
+        &mut database,
+        &mut BufReader::new(std::io::Cursor::new(&file[..file.len() - 3])),
+        |_| {},
+    );
+    assert!(result.is_err());
+
+    // Corrupted value
+    let index = file.len() - 20;
+    file[index] ^= 0xff;
+    let mut database = Database::open_memory(Options::default())?;
+    let result = grebedb::export::import(
+        &mut database,
+        &mut BufReader::new(std::io::Cursor::new(&file)),
+        |_| {},
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_export_progress() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..100 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut file = Vec::new();
+    let mut last_progress = None;
+    grebedb::export::export(&mut database, &mut file, |progress| {
+        last_progress = Some(progress.clone())
+    })?;
+
+    let progress = last_progress.unwrap();
+    assert_eq!(progress.rows(), 100);
+    assert_eq!(progress.total_rows(), Some(100));
+    assert!(progress.bytes() > 0);
+    assert!(progress.bytes() < file.len() as u64);
+    assert_eq!(
+        progress.estimated_remaining().unwrap_or_default().as_secs(),
+        0
+    );
+
+    let mut restored_database = Database::open_memory(Options::default())?;
+    let mut last_progress = None;
+    grebedb::export::import(
+        &mut restored_database,
+        &mut BufReader::new(std::io::Cursor::new(&file)),
+        |progress| last_progress = Some(progress.clone()),
+    )?;
+
+    let progress = last_progress.unwrap();
+    assert_eq!(progress.rows(), 100);
+    assert_eq!(progress.total_rows(), Some(100));
+    assert!(progress.bytes() < file.len() as u64);
+
+    Ok(())
+}
+
+#[test]
+fn test_export_rows() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..100 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut file = Vec::new();
+    grebedb::export::export(&mut database, &mut file, |_| {})?;
+
+    let records = grebedb::export::rows(&mut database)?.collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(records.len(), 102);
+    assert_eq!(records.concat(), file);
+
+    let mut binary_file = Vec::new();
+    grebedb::export::export_binary(&mut database, &mut binary_file, |_| {})?;
+
+    let mut read_file = Vec::new();
+    std::io::copy(
+        &mut grebedb::export::rows_binary(&mut database)?,
+        &mut read_file,
+    )?;
+
+    assert_eq!(read_file, binary_file);
+
+    Ok(())
+}
+
+#[test]
+fn test_export_snapshot() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        automatic_flush: false,
+        metadata_generations: 4,
+        ..Default::default()
+    };
+    let mut writer = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        writer.put(format!("key:{:04x}", num), "old")?;
+    }
+
+    writer.flush()?;
+
+    let mut reader = Database::open(
+        Box::new(vfs),
+        Options {
+            open_mode: OpenMode::ReadOnly,
+            file_locking: false,
+            ..options
+        },
+    )?;
+
+    let mut file = Vec::new();
+    grebedb::export::export(&mut reader, &mut file, |progress| {
+        if progress.rows() == 100 {
+            for round in 0..2 {
+                for num in 0..500 {
+                    writer.put(format!("key:{:04x}", num), "new").unwrap();
+                }
+
+                writer.remove(format!("key:{:04x}", round)).unwrap();
+                writer.flush().unwrap();
+            }
+        }
+    })?;
+
+    let mut restored_database = Database::open_memory(Options::default())?;
+    grebedb::export::import(
+        &mut restored_database,
+        &mut BufReader::new(std::io::Cursor::new(file)),
+        |_| {},
+    )?;
+
+    assert_eq!(restored_database.metadata().key_value_count(), 500);
+
+    for num in 0..500 {
+        assert_eq!(
+            restored_database.get(format!("key:{:04x}", num))?,
+            Some(b"old".to_vec())
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_export_incremental() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut database = Database::open_memory(options.clone())?;
+
+    for num in 0..1000 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut export_count = 0;
+    let mut full_file = Vec::new();
+    grebedb::export::export_incremental(&mut database, &mut full_file, |progress| {
+        export_count = progress.rows()
+    })?;
+
+    assert_eq!(export_count, 1000);
+    assert!(database.metadata().last_export_revision().is_some());
+
+    database.put("key:0100", "new value")?;
+    database.put("key:03e7", "new value")?;
+
+    let mut export_count = 0;
+    let mut file = Vec::new();
+    grebedb::export::export_incremental(&mut database, &mut file, |progress| {
+        export_count = progress.rows()
+    })?;
+
+    assert!(export_count >= 2);
+    assert!(export_count <= 32);
+
+    let mut export_count = 0;
+    let mut empty_file = Vec::new();
+    grebedb::export::export_incremental(&mut database, &mut empty_file, |progress| {
+        export_count = progress.rows()
+    })?;
+
+    assert_eq!(export_count, 0);
+
+    let mut restored_database = Database::open_memory(options)?;
+
+    for file in [full_file, file, empty_file] {
+        grebedb::export::import(
+            &mut restored_database,
+            &mut BufReader::new(std::io::Cursor::new(file)),
+            |_| {},
+        )?;
+    }
+
+    assert_eq!(restored_database.metadata().key_value_count(), 1000);
+    assert_eq!(
+        restored_database.get("key:0100")?,
+        Some(b"new value".to_vec())
+    );
+    assert_eq!(
+        restored_database.get("key:03e7")?,
+        Some(b"new value".to_vec())
+    );
+    assert_eq!(restored_database.get("key:0101")?, Some(b"value".to_vec()));
+
+    Ok(())
+}
+
+#[test]
+fn test_import_resume() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..1000 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut file = Vec::new();
+    grebedb::export::export(&mut database, &mut file, |_| {})?;
+
+    let mut restored_database = Database::open_memory(Options::default())?;
+    let truncated_file = file[..file.len() / 2].to_vec();
+
+    let result = grebedb::export::import(
+        &mut restored_database,
+        &mut BufReader::new(std::io::Cursor::new(truncated_file)),
+        |_| {},
+    );
+    assert!(result.is_err());
+
+    restored_database.flush()?;
+
+    let (applied_count, offset) = restored_database.metadata().import_progress().unwrap();
+    assert!(applied_count > 0);
+    assert!(applied_count < 1000);
+    assert!(offset <= file.len() as u64 / 2);
+
+    restored_database.reset_stats();
+
+    let mut import_count = 0;
+    grebedb::export::import_resume(
+        &mut restored_database,
+        &mut BufReader::new(std::io::Cursor::new(file)),
+        |progress| import_count = progress.rows(),
+    )?;
+
+    assert_eq!(import_count, 1000);
+    assert_eq!(
+        restored_database.stats().put().count(),
+        1000 - applied_count
+    );
+    assert_eq!(restored_database.metadata().key_value_count(), 1000);
+    assert!(restored_database.metadata().import_progress().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_import_flush_interval() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..1000 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut file = Vec::new();
+    grebedb::export::export(&mut database, &mut file, |_| {})?;
+
+    let mut restored_database = Database::open_memory(Options::default())?;
+    let options = ImportOptions {
+        flush_rows: Some(300),
+        ..Default::default()
+    };
+    grebedb::export::import_with_options(
+        &mut restored_database,
+        &mut file.as_slice(),
+        &options,
+        |_| {},
+    )?;
+
+    // 3 periodic flushes and 1 when completed
+    assert_eq!(restored_database.stats().flush().count(), 4);
+
+    let mut restored_database = Database::open_memory(Options::default())?;
+    let options = ImportOptions {
+        flush_bytes: Some(1000),
+        threads: 2,
+        ..Default::default()
+    };
+    grebedb::export::import_with_options(
+        &mut restored_database,
+        &mut file.as_slice(),
+        &options,
+        |_| {},
+    )?;
+
+    // 1 periodic flush after the first batch and 1 when completed
+    assert_eq!(restored_database.stats().flush().count(), 2);
+    assert_eq!(restored_database.metadata().key_value_count(), 1000);
+
+    Ok(())
+}
+
+#[test]
+fn test_import_row_count_mismatch() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..100 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut file = Vec::new();
+    grebedb::export::export(&mut database, &mut file, |_| {})?;
+
+    let file =
+        String::from_utf8(file)?.replace("\"key_value_count\":100", "\"key_value_count\":101");
+
+    let mut restored_database = Database::open_memory(Options::default())?;
+    let result = grebedb::export::import(
+        &mut restored_database,
+        &mut BufReader::new(std::io::Cursor::new(file)),
+        |_| {},
+    );
+
+    assert!(result.is_err());
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("row count mismatch"));
+
+    Ok(())
+}
+
+#[test]
+fn test_import_source_mismatch() -> anyhow::Result<()> {
+    let mut database_1 = Database::open_memory(Options::default())?;
+    let mut database_2 = Database::open_memory(Options::default())?;
+
+    database_1.put("key 1", "value")?;
+    database_2.put("key 2", "value")?;
+
+    let mut file_1 = Vec::new();
+    grebedb::export::export(&mut database_1, &mut file_1, |_| {})?;
+    let mut file_2 = Vec::new();
+    grebedb::export::export(&mut database_2, &mut file_2, |_| {})?;
+
+    let mut restored_database = Database::open_memory(Options::default())?;
+
+    for _ in 0..2 {
+        grebedb::export::import(
+            &mut restored_database,
+            &mut BufReader::new(std::io::Cursor::new(&file_1)),
+            |_| {},
+        )?;
+    }
+
+    assert!(restored_database.metadata().import_source().is_some());
+
+    let result = grebedb::export::import(
+        &mut restored_database,
+        &mut BufReader::new(std::io::Cursor::new(&file_2)),
+        |_| {},
+    );
+
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("source mismatch"));
+    assert!(!restored_database.contains_key("key 2")?);
+
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..100 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut file = Vec::new();
+    grebedb::export::export(&mut database, &mut file, |_| {})?;
+    let mut binary_file = Vec::new();
+    grebedb::export::export_binary(&mut database, &mut binary_file, |_| {})?;
+
+    let summary = grebedb::export::validate(&mut BufReader::new(std::io::Cursor::new(&file)))?;
+
+    assert_eq!(summary.key_value_count(), 100);
+    assert_eq!(summary.bytes(), file.len() as u64);
+    assert!(!summary.is_binary());
+    assert!(summary.uuid().is_some());
+    assert!(summary.since_revision().is_none());
+
+    let summary =
+        grebedb::export::validate(&mut BufReader::new(std::io::Cursor::new(&binary_file)))?;
+
+    assert_eq!(summary.key_value_count(), 100);
+    assert_eq!(summary.bytes(), binary_file.len() as u64);
+    assert!(summary.is_binary());
+
+    let truncated_file = &file[..file.len() - 10];
+    assert!(
+        grebedb::export::validate(&mut BufReader::new(std::io::Cursor::new(truncated_file)))
+            .is_err()
+    );
+
+    let corrupted_file = String::from_utf8(file)?.replacen("76616C7565", "76616C7566", 1);
+    assert!(
+        grebedb::export::validate(&mut BufReader::new(std::io::Cursor::new(corrupted_file)))
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_export_compressed() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for num in 0..100 {
+        database.put(format!("key:{:04x}", num), "value")?;
+    }
+
+    let mut file = Vec::new();
+    grebedb::export::export(&mut database, &mut file, |_| {})?;
+
+    let mut writer = CompressedWriter::new(Vec::new(), ExportCompression::Zstd(3))?;
+    grebedb::export::export(&mut database, &mut writer, |_| {})?;
+    let compressed_file = writer.finish()?;
+
+    assert!(compressed_file.len() < file.len());
+
+    for input in [file, compressed_file] {
+        let mut reader = DecompressedReader::new(std::io::Cursor::new(input))?;
+        let mut restored_database = Database::open_memory(Options::default())?;
+        grebedb::export::import(&mut restored_database, &mut reader, |_| {})?;
+
+        assert_eq!(restored_database.metadata().key_value_count(), 100);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_export_csv() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    database.put("key1", "hello, \"world\"")?;
+    database.put("key2", "tab\tand\nnewline\\")?;
+    database.put(vec![0xffu8], vec![0u8, 1])?;
+
+    let mut file = Vec::new();
+    let options = CsvOptions {
+        key_encoding: ByteEncoding::Utf8Lossy,
+        value_encoding: ByteEncoding::Utf8Lossy,
+        ..Default::default()
+    };
+    grebedb::export::export_csv(&mut database, &mut file, &options, |_| {})?;
+
+    assert_eq!(
+        String::from_utf8(file)?,
+        "key,value\r\n\
+        key1,\"hello, \"\"world\"\"\"\r\n\
+        key2,\"tab\tand\nnewline\\\"\r\n\
+        \u{fffd},\u{0}\u{1}\r\n"
+    );
+
+    let mut file = Vec::new();
+    let options = CsvOptions {
+        delimiter: Delimiter::Tab,
+        key_encoding: ByteEncoding::Hex,
+        value_encoding: ByteEncoding::Utf8Lossy,
+        omit_header: true,
+        ..Default::default()
+    };
+    grebedb::export::export_csv(&mut database, &mut file, &options, |_| {})?;
+
+    assert_eq!(
+        String::from_utf8(file)?,
+        "6B657931\thello, \"world\"\n\
+        6B657932\ttab\\tand\\nnewline\\\\\n\
+        FF\t\u{0}\u{1}\n"
+    );
+
+    let mut file = Vec::new();
+    let options = CsvOptions {
+        value_encoding: ByteEncoding::Base64,
+        omit_header: true,
+        ..Default::default()
+    };
+    let mut count = 0;
+    grebedb::export::export_csv(&mut database, &mut file, &options, |progress| {
+        count = progress.rows()
+    })?;
+
+    assert_eq!(count, 3);
+    assert!(String::from_utf8(file)?.ends_with("FF,AAE=\r\n"));
+
+    let mut file = Vec::new();
+    let options = CsvOptions {
+        delimiter: Delimiter::Tab,
+        key_encoding: ByteEncoding::Utf8Lossy,
+        keys_only: true,
+        ..Default::default()
+    };
+    grebedb::export::export_csv(&mut database, &mut file, &options, |_| {})?;
+
+    assert_eq!(String::from_utf8(file)?, "key\nkey1\nkey2\n\u{fffd}\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_export_listing() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    database.put("key1", "hello")?;
+    database.put("key2", vec![0u8, 1])?;
+
+    let mut file = Vec::new();
+    let options = ExportOptions {
+        key_encoding: ByteEncoding::Utf8Lossy,
+        value_encoding: ByteEncoding::Base64,
+        threads: 2,
+        ..Default::default()
+    };
+    grebedb::export::export_with_options(&mut database, &mut file, &options, |_| {})?;
+
+    let text = String::from_utf8(file.clone())?;
+    assert!(text.contains("\"key_encoding\":\"utf8\",\"value_encoding\":\"base64\""));
+    assert!(text.contains("{\"key_value\":{\"key\":\"key1\",\"value\":\"aGVsbG8=\",\"index\":0,"));
+    assert!(text.contains("{\"key_value\":{\"key\":\"key2\",\"value\":\"AAE=\",\"index\":1,"));
+
+    let mut database_2 = Database::open_memory(Options::default())?;
+    let error = grebedb::export::import(&mut database_2, &mut file.as_slice(), |_| {}).unwrap_err();
+    assert!(error.to_string().contains("cannot be imported"));
+
+    let mut file = Vec::new();
+    let options = ExportOptions {
+        keys_only: true,
+        ..Default::default()
+    };
+    grebedb::export::export_with_options(&mut database, &mut file, &options, |_| {})?;
+
+    let text = String::from_utf8(file)?;
+    assert!(text.contains("\"keys_only\":true,\"key_encoding\":\"hex\""));
+    assert!(text.contains("{\"key_value\":{\"key\":\"6B657931\",\"index\":0,"));
+    assert!(!text.contains("\"value\""));
+
+    let options = ExportOptions {
+        binary: true,
+        keys_only: true,
+        ..Default::default()
+    };
+    assert!(
+        grebedb::export::export_with_options(&mut database, &mut Vec::new(), &options, |_| {})
+            .is_err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_export_threads() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for number in 0..5000 {
+        database.put(format!("key:{:08x}", number), vec![number as u8; 500])?;
+    }
+
+    for binary in [false, true] {
+        let mut expected_file = Vec::new();
+        let options = ExportOptions {
+            binary,
+            ..Default::default()
+        };
+        grebedb::export::export_with_options(&mut database, &mut expected_file, &options, |_| {})?;
+
+        let mut file = Vec::new();
+        let mut count = 0;
+        let options = ExportOptions {
+            binary,
+            threads: 3,
+            ..Default::default()
+        };
+        grebedb::export::export_with_options(&mut database, &mut file, &options, |progress| {
+            assert!(progress.rows() >= count);
+            count = progress.rows();
+        })?;
+
+        assert_eq!(count, 5000);
+        assert!(file == expected_file);
+
+        let mut database_2 = Database::open_memory(Options::default())?;
+        grebedb::export::import(&mut database_2, &mut file.as_slice(), |_| {})?;
+        assert_eq!(database_2.metadata().key_value_count(), 5000);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_import_threads() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    for number in 0..5000 {
+        database.put(format!("key:{:08x}", number), vec![number as u8; 500])?;
+    }
+
+    for binary in [false, true] {
+        let mut file = Vec::new();
+        let options = ExportOptions {
+            binary,
+            ..Default::default()
+        };
+        grebedb::export::export_with_options(&mut database, &mut file, &options, |_| {})?;
+
+        let mut database_2 = Database::open_memory(Options::default())?;
+        let mut count = 0;
+        let options = ImportOptions {
+            threads: 3,
+            ..Default::default()
+        };
+        grebedb::export::import_with_options(
+            &mut database_2,
+            &mut file.as_slice(),
+            &options,
+            |progress| {
+                assert!(progress.rows() >= count);
+                count = progress.rows();
+            },
+        )?;
+
+        assert_eq!(count, 5000);
+        assert_eq!(database_2.metadata().key_value_count(), 5000);
+        assert_eq!(database_2.metadata().import_progress(), None);
+        assert_eq!(database_2.get("key:00000fff")?, Some(vec![0xffu8; 500]));
+
+        let mut corrupted_file = file.clone();
+        let position = corrupted_file.len() / 2;
+        corrupted_file[position] ^= 0x01;
+
+        let mut database_3 = Database::open_memory(Options::default())?;
+        let result = grebedb::export::import_with_options(
+            &mut database_3,
+            &mut corrupted_file.as_slice(),
+            &options,
+            |_| {},
+        );
+        assert!(result.is_err());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_export_sqlite_script() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    database.put("key1", "it's")?;
+    database.put(vec![0xffu8], vec![])?;
+
+    let mut file = Vec::new();
+    grebedb::export::export_sqlite_script(&mut database, &mut file, |_| {})?;
+
+    assert_eq!(
+        String::from_utf8(file)?,
+        "BEGIN TRANSACTION;\n\
+        CREATE TABLE kv(key BLOB PRIMARY KEY, value BLOB);\n\
+        INSERT INTO kv VALUES(X'6B657931',X'69742773');\n\
+        INSERT INTO kv VALUES(X'FF',X'');\n\
+        COMMIT;\n"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_export_sqlite() -> anyhow::Result<()> {
+    let dir = common::make_tempdir();
+    let path = dir.path().join("data.sqlite");
+    let mut database = Database::open_memory(Options::default())?;
+
+    database.put("key1", "it's")?;
+    database.put(vec![0xffu8], vec![])?;
+
+    grebedb::export::export_sqlite(&mut database, &path, |_| {})?;
+
+    let connection = rusqlite::Connection::open(&path)?;
+    let mut statement = connection.prepare("SELECT key, value FROM kv ORDER BY key")?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(
+        rows,
+        vec![(b"key1".to_vec(), b"it's".to_vec()), (vec![0xff], vec![])]
+    );
+
+    assert!(grebedb::export::export_sqlite(&mut database, &path, |_| {}).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_import_foreign() -> anyhow::Result<()> {
+    let mut database = Database::open_memory(Options::default())?;
+
+    let dump = "from [] to []\n\
+        Process example.sst\n\
+        'key1' seq:5, type:1 => new value\n\
+        'key1' seq:2, type:1 => old value\n\
+        'key2' seq:4, type:0 => \n\
+        0x6B657933 ==> 0x76616C7565\n";
+    database.put("key2", "removed value")?;
+    grebedb::export::foreign::import_rocksdb_dump(
+        &mut database,
+        &mut BufReader::new(dump.as_bytes()),
+        |_| {},
+    )?;
+
+    assert_eq!(database.get("key1")?, Some(b"new value".to_vec()));
+    assert_eq!(database.get("key2")?, None);
+    assert_eq!(database.get("key3")?, Some(b"value".to_vec()));
+
+    let items = vec![vec![b"key4".to_vec(), b"value".to_vec()]];
+    grebedb::export::foreign::import_sled(&mut database, items, |_| {})?;
+
+    assert_eq!(database.get("key4")?, Some(b"value".to_vec()));
+
+    let mut file = Vec::new();
+    for (key, value) in [("key5", "value"), ("key6", "")] {
+        file.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        file.extend_from_slice(key.as_bytes());
+        file.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        file.extend_from_slice(value.as_bytes());
+    }
+    grebedb::export::foreign::import_length_prefixed(&mut database, &mut file.as_slice(), |_| {})?;
+
+    assert_eq!(database.get("key5")?, Some(b"value".to_vec()));
+    assert_eq!(database.get("key6")?, Some(Vec::new()));
+
+    let truncated_file = &file[..file.len() - 2];
+    let mut other_database = Database::open_memory(Options::default())?;
+    assert!(grebedb::export::foreign::import_length_prefixed(
+        &mut other_database,
+        &mut &truncated_file[..],
+        |_| {}
+    )
+    .is_err());
+
+    Ok(())
+}
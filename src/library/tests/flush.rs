@@ -1,6 +1,8 @@
 mod common;
 
-use grebedb::Database;
+use std::time::Duration;
+
+use grebedb::{Database, Options};
 
 fn put_one(mut db: Database) -> anyhow::Result<()> {
     db.put("my key", "hello world")?;
@@ -24,3 +26,119 @@ fn put_many(mut db: Database) -> anyhow::Result<()> {
 
 matrix_test!(put_one);
 matrix_test!(put_many);
+
+#[test]
+fn test_automatic_flush_intervals() -> anyhow::Result<()> {
+    let options = Options {
+        automatic_flush_threshold: 10,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..50 {
+        db.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    assert_eq!(db.stats().flush().count(), 0);
+
+    let options = Options {
+        automatic_flush_threshold: 10,
+        automatic_flush_long_interval: Duration::ZERO,
+        automatic_flush_short_interval: Duration::ZERO,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..50 {
+        db.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    assert!(db.stats().flush().count() >= 4);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_dirty_pages() -> anyhow::Result<()> {
+    let temp_dir = common::make_tempdir();
+    let options = Options {
+        keys_per_node: 8,
+        automatic_flush: false,
+        max_dirty_pages: Some(4),
+        ..Default::default()
+    };
+    let mut db = Database::open_path(temp_dir.path(), options.clone())?;
+
+    for num in 0..200 {
+        db.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    assert_eq!(db.stats().flush().count(), 0);
+    assert!(db.stats().page_writes() > 0);
+
+    db.flush()?;
+    drop(db);
+
+    let mut db = Database::open_path(temp_dir.path(), options)?;
+
+    assert_eq!(db.metadata().key_value_count(), 200);
+
+    for num in 0..200 {
+        assert_eq!(
+            db.get(format!("{:08x}", num))?,
+            Some(b"hello world".to_vec())
+        );
+    }
+
+    db.verify(|_, _| {})?;
+
+    let options = Options {
+        max_dirty_pages: Some(0),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        options.validate(),
+        Err(grebedb::Error::InvalidConfig { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_write_back_dirty() -> anyhow::Result<()> {
+    let temp_dir = common::make_tempdir();
+    let options = Options {
+        keys_per_node: 8,
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut db = Database::open_path(temp_dir.path(), options.clone())?;
+    let initial_writes = db.stats().page_writes();
+
+    for num in 0..200 {
+        db.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    assert_eq!(db.stats().page_writes(), initial_writes);
+
+    db.write_back_dirty()?;
+
+    let page_writes = db.stats().page_writes();
+    assert!(page_writes - initial_writes > 10);
+
+    db.write_back_dirty()?;
+    assert_eq!(db.stats().page_writes(), page_writes);
+
+    // Only the metadata is left to write
+    db.flush()?;
+    assert!(db.stats().page_writes() - page_writes <= 2);
+    drop(db);
+
+    let mut db = Database::open_path(temp_dir.path(), options)?;
+
+    assert_eq!(db.metadata().key_value_count(), 200);
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+
+use common::CrashingVfs;
+use grebedb::{
+    vfs::{MemoryVfs, Vfs, VfsSyncOption},
+    Database, FsckIssueKind, Options,
+};
+
+#[test]
+fn test_fsck() -> anyhow::Result<()> {
+    let mut vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+
+    let report = db.fsck()?;
+
+    assert!(report.is_ok(), "{:?}", report.issues());
+    assert_eq!(report.page_count(), db.tree_stats()?.page_count());
+    assert_eq!(report.file_count(), report.page_count());
+    drop(db);
+
+    let page_paths: Vec<String> = common::list_files(&vfs, "")
+        .into_iter()
+        .filter(|path| path.ends_with("_0.grebedb"))
+        .collect();
+
+    // Uncommitted file, file of another database, and outdated metadata copy
+    let data = vfs.read(&page_paths[0])?;
+    vfs.write(
+        &page_paths[0].replace("_0.", "_2."),
+        &data,
+        VfsSyncOption::None,
+    )?;
+
+    let other_vfs = MemoryVfs::new();
+    let mut other_db = Database::open(Box::new(other_vfs.clone()), options.clone())?;
+    other_db.put("key", "value")?;
+    other_db.flush()?;
+    drop(other_db);
+
+    let other_path = common::list_files(&other_vfs, "")
+        .into_iter()
+        .find(|path| path.ends_with("_0.grebedb"))
+        .unwrap();
+    let other_data = other_vfs.read(&other_path)?;
+    vfs.write(&page_paths[1], &other_data, VfsSyncOption::None)?;
+
+    let data = vfs.read("grebedb_meta_prev.grebedb")?;
+    vfs.write("grebedb_meta_copy.grebedb", &data, VfsSyncOption::None)?;
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+    let report = db.fsck()?;
+    let kinds: Vec<FsckIssueKind> = report.issues().iter().map(|issue| issue.kind()).collect();
+
+    assert!(kinds.contains(&FsckIssueKind::OrphanPageFile));
+    assert!(kinds.contains(&FsckIssueKind::UuidMismatch));
+    assert!(kinds.contains(&FsckIssueKind::InvalidPage));
+    assert!(kinds.contains(&FsckIssueKind::MetadataDivergence));
+
+    // Pages below a replaced internal node are not part of the tree anymore
+    let orphan_path = page_paths[0].replace("_0.", "_2.");
+    assert!(report.issues().iter().any(|issue| {
+        issue.kind() == FsckIssueKind::OrphanPageFile && issue.path() == Some(orphan_path.as_str())
+    }));
+
+    Ok(())
+}
+
+#[test]
+fn test_fsck_free_page_files() -> anyhow::Result<()> {
+    let vfs = CrashingVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+
+    for num in 0..450 {
+        db.remove(format!("key:{:04x}", num))?;
+    }
+
+    // Files of the removed pages are left behind
+    vfs.after_metadata_rename_crash
+        .store(true, Ordering::Relaxed);
+    assert!(db.flush().is_err());
+    drop(db);
+    vfs.after_metadata_rename_crash
+        .store(false, Ordering::Relaxed);
+
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+    let stats = db.tree_stats()?;
+    let report = db.fsck()?;
+
+    assert!(stats.free_page_count() > 0);
+    assert!(report.is_ok(), "{:?}", report.issues());
+    assert!(report.file_count() > report.page_count());
+
+    db.purge_free_pages()?;
+
+    let report = db.fsck()?;
+
+    assert!(report.is_ok(), "{:?}", report.issues());
+    assert_eq!(report.file_count(), report.page_count());
+
+    Ok(())
+}
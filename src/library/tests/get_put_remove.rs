@@ -1,6 +1,6 @@
 mod common;
 
-use grebedb::{Database, Error};
+use grebedb::{vfs::MemoryVfs, Database, Error, MergeConflictPolicy, OpenMode, Options};
 
 fn simple_get_put_remove(mut database: Database) -> Result<(), Error> {
     database.put("key1", "hello")?;
@@ -51,3 +51,191 @@ fn sequential_numbers(mut database: Database) -> Result<(), Error> {
 
 matrix_test!(simple_get_put_remove);
 matrix_test!(sequential_numbers);
+
+#[test]
+fn test_open_generic() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::default();
+    let mut database = Database::open_generic(vfs.clone(), Options::default())?;
+
+    database.put("key1", "hello")?;
+    database.flush()?;
+    drop(database);
+
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..Default::default()
+    };
+    let mut database = Database::open_generic(vfs, options)?;
+
+    assert_eq!(database.get("key1")?, Some("hello".into()));
+    assert_eq!(database.cursor()?.count(), 1);
+    assert!(database.put("key2", "world").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_preload() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::default();
+    let options = Options {
+        keys_per_node: 8,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..1000 {
+        database.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    database.flush()?;
+    drop(database);
+
+    let mut database = Database::open(Box::new(vfs.clone()), options)?;
+
+    assert_eq!(database.preload(0)?, 1);
+    assert_eq!(database.preload(usize::MAX)?, 4);
+
+    let options = Options {
+        keys_per_node: 8,
+        page_cache_size: 1000,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs), options)?;
+    let count = database.preload(usize::MAX)?;
+
+    assert!(count > 4 && count < 1000);
+
+    let page_reads = database.stats().page_reads();
+
+    for num in 0..1000 {
+        database.get(format!("{:08x}", num))?;
+    }
+
+    assert_eq!(database.stats().page_reads(), page_reads);
+
+    Ok(())
+}
+
+#[test]
+fn test_compact() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::default();
+    let options = Options {
+        keys_per_node: 8,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    database.compact()?;
+
+    for index in 0..2000 {
+        let num = index * 7919 % 2000;
+        database.put(format!("{:08x}", num), format!("value {}", num))?;
+    }
+
+    for num in (0..2000).step_by(3) {
+        database.remove(format!("{:08x}", num))?;
+    }
+
+    let stats = database.tree_stats()?;
+    let mut last_progress = (0, 0);
+    database.compact_with_progress(|current, total| last_progress = (current, total))?;
+    let compacted_stats = database.tree_stats()?;
+
+    assert_eq!(last_progress.0, last_progress.1);
+    assert_eq!(
+        last_progress.1 as u64,
+        *stats.level_page_counts().last().unwrap()
+    );
+
+    assert!(compacted_stats.page_count() < stats.page_count());
+    assert!(compacted_stats.fill_factor() > 0.9);
+    database.verify(|_, _| {})?;
+    drop(database);
+
+    let mut database = Database::open(Box::new(vfs), options)?;
+    let keys = database.keys::<&str, _>(..)?.collect::<Vec<_>>();
+
+    assert_eq!(keys.len(), 1333);
+    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+
+    for num in 0..2000 {
+        let value = database.get(format!("{:08x}", num))?;
+
+        if num % 3 == 0 {
+            assert_eq!(value, None);
+        } else {
+            assert_eq!(value, Some(format!("value {}", num).into_bytes()));
+        }
+    }
+
+    for num in 0..2000 {
+        database.put(format!("{:08x}", num), "new value")?;
+    }
+
+    database.verify(|_, _| {})?;
+    assert_eq!(database.metadata().key_value_count(), 2000);
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_from() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+
+    let make_databases = || -> anyhow::Result<(Database, Database)> {
+        let mut ours = Database::open_memory(options.clone())?;
+        let mut theirs = Database::open_memory(options.clone())?;
+
+        for num in (0..3000).step_by(2) {
+            ours.put(format!("{:08x}", num), "ours")?;
+        }
+
+        for num in (0..3000).step_by(3) {
+            theirs.put(format!("{:08x}", num), "theirs")?;
+        }
+
+        Ok((ours, theirs))
+    };
+
+    let (mut ours, mut theirs) = make_databases()?;
+    ours.merge_from(&mut theirs, MergeConflictPolicy::KeepOurs)?;
+    ours.verify(|_, _| {})?;
+
+    assert_eq!(ours.metadata().key_value_count(), 2000);
+    assert_eq!(ours.get(format!("{:08x}", 6))?, Some(b"ours".to_vec()));
+    assert_eq!(ours.get(format!("{:08x}", 3))?, Some(b"theirs".to_vec()));
+    assert_eq!(ours.get(format!("{:08x}", 1))?, None);
+    assert_eq!(ours.cursor()?.count(), 2000);
+
+    let (mut ours, mut theirs) = make_databases()?;
+    ours.merge_from(&mut theirs, MergeConflictPolicy::KeepTheirs)?;
+    ours.verify(|_, _| {})?;
+
+    assert_eq!(ours.metadata().key_value_count(), 2000);
+    assert_eq!(ours.get(format!("{:08x}", 6))?, Some(b"theirs".to_vec()));
+    assert_eq!(ours.get(format!("{:08x}", 2))?, Some(b"ours".to_vec()));
+
+    let (mut ours, mut theirs) = make_databases()?;
+
+    assert!(matches!(
+        ours.merge_from(&mut theirs, MergeConflictPolicy::Error),
+        Err(Error::KeyConflict { key }) if key == b"00000000"
+    ));
+
+    let mut empty = Database::open_memory(options.clone())?;
+    empty.merge_from(&mut theirs, MergeConflictPolicy::Error)?;
+    empty.verify(|_, _| {})?;
+
+    assert_eq!(empty.metadata().key_value_count(), 1000);
+    assert_eq!(
+        empty.cursor()?.collect::<Vec<_>>(),
+        theirs.cursor()?.collect::<Vec<_>>()
+    );
+
+    Ok(())
+}
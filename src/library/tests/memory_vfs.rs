@@ -0,0 +1,133 @@
+mod common;
+
+use std::io::Cursor;
+
+use grebedb::{
+    vfs::{MemoryVfs, OsVfs, PackVfs, PackWriter, SegmentVfs, Vfs, VfsSyncOption},
+    Database, OpenMode, Options,
+};
+
+#[test]
+fn test_dump_to_and_load_from() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let memory_vfs = MemoryVfs::new();
+    let mut db = Database::open(Box::new(memory_vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), format!("hello world {}", num))?;
+    }
+
+    db.flush()?;
+    drop(db);
+
+    let dir = common::make_tempdir();
+    memory_vfs.dump_to(&mut OsVfs::new(dir.path()), VfsSyncOption::None)?;
+
+    let mut db = Database::open_path(dir.path(), options.clone())?;
+
+    for num in 0..500 {
+        assert_eq!(
+            db.get(format!("key:{:04x}", num))?,
+            Some(format!("hello world {}", num).into_bytes())
+        );
+    }
+
+    db.put("key:new", "new value")?;
+    db.flush()?;
+    drop(db);
+
+    let mut memory_vfs = MemoryVfs::new();
+    memory_vfs.load_from(&OsVfs::new(dir.path()))?;
+
+    let mut db = Database::open(Box::new(memory_vfs), options)?;
+
+    assert_eq!(db.get("key:0000")?, Some(b"hello world 0".to_vec()));
+    assert_eq!(db.get("key:new")?, Some(b"new value".to_vec()));
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_segment_vfs_database() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let memory_vfs = MemoryVfs::new();
+    let mut vfs = SegmentVfs::open(memory_vfs.clone())?;
+    vfs.set_segment_size(4096);
+    let mut db = Database::open_generic(vfs, options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), format!("hello world {}", num))?;
+    }
+
+    db.flush()?;
+    drop(db);
+
+    assert!(memory_vfs.read_dir("")?.len() < 500);
+
+    let mut vfs = SegmentVfs::open(memory_vfs.clone())?;
+    vfs.compact(VfsSyncOption::None)?;
+    let mut db = Database::open_generic(vfs, options)?;
+
+    for num in 0..500 {
+        assert_eq!(
+            db.get(format!("key:{:04x}", num))?,
+            Some(format!("hello world {}", num).into_bytes())
+        );
+    }
+
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_pack_vfs_database() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), format!("hello world {}", num))?;
+    }
+
+    db.flush()?;
+
+    let mut writer = PackWriter::new(Cursor::new(Vec::new()))?;
+    db.backup_files(|path, data| writer.add_file(path, data))?;
+    let data = writer.finish()?.into_inner();
+    drop(db);
+
+    let mut memory_vfs = MemoryVfs::new();
+    memory_vfs.write("data.grebepack", &data, VfsSyncOption::None)?;
+
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+    let vfs = PackVfs::open(memory_vfs, "data.grebepack")?;
+    let mut db = Database::open_generic(vfs, options)?;
+
+    for num in 0..500 {
+        assert_eq!(
+            db.get(format!("key:{:04x}", num))?,
+            Some(format!("hello world {}", num).into_bytes())
+        );
+    }
+
+    db.verify(|_, _| {})?;
+    assert!(db.put("key", "value").is_err());
+
+    Ok(())
+}
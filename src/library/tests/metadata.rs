@@ -1,4 +1,11 @@
-use grebedb::{Database, Options};
+mod common;
+
+use std::path::Path;
+
+use grebedb::{
+    vfs::{MemoryVfs, OsVfs, Vfs, VfsSyncOption},
+    Database, DebugPrintOptions, Error, OpenMode, Options, PageKind,
+};
 use indexmap::IndexSet;
 
 #[test]
@@ -16,3 +23,629 @@ fn test_metadata() {
 
     assert_eq!(db.metadata().key_value_count(), 500);
 }
+
+#[test]
+fn test_metadata_backup_fallback() -> anyhow::Result<()> {
+    let mut vfs = MemoryVfs::new();
+    let mut db = Database::open(Box::new(vfs.clone()), Options::default())?;
+
+    db.put("key:1", "hello world 1")?;
+    db.flush()?;
+    db.put("key:2", "hello world 2")?;
+    db.flush()?;
+    drop(db);
+
+    // Damaged main file falls back to the copy
+    vfs.write("grebedb_meta.grebedb", b"garbage", VfsSyncOption::None)?;
+
+    let mut db = Database::open(Box::new(vfs.clone()), Options::default())?;
+    assert_eq!(db.get("key:2")?, Some(b"hello world 2".to_vec()));
+
+    db.put("key:3", "hello world 3")?;
+    db.flush()?;
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs.clone()), Options::default())?;
+    assert_eq!(db.get("key:3")?, Some(b"hello world 3".to_vec()));
+    drop(db);
+
+    vfs.write("grebedb_meta.grebedb", b"garbage", VfsSyncOption::None)?;
+    vfs.remove_file("grebedb_meta_copy.grebedb")?;
+    vfs.remove_file("grebedb_meta_prev.grebedb")?;
+
+    assert!(Database::open(Box::new(vfs), Options::default()).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_app_metadata() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    assert_eq!(db.get_app_metadata(), None);
+
+    db.put("key", "value")?;
+    db.set_app_metadata("schema 1")?;
+    db.flush()?;
+
+    // A metadata change alone is flushed
+    db.set_app_metadata("schema 2")?;
+    db.flush()?;
+
+    // Unflushed changes are discarded
+    db.set_app_metadata("schema 3")?;
+    assert_eq!(db.get_app_metadata(), Some(b"schema 3".as_ref()));
+    drop(db);
+
+    let db = Database::open(Box::new(vfs.clone()), options.clone())?;
+    assert_eq!(db.get_app_metadata(), Some(b"schema 2".as_ref()));
+    assert_eq!(db.metadata().app_metadata(), Some(b"schema 2".as_ref()));
+    drop(db);
+
+    let mut db = Database::open(
+        Box::new(vfs),
+        Options {
+            open_mode: OpenMode::ReadOnly,
+            ..options
+        },
+    )?;
+    assert_eq!(db.get_app_metadata(), Some(b"schema 2".as_ref()));
+    assert!(matches!(
+        db.set_app_metadata("schema 4"),
+        Err(Error::ReadOnly)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_open_at_generation() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        automatic_flush: false,
+        metadata_generations: 3,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for round in 0..10 {
+        for num in 0..200 {
+            db.put(format!("key:{:04x}", num), format!("round {}", round))?;
+        }
+
+        db.flush()?;
+    }
+
+    drop(db);
+
+    for generation in 0..=3 {
+        let mut db =
+            Database::open_at_generation(Box::new(vfs.clone()), options.clone(), generation)?;
+
+        for num in 0..200 {
+            assert_eq!(
+                db.get(format!("key:{:04x}", num))?,
+                Some(format!("round {}", 9 - generation).into_bytes())
+            );
+        }
+
+        assert!(db.put("key:new", "new value").is_err());
+    }
+
+    assert!(Database::open_at_generation(Box::new(vfs.clone()), options, 4).is_err());
+
+    // Replaced page files are removed when their generation is discarded
+    let files = common::list_files(&vfs, "");
+    let page_file_count = files
+        .iter()
+        .filter(|path| path.ends_with("_0.grebedb") && !path.contains("meta"))
+        .count();
+    let history_file_count = files.iter().filter(|path| path.contains("_r")).count();
+    assert!(history_file_count > 0);
+    assert!(history_file_count <= page_file_count * 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_open_at_generation_after_purge() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        metadata_generations: 2,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..200 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+
+    for num in 0..200 {
+        db.remove(format!("key:{:04x}", num))?;
+    }
+
+    db.purge_free_pages()?;
+    drop(db);
+
+    let mut db = Database::open_at_generation(Box::new(vfs), options, 1)?;
+
+    for num in 0..200 {
+        assert_eq!(
+            db.get(format!("key:{:04x}", num))?,
+            Some(b"hello world".to_vec())
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_externally_modified() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+
+    let mut reader = Database::open(
+        Box::new(vfs),
+        Options {
+            open_mode: OpenMode::ReadOnly,
+            file_locking: false,
+            ..options
+        },
+    )?;
+
+    assert!(reader.contains_key("key:0000")?);
+
+    for num in 0..500 {
+        db.remove(format!("key:{:04x}", num))?;
+    }
+
+    db.flush()?;
+
+    let mut result = Ok(());
+
+    for num in 0..500 {
+        if let Err(error) = reader.get(format!("key:{:04x}", num)) {
+            result = Err(error);
+            break;
+        }
+    }
+
+    assert!(matches!(result, Err(Error::ExternallyModified)));
+
+    Ok(())
+}
+
+#[test]
+fn test_stats() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..100 {
+        db.put(format!("key:{}", num), "hello world!")?;
+    }
+
+    db.remove("key:0")?;
+    db.get("key:1")?;
+    db.get("key:2")?;
+    db.flush()?;
+
+    let stats = db.stats();
+
+    assert_eq!(stats.put().count(), 100);
+    assert_eq!(stats.put().errors(), 0);
+    assert_eq!(stats.remove().count(), 1);
+    assert_eq!(stats.get().count(), 2);
+    assert_eq!(stats.flush().count(), 1);
+    assert_eq!(stats.flush().latency().buckets().last().unwrap().1, 1);
+    assert!(stats.page_writes() > 0);
+    assert!(stats.cache_hits() > 0);
+
+    #[cfg(feature = "compression")]
+    assert!(stats.bytes_compressed() > 0);
+
+    db.reset_stats();
+
+    assert_eq!(db.stats(), grebedb::Stats::default());
+
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    assert_eq!(db.get("key:1")?, Some(b"hello world!".to_vec()));
+
+    let stats = db.stats();
+
+    assert!(stats.page_reads() > 0);
+    assert_eq!(stats.page_writes(), 0);
+    assert!(stats.cache_misses() > 0);
+
+    #[cfg(feature = "compression")]
+    assert!(stats.bytes_decompressed() > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_tree_stats() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 8,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    let stats = db.tree_stats()?;
+
+    assert_eq!(stats.height(), 1);
+    assert_eq!(stats.page_count(), 1);
+    assert_eq!(stats.fill_factor(), 0.0);
+
+    for num in 0..1000 {
+        db.put(format!("key:{:04}", num), "hello world!")?;
+    }
+
+    db.flush()?;
+
+    let stats = db.tree_stats()?;
+
+    assert!(stats.height() >= 4);
+    assert_eq!(stats.level_page_counts()[0], 1);
+    assert!(stats.level_page_counts().last().unwrap() * 8 >= 1000);
+    assert_eq!(
+        stats.page_count(),
+        stats.level_page_counts().iter().sum::<u64>()
+    );
+    assert!(stats.fill_factor() > 0.4 && stats.fill_factor() <= 1.0);
+    assert_eq!(stats.revision(), db.tree_stats()?.revision());
+    assert_eq!(stats.uuid(), db.metadata().uuid());
+
+    db.clear()?;
+    db.flush()?;
+
+    let stats = db.tree_stats()?;
+
+    assert_eq!(stats.height(), 1);
+    assert!(stats.free_page_count() > 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_tree_stats_saved_keys_per_node() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::default();
+    let options = Options {
+        keys_per_node: 8,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+    for num in 0..1000 {
+        db.put(format!("key:{:04}", num), "hello world!")?;
+    }
+
+    db.flush()?;
+    let fill_factor = db.tree_stats()?.fill_factor();
+    drop(db);
+
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    assert_eq!(db.metadata().keys_per_node(), Some(8));
+    assert_eq!(db.tree_stats()?.fill_factor(), fill_factor);
+
+    Ok(())
+}
+
+#[test]
+fn test_disk_usage() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 8,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..1000 {
+        db.put(format!("key:{:04}", num), "hello world!".repeat(10))?;
+    }
+
+    db.put("key:big", vec![0u8; 10000])?;
+
+    let usage = db.disk_usage()?;
+    let stats = db.tree_stats()?;
+
+    assert_eq!(usage.pages().len() as u64, stats.page_count());
+    assert_eq!(usage.pages()[0].level(), 0);
+    assert_eq!(usage.pages().last().unwrap().level() + 1, stats.height());
+    assert!(usage.metadata_size() > 0);
+    assert_eq!(
+        usage.total_size(),
+        usage
+            .pages()
+            .iter()
+            .map(|page| page.file_size())
+            .sum::<u64>()
+            + usage.metadata_size()
+    );
+
+    for page in usage.pages() {
+        assert!(page.file_size() > 0);
+        assert!(page.payload_size() > 0);
+    }
+
+    let largest_page = usage
+        .pages()
+        .iter()
+        .max_by_key(|page| page.largest_value_size())
+        .unwrap();
+
+    assert_eq!(largest_page.largest_value_size(), 10000);
+    assert!(largest_page.compression_ratio() > 1.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_tree_summary() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 8,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    let summary = db.tree_summary()?;
+
+    assert_eq!(summary.pages().len(), 1);
+    assert_eq!(summary.pages()[0].kind(), PageKind::EmptyRoot);
+
+    for num in 0..100 {
+        db.put(format!("key:{:04}", num), "hello world!")?;
+    }
+
+    let summary = db.tree_summary()?;
+    let stats = db.tree_stats()?;
+
+    assert_eq!(summary.pages().len() as u64, stats.page_count());
+    assert_eq!(summary.pages()[0].page_id(), summary.root_id().unwrap());
+    assert_eq!(summary.pages()[0].kind(), PageKind::Internal);
+
+    let leaves: Vec<_> = summary
+        .pages()
+        .iter()
+        .filter(|page| page.kind() == PageKind::Leaf)
+        .collect();
+
+    assert_eq!(leaves.iter().map(|page| page.key_count()).sum::<u64>(), 100);
+    assert!(leaves.iter().all(|page| page.children().is_empty()));
+    assert_eq!(
+        leaves
+            .iter()
+            .filter(|page| page.next_leaf().is_none())
+            .count(),
+        1
+    );
+
+    let json = serde_json::to_value(&summary)?;
+
+    assert_eq!(json["pages"][0]["kind"], "internal");
+
+    let mut text = Vec::new();
+    db.debug_print_tree(&mut text)?;
+    let text = String::from_utf8(text)?;
+
+    assert!(text.starts_with(&format!("Root page: {}", summary.root_id().unwrap())));
+    assert_eq!(text.lines().count(), summary.pages().len() + 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_regenerate_uuid() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+
+    let old_uuid = db.metadata().uuid();
+    let mut last_progress = (0, 0);
+    db.regenerate_uuid(|current, total| last_progress = (current, total))?;
+
+    assert_ne!(db.metadata().uuid(), old_uuid);
+    assert!(last_progress.0 > 1);
+    assert_eq!(last_progress.0, last_progress.1);
+    assert_eq!(db.get("key:0000")?, Some(b"hello world".to_vec()));
+
+    let new_uuid = db.metadata().uuid();
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    assert_eq!(db.metadata().uuid(), new_uuid);
+
+    for num in 0..500 {
+        assert!(db.contains_key(format!("key:{:04x}", num))?);
+    }
+
+    db.verify(|_, _| {})?;
+    db.purge_free_pages()?;
+
+    let report = db.fsck()?;
+    assert!(report.is_ok(), "{:?}", report.issues());
+
+    Ok(())
+}
+
+#[test]
+fn test_debug_format_page() -> anyhow::Result<()> {
+    let temp_dir = common::make_tempdir();
+    let mut db = Database::open_path(temp_dir.path(), Options::default())?;
+
+    db.put("key", vec![0xabu8; 1000])?;
+    db.flush()?;
+    drop(db);
+
+    let page_path = common::list_files(&OsVfs::new(temp_dir.path()), "")
+        .into_iter()
+        .find(|path| !path.contains("meta") && path.ends_with(".grebedb"))
+        .unwrap();
+    let page_path = temp_dir.path().join(page_path);
+
+    let text = grebedb::debug_format_page(&page_path, &DebugPrintOptions::default())?;
+    assert!(text.starts_with("Page {"));
+
+    let options = DebugPrintOptions {
+        json: true,
+        truncate: Some(2),
+        ..Default::default()
+    };
+    let text = grebedb::debug_format_page(&page_path, &options)?;
+    let value: serde_json::Value = serde_json::from_str(&text)?;
+
+    assert_eq!(value["kind"], "leaf");
+    assert_eq!(value["key_count"], 1);
+    assert_eq!(value["node"]["keys"][0], "6B65..");
+    assert_eq!(value["node"]["values"][0], "ABAB..");
+
+    let options = DebugPrintOptions {
+        json: true,
+        header_only: true,
+        ..Default::default()
+    };
+    let text = grebedb::debug_format_page(&page_path, &options)?;
+    let value: serde_json::Value = serde_json::from_str(&text)?;
+
+    assert_eq!(value["deleted"], false);
+    assert!(value.get("node").is_none());
+
+    let metadata_path = temp_dir.path().join("grebedb_meta.grebedb");
+    let text = grebedb::debug_format_page(&metadata_path, &options)?;
+    assert!(serde_json::from_str::<serde_json::Value>(&text)?["uuid"].is_string());
+
+    // Copies of files are read regardless of their name
+    let copy_path = temp_dir.path().join("Page-Copy (1).grebedb");
+    std::fs::copy(&page_path, &copy_path)?;
+    let text = grebedb::debug_format_page(&copy_path, &options)?;
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&text)?["deleted"],
+        false
+    );
+    grebedb::inspect_page(&copy_path)?;
+
+    for path in ["/", ""] {
+        assert!(matches!(
+            grebedb::debug_format_page(Path::new(path), &options),
+            Err(Error::InvalidPath { .. })
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_inspect_page() -> anyhow::Result<()> {
+    let temp_dir = common::make_tempdir();
+    let mut db = Database::open_path(temp_dir.path(), Options::default())?;
+
+    db.put("key", "hello world")?;
+    db.flush()?;
+
+    let uuid = db.metadata().uuid();
+    let revision = db.revision();
+    drop(db);
+
+    let page_path = common::list_files(&OsVfs::new(temp_dir.path()), "")
+        .into_iter()
+        .find(|path| !path.contains("meta") && path.ends_with(".grebedb"))
+        .unwrap();
+    let info = grebedb::inspect_page(&temp_dir.path().join(page_path))?;
+
+    assert_eq!(info.uuid(), uuid);
+    assert!(info.revision() <= revision);
+    assert!(!info.is_deleted());
+    assert_eq!(info.kind(), Some(PageKind::Leaf));
+    assert_eq!(info.keys(), &[b"key".to_vec()]);
+    assert_eq!(info.values(), &[b"hello world".to_vec()]);
+    assert!(info.children().is_empty());
+    assert_eq!(info.next_leaf(), None);
+
+    let metadata_path = temp_dir.path().join("grebedb_meta.grebedb");
+    assert!(matches!(
+        grebedb::inspect_page(&metadata_path),
+        Err(Error::InvalidFileFormat { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_estimate_range_size() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    assert_eq!(db.estimate_range_size::<&str, _>(..)?, 0);
+
+    for num in 0..10 {
+        db.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    assert_eq!(db.estimate_range_size("00000002".."00000005")?, 3);
+
+    for num in 10..10000 {
+        db.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    let all = db.estimate_range_size::<&str, _>(..)?;
+    assert!((9000..=11000).contains(&all), "{}", all);
+
+    let half = db.estimate_range_size(format!("{:08x}", 5000)..)?;
+    assert!((4500..=5500).contains(&half), "{}", half);
+
+    let small = db.estimate_range_size(format!("{:08x}", 100)..format!("{:08x}", 1100))?;
+    assert!((800..=1200).contains(&small), "{}", small);
+
+    let tiny = db.estimate_range_size(format!("{:08x}", 100)..format!("{:08x}", 105))?;
+    assert_eq!(tiny, 5);
+
+    assert_eq!(db.estimate_range_size("zzz"..)?, 0);
+
+    Ok(())
+}
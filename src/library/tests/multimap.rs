@@ -0,0 +1,75 @@
+use grebedb::{collections::MultiMap, vfs::MemoryVfs, Database, Error, Options};
+
+#[test]
+fn test_multimap() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut map = MultiMap::new(Database::open(Box::new(vfs.clone()), options.clone())?)?;
+
+    for num in 0..300 {
+        map.insert(format!("key:{:02x}", num % 30), format!("value {}", num))?;
+    }
+
+    map.insert("key", "prefix of other keys")?;
+    map.insert(b"key\x00", "null byte")?;
+
+    let values = map.get_all("key:03")?;
+    assert_eq!(values.len(), 10);
+    assert_eq!(values[0], b"value 3");
+    assert_eq!(values[9], b"value 273");
+
+    assert_eq!(map.get_all("key")?, vec![b"prefix of other keys".to_vec()]);
+    assert_eq!(map.get_all(b"key\x00")?, vec![b"null byte".to_vec()]);
+    assert!(map.get_all("missing")?.is_empty());
+    assert!(map.contains_key("key:1d")?);
+    assert!(!map.contains_key("key:1e")?);
+
+    assert_eq!(map.remove_all("key:05")?, 10);
+    assert_eq!(map.remove_all("key:05")?, 0);
+    assert!(!map.contains_key("key:05")?);
+    map.flush()?;
+    drop(map);
+
+    let mut map = MultiMap::new(Database::open(Box::new(vfs), options)?)?;
+
+    // Values inserted after reopening still follow the existing values
+    map.insert("key:03", "new value")?;
+    assert_eq!(map.get_all("key:03")?.last().unwrap(), b"new value");
+
+    let mut cursor = map.cursor()?;
+    let pairs: Vec<(Vec<u8>, Vec<u8>)> = cursor.by_ref().collect();
+    assert!(cursor.error().is_none());
+    assert_eq!(pairs.len(), 293);
+    assert_eq!(
+        pairs[0],
+        (b"key".to_vec(), b"prefix of other keys".to_vec())
+    );
+    assert_eq!(pairs[1], (b"key\x00".to_vec(), b"null byte".to_vec()));
+    assert_eq!(pairs[2], (b"key:00".to_vec(), b"value 0".to_vec()));
+
+    let mut cursor = map.cursor()?;
+    cursor.seek("key:1d")?;
+    assert_eq!(cursor.count(), 10);
+
+    let mut db = map.into_inner();
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_multimap_not_a_multimap() -> anyhow::Result<()> {
+    let mut db = Database::open_memory(Options::default())?;
+    db.put("key", "value")?;
+
+    assert!(matches!(
+        MultiMap::new(db),
+        Err(Error::InvalidMetadata { .. })
+    ));
+
+    Ok(())
+}
@@ -1,8 +1,17 @@
 mod common;
 
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
 use grebedb::{
-    vfs::{MemoryVfs, ReadOnlyVfs},
-    CompressionLevel, Database, OpenMode, Options, SyncOption,
+    vfs::{MemoryVfs, ReadOnlyVfs, Vfs, VfsSyncOption},
+    CompressionLevel, Database, DirectoryFanout, DropFailure, DropPolicy, Error, OpenMode, Options,
+    SyncOption,
 };
 
 #[test]
@@ -64,6 +73,134 @@ fn test_read_only_no_clobber_not_a_db() -> anyhow::Result<()> {
     Ok(())
 }
 
+// File system that fails any modification and counts the attempts
+struct WriteFailingVfs {
+    inner: MemoryVfs,
+    write_attempts: Arc<AtomicUsize>,
+}
+
+impl WriteFailingVfs {
+    fn fail(&self) -> Result<(), Error> {
+        self.write_attempts.fetch_add(1, Ordering::Relaxed);
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            "write attempted",
+        )))
+    }
+}
+
+impl Vfs for WriteFailingVfs {
+    fn lock(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.lock(path)
+    }
+
+    fn unlock(&mut self, path: &str) -> Result<(), Error> {
+        self.inner.unlock(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.inner.read(path)
+    }
+
+    fn write(
+        &mut self,
+        _path: &str,
+        _data: &[u8],
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        self.fail()
+    }
+
+    fn sync_file(&mut self, _path: &str, _sync_option: VfsSyncOption) -> Result<(), Error> {
+        self.fail()
+    }
+
+    fn remove_file(&mut self, _path: &str) -> Result<(), Error> {
+        self.fail()
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        self.inner.read_dir(path)
+    }
+
+    fn create_dir(&mut self, _path: &str) -> Result<(), Error> {
+        self.fail()
+    }
+
+    fn remove_dir(&mut self, _path: &str) -> Result<(), Error> {
+        self.fail()
+    }
+
+    fn rename_file(&mut self, _old_path: &str, _new_path: &str) -> Result<(), Error> {
+        self.fail()
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        self.inner.is_dir(path)
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        self.inner.exists(path)
+    }
+}
+
+#[test]
+fn test_read_only_generic_no_writes() -> anyhow::Result<()> {
+    let mut memory_vfs = MemoryVfs::default();
+    let options = Options {
+        keys_per_node: 128,
+        page_cache_size: 4,
+        metadata_generations: 2,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(memory_vfs.clone()), options)?;
+
+    for num in 0..1000 {
+        db.put(format!("key:{:016x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+    db.put("key:0000000000000000", "new value")?;
+    db.flush()?;
+    drop(db);
+
+    // Falling back to a metadata backup file would repair the main file
+    // when writable
+    memory_vfs.remove_file("grebedb_meta.grebedb")?;
+
+    let write_attempts = Arc::new(AtomicUsize::new(0));
+    let vfs = WriteFailingVfs {
+        inner: memory_vfs,
+        write_attempts: write_attempts.clone(),
+    };
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        keys_per_node: 128,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open_generic(vfs, options)?;
+
+    for num in 1..1000 {
+        let key = format!("key:{:016x}", num);
+        assert_eq!(db.get(key)?, Some(b"hello world".to_vec()));
+    }
+
+    db.verify(|_, _| {})?;
+    db.fsck()?;
+    db.reopen()?;
+    assert_eq!(db.cursor()?.count(), 1000);
+
+    assert!(matches!(db.put("key", "value"), Err(Error::ReadOnly)));
+    assert!(matches!(db.flush(), Err(Error::ReadOnly)));
+    assert!(matches!(db.clean_up(), Err(Error::ReadOnly)));
+    drop(db);
+
+    assert_eq!(write_attempts.load(Ordering::Relaxed), 0);
+
+    Ok(())
+}
+
 #[test]
 fn test_create_only() -> anyhow::Result<()> {
     let memory_vfs = MemoryVfs::default();
@@ -139,6 +276,33 @@ fn test_no_compression() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "compression")]
+#[test]
+fn test_custom_compression_level() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::default();
+    let options = Options {
+        compression_level: CompressionLevel::Custom(-5),
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+    db.put("my key", "hello world")?;
+    db.flush()?;
+
+    let mut db = Database::open(Box::new(vfs), Options::default())?;
+
+    assert!(db.get("my key")?.is_some());
+
+    let options = Options {
+        compression_level: CompressionLevel::Custom(1000),
+        ..Default::default()
+    };
+
+    assert!(Database::open_memory(options).is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_no_file_locking() -> anyhow::Result<()> {
     let dir = common::make_tempdir();
@@ -176,3 +340,243 @@ fn test_no_file_sync() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "compression_lz4")]
+#[test]
+fn test_lz4_compression() -> anyhow::Result<()> {
+    use grebedb::CompressionAlgorithm;
+
+    let vfs = MemoryVfs::default();
+    let options = Options {
+        compression_algorithm: CompressionAlgorithm::Lz4,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+    db.put("my key", "hello world")?;
+    db.flush()?;
+
+    let mut db = Database::open(Box::new(vfs), Options::default())?;
+
+    assert_eq!(db.get("my key")?, Some(b"hello world".to_vec()));
+
+    Ok(())
+}
+
+#[cfg(feature = "codec_bincode")]
+#[test]
+fn test_bincode_codec() -> anyhow::Result<()> {
+    use grebedb::{vfs::Vfs, Codec};
+
+    let vfs = MemoryVfs::default();
+    let options = Options {
+        keys_per_node: 16,
+        codec: Codec::Bincode,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+    for num in 0..100 {
+        db.put(format!("my key {}", num), "hello world")?;
+    }
+    db.flush()?;
+
+    let options = Options {
+        keys_per_node: 16,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+    for num in 100..200 {
+        db.put(format!("my key {}", num), "hello world")?;
+    }
+    db.flush()?;
+
+    assert_eq!(db.metadata().key_value_count(), 200);
+    db.verify(|_, _| {})?;
+
+    // Codec flag of the database is kept, but metadata is always MessagePack
+    assert_eq!(vfs.read("grebedb_meta.grebedb")?[8] & 0x70, 0x00);
+
+    for path in common::list_files(&vfs, "") {
+        if !path.contains("grebedb_meta") && path.ends_with(".grebedb") {
+            assert_eq!(vfs.read(&path)?[8] & 0x70, 0x10);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(feature = "checksum_xxh3", feature = "checksum_crc64"))]
+#[test]
+fn test_mixed_checksums() -> anyhow::Result<()> {
+    use grebedb::Checksum;
+
+    let vfs = MemoryVfs::default();
+
+    for checksum in [Checksum::Xxh3, Checksum::Crc64, Checksum::Crc32c] {
+        let options = Options {
+            keys_per_node: 16,
+            checksum,
+            ..Default::default()
+        };
+        let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+        for num in 0..100 {
+            db.put(format!("my key {}", num), format!("{:?}", checksum))?;
+        }
+        db.flush()?;
+    }
+
+    let mut db = Database::open(Box::new(vfs), Options::default())?;
+
+    assert_eq!(db.get("my key 0")?, Some(b"Crc32c".to_vec()));
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_directory_fanout() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::default();
+    let options = Options {
+        keys_per_node: 16,
+        directory_fanout: DirectoryFanout::Small,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options)?;
+
+    for num in 0..100 {
+        db.put(format!("my key {}", num), "hello world")?;
+    }
+    db.flush()?;
+    drop(db);
+
+    let files = common::list_files(&vfs, "");
+    assert!(files.contains(&"00/grebedb_0000000000000001_0.grebedb".to_string()));
+
+    // Layout of the database is kept
+    let mut db = Database::open(Box::new(vfs.clone()), Options::default())?;
+
+    for num in 100..200 {
+        db.put(format!("my key {}", num), "hello world")?;
+    }
+    db.flush()?;
+
+    assert_eq!(db.metadata().key_value_count(), 200);
+    db.verify(|_, _| {})?;
+
+    let files = common::list_files(&vfs, "");
+    assert!(files
+        .iter()
+        .filter(|path| path.ends_with("_0.grebedb"))
+        .all(|path| path.starts_with("00/") && path.matches('/').count() == 1));
+
+    Ok(())
+}
+
+#[test]
+fn test_directory_fanout_invalid() {
+    for (depth, width) in [(0, 8), (1, 0), (1, 17), (8, 9)] {
+        let options = Options {
+            directory_fanout: DirectoryFanout::Custom { depth, width },
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Database::open(Box::new(MemoryVfs::default()), options),
+            Err(Error::InvalidConfig { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_drop_policy_unflushed() -> anyhow::Result<()> {
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let failures_clone = failures.clone();
+    let options = Options {
+        automatic_flush: false,
+        drop_policy: DropPolicy::Callback(Arc::new(move |failure| {
+            failures_clone.lock().unwrap().push(failure.to_string());
+        })),
+        ..Default::default()
+    };
+
+    let mut db = Database::open_memory(options.clone())?;
+    db.put("key", "value")?;
+    db.flush()?;
+    drop(db);
+
+    assert!(failures.lock().unwrap().is_empty());
+
+    let mut db = Database::open_memory(options)?;
+    db.put("key", "value")?;
+    drop(db);
+
+    assert_eq!(
+        failures.lock().unwrap().as_slice(),
+        [DropFailure::UnflushedChanges.to_string()]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_options_serde() -> anyhow::Result<()> {
+    let options: Options = serde_json::from_str(
+        r#"{
+            "page_cache_size": 128,
+            "file_sync": "none",
+            "compression_level": {"custom": 5},
+            "directory_fanout": "small"
+        }"#,
+    )?;
+
+    assert_eq!(options.page_cache_size, 128);
+    assert_eq!(options.file_sync, SyncOption::None);
+    assert_eq!(options.compression_level, CompressionLevel::Custom(5));
+    assert_eq!(options.directory_fanout, DirectoryFanout::Small);
+    assert_eq!(options.keys_per_node, Options::default().keys_per_node);
+
+    let text = serde_json::to_string(&options)?;
+    let options: Options = serde_json::from_str(&text)?;
+
+    assert_eq!(options.page_cache_size, 128);
+
+    assert!(serde_json::from_str::<Options>(r#"{"page_cache_sise": 128}"#).is_err());
+
+    let options: Options = serde_json::from_str(
+        r#"{
+            "automatic_flush_long_interval": 10,
+            "automatic_flush_short_interval": 2.5
+        }"#,
+    )?;
+
+    assert_eq!(
+        options.automatic_flush_long_interval,
+        Duration::from_secs(10)
+    );
+    assert_eq!(
+        options.automatic_flush_short_interval,
+        Duration::from_millis(2500)
+    );
+
+    let text = serde_json::to_string(&options)?;
+    assert!(text.contains(r#""automatic_flush_long_interval":10,"#));
+    assert!(text.contains(r#""automatic_flush_short_interval":2.5,"#));
+
+    assert!(serde_json::from_str::<Options>(r#"{"automatic_flush_long_interval": -1}"#).is_err());
+    assert!(
+        serde_json::from_str::<Options>(r#"{"automatic_flush_long_interval": {"secs": 10}}"#)
+            .is_err()
+    );
+
+    let options: Options = serde_json::from_str(r#"{"page_cache_size": 0}"#)?;
+
+    assert!(matches!(
+        options.validate(),
+        Err(Error::InvalidConfig { .. })
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use grebedb::{collections::Queue, vfs::MemoryVfs, Database, Error, Options};
+
+#[test]
+fn test_queue() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut queue = Queue::new(Database::open(Box::new(vfs.clone()), options.clone())?)?;
+
+    assert!(queue.is_empty());
+    assert_eq!(queue.pop_front()?, None);
+    assert_eq!(queue.front()?, None);
+
+    for num in 0..500 {
+        queue.push_back(format!("value {}", num))?;
+    }
+
+    for num in 0..200 {
+        assert_eq!(
+            queue.pop_front()?,
+            Some(format!("value {}", num).into_bytes())
+        );
+    }
+
+    assert_eq!(queue.len(), 300);
+    assert_eq!(queue.front()?, Some(b"value 200".to_vec()));
+    queue.flush()?;
+
+    // Unflushed operations are discarded together with the head and tail
+    for num in 500..600 {
+        queue.push_back(format!("value {}", num))?;
+    }
+    queue.pop_front()?;
+    drop(queue);
+
+    let mut queue = Queue::new(Database::open(Box::new(vfs), options)?)?;
+
+    assert_eq!(queue.len(), 300);
+    assert_eq!(queue.database().metadata().key_value_count(), 300);
+
+    queue.push_back("value 500")?;
+
+    for num in 200..=500 {
+        assert_eq!(
+            queue.pop_front()?,
+            Some(format!("value {}", num).into_bytes())
+        );
+    }
+
+    assert!(queue.is_empty());
+
+    let mut db = queue.into_inner();
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_queue_not_a_queue() -> anyhow::Result<()> {
+    let mut db = Database::open_memory(Options::default())?;
+    db.put("key", "value")?;
+
+    assert!(matches!(Queue::new(db), Err(Error::InvalidMetadata { .. })));
+
+    Ok(())
+}
@@ -1,6 +1,9 @@
 mod common;
 
-use grebedb::{Database, Error};
+use grebedb::{
+    vfs::{MemoryVfs, Vfs, VfsSyncOption},
+    CompressionLevel, Database, Error, Options,
+};
 use indexmap::IndexSet;
 use rand::{Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
@@ -39,3 +42,345 @@ fn fill_and_random_remove(mut db: Database) -> Result<(), Error> {
 }
 
 matrix_test!(fill_and_random_remove);
+
+#[test]
+fn test_purge_free_pages() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+    let file_count = common::list_files(&vfs, "").len();
+
+    for num in 0..450 {
+        db.remove(format!("key:{:04x}", num))?;
+    }
+
+    db.purge_free_pages()?;
+    assert!(common::list_files(&vfs, "").len() < file_count);
+
+    db.verify(|_, _| {})?;
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    for num in 0..500 {
+        let value = db.get(format!("key:{:04x}", num))?;
+        assert_eq!(value.is_some(), num >= 450);
+    }
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_reclaim_ids() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    for num in 250..500 {
+        db.remove(format!("key:{:04x}", num))?;
+    }
+
+    db.reclaim_ids()?;
+    db.verify(|_, _| {})?;
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    for num in 0..500 {
+        let value = db.get(format!("key:{:04x}", num))?;
+        assert_eq!(value.is_some(), num < 250);
+    }
+
+    for num in 250..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    assert_eq!(db.metadata().key_value_count(), 500);
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_clean_up() -> anyhow::Result<()> {
+    let mut vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        compression_level: CompressionLevel::None,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+    drop(db);
+
+    let page_paths: Vec<String> = common::list_files(&vfs, "")
+        .into_iter()
+        .filter(|path| path.ends_with("_0.grebedb"))
+        .collect();
+    let file_count = common::list_files(&vfs, "").len();
+
+    // Committed file that was not renamed, uncommitted file, and torn file
+    let data = vfs.read(&page_paths[0])?;
+    vfs.rename_file(&page_paths[0], &page_paths[0].replace("_0.", "_1."))?;
+    vfs.write(
+        &page_paths[1].replace("_0.", "_2."),
+        &data,
+        VfsSyncOption::None,
+    )?;
+    let mut torn_data = data.clone();
+    let last_index = torn_data.len() - 1;
+    torn_data[last_index] ^= 0xff;
+    vfs.write(
+        &page_paths[2].replace("_0.", "_1."),
+        &torn_data,
+        VfsSyncOption::None,
+    )?;
+    vfs.create_dir_all("ff/ff")?;
+
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+    let report = db.clean_up()?;
+
+    assert_eq!(report.promoted_files(), 1);
+    assert_eq!(report.removed_files(), 2);
+    assert_eq!(report.removed_dirs(), 2);
+    assert_eq!(common::list_files(&vfs, "").len(), file_count);
+    assert!(!vfs.exists("ff")?);
+
+    db.verify(|_, _| {})?;
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    for num in 0..500 {
+        assert!(db.contains_key(format!("key:{:04x}", num))?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_clear() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    db.flush()?;
+
+    let page_file_count = |vfs: &MemoryVfs| {
+        common::list_files(vfs, "")
+            .iter()
+            .filter(|path| !path.contains("meta") && !path.contains("lock"))
+            .count()
+    };
+
+    assert!(page_file_count(&vfs) > 1);
+
+    db.clear()?;
+    db.flush()?;
+
+    assert_eq!(db.metadata().key_value_count(), 0);
+    assert_eq!(db.cursor()?.count(), 0);
+    assert_eq!(page_file_count(&vfs), 1);
+    db.verify(|_, _| {})?;
+    drop(db);
+
+    let mut db = Database::open(Box::new(vfs), options)?;
+
+    assert_eq!(db.get("key:0000")?, None);
+
+    for num in 0..100 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    assert_eq!(db.metadata().key_value_count(), 100);
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_retain() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..2000 {
+        db.put(format!("key:{:04x}", num), format!("{}", num))?;
+    }
+
+    let is_kept = |num: u32| !(500..1500).contains(&num) && num % 5 != 4;
+
+    // Drops whole leaves in the middle and scattered keys elsewhere
+    db.retain(|_key, value| is_kept(std::str::from_utf8(value).unwrap().parse().unwrap()))?;
+    db.verify(|_, _| {})?;
+
+    let mut count = 0;
+
+    for num in 0..2000 {
+        let value = db.get(format!("key:{:04x}", num))?;
+        let expected = is_kept(num);
+
+        assert_eq!(value.is_some(), expected);
+
+        if expected {
+            count += 1;
+        }
+    }
+
+    assert_eq!(db.metadata().key_value_count(), count);
+    assert_eq!(db.cursor()?.count() as u64, count);
+
+    db.retain(|_, _| false)?;
+    db.verify(|_, _| {})?;
+
+    assert_eq!(db.metadata().key_value_count(), 0);
+    assert_eq!(db.cursor()?.count(), 0);
+
+    db.put("key", "value")?;
+    assert_eq!(db.get("key")?, Some(b"value".to_vec()));
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_many() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..2000 {
+        db.put(format!("key:{:04x}", num), "hello world")?;
+    }
+
+    let mut rng = XorShiftRng::seed_from_u64(1);
+    let mut removed_keys: Vec<u32> = (0..3000).map(|_| rng.gen_range(0..2500)).collect();
+    removed_keys.extend(100..300);
+
+    db.remove_many(removed_keys.iter().map(|num| format!("key:{:04x}", num)))?;
+    db.verify(|_, _| {})?;
+
+    let mut count = 0;
+
+    for num in 0..2000 {
+        let value = db.get(format!("key:{:04x}", num))?;
+
+        assert_eq!(value.is_some(), !removed_keys.contains(&num));
+
+        if value.is_some() {
+            count += 1;
+        }
+    }
+
+    assert_eq!(db.metadata().key_value_count(), count);
+
+    db.remove_many((0..2000).map(|num| format!("key:{:04x}", num)))?;
+    db.verify(|_, _| {})?;
+
+    assert_eq!(db.metadata().key_value_count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_cursor_after_removed_leaf_nodes() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..2000 {
+        db.put(format!("key:{:04}", num), "hello world")?;
+    }
+
+    // Removes whole leaf nodes and their parent internal nodes
+    for num in 100..300 {
+        db.remove(format!("key:{:04}", num))?;
+    }
+
+    let mut cursor = db.cursor()?;
+
+    assert_eq!(cursor.by_ref().count(), 1800);
+    assert!(cursor.error().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_range() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..2000 {
+        db.put(format!("key:{:04}", num), "hello world")?;
+    }
+
+    let count = db.remove_range("key:0100".."key:0300")?;
+    db.verify(|_, _| {})?;
+
+    assert_eq!(count, 200);
+    assert_eq!(db.metadata().key_value_count(), 1800);
+    assert!(db.get("key:0099")?.is_some());
+    assert!(db.get("key:0100")?.is_none());
+    assert!(db.get("key:0299")?.is_none());
+    assert!(db.get("key:0300")?.is_some());
+
+    assert_eq!(db.remove_range("key:0100".."key:0300")?, 0);
+
+    let count = db.remove_range::<&str, _>(..)?;
+    db.verify(|_, _| {})?;
+
+    assert_eq!(count, 1800);
+    assert_eq!(db.metadata().key_value_count(), 0);
+
+    Ok(())
+}
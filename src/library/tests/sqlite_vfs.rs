@@ -0,0 +1,39 @@
+#![cfg(feature = "sqlite")]
+
+use grebedb::{vfs::SqliteVfs, Database, Error, Options};
+
+mod common;
+
+#[test]
+fn test_sqlite_vfs_database() -> anyhow::Result<()> {
+    let temp_dir = common::make_tempdir();
+    let path = temp_dir.path().join("my_database.sqlite");
+    let options = Options {
+        keys_per_node: 16,
+        ..Default::default()
+    };
+
+    let mut db = Database::open(Box::new(SqliteVfs::open(&path)?), options.clone())?;
+
+    for num in 0..500 {
+        db.put(format!("key:{:04x}", num), format!("value:{}", num))?;
+    }
+    db.flush()?;
+
+    // The lock is held on the whole SQLite file
+    assert!(matches!(
+        SqliteVfs::open(&path)
+            .and_then(|vfs| Database::open(Box::new(vfs), options.clone()).map(|_| ())),
+        Err(Error::Locked)
+    ));
+
+    drop(db);
+
+    let mut db = Database::open(Box::new(SqliteVfs::open(&path)?), options)?;
+
+    assert_eq!(db.metadata().key_value_count(), 500);
+    assert_eq!(db.get("key:0010")?, Some(b"value:16".to_vec()));
+    db.verify(|_, _| {})?;
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "async")]
+
+mod common;
+
+use futures::{executor::block_on, StreamExt};
+use grebedb::{
+    vfs::{MemoryVfs, Vfs},
+    Database, Options,
+};
+
+#[test]
+fn test_cursor_into_blocking_stream() -> anyhow::Result<()> {
+    let vfs = MemoryVfs::new();
+    let options = Options {
+        keys_per_node: 8,
+        page_cache_size: 4,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    for num in 0..200 {
+        database.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    database.flush()?;
+
+    block_on(async {
+        let mut stream = database
+            .cursor_range("00000010".."00000020")?
+            .into_blocking_stream();
+        let mut keys = Vec::new();
+
+        while let Some(item) = stream.next().await {
+            let (key, value) = item?;
+            assert_eq!(value, b"hello world");
+            keys.push(String::from_utf8(key)?);
+        }
+
+        assert_eq!(keys.len(), 16);
+        assert_eq!(keys.first().unwrap(), "00000010");
+
+        anyhow::Ok(())
+    })?;
+
+    drop(database);
+
+    // The page with the largest ID is a leaf created by the last split
+    let mut vfs_mut = vfs.clone();
+    let page_paths: Vec<String> = common::list_files(&vfs, "")
+        .into_iter()
+        .filter(|path| path.contains('/'))
+        .collect();
+    let last_page_prefix = page_paths.iter().max().unwrap().rsplit_once('_').unwrap().0;
+
+    for path in page_paths
+        .iter()
+        .filter(|path| path.starts_with(last_page_prefix))
+    {
+        vfs_mut.remove_file(path)?;
+    }
+
+    let mut database = Database::open(Box::new(vfs), options)?;
+    let results: Vec<_> = block_on(database.cursor()?.into_blocking_stream().collect());
+
+    assert!(results.len() < 200);
+    assert!(results.last().unwrap().is_err());
+    assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "testing")]
+
+use std::time::Duration;
+
+use grebedb::{testing::MockClock, Database, Options};
+
+#[test]
+fn test_mock_clock_automatic_flush() -> anyhow::Result<()> {
+    let clock = MockClock::new();
+    let options = Options {
+        automatic_flush_threshold: 10,
+        automatic_flush_long_interval: Duration::from_secs(300),
+        automatic_flush_short_interval: Duration::from_secs(60),
+        clock: Some(clock.clone()),
+        ..Default::default()
+    };
+    let mut db = Database::open_memory(options)?;
+
+    for num in 0..50 {
+        db.put(format!("{:08x}", num), "hello world")?;
+    }
+
+    assert_eq!(db.stats().flush().count(), 0);
+
+    clock.advance(Duration::from_secs(60));
+    db.put("a", "hello world")?;
+
+    assert_eq!(db.stats().flush().count(), 1);
+
+    for num in 0..15 {
+        db.put(format!("b{:08x}", num), "hello world")?;
+    }
+
+    clock.advance(Duration::from_secs(60));
+    db.put("c", "hello world")?;
+
+    assert_eq!(db.stats().flush().count(), 1);
+
+    clock.advance(Duration::from_secs(240));
+    db.put("d", "hello world")?;
+
+    assert_eq!(db.stats().flush().count(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_uuid_seed() -> anyhow::Result<()> {
+    let options = Options {
+        uuid_seed: Some(123),
+        ..Default::default()
+    };
+
+    let mut db1 = Database::open_memory(options.clone())?;
+    let mut db2 = Database::open_memory(options)?;
+
+    assert_eq!(db1.metadata().uuid(), db2.metadata().uuid());
+    assert_ne!(db1.metadata().uuid(), uuid::Uuid::nil().to_string());
+
+    let uuid = db1.metadata().uuid();
+    db1.regenerate_uuid(|_, _| {})?;
+    db2.regenerate_uuid(|_, _| {})?;
+
+    assert_ne!(db1.metadata().uuid(), uuid);
+    assert_eq!(db1.metadata().uuid(), db2.metadata().uuid());
+
+    let options = Options {
+        uuid_seed: Some(456),
+        ..Default::default()
+    };
+    let db3 = Database::open_memory(options)?;
+
+    assert_ne!(db3.metadata().uuid(), uuid);
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use grebedb::{collections::VersionedMap, Database, Error, Options};
+
+#[test]
+fn test_versioned_map() -> anyhow::Result<()> {
+    let options = Options {
+        keys_per_node: 16,
+        ..Default::default()
+    };
+    let mut map = VersionedMap::new(Database::open_memory(options)?, 3)?;
+
+    for round in 0..5 {
+        for num in 0..50 {
+            let version = map.put(format!("key:{:02x}", num), format!("round {}", round))?;
+            assert_eq!(version, round);
+        }
+    }
+
+    map.put("key", "prefix of other keys")?;
+
+    assert_eq!(map.get("key:00")?, Some(b"round 4".to_vec()));
+    assert_eq!(map.get_version("key:00", 2)?, Some(b"round 2".to_vec()));
+    assert_eq!(map.get_version("key:00", 3)?, None);
+    assert_eq!(map.get("key")?, Some(b"prefix of other keys".to_vec()));
+    assert_eq!(map.get("missing")?, None);
+
+    let mut history = map.history("key:01")?;
+    let versions: Vec<(u64, Vec<u8>)> = history.by_ref().collect();
+    assert!(history.error().is_none());
+    assert_eq!(
+        versions,
+        vec![
+            (4, b"round 4".to_vec()),
+            (3, b"round 3".to_vec()),
+            (2, b"round 2".to_vec())
+        ]
+    );
+
+    assert_eq!(map.database().metadata().key_value_count(), 251);
+    assert_eq!(map.compact()?, 100);
+    assert_eq!(map.compact()?, 0);
+    assert_eq!(map.database().metadata().key_value_count(), 151);
+    assert_eq!(map.get_version("key:00", 2)?, Some(b"round 2".to_vec()));
+
+    // Version numbers continue after compaction
+    assert_eq!(map.put("key:00", "round 5")?, 5);
+
+    map.remove("key:01")?;
+    assert_eq!(map.get("key:01")?, None);
+    assert_eq!(map.put("key:01", "new")?, 0);
+
+    let mut db = map.into_inner();
+    db.verify(|_, _| {})?;
+
+    assert!(matches!(
+        VersionedMap::new(db, 0),
+        Err(Error::InvalidConfig { .. })
+    ));
+
+    Ok(())
+}
@@ -1,9 +1,15 @@
 mod common;
 
-use std::sync::atomic::Ordering;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 use common::CrashingVfs;
-use grebedb::{Database, Options};
+use grebedb::{
+    vfs::{MemoryVfs, Vfs, VfsSyncOption},
+    CompressionLevel, Database, DropFailure, DropPolicy, Options,
+};
 
 #[test]
 fn test_crash_before_metadata_commit() {
@@ -108,3 +114,112 @@ fn test_crash_after_metadata_commit() {
         Some("new value".to_string())
     );
 }
+
+#[test]
+fn test_torn_write_rollback() -> anyhow::Result<()> {
+    let mut vfs = MemoryVfs::new();
+    let options = Options {
+        compression_level: CompressionLevel::None,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options.clone())?;
+
+    database.put("key", "hello world")?;
+    database.flush()?;
+    drop(database);
+
+    // Simulate newer page files that were interrupted while being written
+    for path in common::list_files(&vfs, "") {
+        if path.ends_with("_0.grebedb") && !path.contains("meta") {
+            let mut data = vfs.read(&path)?;
+            let last_index = data.len() - 1;
+            data[last_index] ^= 0xff;
+
+            let torn_path = path.replace("_0.grebedb", "_1.grebedb");
+            vfs.write(&torn_path, &data, VfsSyncOption::None)?;
+        }
+    }
+
+    let mut database = Database::open(Box::new(vfs), options)?;
+
+    assert!(database.recovered_pages().is_empty());
+    assert_eq!(database.get("key")?, Some(b"hello world".to_vec()));
+    assert!(!database.recovered_pages().is_empty());
+
+    database.verify(|_, _| {})?;
+
+    Ok(())
+}
+
+#[test]
+fn test_reopen_after_error() {
+    let vfs = CrashingVfs::new();
+    let options = Options {
+        keys_per_node: 128,
+        page_cache_size: 4,
+        automatic_flush: false,
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options).unwrap();
+
+    for num in 0..2000 {
+        database
+            .put(format!("key:{:04x}", num), "hello world")
+            .unwrap();
+
+        if num == 1000 {
+            database.flush().unwrap();
+        }
+    }
+
+    database.put("key:0000", "new value").unwrap();
+
+    vfs.metadata_rename_crash.store(true, Ordering::Relaxed);
+    database.flush().unwrap_err();
+
+    assert!(matches!(
+        database.put("key:0000", "new value"),
+        Err(grebedb::Error::Closed)
+    ));
+    assert!(database.last_error().unwrap().contains("crash"));
+
+    vfs.metadata_rename_crash.store(false, Ordering::Relaxed);
+    database.reopen().unwrap();
+
+    assert!(database.last_error().is_none());
+    assert_eq!(
+        database
+            .get("key:0000")
+            .unwrap()
+            .map(|item| String::from_utf8(item).unwrap()),
+        Some("hello world".to_string())
+    );
+    assert_eq!(database.get("key:07A0").unwrap(), None);
+    assert_eq!(database.metadata().key_value_count(), 1001);
+
+    database.put("key:07A0", "new value").unwrap();
+    database.flush().unwrap();
+    database.verify(|_, _| {}).unwrap();
+}
+
+#[test]
+fn test_drop_policy_flush_failed() {
+    let vfs = CrashingVfs::new();
+    let called = Arc::new(AtomicBool::new(false));
+    let called_clone = called.clone();
+    let options = Options {
+        drop_policy: DropPolicy::Callback(Arc::new(move |failure| {
+            assert!(matches!(failure, DropFailure::FlushFailed(_)));
+            called_clone.store(true, Ordering::Relaxed);
+        })),
+        ..Default::default()
+    };
+    let mut database = Database::open(Box::new(vfs.clone()), options).unwrap();
+
+    database.put("key", "value").unwrap();
+
+    vfs.metadata_rename_crash.store(true, Ordering::Relaxed);
+    drop(database);
+
+    assert!(called.load(Ordering::Relaxed));
+}
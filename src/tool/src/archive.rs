@@ -0,0 +1,256 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Debug,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    sync::{Mutex, PoisonError},
+};
+
+use anyhow::Context;
+use grebedb::{
+    export::DecompressedReader,
+    vfs::{Vfs, VfsSyncOption},
+    Database, Error, OpenMode, Options,
+};
+
+/// Open a database directory, or a backup archive file read-only.
+pub fn open_database(path: &Path, options: Options) -> anyhow::Result<Database> {
+    if path.is_file() {
+        let options = Options {
+            open_mode: OpenMode::ReadOnly,
+            ..options
+        };
+        let vfs =
+            ArchiveVfs::open(path).with_context(|| format!("could not read archive {:?}", path))?;
+
+        Ok(Database::open(Box::new(vfs), options)?)
+    } else {
+        Ok(Database::open_path(path, options)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ArchiveEntry {
+    offset: u64,
+    length: u64,
+}
+
+/// Read-only file system of the files in a tar archive written by the backup
+/// command.
+///
+/// The positions of the members are indexed when opened and their contents
+/// are read on demand. A compressed archive is first decompressed to an
+/// anonymous temporary file because a compressed stream can't be seeked.
+pub struct ArchiveVfs {
+    file: Mutex<File>,
+    files: BTreeMap<String, ArchiveEntry>,
+    dirs: BTreeSet<String>,
+}
+
+impl ArchiveVfs {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = DecompressedReader::new(BufReader::new(File::open(path)?))?;
+
+        let file = if reader.is_compressed() {
+            let mut file = tempfile::tempfile()?;
+            std::io::copy(&mut reader, &mut file)?;
+            file
+        } else {
+            File::open(path)?
+        };
+
+        let mut vfs = Self {
+            file: Mutex::new(file),
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+        };
+
+        vfs.load_index()?;
+
+        Ok(vfs)
+    }
+
+    fn load_index(&mut self) -> anyhow::Result<()> {
+        let file = self.file.get_mut().unwrap_or_else(PoisonError::into_inner);
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut archive = tar::Archive::new(&*file);
+
+        for entry in archive.entries_with_seek()? {
+            let entry = entry?;
+            let path = entry
+                .path()?
+                .to_str()
+                .context("archive member path is not UTF-8")?
+                .trim_end_matches('/')
+                .to_string();
+
+            match entry.header().entry_type() {
+                tar::EntryType::Regular => {
+                    let mut parent = path.rsplit_once('/').map(|(parent, _)| parent);
+
+                    while let Some(dir_path) = parent {
+                        self.dirs.insert(dir_path.to_string());
+                        parent = dir_path.rsplit_once('/').map(|(parent, _)| parent);
+                    }
+
+                    self.files.insert(
+                        path,
+                        ArchiveEntry {
+                            offset: entry.raw_file_position(),
+                            length: entry.size(),
+                        },
+                    );
+                }
+                tar::EntryType::Directory => {
+                    self.dirs.insert(path);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_entry(
+        &self,
+        entry: ArchiveEntry,
+        offset: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, Error> {
+        if offset.saturating_add(length as u64) > entry.length {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "range past end of file",
+            )));
+        }
+
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut data = vec![0; length];
+
+        file.seek(SeekFrom::Start(entry.offset + offset))?;
+        file.read_exact(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+impl Vfs for ArchiveVfs {
+    fn lock(&mut self, _path: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn unlock(&mut self, _path: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let entry = *self.files.get(path).ok_or_else(make_not_found_error)?;
+
+        self.read_entry(entry, 0, entry.length as usize)
+    }
+
+    fn write(
+        &mut self,
+        _path: &str,
+        _data: &[u8],
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn sync_file(&mut self, _path: &str, _sync_option: VfsSyncOption) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn remove_file(&mut self, _path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<String>, Error> {
+        if !path.is_empty() && !self.dirs.contains(path) {
+            return Err(make_not_found_error());
+        }
+
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", path)
+        };
+
+        let children = self
+            .dirs
+            .range(prefix.clone()..)
+            .chain(self.files.range(prefix.clone()..).map(|(key, _)| key))
+            .filter_map(|child| child.strip_prefix(&prefix))
+            .filter(|filename| !filename.is_empty() && !filename.contains('/'))
+            .map(|filename| filename.to_string())
+            .collect();
+
+        Ok(children)
+    }
+
+    fn create_dir(&mut self, _path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn remove_dir(&mut self, _path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn rename_file(&mut self, _old_path: &str, _new_path: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn is_dir(&self, path: &str) -> Result<bool, Error> {
+        if path.is_empty() || self.dirs.contains(path) {
+            Ok(true)
+        } else if self.files.contains_key(path) {
+            Ok(false)
+        } else {
+            Err(make_not_found_error())
+        }
+    }
+
+    fn exists(&self, path: &str) -> Result<bool, Error> {
+        Ok(path.is_empty() || self.dirs.contains(path) || self.files.contains_key(path))
+    }
+
+    fn append(
+        &mut self,
+        _path: &str,
+        _data: &[u8],
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    fn read_range(&self, path: &str, offset: u64, length: usize) -> Result<Vec<u8>, Error> {
+        let entry = *self.files.get(path).ok_or_else(make_not_found_error)?;
+
+        self.read_entry(entry, offset, length)
+    }
+
+    fn link_file(
+        &mut self,
+        _old_path: &str,
+        _new_path: &str,
+        _sync_option: VfsSyncOption,
+    ) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+}
+
+impl Debug for ArchiveVfs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ArchiveVfs {{ files: {} }}", self.files.len())
+    }
+}
+
+fn make_not_found_error() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "file not found",
+    ))
+}
@@ -0,0 +1,156 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use grebedb::{
+    export::{CompressedWriter, DecompressedReader, ExportCompression},
+    Database, Error, OpenMode, Options,
+};
+
+use crate::progress::ProgressStyle;
+
+const MAX_ATTEMPTS: usize = 10;
+
+pub fn backup(
+    database_path: &Path,
+    archive_path: &Path,
+    compression: ExportCompression,
+    options: Options,
+) -> anyhow::Result<()> {
+    // The lock would prevent the backup while another process uses the database
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        file_locking: false,
+        ..options
+    };
+
+    let mut database = Database::open_path(database_path, options)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(archive_path)?;
+    let mut attempt = 1;
+
+    let result = loop {
+        let result = write_archive(&mut database, &file, compression);
+
+        match &result {
+            Err(error)
+                if attempt < MAX_ATTEMPTS
+                    && matches!(error.downcast_ref(), Some(Error::ExternallyModified)) =>
+            {
+                eprintln!("Retrying after the database was modified");
+
+                file.set_len(0)?;
+                file.seek(SeekFrom::Start(0))?;
+                database.reopen()?;
+                attempt += 1;
+            }
+            _ => break result,
+        }
+    };
+
+    match result {
+        Ok(file_count) => {
+            file.sync_all()?;
+
+            eprintln!(
+                "Backed up revision {} with {} files",
+                database.revision(),
+                file_count
+            );
+
+            Ok(())
+        }
+        Err(error) => {
+            drop(file);
+            std::fs::remove_file(archive_path)?;
+
+            Err(error)
+        }
+    }
+}
+
+// Returns the number of files written
+fn write_archive(
+    database: &mut Database,
+    file: &File,
+    compression: ExportCompression,
+) -> anyhow::Result<usize> {
+    let writer = CompressedWriter::new(BufWriter::new(file.try_clone()?), compression)?;
+    let mut builder = tar::Builder::new(writer);
+    let mut file_count = 0;
+    let mtime = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    database.backup_files(|path, data| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+
+        builder.append_data(&mut header, path, data)?;
+        file_count += 1;
+
+        Ok(())
+    })?;
+
+    builder.into_inner()?.finish()?.flush()?;
+
+    Ok(file_count)
+}
+
+pub fn restore(archive_path: &Path, target_path: &Path, options: Options) -> anyhow::Result<()> {
+    create_empty_dir(target_path)?;
+
+    let file = BufReader::new(File::open(archive_path)?);
+    let mut archive = tar::Archive::new(DecompressedReader::new(file)?);
+
+    archive.unpack(target_path)?;
+
+    validate_restored_database(target_path, options)
+}
+
+pub fn create_empty_dir(path: &Path) -> anyhow::Result<()> {
+    if path.exists() && path.read_dir()?.next().is_some() {
+        anyhow::bail!("directory {:?} is not empty", path);
+    }
+
+    std::fs::create_dir_all(path)?;
+
+    Ok(())
+}
+
+// Verify and check a database that was written from an archive or pack file
+pub fn validate_restored_database(path: &Path, options: Options) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+    let mut database =
+        Database::open_path(path, options).context("restored files are not a database")?;
+
+    crate::verify::verify_database(&mut database, ProgressStyle::Hidden)?;
+
+    let report = database.fsck()?;
+
+    if !report.is_ok() {
+        crate::fsck::print_issues(&report);
+        anyhow::bail!("restored database has {} problems", report.issues().len());
+    }
+
+    eprintln!(
+        "Restored revision {} with {} key-value pairs",
+        database.revision(),
+        database.metadata().key_value_count()
+    );
+
+    Ok(())
+}
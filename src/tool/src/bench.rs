@@ -0,0 +1,262 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use grebedb::{Database, OpenMode, Options};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDistribution {
+    Sequential,
+    Random,
+}
+
+impl KeyDistribution {
+    fn name(&self) -> &'static str {
+        match self {
+            KeyDistribution::Sequential => "sequential",
+            KeyDistribution::Random => "random",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub path: Option<PathBuf>,
+    pub batches: usize,
+    pub max_batch_size: usize,
+    pub rounds: usize,
+    pub value_size: usize,
+    pub distributions: Vec<KeyDistribution>,
+    pub result_format: ResultFormat,
+}
+
+struct Workload {
+    distribution: KeyDistribution,
+    database: Database,
+    path: Option<PathBuf>,
+    next_id: u64,
+    rng: XorShiftRng,
+}
+
+#[derive(Default)]
+struct Timings {
+    insert: Vec<Duration>,
+    flush: Vec<Duration>,
+    read: Vec<Duration>,
+}
+
+pub fn bench(bench_options: BenchOptions, options: Options) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: OpenMode::CreateOnly,
+        automatic_flush: false,
+        ..options
+    };
+    let mut workloads = Vec::new();
+
+    for (index, &distribution) in bench_options.distributions.iter().enumerate() {
+        let (database, path) = match &bench_options.path {
+            Some(path) => {
+                let path = path.join(format!("grebedb_bench_{}", distribution.name()));
+                std::fs::create_dir_all(path.parent().unwrap())?;
+                std::fs::create_dir(&path)?;
+
+                (Database::open_path(&path, options.clone())?, Some(path))
+            }
+            None => (Database::open_memory(options.clone())?, None),
+        };
+
+        workloads.push(Workload {
+            distribution,
+            database,
+            path,
+            next_id: 0,
+            rng: XorShiftRng::new(index as u64 + 1),
+        });
+    }
+
+    if bench_options.result_format == ResultFormat::Csv {
+        println!("batch_size,rounds,distribution,operation,median_seconds,pairs_per_second,key_value_count");
+    }
+
+    for batch_id in 0..bench_options.batches {
+        let batch_size = (batch_id + 1)
+            .saturating_pow(4)
+            .min(bench_options.max_batch_size);
+
+        if bench_options.result_format == ResultFormat::Text {
+            println!("Batch size {}, rounds {}", batch_size, bench_options.rounds);
+        }
+
+        for workload in &mut workloads {
+            let mut timings = Timings::default();
+
+            for _ in 0..bench_options.rounds {
+                run_round(workload, batch_size, bench_options.value_size, &mut timings)?;
+            }
+
+            print_results(&bench_options, workload, batch_size, timings);
+        }
+
+        std::thread::sleep(Duration::from_secs_f32(0.1));
+    }
+
+    for workload in workloads {
+        drop(workload.database);
+
+        if let Some(path) = workload.path {
+            std::fs::remove_dir_all(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_round(
+    workload: &mut Workload,
+    batch_size: usize,
+    value_size: usize,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    let mut pairs = Vec::with_capacity(batch_size);
+
+    for _ in 0..batch_size {
+        let id = match workload.distribution {
+            KeyDistribution::Sequential => {
+                workload.next_id += 1;
+                workload.next_id
+            }
+            KeyDistribution::Random => workload.rng.next_u64(),
+        };
+
+        let mut value = vec![0u8; value_size];
+        workload.rng.fill_bytes(&mut value);
+
+        pairs.push((format!("{:016x}", id), value));
+    }
+
+    let database = &mut workload.database;
+    let time_start = Instant::now();
+
+    for (key, value) in &pairs {
+        database.put(key.as_str(), value.as_slice())?;
+    }
+
+    timings.insert.push(time_start.elapsed());
+
+    let time_start = Instant::now();
+
+    database.flush()?;
+
+    timings.flush.push(time_start.elapsed());
+
+    let time_start = Instant::now();
+
+    for (key, _value) in &pairs {
+        database.get(key)?;
+    }
+
+    timings.read.push(time_start.elapsed());
+
+    Ok(())
+}
+
+fn print_results(
+    bench_options: &BenchOptions,
+    workload: &Workload,
+    batch_size: usize,
+    timings: Timings,
+) {
+    let key_value_count = workload.database.metadata().key_value_count();
+    let distribution = workload.distribution.name();
+
+    if bench_options.result_format == ResultFormat::Text {
+        println!("  {} (total {})", distribution, key_value_count);
+    }
+
+    for (operation, durations) in [
+        ("insert", timings.insert),
+        ("flush", timings.flush),
+        ("read", timings.read),
+    ] {
+        let median = median_seconds(durations);
+        let rate = batch_size as f64 / median;
+
+        match bench_options.result_format {
+            ResultFormat::Text => {
+                println!(
+                    "    {}\t{:.6} s/batch\t{:.2} pairs/s",
+                    operation, median, rate
+                )
+            }
+            ResultFormat::Csv => println!(
+                "{},{},{},{},{:.6},{:.2},{}",
+                batch_size,
+                bench_options.rounds,
+                distribution,
+                operation,
+                median,
+                rate,
+                key_value_count
+            ),
+            ResultFormat::Json => println!(
+                "{}",
+                serde_json::json!({
+                    "batch_size": batch_size,
+                    "rounds": bench_options.rounds,
+                    "distribution": distribution,
+                    "operation": operation,
+                    "median_seconds": median,
+                    "pairs_per_second": rate,
+                    "key_value_count": key_value_count,
+                })
+            ),
+        }
+    }
+}
+
+fn median_seconds(mut durations: Vec<Duration>) -> f64 {
+    durations.sort_unstable();
+
+    let middle = durations.len() / 2;
+
+    if durations.len().is_multiple_of(2) {
+        (durations[middle - 1] + durations[middle]).as_secs_f64() / 2.0
+    } else {
+        durations[middle].as_secs_f64()
+    }
+}
+
+// Fast non-cryptographic generator for keys and incompressible values
+struct XorShiftRng {
+    state: u64,
+}
+
+impl XorShiftRng {
+    fn new(seed: u64) -> Self {
+        Self {
+            state: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn fill_bytes(&mut self, buffer: &mut [u8]) {
+        for chunk in buffer.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use grebedb::{vfs::OsVfs, CompressionLevel, Database, OpenMode, Options};
+
+use crate::{
+    progress::{ProgressBar, ProgressStyle},
+    stats::disk_usage,
+};
+
+pub fn compact(
+    database_path: &Path,
+    target_path: Option<&Path>,
+    compression_level: Option<CompressionLevel>,
+    keys_per_node: Option<usize>,
+    progress_style: ProgressStyle,
+    options: Options,
+) -> anyhow::Result<()> {
+    let size_before = disk_usage(database_path)?;
+    let keys_per_node = match keys_per_node {
+        Some(keys_per_node) => keys_per_node,
+        None => saved_keys_per_node(database_path, &options)?.unwrap_or(options.keys_per_node),
+    };
+    let new_options = Options {
+        open_mode: OpenMode::LoadOnly,
+        compression_level: compression_level.unwrap_or(options.compression_level),
+        keys_per_node,
+        ..options.clone()
+    };
+
+    let size_after = if let Some(target_path) = target_path {
+        let options = Options {
+            open_mode: OpenMode::ReadOnly,
+            ..options
+        };
+        let mut database = Database::open_path(database_path, options)?;
+
+        std::fs::create_dir_all(target_path)?;
+        database.clone_to(OsVfs::new(target_path), new_options.clone())?;
+
+        compact_database(target_path, progress_style, new_options)?
+    } else {
+        compact_database(database_path, progress_style, new_options)?
+    };
+
+    println!("Size before: {} bytes", size_before);
+    println!("Size after: {} bytes", size_after);
+    println!(
+        "Reclaimed: {} bytes",
+        size_before as i64 - size_after as i64
+    );
+
+    Ok(())
+}
+
+// The node size that the database was last modified with, so that the nodes
+// are rebuilt to the same size
+fn saved_keys_per_node(database_path: &Path, options: &Options) -> anyhow::Result<Option<usize>> {
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options.clone()
+    };
+    let database = Database::open_path(database_path, options)?;
+
+    Ok(database.metadata().keys_per_node())
+}
+
+pub fn compact_database(
+    database_path: &Path,
+    progress_style: ProgressStyle,
+    options: Options,
+) -> anyhow::Result<u64> {
+    let mut database = Database::open_path(database_path, options)?;
+    let mut progress_bar = ProgressBar::new(progress_style, "Compacting", "pages");
+
+    database.compact_with_progress(|current, total| {
+        progress_bar.update(current as u64, Some(total as u64));
+    })?;
+    progress_bar.finish();
+    database.purge_free_pages()?;
+    drop(database);
+
+    disk_usage(database_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_keeps_keys_per_node() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let options = Options {
+            keys_per_node: 8,
+            ..Default::default()
+        };
+        let mut database = Database::open_path(dir.path(), options)?;
+
+        for num in 0..1000 {
+            database.put(format!("key:{:04}", num), "hello world!")?;
+        }
+
+        database.flush()?;
+        drop(database);
+
+        compact(
+            dir.path(),
+            None,
+            None,
+            None,
+            ProgressStyle::Hidden,
+            Options::default(),
+        )?;
+
+        let options = Options {
+            open_mode: OpenMode::ReadOnly,
+            ..Default::default()
+        };
+        let mut database = Database::open_path(dir.path(), options)?;
+        let tree_stats = database.tree_stats()?;
+
+        assert_eq!(database.metadata().keys_per_node(), Some(8));
+        assert_eq!(tree_stats.level_page_counts().last(), Some(&125));
+
+        Ok(())
+    }
+}
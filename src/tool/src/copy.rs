@@ -0,0 +1,33 @@
+use std::path::Path;
+
+use grebedb::{vfs::OsVfs, CompressionLevel, Database, OpenMode, Options};
+
+use crate::{compact::compact_database, progress::ProgressStyle};
+
+pub fn copy(
+    source_path: &Path,
+    target_path: &Path,
+    compression_level: Option<CompressionLevel>,
+    keys_per_node: Option<usize>,
+    options: Options,
+) -> anyhow::Result<()> {
+    let target_options = Options {
+        open_mode: OpenMode::LoadOnly,
+        compression_level: compression_level.unwrap_or(options.compression_level),
+        keys_per_node: keys_per_node.unwrap_or(options.keys_per_node),
+        ..options.clone()
+    };
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+    let mut database = Database::open_path(source_path, options)?;
+
+    std::fs::create_dir_all(target_path)?;
+    database.clone_to(OsVfs::new(target_path), target_options.clone())?;
+
+    // Rebuilding the tree splits or merges the copied nodes to the new size
+    compact_database(target_path, ProgressStyle::Hidden, target_options)?;
+
+    Ok(())
+}
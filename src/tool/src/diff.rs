@@ -0,0 +1,97 @@
+use std::{cmp::Ordering, path::Path};
+
+use grebedb::{OpenMode, Options};
+
+use crate::repl::encoding::{binary_to_text, Encoding};
+
+#[derive(Debug, Default)]
+struct DiffCounts {
+    only_a: u64,
+    only_b: u64,
+    different: u64,
+}
+
+// Returns whether the databases contain the same pairs
+pub fn diff(
+    path_a: &Path,
+    path_b: &Path,
+    show_values: bool,
+    encoding: Encoding,
+    options: Options,
+) -> anyhow::Result<bool> {
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+
+    let mut database_a = crate::archive::open_database(path_a, options.clone())?;
+    let mut database_b = crate::archive::open_database(path_b, options)?;
+    let mut cursor_a = database_a.cursor()?;
+    let mut cursor_b = database_b.cursor()?;
+
+    let (mut key_a, mut value_a) = (Vec::new(), Vec::new());
+    let (mut key_b, mut value_b) = (Vec::new(), Vec::new());
+    let mut has_a = cursor_a.next_buf(&mut key_a, &mut value_a)?;
+    let mut has_b = cursor_b.next_buf(&mut key_b, &mut value_b)?;
+    let mut counts = DiffCounts::default();
+
+    let encode = |value: &[u8]| binary_to_text(value, encoding);
+
+    while has_a || has_b {
+        let ordering = match (has_a, has_b) {
+            (true, true) => key_a.cmp(&key_b),
+            (true, false) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+
+        match ordering {
+            Ordering::Less => {
+                counts.only_a += 1;
+
+                if show_values {
+                    println!("< {}\t{}", encode(&key_a), encode(&value_a));
+                } else {
+                    println!("< {}", encode(&key_a));
+                }
+            }
+            Ordering::Greater => {
+                counts.only_b += 1;
+
+                if show_values {
+                    println!("> {}\t{}", encode(&key_b), encode(&value_b));
+                } else {
+                    println!("> {}", encode(&key_b));
+                }
+            }
+            Ordering::Equal => {
+                if value_a != value_b {
+                    counts.different += 1;
+
+                    if show_values {
+                        println!(
+                            "! {}\t{}\t{}",
+                            encode(&key_a),
+                            encode(&value_a),
+                            encode(&value_b)
+                        );
+                    } else {
+                        println!("! {}", encode(&key_a));
+                    }
+                }
+            }
+        }
+
+        if ordering != Ordering::Greater {
+            has_a = cursor_a.next_buf(&mut key_a, &mut value_a)?;
+        }
+        if ordering != Ordering::Less {
+            has_b = cursor_b.next_buf(&mut key_b, &mut value_b)?;
+        }
+    }
+
+    eprintln!("Only in A: {}", counts.only_a);
+    eprintln!("Only in B: {}", counts.only_b);
+    eprintln!("Different values: {}", counts.different);
+
+    Ok(counts.only_a == 0 && counts.only_b == 0 && counts.different == 0)
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use grebedb::{DiskUsage, OpenMode, Options, PageDiskUsage};
+
+pub fn du(database_path: &Path, top_count: usize, options: Options) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+
+    let mut database = crate::archive::open_database(database_path, options)?;
+    let usage = database.disk_usage()?;
+
+    print_levels(&usage);
+    println!();
+    print_histogram(&usage);
+    println!();
+    print_largest_pages(&usage, top_count);
+
+    Ok(())
+}
+
+fn print_levels(usage: &DiskUsage) {
+    let height = usage
+        .pages()
+        .last()
+        .map(|page| page.level() + 1)
+        .unwrap_or(0);
+
+    println!("Level\tPages\tFile bytes\tPayload bytes\tRatio");
+
+    for level in 0..height {
+        let pages = usage.pages().iter().filter(|page| page.level() == level);
+        let (count, file_size, payload_size) = sum_sizes(pages);
+
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            level,
+            count,
+            file_size,
+            payload_size,
+            format_ratio(payload_size, file_size)
+        );
+    }
+
+    let (count, file_size, payload_size) = sum_sizes(usage.pages().iter());
+
+    println!(
+        "Total\t{}\t{}\t{}\t{}",
+        count,
+        file_size,
+        payload_size,
+        format_ratio(payload_size, file_size)
+    );
+    println!("Metadata file: {} bytes", usage.metadata_size());
+    println!("Disk usage: {} bytes", usage.total_size());
+}
+
+// Count the pages by the power of two that their file size is rounded down to
+fn print_histogram(usage: &DiskUsage) {
+    let mut counts = Vec::new();
+
+    for page in usage.pages() {
+        let bucket = 63 - page.file_size().max(1).leading_zeros() as usize;
+
+        if counts.len() <= bucket {
+            counts.resize(bucket + 1, 0);
+        }
+
+        counts[bucket] += 1;
+    }
+
+    println!("File size\tPages");
+
+    for (bucket, count) in counts.iter().enumerate() {
+        if *count > 0 {
+            println!(
+                "{}-{}\t{}",
+                1u64 << bucket,
+                (1u64 << (bucket + 1)) - 1,
+                count
+            );
+        }
+    }
+}
+
+fn print_largest_pages(usage: &DiskUsage, top_count: usize) {
+    let mut pages: Vec<&PageDiskUsage> = usage.pages().iter().collect();
+    pages.sort_by_key(|page| std::cmp::Reverse(page.file_size()));
+
+    println!("Page\tLevel\tKeys\tFile bytes\tPayload bytes\tRatio\tLargest value");
+
+    for page in pages.iter().take(top_count) {
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            page.page_id(),
+            page.level(),
+            page.key_count(),
+            page.file_size(),
+            page.payload_size(),
+            format_ratio(page.payload_size(), page.file_size()),
+            page.largest_value_size()
+        );
+    }
+}
+
+fn sum_sizes<'a, I>(pages: I) -> (u64, u64, u64)
+where
+    I: Iterator<Item = &'a PageDiskUsage>,
+{
+    pages.fold((0, 0, 0), |(count, file_size, payload_size), page| {
+        (
+            count + 1,
+            file_size + page.file_size(),
+            payload_size + page.payload_size(),
+        )
+    })
+}
+
+fn format_ratio(payload_size: u64, file_size: u64) -> String {
+    if file_size > 0 {
+        format!("{:.2}", payload_size as f64 / file_size as f64)
+    } else {
+        "-".to_string()
+    }
+}
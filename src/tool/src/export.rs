@@ -1,99 +1,225 @@
 use std::{
     fs::{File, OpenOptions},
-    io::{BufReader, BufWriter, Read, Write},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
     path::Path,
 };
 
-use grebedb::{Database, OpenMode, Options};
+use grebedb::{
+    export::{
+        CompressedWriter, CsvOptions, DecompressedReader, ExportCompression, ExportOptions,
+        ImportOptions, Progress,
+    },
+    Database, OpenMode, Options,
+};
+
+use crate::progress::{ProgressBar, ProgressStyle};
+
+/// Format of the exported file.
+pub enum ExportFormat {
+    /// JSON text sequence or binary records. The number of threads is set
+    /// by the export function.
+    Records(ExportOptions),
+    Csv(CsvOptions),
+    /// SQLite database file written to the output path directly.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    SqliteScript,
+}
 
 pub fn dump(
     database_path: &Path,
     output_path: &Path,
-    compression: Option<i32>,
+    compression: ExportCompression,
+    format: ExportFormat,
+    threads: usize,
+    progress_style: ProgressStyle,
+    options: Options,
 ) -> anyhow::Result<()> {
     let options = Options {
         open_mode: OpenMode::ReadOnly,
-        ..Default::default()
+        ..options
     };
-    let mut database = Database::open_path(database_path, options)?;
+    let mut database = crate::archive::open_database(database_path, options)?;
+
+    #[cfg(feature = "sqlite")]
+    if let ExportFormat::Sqlite = format {
+        return dump_sqlite(&mut database, output_path, progress_style);
+    }
 
-    // TODO: this needs refactoring
     if output_path.as_os_str() != "-" {
-        let mut file = OpenOptions::new()
+        let file = OpenOptions::new()
             .write(true)
             .create_new(true)
             .open(output_path)?;
 
-        if let Some(compression) = compression {
-            #[cfg(feature = "zstd")]
-            {
-                let mut file = zstd::Encoder::new(&mut file, compression)?;
-                grebedb::export::export(&mut database, &mut file, |_| {})?;
-                file.finish()?;
-            }
-            #[cfg(not(feature = "zstd"))]
-            {
-                let _ = compression;
-                return Err(anyhow::anyhow!("Compression feature not enabled"));
-            }
-        } else {
-            grebedb::export::export(&mut database, &mut file, |_| {})?;
-        }
+        let mut file = CompressedWriter::new(file, compression)?;
+        write_export(&mut database, &mut file, &format, threads, progress_style)?;
 
+        let mut file = file.finish()?;
         file.flush()?;
         file.sync_all()?;
     } else {
-        let mut file = BufWriter::new(std::io::stdout());
-
-        if let Some(compression) = compression {
-            #[cfg(feature = "zstd")]
-            {
-                let mut file = zstd::Encoder::new(&mut file, compression)?;
-                grebedb::export::export(&mut database, &mut file, |_| {})?;
-                file.finish()?;
-            }
-            #[cfg(not(feature = "zstd"))]
-            {
-                let _ = compression;
-                return Err(anyhow::anyhow!("Compression feature not enabled"));
-            }
-        } else {
-            grebedb::export::export(&mut database, &mut file, |_| {})?;
+        let file = BufWriter::new(std::io::stdout());
+
+        let mut file = CompressedWriter::new(file, compression)?;
+        write_export(&mut database, &mut file, &format, threads, progress_style)?;
+
+        file.finish()?.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn dump_sqlite(
+    database: &mut Database,
+    output_path: &Path,
+    progress_style: ProgressStyle,
+) -> anyhow::Result<()> {
+    // Like the other formats, an existing file is not overwritten
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(output_path)?;
+
+    let mut progress_bar = ProgressBar::new(progress_style, "Exporting", "rows");
+    let progress =
+        |progress: &Progress| progress_bar.update(progress.rows(), progress.total_rows());
+
+    grebedb::export::export_sqlite(database, output_path, progress)?;
+
+    progress_bar.finish();
+
+    if !progress_style.is_hidden() {
+        eprintln!("OK");
+    }
+
+    Ok(())
+}
+
+fn write_export<W: Write>(
+    database: &mut Database,
+    file: &mut W,
+    format: &ExportFormat,
+    threads: usize,
+    progress_style: ProgressStyle,
+) -> anyhow::Result<()> {
+    let mut progress_bar = ProgressBar::new(progress_style, "Exporting", "rows");
+    let progress =
+        |progress: &Progress| progress_bar.update(progress.rows(), progress.total_rows());
+
+    match format {
+        ExportFormat::Records(export_options) => {
+            let export_options = ExportOptions {
+                threads,
+                ..export_options.clone()
+            };
+            grebedb::export::export_with_options(database, file, &export_options, progress)?
         }
-        file.flush()?;
+        ExportFormat::Csv(csv_options) => {
+            grebedb::export::export_csv(database, file, csv_options, progress)?
+        }
+        #[cfg(feature = "sqlite")]
+        ExportFormat::Sqlite => unreachable!("written by dump_sqlite()"),
+        ExportFormat::SqliteScript => {
+            grebedb::export::export_sqlite_script(database, file, progress)?
+        }
+    }
+
+    progress_bar.finish();
+
+    if !progress_style.is_hidden() {
+        eprintln!("OK");
     }
 
     Ok(())
 }
 
-pub fn load(database_path: &Path, input_path: &Path, compression: bool) -> anyhow::Result<()> {
+pub fn load(
+    database_path: &Path,
+    input_path: &Path,
+    import_options: ImportOptions,
+    progress_style: ProgressStyle,
+    options: Options,
+) -> anyhow::Result<()> {
     let options = Options {
-        open_mode: OpenMode::CreateOnly,
-        ..Default::default()
+        open_mode: if import_options.resume {
+            OpenMode::LoadOnly
+        } else {
+            OpenMode::CreateOnly
+        },
+        ..options
     };
     let mut database = Database::open_path(database_path, options)?;
 
-    let mut file: BufReader<Box<dyn Read>> = if input_path.as_os_str() != "-" {
+    let mut file = open_input(input_path)?;
+    import(&mut database, &mut file, &import_options, progress_style)?;
+
+    database.flush()?;
+
+    Ok(())
+}
+
+pub fn validate(input_path: &Path) -> anyhow::Result<()> {
+    let mut file = open_input(input_path)?;
+    let summary = grebedb::export::validate(&mut file)?;
+
+    println!("Pairs: {}", summary.key_value_count());
+    println!("Bytes: {}", summary.bytes());
+    println!(
+        "Format: {}",
+        if summary.is_binary() {
+            "binary"
+        } else {
+            "json-seq"
+        }
+    );
+
+    if let Some(uuid) = summary.uuid() {
+        println!("Database UUID: {}", uuid);
+    }
+
+    if let Some(revision) = summary.revision() {
+        println!("Revision: {}", revision);
+    }
+
+    if let Some(revision) = summary.since_revision() {
+        println!("Incremental since revision: {}", revision);
+    }
+
+    println!("OK");
+
+    Ok(())
+}
+
+// Compressed files are detected automatically
+fn open_input(input_path: &Path) -> anyhow::Result<DecompressedReader<BufReader<Box<dyn Read>>>> {
+    let file: BufReader<Box<dyn Read>> = if input_path.as_os_str() != "-" {
         BufReader::new(Box::new(File::open(input_path)?))
     } else {
         BufReader::new(Box::new(std::io::stdin()))
     };
 
-    if compression {
-        #[cfg(feature = "zstd")]
-        {
-            let mut file = BufReader::new(zstd::Decoder::new(file)?);
-            grebedb::export::import(&mut database, &mut file, |_| {})?
-        }
-        #[cfg(not(feature = "zstd"))]
-        {
-            return Err(anyhow::anyhow!("Compression feature not enabled"));
-        }
-    } else {
-        grebedb::export::import(&mut database, &mut file, |_| {})?
-    }
+    Ok(DecompressedReader::new(file)?)
+}
 
-    database.flush()?;
+fn import<R: BufRead>(
+    database: &mut Database,
+    file: &mut R,
+    import_options: &ImportOptions,
+    progress_style: ProgressStyle,
+) -> anyhow::Result<()> {
+    let mut progress_bar = ProgressBar::new(progress_style, "Importing", "rows");
+    let progress =
+        |progress: &Progress| progress_bar.update(progress.rows(), progress.total_rows());
+
+    grebedb::export::import_with_options(database, file, import_options, progress)?;
+
+    progress_bar.finish();
+
+    if !progress_style.is_hidden() {
+        eprintln!("OK");
+    }
 
     Ok(())
 }
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use grebedb::{FsckIssue, FsckIssueKind, FsckReport, OpenMode, Options};
+
+// Returns whether no problems were found
+pub fn fsck(database_path: &Path, json: bool, options: Options) -> anyhow::Result<bool> {
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+
+    let mut database = crate::archive::open_database(database_path, options)?;
+    let report = database.fsck()?;
+
+    if json {
+        let issues: Vec<serde_json::Value> = report.issues().iter().map(issue_to_json).collect();
+
+        println!(
+            "{}",
+            serde_json::json!({
+                "uuid": database.metadata().uuid(),
+                "revision": database.revision(),
+                "ok": report.is_ok(),
+                "page_count": report.page_count(),
+                "file_count": report.file_count(),
+                "issues": issues,
+            })
+        );
+    } else {
+        print_issues(&report);
+
+        eprintln!(
+            "Checked {} pages and {} page files, found {} problems",
+            report.page_count(),
+            report.file_count(),
+            report.issues().len()
+        );
+    }
+
+    Ok(report.is_ok())
+}
+
+pub fn print_issues(report: &FsckReport) {
+    for issue in report.issues() {
+        println!(
+            "{}\t{}\t{}\t{}",
+            kind_name(issue.kind()),
+            issue
+                .page_id()
+                .map(|page_id| page_id.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            issue.path().unwrap_or("-"),
+            issue.message()
+        );
+    }
+}
+
+fn issue_to_json(issue: &FsckIssue) -> serde_json::Value {
+    serde_json::json!({
+        "kind": kind_name(issue.kind()),
+        "page_id": issue.page_id(),
+        "path": issue.path(),
+        "message": issue.message(),
+    })
+}
+
+fn kind_name(kind: FsckIssueKind) -> &'static str {
+    match kind {
+        FsckIssueKind::InvalidPage => "invalid_page",
+        FsckIssueKind::OrphanPageFile => "orphan_page_file",
+        FsckIssueKind::DanglingNextLeaf => "dangling_next_leaf",
+        FsckIssueKind::FreeListInconsistency => "free_list_inconsistency",
+        FsckIssueKind::UuidMismatch => "uuid_mismatch",
+        FsckIssueKind::MetadataDivergence => "metadata_divergence",
+    }
+}
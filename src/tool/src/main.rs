@@ -1,11 +1,40 @@
+mod archive;
+mod backup;
+mod bench;
+mod compact;
+mod copy;
+mod diff;
+mod du;
 mod export;
+mod fsck;
+mod metrics;
+mod pack;
+mod progress;
+mod rekey;
+mod repair;
 mod repl;
+mod resp;
+mod serve;
+mod stats;
 mod verify;
+mod watch;
 
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
-use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
-use grebedb::{Database, OpenMode, Options};
+use anyhow::Context;
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+use grebedb::{
+    export::{
+        ByteEncoding, CsvOptions, Delimiter, ExportCompression, ExportOptions, ImportOptions,
+    },
+    CompressionLevel, Database, DebugPrintOptions, OpenMode, Options, SyncOption,
+};
+
+use crate::bench::{BenchOptions, KeyDistribution, ResultFormat};
+use crate::export::ExportFormat;
+use crate::progress::ProgressStyle;
+use crate::repl::config::InspectConfig;
+use crate::repl::encoding::{text_to_binary, Encoding};
 
 fn main() -> anyhow::Result<()> {
     let db_path_arg = Arg::with_name("database_path")
@@ -13,20 +42,55 @@ fn main() -> anyhow::Result<()> {
         .help("Path to the directory containing the database.")
         .required(true);
 
+    // The settings of the interactive session are not applied to the
+    // one-shot inspector commands
+    let inspect_config = InspectConfig::default();
+
     let zstd_arg = Arg::with_name("zstd").long("zstd");
+    let verbose_arg = Arg::with_name("verbose")
+        .long("verbose")
+        .short("v")
+        .help("Print progress lines when standard error is not a terminal.");
+    let quiet_arg = Arg::with_name("quiet")
+        .long("quiet")
+        .short("q")
+        .help("Don't show the progress bar.");
     let compression_level_arg = Arg::with_name("compression_level")
         .long("compression-level")
         .short("l")
         .help("Compression level where 1 is worst (fastest) and 3 is best (slowest).")
         .default_value("2")
         .possible_values(&["1", "2", "3", "4", "5"]);
+    let page_compression_arg = Arg::with_name("page_compression")
+        .long("compression")
+        .value_name("LEVEL")
+        .possible_values(&["none", "very-low", "low", "medium", "high"])
+        .help("Compression level of the written pages. Defaults to the database options.");
+    let threads_arg = Arg::with_name("threads")
+        .long("threads")
+        .short("j")
+        .value_name("COUNT")
+        .default_value("0")
+        .validator(validate_count);
 
-    let app = App::new("GrebeDB database manipulation tool")
+    let mut app = App::new("GrebeDB database manipulation tool")
         .version(crate_version!())
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .global(true)
+                .help("Load database options from a TOML file.")
+                .long_help("Load database options from a TOML file.\n\n\
+                    The file contains fields of the library's Options struct, such as \
+                    `page_cache_size = 128` or `file_sync = \"none\"`. Durations, such as \
+                    `automatic_flush_long_interval`, are a number of seconds. The open mode is \
+                    determined by the command.")
+        )
         .subcommand(
             SubCommand::with_name("export")
-                .about("Export the contents of the database to a JSON text sequence (RFC 7464) file.")
+                .about("Export the contents of the database to a JSON text sequence (RFC 7464), binary, CSV, TSV, or SQLite file.")
                 .arg(db_path_arg.clone())
                 .arg(
                     Arg::with_name("json_path")
@@ -35,11 +99,62 @@ fn main() -> anyhow::Result<()> {
                         .help("Filename of the exported file."),
                 )
                 .arg(zstd_arg.clone().help("Use Zstandard compression when writing to DESTINATION."))
-                .arg(compression_level_arg)
+                .arg(compression_level_arg.clone())
+                .arg(verbose_arg.clone())
+                .arg(quiet_arg.clone())
+                .arg(
+                    threads_arg.clone()
+                        .help("Number of worker threads for encoding records.")
+                        .long_help("Number of worker threads for encoding records.\n\n\
+                            When 0, records are encoded by the main thread. \
+                            Applies to the JSON text sequence and binary formats.")
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .default_value("json-seq")
+                        .possible_values(&["json-seq", "binary", "csv", "tsv", "sqlite", "sqlite-script"])
+                        .help("Format of the exported file.")
+                        .long_help("Format of the exported file.\n\n\
+                            The binary format contains the same records as the JSON text \
+                            sequence encoded as length-prefixed MessagePack. \
+                            CSV and TSV files contain a row for each key-value pair and are \
+                            intended for viewing or loading into other systems. They cannot \
+                            be imported. The SQLite format writes a SQLite database file \
+                            with a `kv` table of the pairs and requires the sqlite feature. \
+                            The SQLite script creates the same table when run with the \
+                            sqlite3 program. Neither can be imported.")
+                )
+                .arg(
+                    Arg::with_name("keys_only")
+                        .long("keys-only")
+                        .help("Write only the keys.")
+                        .long_help("Write only the keys.\n\n\
+                            A JSON text sequence file without values is a listing that \
+                            cannot be imported.")
+                )
+                .arg(
+                    Arg::with_name("key_encoding")
+                        .long("key-encoding")
+                        .default_value("hex")
+                        .possible_values(&["hex", "base64", "utf8"])
+                        .help("Encoding of keys in JSON text sequence, CSV, and TSV files.")
+                        .long_help("Encoding of keys in JSON text sequence, CSV, and TSV files.\n\n\
+                            A JSON text sequence file with an encoding other than hex is a \
+                            listing that cannot be imported.")
+                )
+                .arg(
+                    Arg::with_name("value_encoding")
+                        .long("value-encoding")
+                        .default_value("hex")
+                        .possible_values(&["hex", "base64", "utf8"])
+                        .help("Encoding of values in JSON text sequence, CSV, and TSV files. \
+                            Invalid UTF-8 is replaced when using utf8.")
+                )
         )
         .subcommand(
             SubCommand::with_name("import")
-                .about("Import the contents from a JSON text sequence (RFC 7464) file into the database.")
+                .about("Import the contents from a JSON text sequence (RFC 7464) or binary file into the database.")
                 .arg(db_path_arg.clone())
                 .arg(
                     Arg::with_name("json_path")
@@ -47,7 +162,51 @@ fn main() -> anyhow::Result<()> {
                         .default_value("-")
                         .help("Filename of the source file."),
                 )
-                .arg(zstd_arg.clone().help("Use Zstandard decompression when reading from SOURCE."))
+                .arg(zstd_arg.clone().help("Ignored. Compressed files are detected automatically."))
+                .arg(verbose_arg.clone())
+                .arg(quiet_arg.clone())
+                .arg(
+                    threads_arg
+                        .help("Number of worker threads for decoding and verifying records.")
+                        .long_help("Number of worker threads for decoding and verifying records.\n\n\
+                            When 0, records are decoded by the main thread.")
+                )
+                .arg(
+                    Arg::with_name("flush_rows")
+                        .long("flush-rows")
+                        .value_name("COUNT")
+                        .validator(validate_count)
+                        .help("Flush the database after storing COUNT pairs.")
+                        .long_help("Flush the database after storing COUNT pairs.\n\n\
+                            Periodic flushes limit memory usage and save the progress \
+                            for --resume. By default, the database is flushed only when \
+                            the import completes.")
+                )
+                .arg(
+                    Arg::with_name("flush_bytes")
+                        .long("flush-bytes")
+                        .value_name("COUNT")
+                        .validator(validate_count)
+                        .help("Flush the database after storing COUNT bytes of keys and values.")
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .help("Continue an interrupted import into an existing database.")
+                        .long_help("Continue an interrupted import into an existing database.\n\n\
+                            SOURCE must be the same file as the interrupted import. \
+                            Pairs that were already stored are skipped.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Check an exported file without importing it.")
+                .arg(
+                    Arg::with_name("json_path")
+                        .value_name("SOURCE")
+                        .default_value("-")
+                        .help("Filename of the source file."),
+                )
         )
         .subcommand(
             SubCommand::with_name("verify")
@@ -57,15 +216,286 @@ fn main() -> anyhow::Result<()> {
                     Arg::with_name("write")
                         .long("write")
                         .short("w")
-                        .help("Open in read & write mode to allow cleanup operations."),
+                        .help("Clean up leftover files after verifying.")
+                        .long_help("Clean up leftover files after verifying.\n\n\
+                            Opens the database in read & write mode. Uncommitted page files \
+                            are deleted, committed page files are renamed to their main \
+                            filename, unused page IDs are reclaimed, and the files of unused \
+                            pages and empty directories are deleted."),
+                )
+                .arg(verbose_arg.clone())
+                .arg(quiet_arg.clone())
+        )
+        .subcommand(
+            SubCommand::with_name("fsck")
+                .about("Check the structure and files of the database and report all problems found.")
+                .long_about("Check the structure and files of the database and report all problems found.\n\n\
+                    Unlike `verify`, checking continues after problems. Problems include page files \
+                    that don't belong to the tree or weren't committed, links between leaf nodes \
+                    that don't match the tree, an inconsistent list of unused page IDs, files with \
+                    a different UUID, and metadata backup files that disagree with the main file. \
+                    Each problem is printed as a line of tab separated kind, page ID, path, and \
+                    message. The exit status is 1 if any problems are found.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print the report as a JSON object.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Print the keys changed by another process as changes are flushed.")
+                .long_about("Print the keys changed by another process as changes are flushed.\n\n\
+                    The database is opened in read-only mode and checked for a new revision \
+                    at an interval. New keys are printed with `+`, keys with changed values \
+                    with `~`, and removed keys with `-`.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("prefix")
+                        .long("prefix")
+                        .value_name("PREFIX")
+                        .help("Only watch keys starting with PREFIX, decoded with the encoding.")
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .default_value("1")
+                        .validator(validate_interval)
+                        .help("Time between checks for changes.")
+                )
+                .arg(
+                    Arg::with_name("values")
+                        .long("values")
+                        .help("Print the values after the keys, separated by tabs.")
+                )
+                .arg(
+                    Arg::with_name("encoding")
+                        .long("encoding")
+                        .value_name("ENCODING")
+                        .possible_values(&Encoding::list())
+                        .default_value(Encoding::Percent.into())
+                        .help("Encoding of the prefix and the printed keys and values.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Print an overview of the database structure and disk usage.")
+                .arg(db_path_arg.clone())
+        )
+        .subcommand(
+            SubCommand::with_name("du")
+                .about("Print the disk usage of the pages of the database.")
+                .long_about("Print the disk usage of the pages of the database.\n\n\
+                    The file sizes are broken down by tree level and by size. The payload size \
+                    is the size of a page before compression, so the ratio of the payload size \
+                    to the file size shows how well the pages compress. The largest pages are \
+                    listed with the size of their largest value to help find oversized values.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("top")
+                        .long("top")
+                        .short("n")
+                        .value_name("COUNT")
+                        .default_value("10")
+                        .validator(validate_count)
+                        .help("Number of largest pages to list.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("compact")
+                .about("Rewrite the database with densely packed nodes and print the space reclaimed.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("target")
+                        .long("target")
+                        .value_name("DIR")
+                        .help("Write the compacted database to DIR instead of in place.")
+                        .long_help("Write the compacted database to DIR instead of in place.\n\n\
+                            DIR must not contain a database. The source database is not modified.")
+                )
+                .arg(page_compression_arg.clone())
+                .arg(
+                    Arg::with_name("keys_per_node")
+                        .long("keys-per-node")
+                        .value_name("COUNT")
+                        .validator(validate_count)
+                        .help("Maximum number of keys in the rebuilt nodes. Defaults to the value saved in the database.")
+                        .long_help("Maximum number of keys in the rebuilt nodes.\n\n\
+                            Defaults to the value that the database was last modified with, or the \
+                            database options if the database does not have the saved value.")
+                )
+                .arg(quiet_arg.clone())
+        )
+        .subcommand(
+            SubCommand::with_name("copy")
+                .about("Copy the database to a new directory with different storage options.")
+                .arg(
+                    Arg::with_name("source_path")
+                        .value_name("SOURCE")
+                        .help("Path to the directory containing the database.")
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("target_path")
+                        .value_name("DEST")
+                        .help("Path to the directory of the new database.")
+                        .long_help("Path to the directory of the new database.\n\n\
+                            DEST must not contain a database. The source database is not modified.")
+                        .required(true)
+                )
+                .arg(page_compression_arg.clone())
+                .arg(
+                    Arg::with_name("keys_per_node")
+                        .long("keys-per-node")
+                        .value_name("COUNT")
+                        .validator(validate_count)
+                        .help("Maximum number of keys in the nodes of the new database. Defaults to the database options.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("Write a copy of the database files to a tar archive.")
+                .long_about("Write a copy of the database files to a tar archive.\n\n\
+                    The files of the latest flushed revision are copied while the database \
+                    may be in use by another process. If the other process flushes and the \
+                    files are replaced while they are copied, copying is retried. The archive \
+                    is compressed with Zstandard if its name ends with `.zst`. Previous \
+                    generations are not included.\n\n\
+                    Read-only commands such as inspect and export can open the archive \
+                    directly when given its path instead of the database directory.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("archive_path")
+                        .value_name("ARCHIVE")
+                        .help("Path of the archive file to create.")
+                        .required(true)
+                )
+                .arg(compression_level_arg)
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("Unpack a backup archive to a directory and validate the database.")
+                .long_about("Unpack a backup archive to a directory and validate the database.\n\n\
+                    The directory must be empty or not exist. Compressed archives are detected \
+                    automatically. The restored database is verified and checked like the \
+                    `verify` and `fsck` commands.")
+                .arg(
+                    Arg::with_name("archive_path")
+                        .value_name("ARCHIVE")
+                        .help("Path of the archive file.")
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("target_path")
+                        .value_name("DIR")
+                        .help("Path to the directory of the restored database.")
+                        .required(true)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("pack")
+                .about("Write the database files to a single pack file.")
+                .long_about("Write the database files to a single pack file.\n\n\
+                    The pack file contains the files of the latest flushed revision and can be \
+                    opened read-only by applications using `PackVfs` of the library. Previous \
+                    generations are not included.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("pack_path")
+                        .value_name("FILE")
+                        .help("Path of the pack file to create, such as `data.grebepack`.")
+                        .required(true)
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("unpack")
+                .about("Unpack a pack file to a directory and validate the database.")
+                .long_about("Unpack a pack file to a directory and validate the database.\n\n\
+                    The directory must be empty or not exist. The unpacked database is verified \
+                    and checked like the `verify` and `fsck` commands.")
+                .arg(
+                    Arg::with_name("pack_path")
+                        .value_name("FILE")
+                        .help("Path of the pack file.")
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("target_path")
+                        .value_name("DIR")
+                        .help("Path to the directory of the unpacked database.")
+                        .required(true)
                 )
+        )
+        .subcommand(
+            SubCommand::with_name("repair")
+                .about("Copy the readable contents of a damaged database to a new database.")
+                .long_about("Copy the readable contents of a damaged database to a new database.\n\n\
+                    Pages that cannot be read are skipped. The IDs of the lost pages \
+                    and the range of keys that they contained are printed in hexadecimal. \
+                    The range includes the start key and excludes the end key.")
+                .arg(db_path_arg.clone())
                 .arg(
-                    Arg::with_name("verbose")
-                        .long("verbose")
-                        .short("v")
-                        .help("Print rough progress."),
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("DIR")
+                        .required(true)
+                        .help("Directory of the new database.")
+                        .long_help("Directory of the new database.\n\n\
+                            DIR must not contain a database. The source database is not modified.")
                 )
         )
+        .subcommand(
+            SubCommand::with_name("rekey")
+                .about("Assign a new UUID to the database.")
+                .long_about("Assign a new UUID to the database.\n\n\
+                    Use this command on a copy of a database, such as a restored backup, so it \
+                    can be told apart from the original. All pages are written again with the \
+                    new UUID. If interrupted, the database keeps the old UUID.")
+                .arg(db_path_arg.clone())
+                .arg(quiet_arg.clone())
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compare the key-value pairs of two databases.")
+                .long_about("Compare the key-value pairs of two databases.\n\n\
+                    Keys only in DB_A are printed with `<`, keys only in DB_B with `>`, \
+                    and keys with different values with `!`. A summary is printed to \
+                    standard error. The exit status is 1 if the databases differ.")
+                .arg(
+                    Arg::with_name("path_a")
+                        .value_name("DB_A")
+                        .help("Path to the directory containing the first database.")
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("path_b")
+                        .value_name("DB_B")
+                        .help("Path to the directory containing the second database.")
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("values")
+                        .long("values")
+                        .help("Print the values after the keys, separated by tabs.")
+                )
+                .arg(
+                    Arg::with_name("encoding")
+                        .long("encoding")
+                        .value_name("ENCODING")
+                        .possible_values(&Encoding::list())
+                        .default_value(Encoding::Percent.into())
+                        .help("Encoding of the printed keys and values.")
+                )
+        )
+        .subcommand(crate::repl::get_subcommand(Some(db_path_arg.clone()), &inspect_config))
+        .subcommand(crate::repl::scan_subcommand(Some(db_path_arg.clone()), &inspect_config))
+        .subcommand(crate::repl::head_subcommand(Some(db_path_arg.clone()), &inspect_config))
+        .subcommand(crate::repl::tail_subcommand(Some(db_path_arg.clone()), &inspect_config))
+        .subcommand(crate::repl::grep_subcommand(Some(db_path_arg.clone()), &inspect_config))
+        .subcommand(crate::repl::put_subcommand(Some(db_path_arg.clone()), &inspect_config))
+        .subcommand(crate::repl::remove_subcommand(Some(db_path_arg.clone()), &inspect_config))
         .subcommand(
             SubCommand::with_name("inspect")
                 .about("Start a interactive session for browsing and editing the database contents.")
@@ -86,11 +516,182 @@ fn main() -> anyhow::Result<()> {
                             program to exit. This can be useful for scripts to send commands \
                             using standard input.")
                 )
+                .arg(
+                    Arg::with_name("exec")
+                        .long("exec")
+                        .short("e")
+                        .value_name("COMMAND")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Run the given command and exit. Can be repeated.")
+                        .long_help("Run the given command and exit. Can be repeated.\n\n\
+                            The commands are run in order and the first error causes the \
+                            program to exit. In write mode, modifications are flushed after \
+                            the commands.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Start a HTTP server for getting, putting, and removing key-value pairs.")
+                .long_about("Start a HTTP server for getting, putting, and removing key-value pairs.\n\n\
+                    The value of a key is accessed with GET, PUT, and DELETE requests to \
+                    /keys/KEY where KEY is percent-encoded. Modifications are flushed before \
+                    responding. A GET request to /keys returns a JSON array of the pairs \
+                    in a range given by the optional query parameters `start` (inclusive), \
+                    `end` (exclusive), `limit` (default 1000), and `encoding` of the \
+                    returned keys and values (default percent). Values larger than \
+                    64 MiB are rejected with status 413.\n\n\
+                    The server is intended for scripts and debugging. It does not provide \
+                    authentication or encryption.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .value_name("ADDR")
+                        .default_value("127.0.0.1:8080")
+                        .help("Address and port to listen on.")
+                )
+                .arg(
+                    Arg::with_name("read_only")
+                        .long("read-only")
+                        .help("Open in read-only mode and reject modifications.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("serve-resp")
+                .about("Start a server speaking a subset of the Redis protocol (RESP).")
+                .long_about("Start a server speaking a subset of the Redis protocol (RESP).\n\n\
+                    Supported commands are GET, SET (without options), DEL, EXISTS, \
+                    SCAN (with MATCH and COUNT), PING, and QUIT. Modifications are flushed \
+                    before replying. SCAN cursors are positions in the key order, so keys \
+                    inserted or removed during a scan may be skipped or returned twice. \
+                    Inline commands are limited to 64 KiB, bulk strings to 512 MiB, and \
+                    a whole command to 1 GiB.\n\n\
+                    The server is intended for scripts and debugging. It does not provide \
+                    authentication or encryption.")
+                .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .value_name("ADDR")
+                        .default_value("127.0.0.1:6379")
+                        .help("Address and port to listen on.")
+                )
+                .arg(
+                    Arg::with_name("read_only")
+                        .long("read-only")
+                        .help("Open in read-only mode and reject modifications.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Measure the speed of inserting, flushing, and reading key-value pairs.")
+                .long_about("Measure the speed of inserting, flushing, and reading key-value pairs.\n\n\
+                    Each batch inserts a number of pairs, flushes the database, and reads \
+                    the pairs again. The batch size grows with each batch up to the maximum. \
+                    The median time of the rounds of each batch is printed.")
+                .arg(
+                    Arg::with_name("path")
+                        .value_name("DIRECTORY")
+                        .help("Directory in which temporary databases are created.")
+                        .long_help("Directory in which temporary databases are created.\n\n\
+                            The databases are removed when the benchmark completes. If omitted, \
+                            the databases are kept in memory.")
+                )
+                .arg(
+                    Arg::with_name("batches")
+                        .long("batches")
+                        .value_name("COUNT")
+                        .default_value("100")
+                        .validator(validate_positive_count)
+                        .help("Number of batches.")
+                )
+                .arg(
+                    Arg::with_name("max_batch_size")
+                        .long("max-batch-size")
+                        .value_name("COUNT")
+                        .default_value("2000")
+                        .validator(validate_positive_count)
+                        .help("Maximum number of pairs in a batch.")
+                )
+                .arg(
+                    Arg::with_name("rounds")
+                        .long("rounds")
+                        .value_name("COUNT")
+                        .default_value("3")
+                        .validator(validate_positive_count)
+                        .help("Number of times each batch is repeated.")
+                )
+                .arg(
+                    Arg::with_name("value_size")
+                        .long("value-size")
+                        .value_name("BYTES")
+                        .default_value("1024")
+                        .validator(validate_count)
+                        .help("Size of the random values.")
+                )
+                .arg(
+                    Arg::with_name("distribution")
+                        .long("distribution")
+                        .value_name("DISTRIBUTION")
+                        .possible_values(&["sequential", "random", "both"])
+                        .default_value("both")
+                        .help("Order of the inserted keys.")
+                )
+                .arg(
+                    Arg::with_name("sync")
+                        .long("sync")
+                        .value_name("LEVEL")
+                        .possible_values(&["none", "data", "all"])
+                        .help("Level of file synchronization. Defaults to the database options.")
+                )
+                .arg(
+                    Arg::with_name("keys_per_node")
+                        .long("keys-per-node")
+                        .value_name("COUNT")
+                        .validator(validate_count)
+                        .help("Maximum number of keys in a node. Defaults to the database options.")
+                )
+                .arg(
+                    Arg::with_name("page_cache_size")
+                        .long("page-cache-size")
+                        .value_name("COUNT")
+                        .validator(validate_count)
+                        .help("Maximum number of pages held in memory. Defaults to the database options.")
+                )
+                .arg(page_compression_arg.clone())
+                .arg(
+                    Arg::with_name("result_format")
+                        .long("format")
+                        .possible_values(&["text", "csv", "json"])
+                        .default_value("text")
+                        .help("Format of the results. JSON results are printed one object per line.")
+                )
         )
         .subcommand(
             SubCommand::with_name("debug_print_tree")
                 .about("Print the database tree for debugging purposes.")
                 .arg(db_path_arg.clone())
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print a JSON object with the kind, key count, and links of each page.")
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("Print a shell completion script.")
+                .long_about("Print a shell completion script.\n\n\
+                    The script completes the commands and options of the tool, including the \
+                    inspector commands that can be run directly. For example, in Bash, run \
+                    `source <(grebedb-tool completions bash)` or save the output to the \
+                    completions directory of the shell.")
+                .arg(
+                    Arg::with_name("shell")
+                        .value_name("SHELL")
+                        .possible_values(&Shell::variants())
+                        .required(true)
+                )
         )
         .subcommand(
             SubCommand::with_name("debug_print_page")
@@ -101,36 +702,224 @@ fn main() -> anyhow::Result<()> {
                         .help("Path to the database page.")
                         .required(true)
                 )
+                .arg(
+                    Arg::with_name("json")
+                        .long("json")
+                        .help("Print a JSON object with keys and values as hex.")
+                )
+                .arg(
+                    Arg::with_name("truncate")
+                        .long("truncate")
+                        .value_name("COUNT")
+                        .validator(validate_count)
+                        .help("Print at most COUNT bytes of each key and value.")
+                )
+                .arg(
+                    Arg::with_name("header_only")
+                        .long("header-only")
+                        .help("Print only the page ID, revision, UUID, deleted flag, node kind, and key count.")
+                )
         );
 
-    let matches = app.get_matches();
+    // The definitions are kept for generating completion scripts
+    let matches = app.clone().get_matches();
 
     match matches.subcommand() {
         ("export", Some(sub_m)) => crate::export::dump(
             sub_m.value_of_os("database_path").unwrap().as_ref(),
             sub_m.value_of_os("json_path").unwrap().as_ref(),
             parse_zstd_compression_args(sub_m),
+            parse_export_format_args(sub_m)?,
+            parse_threads_arg(sub_m),
+            parse_progress_style_args(sub_m),
+            load_options(sub_m)?,
         ),
         ("import", Some(sub_m)) => crate::export::load(
             sub_m.value_of_os("database_path").unwrap().as_ref(),
             sub_m.value_of_os("json_path").unwrap().as_ref(),
-            sub_m.is_present("zstd"),
+            parse_import_options_args(sub_m),
+            parse_progress_style_args(sub_m),
+            load_options(sub_m)?,
         ),
+        ("validate", Some(sub_m)) => {
+            crate::export::validate(sub_m.value_of_os("json_path").unwrap().as_ref())
+        }
         ("verify", Some(sub_m)) => crate::verify::verify(
             sub_m.value_of_os("database_path").unwrap().as_ref(),
             sub_m.is_present("write"),
-            sub_m.is_present("verbose"),
+            parse_progress_style_args(sub_m),
+            load_options(sub_m)?,
         ),
+        ("backup", Some(sub_m)) => {
+            let archive_path: &Path = sub_m.value_of_os("archive_path").unwrap().as_ref();
+            let compression = if archive_path.extension() == Some("zst".as_ref()) {
+                parse_zstd_compression_level_arg(sub_m)
+            } else {
+                ExportCompression::None
+            };
+
+            crate::backup::backup(
+                sub_m.value_of_os("database_path").unwrap().as_ref(),
+                archive_path,
+                compression,
+                load_options(sub_m)?,
+            )
+        }
+        ("restore", Some(sub_m)) => crate::backup::restore(
+            sub_m.value_of_os("archive_path").unwrap().as_ref(),
+            sub_m.value_of_os("target_path").unwrap().as_ref(),
+            load_options(sub_m)?,
+        ),
+        ("pack", Some(sub_m)) => crate::pack::pack(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            sub_m.value_of_os("pack_path").unwrap().as_ref(),
+            load_options(sub_m)?,
+        ),
+        ("unpack", Some(sub_m)) => crate::pack::unpack(
+            sub_m.value_of_os("pack_path").unwrap().as_ref(),
+            sub_m.value_of_os("target_path").unwrap().as_ref(),
+            load_options(sub_m)?,
+        ),
+        ("fsck", Some(sub_m)) => {
+            let ok = crate::fsck::fsck(
+                sub_m.value_of_os("database_path").unwrap().as_ref(),
+                sub_m.is_present("json"),
+                load_options(sub_m)?,
+            )?;
+
+            if !ok {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+        ("watch", Some(sub_m)) => {
+            let encoding = sub_m.value_of("encoding").unwrap().try_into()?;
+            let prefix = match sub_m.value_of("prefix") {
+                Some(prefix) => text_to_binary(prefix, encoding)?,
+                None => Vec::new(),
+            };
+
+            crate::watch::watch(
+                sub_m.value_of_os("database_path").unwrap().as_ref(),
+                &prefix,
+                Duration::from_secs_f64(sub_m.value_of("interval").unwrap().parse().unwrap()),
+                sub_m.is_present("values"),
+                encoding,
+                load_options(sub_m)?,
+            )
+        }
+        ("stats", Some(sub_m)) => crate::stats::stats(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            load_options(sub_m)?,
+        ),
+        ("du", Some(sub_m)) => crate::du::du(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            sub_m.value_of("top").unwrap().parse().unwrap(),
+            load_options(sub_m)?,
+        ),
+        ("compact", Some(sub_m)) => crate::compact::compact(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            sub_m.value_of_os("target").map(|path| path.as_ref()),
+            parse_page_compression_arg(sub_m),
+            sub_m
+                .value_of("keys_per_node")
+                .map(|value| value.parse().unwrap()),
+            parse_progress_style_args(sub_m),
+            load_options(sub_m)?,
+        ),
+        ("copy", Some(sub_m)) => crate::copy::copy(
+            sub_m.value_of_os("source_path").unwrap().as_ref(),
+            sub_m.value_of_os("target_path").unwrap().as_ref(),
+            parse_page_compression_arg(sub_m),
+            sub_m
+                .value_of("keys_per_node")
+                .map(|value| value.parse().unwrap()),
+            load_options(sub_m)?,
+        ),
+        ("repair", Some(sub_m)) => crate::repair::repair(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            sub_m.value_of_os("output").unwrap().as_ref(),
+            load_options(sub_m)?,
+        ),
+        ("rekey", Some(sub_m)) => crate::rekey::rekey(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            parse_progress_style_args(sub_m),
+            load_options(sub_m)?,
+        ),
+        ("diff", Some(sub_m)) => {
+            let same = crate::diff::diff(
+                sub_m.value_of_os("path_a").unwrap().as_ref(),
+                sub_m.value_of_os("path_b").unwrap().as_ref(),
+                sub_m.is_present("values"),
+                sub_m.value_of("encoding").unwrap().try_into()?,
+                load_options(sub_m)?,
+            )?;
+
+            if !same {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+        (name @ ("get" | "scan" | "head" | "tail" | "grep" | "put" | "remove"), Some(sub_m)) => {
+            let found = crate::repl::run_command(
+                sub_m.value_of_os("database_path").unwrap().as_ref(),
+                name,
+                sub_m,
+                load_options(sub_m)?,
+            )?;
+
+            if !found {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
         ("inspect", Some(sub_m)) => crate::repl::inspect(
             sub_m.value_of_os("database_path").unwrap().as_ref(),
             sub_m.is_present("write"),
             sub_m.is_present("batch"),
+            &sub_m.values_of("exec").map(|values| values.collect::<Vec<_>>()).unwrap_or_default(),
+            load_options(sub_m)?,
+        ),
+        ("serve", Some(sub_m)) => crate::serve::serve(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            sub_m.value_of("listen").unwrap(),
+            sub_m.is_present("read_only"),
+            load_options(sub_m)?,
+        ),
+        ("serve-resp", Some(sub_m)) => crate::resp::serve_resp(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            sub_m.value_of("listen").unwrap(),
+            sub_m.is_present("read_only"),
+            load_options(sub_m)?,
+        ),
+        ("bench", Some(sub_m)) => crate::bench::bench(
+            parse_bench_options_args(sub_m),
+            parse_bench_database_options_args(sub_m, load_options(sub_m)?),
+        ),
+        ("debug_print_tree", Some(sub_m)) => debug_print_tree_command(
+            sub_m.value_of_os("database_path").unwrap().as_ref(),
+            sub_m.is_present("json"),
+            load_options(sub_m)?,
         ),
-        ("debug_print_tree", Some(sub_m)) => {
-            debug_print_tree_command(sub_m.value_of_os("database_path").unwrap().as_ref())
+        ("completions", Some(sub_m)) => {
+            let shell = sub_m.value_of("shell").unwrap().parse().unwrap();
+
+            app.gen_completions_to("grebedb-tool", shell, &mut std::io::stdout());
+
+            Ok(())
         }
         ("debug_print_page", Some(sub_m)) => {
-            debug_print_page_command(sub_m.value_of_os("page_path").unwrap().as_ref())
+            debug_print_page_command(
+                sub_m.value_of_os("page_path").unwrap().as_ref(),
+                &DebugPrintOptions {
+                    json: sub_m.is_present("json"),
+                    truncate: sub_m.value_of("truncate").map(|value| value.parse().unwrap()),
+                    header_only: sub_m.is_present("header_only"),
+                },
+            )
         }
         _ => {
             unreachable!();
@@ -138,39 +927,216 @@ fn main() -> anyhow::Result<()> {
     }
 }
 
-fn parse_zstd_compression_args(args: &ArgMatches) -> Option<i32> {
+fn parse_zstd_compression_args(args: &ArgMatches) -> ExportCompression {
     if args.is_present("zstd") {
-        let level = args.value_of("compression_level").unwrap();
-        let level: u8 = level.parse().unwrap();
-
-        match level {
-            1 => Some(1),
-            2 => Some(3),
-            3 => Some(9),
-            4 => Some(15),
-            5 => Some(19),
-            _ => unreachable!(),
-        }
+        parse_zstd_compression_level_arg(args)
     } else {
-        None
+        ExportCompression::None
+    }
+}
+
+fn parse_zstd_compression_level_arg(args: &ArgMatches) -> ExportCompression {
+    let level = args.value_of("compression_level").unwrap();
+    let level: u8 = level.parse().unwrap();
+
+    match level {
+        1 => ExportCompression::Zstd(1),
+        2 => ExportCompression::Zstd(3),
+        3 => ExportCompression::Zstd(9),
+        4 => ExportCompression::Zstd(15),
+        5 => ExportCompression::Zstd(19),
+        _ => unreachable!(),
     }
 }
 
-fn debug_print_tree_command(database_path: &Path) -> anyhow::Result<()> {
+fn validate_interval(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(()),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn validate_count(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|error| error.to_string())
+}
+
+fn validate_positive_count(value: String) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(0) => Err("must be greater than 0".to_string()),
+        Ok(_) => Ok(()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn parse_page_compression_arg(args: &ArgMatches) -> Option<CompressionLevel> {
+    let level = match args.value_of("page_compression")? {
+        "none" => CompressionLevel::None,
+        "very-low" => CompressionLevel::VeryLow,
+        "low" => CompressionLevel::Low,
+        "medium" => CompressionLevel::Medium,
+        "high" => CompressionLevel::High,
+        _ => unreachable!(),
+    };
+
+    Some(level)
+}
+
+fn parse_threads_arg(args: &ArgMatches) -> usize {
+    args.value_of("threads").unwrap().parse().unwrap()
+}
+
+fn parse_progress_style_args(args: &ArgMatches) -> ProgressStyle {
+    ProgressStyle::from_flags(args.is_present("quiet"), args.is_present("verbose"))
+}
+
+fn parse_import_options_args(args: &ArgMatches) -> ImportOptions {
+    let parse_count = |name| args.value_of(name).map(|value| value.parse().unwrap());
+
+    ImportOptions {
+        resume: args.is_present("resume"),
+        threads: parse_threads_arg(args),
+        flush_rows: parse_count("flush_rows"),
+        flush_bytes: parse_count("flush_bytes"),
+    }
+}
+
+fn parse_bench_options_args(args: &ArgMatches) -> BenchOptions {
+    let parse_count = |name| args.value_of(name).unwrap().parse().unwrap();
+
+    BenchOptions {
+        path: args.value_of_os("path").map(|path| path.into()),
+        batches: parse_count("batches"),
+        max_batch_size: parse_count("max_batch_size"),
+        rounds: parse_count("rounds"),
+        value_size: parse_count("value_size"),
+        distributions: match args.value_of("distribution").unwrap() {
+            "sequential" => vec![KeyDistribution::Sequential],
+            "random" => vec![KeyDistribution::Random],
+            "both" => vec![KeyDistribution::Sequential, KeyDistribution::Random],
+            _ => unreachable!(),
+        },
+        result_format: match args.value_of("result_format").unwrap() {
+            "text" => ResultFormat::Text,
+            "csv" => ResultFormat::Csv,
+            "json" => ResultFormat::Json,
+            _ => unreachable!(),
+        },
+    }
+}
+
+fn parse_bench_database_options_args(args: &ArgMatches, options: Options) -> Options {
+    let parse_count = |name| args.value_of(name).map(|value| value.parse().unwrap());
+
+    Options {
+        file_sync: match args.value_of("sync") {
+            Some("none") => SyncOption::None,
+            Some("data") => SyncOption::Data,
+            Some("all") => SyncOption::All,
+            _ => options.file_sync,
+        },
+        keys_per_node: parse_count("keys_per_node").unwrap_or(options.keys_per_node),
+        page_cache_size: parse_count("page_cache_size").unwrap_or(options.page_cache_size),
+        compression_level: parse_page_compression_arg(args).unwrap_or(options.compression_level),
+        ..options
+    }
+}
+
+fn parse_export_format_args(args: &ArgMatches) -> anyhow::Result<ExportFormat> {
+    let parse_encoding = |name| match args.value_of(name).unwrap() {
+        "hex" => ByteEncoding::Hex,
+        "base64" => ByteEncoding::Base64,
+        "utf8" => ByteEncoding::Utf8Lossy,
+        _ => unreachable!(),
+    };
+    let keys_only = args.is_present("keys_only");
+    let key_encoding = parse_encoding("key_encoding");
+    let value_encoding = parse_encoding("value_encoding");
+    let is_listing =
+        keys_only || key_encoding != ByteEncoding::Hex || value_encoding != ByteEncoding::Hex;
+
+    let delimiter = match args.value_of("format").unwrap() {
+        "json-seq" => {
+            return Ok(ExportFormat::Records(ExportOptions {
+                keys_only,
+                key_encoding,
+                value_encoding,
+                ..Default::default()
+            }))
+        }
+        "binary" | "sqlite" | "sqlite-script" if is_listing => anyhow::bail!(
+            "--keys-only and encodings other than hex require the json-seq, csv, or tsv format"
+        ),
+        "sqlite" if args.is_present("zstd") || args.value_of("json_path") == Some("-") => {
+            anyhow::bail!("the sqlite format requires a DESTINATION file without --zstd")
+        }
+        #[cfg(feature = "sqlite")]
+        "sqlite" => return Ok(ExportFormat::Sqlite),
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => anyhow::bail!("the sqlite format requires the sqlite feature"),
+        "binary" => {
+            return Ok(ExportFormat::Records(ExportOptions {
+                binary: true,
+                ..Default::default()
+            }))
+        }
+        "sqlite-script" => return Ok(ExportFormat::SqliteScript),
+        "csv" => Delimiter::Comma,
+        "tsv" => Delimiter::Tab,
+        _ => unreachable!(),
+    };
+
+    Ok(ExportFormat::Csv(CsvOptions {
+        delimiter,
+        key_encoding,
+        value_encoding,
+        keys_only,
+        ..Default::default()
+    }))
+}
+
+fn load_options(args: &ArgMatches) -> anyhow::Result<Options> {
+    let options = match args.value_of_os("config") {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read config file {:?}", path))?;
+            toml::from_str(&text).with_context(|| format!("invalid config file {:?}", path))?
+        }
+        None => Options::default(),
+    };
+
+    options.validate()?;
+
+    Ok(options)
+}
+
+fn debug_print_tree_command(
+    database_path: &Path,
+    json: bool,
+    options: Options,
+) -> anyhow::Result<()> {
     let mut database = Database::open_path(
         database_path,
         Options {
             open_mode: OpenMode::ReadOnly,
-            ..Default::default()
+            ..options
         },
     )?;
-    database.debug_print_tree()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&database.tree_summary()?)?);
+    } else {
+        database.debug_print_tree(std::io::stdout().lock())?;
+    }
 
     Ok(())
 }
 
-fn debug_print_page_command(path: &Path) -> anyhow::Result<()> {
-    grebedb::debug_print_page(path)?;
+fn debug_print_page_command(path: &Path, options: &DebugPrintOptions) -> anyhow::Result<()> {
+    println!("{}", grebedb::debug_format_page(path, options)?);
 
     Ok(())
 }
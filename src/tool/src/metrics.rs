@@ -0,0 +1,145 @@
+use std::{fmt::Write, path::Path};
+
+use grebedb::{vfs::Vfs, Database, OperationStats};
+
+/// Format the statistics of the database in the Prometheus text exposition
+/// format.
+pub fn format_metrics<F>(database: &Database<F>, database_path: &Path) -> anyhow::Result<String>
+where
+    F: Vfs,
+{
+    let stats = database.stats();
+    let mut text = String::new();
+
+    write_header(
+        &mut text,
+        "grebedb_operation_errors_total",
+        "counter",
+        "Number of operations that returned an error.",
+    );
+
+    for (operation, operation_stats) in operations(&stats) {
+        writeln!(
+            text,
+            "grebedb_operation_errors_total{{operation=\"{}\"}} {}",
+            operation,
+            operation_stats.errors()
+        )?;
+    }
+
+    write_header(
+        &mut text,
+        "grebedb_operation_duration_seconds",
+        "histogram",
+        "Latency of operations.",
+    );
+
+    for (operation, operation_stats) in operations(&stats) {
+        let latency = operation_stats.latency();
+
+        for (bound, count) in latency.buckets() {
+            let bound = match bound {
+                Some(bound) => bound.as_secs_f64().to_string(),
+                None => "+Inf".to_string(),
+            };
+
+            writeln!(
+                text,
+                "grebedb_operation_duration_seconds_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                operation, bound, count
+            )?;
+        }
+
+        writeln!(
+            text,
+            "grebedb_operation_duration_seconds_sum{{operation=\"{}\"}} {}",
+            operation,
+            latency.sum().as_secs_f64()
+        )?;
+        writeln!(
+            text,
+            "grebedb_operation_duration_seconds_count{{operation=\"{}\"}} {}",
+            operation,
+            latency.count()
+        )?;
+    }
+
+    let metadata = database.metadata();
+    let values = [
+        (
+            "grebedb_page_reads_total",
+            "counter",
+            "Number of page files read, including metadata.",
+            stats.page_reads(),
+        ),
+        (
+            "grebedb_page_writes_total",
+            "counter",
+            "Number of page files written, including metadata.",
+            stats.page_writes(),
+        ),
+        (
+            "grebedb_bytes_compressed_total",
+            "counter",
+            "Number of uncompressed bytes passed to the compressor.",
+            stats.bytes_compressed(),
+        ),
+        (
+            "grebedb_bytes_decompressed_total",
+            "counter",
+            "Number of uncompressed bytes produced by the decompressor.",
+            stats.bytes_decompressed(),
+        ),
+        (
+            "grebedb_page_cache_hits_total",
+            "counter",
+            "Number of page accesses found in the page cache.",
+            stats.cache_hits(),
+        ),
+        (
+            "grebedb_page_cache_misses_total",
+            "counter",
+            "Number of page accesses that loaded the page into the page cache.",
+            stats.cache_misses(),
+        ),
+        (
+            "grebedb_key_value_pairs",
+            "gauge",
+            "Number of key-value pairs.",
+            metadata.key_value_count(),
+        ),
+        (
+            "grebedb_revision",
+            "gauge",
+            "Revision of the database.",
+            database.revision(),
+        ),
+        (
+            "grebedb_disk_usage_bytes",
+            "gauge",
+            "Size of the files in the database directory.",
+            crate::stats::disk_usage(database_path)?,
+        ),
+    ];
+
+    for (name, kind, help, value) in values {
+        write_header(&mut text, name, kind, help);
+        writeln!(text, "{} {}", name, value)?;
+    }
+
+    Ok(text)
+}
+
+fn operations(stats: &grebedb::Stats) -> [(&'static str, &OperationStats); 4] {
+    [
+        ("get", stats.get()),
+        ("put", stats.put()),
+        ("remove", stats.remove()),
+        ("flush", stats.flush()),
+    ]
+}
+
+fn write_header(text: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+}
@@ -0,0 +1,71 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use grebedb::{
+    vfs::{OsVfs, PackVfs, PackWriter, VfsSyncOption},
+    Database, OpenMode, Options,
+};
+
+pub fn pack(database_path: &Path, pack_path: &Path, options: Options) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+
+    let mut database = Database::open_path(database_path, options)?;
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(pack_path)?;
+
+    match write_pack(&mut database, &file) {
+        Ok(file_count) => {
+            file.sync_all()?;
+
+            eprintln!(
+                "Packed revision {} with {} files",
+                database.revision(),
+                file_count
+            );
+
+            Ok(())
+        }
+        Err(error) => {
+            drop(file);
+            std::fs::remove_file(pack_path)?;
+
+            Err(error)
+        }
+    }
+}
+
+// Returns the number of files written
+fn write_pack(database: &mut Database, file: &File) -> anyhow::Result<usize> {
+    let mut writer = PackWriter::new(BufWriter::new(file.try_clone()?))?;
+    let mut file_count = 0;
+
+    database.backup_files(|path, data| {
+        writer.add_file(path, data)?;
+        file_count += 1;
+
+        Ok(())
+    })?;
+
+    writer.finish()?.flush()?;
+
+    Ok(file_count)
+}
+
+pub fn unpack(pack_path: &Path, target_path: &Path, options: Options) -> anyhow::Result<()> {
+    // The pack file is the root of the file system, so its filename doesn't
+    // need to follow the rules of the file system paths
+    let vfs = PackVfs::open(OsVfs::new(pack_path), "")?;
+
+    crate::backup::create_empty_dir(target_path)?;
+    vfs.unpack_to(&mut OsVfs::new(target_path), VfsSyncOption::Data)?;
+
+    crate::backup::validate_restored_database(target_path, options)
+}
@@ -0,0 +1,183 @@
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+const BAR_WIDTH: usize = 30;
+const BAR_INTERVAL: Duration = Duration::from_millis(100);
+const LINE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How the progress of an operation is shown on standard error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressStyle {
+    /// Redrawn bar with rate and estimated time remaining.
+    Bar,
+    /// Line printed once per second.
+    Lines,
+    /// Nothing is printed.
+    Hidden,
+}
+
+impl ProgressStyle {
+    /// Choose the style from the `--quiet` and `--verbose` options.
+    ///
+    /// The bar is shown only when standard error is a terminal. Otherwise,
+    /// lines are printed if verbose.
+    pub fn from_flags(quiet: bool, verbose: bool) -> Self {
+        if quiet {
+            ProgressStyle::Hidden
+        } else if atty::is(atty::Stream::Stderr) {
+            ProgressStyle::Bar
+        } else if verbose {
+            ProgressStyle::Lines
+        } else {
+            ProgressStyle::Hidden
+        }
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        *self == ProgressStyle::Hidden
+    }
+}
+
+/// Progress display of a long operation.
+pub struct ProgressBar {
+    style: ProgressStyle,
+    label: &'static str,
+    unit: &'static str,
+    start: Instant,
+    next_draw: Duration,
+    current: u64,
+    total: Option<u64>,
+}
+
+impl ProgressBar {
+    pub fn new(style: ProgressStyle, label: &'static str, unit: &'static str) -> Self {
+        Self {
+            style,
+            label,
+            unit,
+            start: Instant::now(),
+            next_draw: Duration::ZERO,
+            current: 0,
+            total: None,
+        }
+    }
+
+    /// Record the amount of work done and redraw if enough time has passed.
+    pub fn update(&mut self, current: u64, total: Option<u64>) {
+        self.current = current;
+        self.total = total;
+
+        let elapsed = self.start.elapsed();
+
+        if elapsed < self.next_draw {
+            return;
+        }
+
+        match self.style {
+            ProgressStyle::Bar => {
+                self.next_draw = elapsed + BAR_INTERVAL;
+                self.draw_bar(elapsed);
+            }
+            ProgressStyle::Lines => {
+                self.next_draw = elapsed + LINE_INTERVAL;
+                self.print_line(elapsed);
+            }
+            ProgressStyle::Hidden => {}
+        }
+    }
+
+    /// Draw the final state and end the line of the bar.
+    pub fn finish(&mut self) {
+        if self.style == ProgressStyle::Bar {
+            self.draw_bar(self.start.elapsed());
+            eprintln!();
+        }
+    }
+
+    fn draw_bar(&self, elapsed: Duration) {
+        let text = match self.total {
+            Some(total) => {
+                let fraction = fraction(self.current, total);
+                let filled = (fraction * BAR_WIDTH as f64) as usize;
+                let head = if filled < BAR_WIDTH { ">" } else { "" };
+
+                format!(
+                    "{} [{:=<filled$}{}{:width$}] {:5.1}% {}/{} {} ETA {}",
+                    self.label,
+                    "",
+                    head,
+                    "",
+                    fraction * 100.0,
+                    self.current,
+                    total,
+                    self.format_rate(elapsed),
+                    self.format_remaining(elapsed),
+                    filled = filled,
+                    width = BAR_WIDTH.saturating_sub(filled + 1),
+                )
+            }
+            None => format!(
+                "{} {} {}",
+                self.label,
+                self.current,
+                self.format_rate(elapsed)
+            ),
+        };
+
+        // Clear the rest of the line in case the previous text was longer
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r{}\x1b[K", text);
+        let _ = stderr.flush();
+    }
+
+    fn print_line(&self, elapsed: Duration) {
+        let percent = match self.total {
+            Some(total) => format!("{:.1}%", fraction(self.current, total) * 100.0),
+            None => "?".to_string(),
+        };
+
+        eprintln!(
+            "\t{}\t{}\t{}\tETA {}",
+            percent,
+            self.current,
+            self.format_rate(elapsed),
+            self.format_remaining(elapsed)
+        );
+    }
+
+    fn rate(&self, elapsed: Duration) -> f64 {
+        let seconds = elapsed.as_secs_f64();
+
+        if seconds > 0.0 {
+            self.current as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    fn format_rate(&self, elapsed: Duration) -> String {
+        format!("{:.0} {}/s", self.rate(elapsed), self.unit)
+    }
+
+    fn format_remaining(&self, elapsed: Duration) -> String {
+        let rate = self.rate(elapsed);
+
+        match self.total {
+            Some(total) if rate > 0.0 => {
+                let seconds = (total.saturating_sub(self.current) as f64 / rate).ceil() as u64;
+                format!("{}:{:02}", seconds / 60, seconds % 60)
+            }
+            _ => "?".to_string(),
+        }
+    }
+}
+
+fn fraction(current: u64, total: u64) -> f64 {
+    if total > 0 {
+        (current as f64 / total as f64).min(1.0)
+    } else {
+        1.0
+    }
+}
@@ -0,0 +1,29 @@
+use std::path::Path;
+
+use grebedb::{Database, OpenMode, Options};
+
+use crate::progress::{ProgressBar, ProgressStyle};
+
+pub fn rekey(
+    database_path: &Path,
+    progress_style: ProgressStyle,
+    options: Options,
+) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: OpenMode::LoadOnly,
+        ..options
+    };
+    let mut database = Database::open_path(database_path, options)?;
+    let old_uuid = database.metadata().uuid();
+    let mut progress_bar = ProgressBar::new(progress_style, "Rewriting", "pages");
+
+    database.regenerate_uuid(|current, total| {
+        progress_bar.update(current as u64, Some(total as u64));
+    })?;
+    progress_bar.finish();
+
+    println!("Old UUID: {}", old_uuid);
+    println!("New UUID: {}", database.metadata().uuid());
+
+    Ok(())
+}
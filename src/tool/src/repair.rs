@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use grebedb::{vfs::OsVfs, Database, OpenMode, Options};
+
+pub fn repair(database_path: &Path, output_path: &Path, options: Options) -> anyhow::Result<()> {
+    let source_options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options.clone()
+    };
+    let mut database = Database::open_path(database_path, source_options)?;
+
+    std::fs::create_dir_all(output_path)?;
+    let report = database.salvage_to(OsVfs::new(output_path), options)?;
+
+    println!("Key-value pairs recovered: {}", report.pair_count());
+    println!("Pages lost: {}", report.lost_pages().len());
+
+    for lost_page in report.lost_pages() {
+        println!(
+            "Lost page {}: keys {} to {} ({})",
+            lost_page.page_id(),
+            format_key_bound(lost_page.start_key(), "start"),
+            format_key_bound(lost_page.end_key(), "end"),
+            lost_page.message()
+        );
+    }
+
+    Ok(())
+}
+
+fn format_key_bound(key: Option<&[u8]>, unbounded: &str) -> String {
+    match key {
+        Some(key) => data_encoding::HEXUPPER.encode(key),
+        None => format!("({})", unbounded),
+    }
+}
@@ -0,0 +1,283 @@
+use std::{cell::RefCell, convert::TryInto, rc::Rc};
+
+use grebedb::Database;
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+
+use super::encoding::{binary_to_text, text_to_binary, DocumentFormat, Encoding, OutputFormat};
+
+const MAX_KEY_CANDIDATES: usize = 100;
+
+const COMMAND_NAMES: [&str; 17] = [
+    "count",
+    "del",
+    "exit",
+    "flush",
+    "get",
+    "grep",
+    "head",
+    "help",
+    "meta",
+    "preview",
+    "put",
+    "remove",
+    "remove-range",
+    "scan",
+    "stats",
+    "tail",
+    "verify",
+];
+
+const ENCODING_FLAGS: [&str; 6] = [
+    "-K",
+    "--key-encoding",
+    "-V",
+    "--value-encoding",
+    "-P",
+    "--pattern-encoding",
+];
+
+const VALUE_FLAGS: [&str; 9] = [
+    "-n", "--limit", "--offset", "-o", "--output", "-i", "--input", "-f", "--format",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ArgumentKind {
+    Key,
+    DocumentFormat,
+    Command,
+    Other,
+}
+
+// Flag names and the kinds of positional arguments of each command
+fn command_syntax(command: &str) -> (&'static [&'static str], &'static [ArgumentKind]) {
+    use ArgumentKind::*;
+
+    match command {
+        "get" => (
+            &["--output", "--format", "--key-encoding", "--value-encoding"],
+            &[Key],
+        ),
+        "scan" => (
+            &[
+                "--keys-only",
+                "--limit",
+                "--offset",
+                "--reverse",
+                "--count-only",
+                "--format",
+                "--key-encoding",
+                "--value-encoding",
+            ],
+            &[Key, Key],
+        ),
+        "head" | "tail" => (
+            &[
+                "--limit",
+                "--keys-only",
+                "--format",
+                "--key-encoding",
+                "--value-encoding",
+            ],
+            &[],
+        ),
+        "grep" => (
+            &[
+                "--keys",
+                "--values",
+                "--ignore-case",
+                "--pattern-encoding",
+                "--limit",
+                "--keys-only",
+                "--count-only",
+                "--format",
+                "--key-encoding",
+                "--value-encoding",
+            ],
+            &[Other],
+        ),
+        "put" => (
+            &["--input", "--key-encoding", "--value-encoding"],
+            &[Key, Other],
+        ),
+        "remove" | "del" => (&["--key-encoding"], &[Key]),
+        "remove-range" => (&["--dry-run", "--key-encoding"], &[Key, Key]),
+        "preview" => (&["--key-encoding"], &[Key, DocumentFormat]),
+        "help" => (&[], &[Command]),
+        _ => (&[], &[]),
+    }
+}
+
+/// Completion of command names, flag names, and keys for the inspector.
+pub struct InspectorHelper {
+    database: Rc<RefCell<Database>>,
+    default_key_encoding: Encoding,
+}
+
+impl InspectorHelper {
+    pub fn new(database: Rc<RefCell<Database>>, default_key_encoding: Encoding) -> Self {
+        Self {
+            database,
+            default_key_encoding,
+        }
+    }
+
+    fn complete_key(&self, prefix: &str, key_encoding: Encoding) -> Vec<String> {
+        let prefix = match text_to_binary(prefix, key_encoding) {
+            Ok(prefix) => prefix,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut database = self.database.borrow_mut();
+        let cursor = match database.cursor_range(prefix.as_slice()..) {
+            Ok(cursor) => cursor,
+            Err(_) => return Vec::new(),
+        };
+
+        cursor
+            .map(|(key, _value)| key)
+            .take_while(|key| key.starts_with(&prefix))
+            .take(MAX_KEY_CANDIDATES)
+            .map(|key| shell_words::quote(&binary_to_text(&key, key_encoding)).into_owned())
+            .collect()
+    }
+}
+
+impl Completer for InspectorHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let word_start = find_word_start(line);
+        let word = &line[word_start..];
+        let previous_words = match shell_words::split(&line[..word_start]) {
+            Ok(words) => words,
+            Err(_) => return Ok((word_start, Vec::new())),
+        };
+        let previous_words: Vec<&str> = previous_words.iter().map(String::as_str).collect();
+
+        let (command, args) = match previous_words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok((word_start, filter_prefix(&COMMAND_NAMES, word))),
+        };
+        let (flags, argument_kinds) = command_syntax(command);
+
+        match previous_words.last() {
+            Some(flag) if ENCODING_FLAGS.contains(flag) => {
+                return Ok((word_start, filter_prefix(&Encoding::list(), word)));
+            }
+            Some(&"-f" | &"--format") => {
+                return Ok((word_start, filter_prefix(&OutputFormat::list(), word)));
+            }
+            _ => {}
+        }
+
+        if word.starts_with('-') {
+            return Ok((word_start, filter_prefix(flags, word)));
+        }
+
+        let mut key_encoding = self.default_key_encoding;
+        let mut position = 0;
+        let mut index = 0;
+
+        while index < args.len() {
+            if ENCODING_FLAGS.contains(&args[index]) || VALUE_FLAGS.contains(&args[index]) {
+                if matches!(args[index], "-K" | "--key-encoding") {
+                    if let Some(Ok(encoding)) = args.get(index + 1).map(|&name| name.try_into()) {
+                        key_encoding = encoding;
+                    }
+                }
+
+                index += 2;
+            } else {
+                if !args[index].starts_with('-') {
+                    position += 1;
+                }
+
+                index += 1;
+            }
+        }
+
+        let candidates = match argument_kinds.get(position) {
+            Some(ArgumentKind::Key) => match unquote_partial_word(word) {
+                Some(prefix) => self.complete_key(&prefix, key_encoding),
+                None => Vec::new(),
+            },
+            Some(ArgumentKind::DocumentFormat) => filter_prefix(&DocumentFormat::list(), word),
+            Some(ArgumentKind::Command) => filter_prefix(&COMMAND_NAMES, word),
+            _ => Vec::new(),
+        };
+
+        Ok((word_start, candidates))
+    }
+}
+
+impl Hinter for InspectorHelper {
+    type Hint = String;
+}
+
+impl Highlighter for InspectorHelper {}
+
+impl Validator for InspectorHelper {}
+
+impl Helper for InspectorHelper {}
+
+// Return the index where the last word starts, taking quotes and escapes
+// into account like the shell-like splitting of the commands
+fn find_word_start(line: &str) -> usize {
+    let mut word_start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, character) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+
+        match (quote, character) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some('"'), '"') => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(character),
+            (None, character) if character.is_whitespace() => {
+                word_start = index + character.len_utf8()
+            }
+            (None, _) => {}
+        }
+    }
+
+    word_start
+}
+
+// Remove the quotes and escapes of a word that may be missing its
+// closing quote
+fn unquote_partial_word(word: &str) -> Option<String> {
+    let words = shell_words::split(word)
+        .or_else(|_| shell_words::split(&format!("{}\"", word)))
+        .or_else(|_| shell_words::split(&format!("{}'", word)))
+        .ok()?;
+
+    match words.as_slice() {
+        [] => Some(String::new()),
+        [word] => Some(word.clone()),
+        _ => None,
+    }
+}
+
+fn filter_prefix(names: &[&str], prefix: &str) -> Vec<String> {
+    names
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect()
+}
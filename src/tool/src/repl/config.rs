@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use super::encoding::Encoding;
+
+const DIRECTORY_NAME: &str = "grebedb-tool";
+const CONFIG_FILENAME: &str = "inspect.toml";
+const HISTORY_FILENAME: &str = "inspect_history.txt";
+
+/// Settings of the inspector loaded from the user's config directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InspectConfig {
+    /// Default of the `--key-encoding` option of the commands.
+    pub key_encoding: Encoding,
+
+    /// Default of the `--value-encoding` option of the commands.
+    pub value_encoding: Encoding,
+
+    /// Whether commands are saved to the history file.
+    pub history: bool,
+
+    /// Maximum number of commands in the history file.
+    pub history_size: usize,
+
+    /// Whether an error causes the program to exit in batch mode.
+    pub batch_exit_on_error: bool,
+}
+
+impl Default for InspectConfig {
+    fn default() -> Self {
+        Self {
+            key_encoding: Encoding::Utf8,
+            value_encoding: Encoding::Utf8,
+            history: true,
+            history_size: 1000,
+            batch_exit_on_error: true,
+        }
+    }
+}
+
+impl InspectConfig {
+    /// Load the config file if it exists.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = match config_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Self::default()),
+        };
+
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read config file {:?}", path))?;
+
+        toml::from_str(&text).with_context(|| format!("invalid config file {:?}", path))
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|path| path.join(DIRECTORY_NAME).join(CONFIG_FILENAME))
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|path| path.join(DIRECTORY_NAME).join(HISTORY_FILENAME))
+}
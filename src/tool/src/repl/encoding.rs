@@ -1,6 +1,9 @@
 use std::convert::TryFrom;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Encoding {
     Utf8,
     Percent,
@@ -108,6 +111,46 @@ impl DocumentFormat {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Raw,
+    Json,
+    Table,
+}
+
+impl From<OutputFormat> for &str {
+    fn from(value: OutputFormat) -> Self {
+        match value {
+            OutputFormat::Raw => "raw",
+            OutputFormat::Json => "json",
+            OutputFormat::Table => "table",
+        }
+    }
+}
+
+impl TryFrom<&str> for OutputFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "raw" => Ok(OutputFormat::Raw),
+            "json" => Ok(OutputFormat::Json),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(anyhow::anyhow!("Unknown output format")),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn list() -> [&'static str; 3] {
+        [
+            OutputFormat::Raw.into(),
+            OutputFormat::Json.into(),
+            OutputFormat::Table.into(),
+        ]
+    }
+}
+
 pub fn binary_to_document(value: &[u8], format: DocumentFormat) -> anyhow::Result<String> {
     match format {
         DocumentFormat::Json => {
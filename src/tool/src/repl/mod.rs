@@ -1,15 +1,30 @@
-mod encoding;
+mod completion;
+pub mod config;
+pub mod encoding;
 
+use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::convert::TryInto;
 use std::path::Path;
+use std::rc::Rc;
 
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use grebedb::{Database, OpenMode, Options};
-use rustyline::{error::ReadlineError, Editor};
-
-use self::encoding::{DocumentFormat, Encoding};
-
-pub fn inspect(database_path: &Path, write: bool, batch_mode: bool) -> anyhow::Result<()> {
+use rustyline::{error::ReadlineError, Config, Editor};
+
+use self::completion::InspectorHelper;
+use self::config::InspectConfig;
+use self::encoding::{DocumentFormat, Encoding, OutputFormat};
+
+/// Start an interactive session, or run the given commands and exit if
+/// there are any.
+pub fn inspect(
+    database_path: &Path,
+    write: bool,
+    batch_mode: bool,
+    commands: &[&str],
+    options: Options,
+) -> anyhow::Result<()> {
     let options = Options {
         open_mode: if write {
             OpenMode::LoadOnly
@@ -17,12 +32,53 @@ pub fn inspect(database_path: &Path, write: bool, batch_mode: bool) -> anyhow::R
             OpenMode::ReadOnly
         },
         automatic_flush: false,
-        ..Default::default()
+        ..options
+    };
+
+    let config = InspectConfig::load()?;
+
+    // Shared with the helper for completing keys
+    let database = Rc::new(RefCell::new(crate::archive::open_database(database_path, options)?));
+
+    if !commands.is_empty() {
+        let session = Session {
+            database_path,
+            write,
+            batch_mode: true,
+            config,
+        };
+
+        return execute_commands(&mut database.borrow_mut(), commands, &session);
+    }
+
+    let readline_config = Config::builder()
+        .max_history_size(config.history_size)
+        .build();
+    let mut readline = Editor::<InspectorHelper>::with_config(readline_config);
+    readline.set_helper(Some(InspectorHelper::new(
+        database.clone(),
+        config.key_encoding,
+    )));
+
+    // Scripts should not be affected by or fill the history
+    let history_path = if config.history && !batch_mode {
+        self::config::history_path()
+    } else {
+        None
     };
 
-    let mut database = Database::open_path(database_path, options)?;
+    if let Some(history_path) = &history_path {
+        // The file does not exist on the first session
+        let _ = readline.load_history(history_path);
+    }
 
-    let mut readline = Editor::<()>::new();
+    let exit_on_error = batch_mode && config.batch_exit_on_error;
+    let session = Session {
+        database_path,
+        write,
+        batch_mode,
+        config,
+    };
 
     if !batch_mode {
         eprintln!("Welcome to the inspector. Type `help` and press enter for list of commands.");
@@ -35,14 +91,14 @@ pub fn inspect(database_path: &Path, write: bool, batch_mode: bool) -> anyhow::R
             Ok(line) => {
                 readline.add_history_entry(line.as_str());
 
-                match execute_command(&mut database, &line) {
+                match execute_command(&mut database.borrow_mut(), &line, &session) {
                     Ok(command_result) => match command_result {
                         CommandResult::Continue => {}
                         CommandResult::Exit => {
                             break;
                         }
                         CommandResult::Error(error) => {
-                            if batch_mode {
+                            if exit_on_error {
                                 return Err(error);
                             } else {
                                 eprintln!("{}", error);
@@ -50,7 +106,7 @@ pub fn inspect(database_path: &Path, write: bool, batch_mode: bool) -> anyhow::R
                         }
                     },
                     Err(error) => {
-                        if batch_mode {
+                        if exit_on_error {
                             return Err(error);
                         } else {
                             eprintln!("Error: {}", error);
@@ -63,28 +119,272 @@ pub fn inspect(database_path: &Path, write: bool, batch_mode: bool) -> anyhow::R
         }
     }
 
+    if let Some(history_path) = &history_path {
+        if let Some(directory) = history_path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+
+        readline.save_history(history_path)?;
+    }
+
     eprintln!("Exiting.");
 
     Ok(())
 }
 
-fn build_command_args() -> App<'static, 'static> {
-    let key_format_arg = Arg::with_name("key_encoding")
+fn key_encoding_arg(default: Encoding) -> Arg<'static, 'static> {
+    Arg::with_name("key_encoding")
         .value_name("ENCODING")
         .long("key-encoding")
         .short("K")
         .help("Use the given encoding to show keys in textual form.")
         .possible_values(&Encoding::list())
-        .default_value(Encoding::Utf8.into());
+        .default_value(default.into())
+}
 
-    let value_format_arg = Arg::with_name("value_encoding")
+fn output_format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("format")
+        .value_name("FORMAT")
+        .long("format")
+        .short("f")
+        .help("Show key-value pairs as lines, JSON objects, or aligned columns.")
+        .long_help(
+            "Show key-value pairs as lines, JSON objects, or aligned columns.\n\n\
+            The raw format shows keys and values on alternating lines. The json format \
+            shows each pair as a JSON object on its own line. The table format shows \
+            each pair on a line with the keys aligned in a column.",
+        )
+        .possible_values(&OutputFormat::list())
+        .default_value(OutputFormat::Raw.into())
+}
+
+fn value_encoding_arg(default: Encoding) -> Arg<'static, 'static> {
+    Arg::with_name("value_encoding")
         .value_name("ENCODING")
         .long("value-encoding")
         .short("V")
         .help("Use the given encoding to show values in textual form.")
         .possible_values(&Encoding::list())
-        .default_value(Encoding::Utf8.into());
+        .default_value(default.into())
+}
+
+// The database path argument is given when used outside of the inspector
+pub fn get_subcommand(
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    SubCommand::with_name("get")
+        .about("Get key-value pair by its key.")
+        .args(database_path_arg.as_slice())
+        .arg(Arg::with_name("key").required(true))
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .value_name("FILE")
+                .help("Write the value to the given file instead of showing it."),
+        )
+        .arg(output_format_arg())
+        .arg(key_encoding_arg(config.key_encoding))
+        .arg(value_encoding_arg(config.value_encoding))
+}
+
+pub fn scan_subcommand(
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    SubCommand::with_name("scan")
+        .about("Get all key-value pairs within a range.")
+        .args(database_path_arg.as_slice())
+        .arg(
+            Arg::with_name("key_start")
+                .value_name("START")
+                .help("Starting key range (inclusive)."),
+        )
+        .arg(
+            Arg::with_name("key_end")
+                .value_name("END")
+                .help("Ending key range (exclusive)."),
+        )
+        .arg(
+            Arg::with_name("keys_only")
+                .long("keys-only")
+                .short("k")
+                .help("Show only keys and don't print values."),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .long("limit")
+                .short("n")
+                .value_name("N")
+                .help("Show at most N key-value pairs."),
+        )
+        .arg(
+            Arg::with_name("offset")
+                .long("offset")
+                .value_name("N")
+                .help("Skip the first N key-value pairs."),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .long("reverse")
+                .short("r")
+                .help("Show key-value pairs in descending order of keys."),
+        )
+        .arg(
+            Arg::with_name("count_only")
+                .long("count-only")
+                .short("c")
+                .help("Show only the number of key-value pairs."),
+        )
+        .arg(output_format_arg())
+        .arg(key_encoding_arg(config.key_encoding))
+        .arg(value_encoding_arg(config.value_encoding))
+}
+
+pub fn put_subcommand(
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    SubCommand::with_name("put")
+        .about("Insert a key-value pair.")
+        .args(database_path_arg.as_slice())
+        .arg(Arg::with_name("key").required(true))
+        .arg(Arg::with_name("value").required_unless("input"))
+        .arg(
+            Arg::with_name("input")
+                .long("input")
+                .short("i")
+                .value_name("FILE")
+                .conflicts_with("value")
+                .help("Read the value from the given file."),
+        )
+        .arg(key_encoding_arg(config.key_encoding))
+        .arg(value_encoding_arg(config.value_encoding))
+}
+
+pub fn remove_subcommand(
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    SubCommand::with_name("remove")
+        .about("Remove a key-value pair by its key.")
+        .alias("del")
+        .args(database_path_arg.as_slice())
+        .arg(Arg::with_name("key").required(true))
+        .arg(key_encoding_arg(config.key_encoding))
+}
+
+pub fn head_subcommand(
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    first_last_subcommand("head", database_path_arg, config)
+        .about("Get the key-value pairs with the smallest keys.")
+}
+
+pub fn tail_subcommand(
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    first_last_subcommand("tail", database_path_arg, config)
+        .about("Get the key-value pairs with the largest keys.")
+}
+
+fn first_last_subcommand(
+    name: &'static str,
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    SubCommand::with_name(name)
+        .args(database_path_arg.as_slice())
+        .arg(
+            Arg::with_name("limit")
+                .long("limit")
+                .short("n")
+                .value_name("N")
+                .default_value("10")
+                .help("Show N key-value pairs."),
+        )
+        .arg(
+            Arg::with_name("keys_only")
+                .long("keys-only")
+                .short("k")
+                .help("Show only keys and don't print values."),
+        )
+        .arg(output_format_arg())
+        .arg(key_encoding_arg(config.key_encoding))
+        .arg(value_encoding_arg(config.value_encoding))
+}
+
+pub fn grep_subcommand(
+    database_path_arg: Option<Arg<'static, 'static>>,
+    config: &InspectConfig,
+) -> App<'static, 'static> {
+    SubCommand::with_name("grep")
+        .about("Get the key-value pairs containing a pattern.")
+        .long_about(
+            "Get the key-value pairs containing a pattern.\n\n\
+            The pattern is a sequence of bytes that is searched for in the keys and values. \
+            Use --pattern-encoding to search for binary data.",
+        )
+        .args(database_path_arg.as_slice())
+        .arg(
+            Arg::with_name("pattern")
+                .value_name("PATTERN")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("search_keys")
+                .long("keys")
+                .conflicts_with("search_values")
+                .help("Search only the keys."),
+        )
+        .arg(
+            Arg::with_name("search_values")
+                .long("values")
+                .help("Search only the values."),
+        )
+        .arg(
+            Arg::with_name("ignore_case")
+                .long("ignore-case")
+                .short("i")
+                .help("Ignore the case of ASCII letters."),
+        )
+        .arg(
+            Arg::with_name("pattern_encoding")
+                .value_name("ENCODING")
+                .long("pattern-encoding")
+                .short("P")
+                .help("Use the given encoding to read the pattern.")
+                .possible_values(&Encoding::list())
+                .default_value(Encoding::Utf8.into()),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .long("limit")
+                .short("n")
+                .value_name("N")
+                .help("Show at most N key-value pairs."),
+        )
+        .arg(
+            Arg::with_name("keys_only")
+                .long("keys-only")
+                .short("k")
+                .help("Show only keys and don't print values."),
+        )
+        .arg(
+            Arg::with_name("count_only")
+                .long("count-only")
+                .short("c")
+                .help("Show only the number of key-value pairs."),
+        )
+        .arg(output_format_arg())
+        .arg(key_encoding_arg(config.key_encoding))
+        .arg(value_encoding_arg(config.value_encoding))
+}
 
+fn build_command_args(config: &InspectConfig) -> App<'static, 'static> {
     App::new("")
         .setting(AppSettings::DisableVersion)
         .setting(AppSettings::SubcommandRequiredElseHelp)
@@ -92,48 +392,34 @@ fn build_command_args() -> App<'static, 'static> {
         .subcommand(
             SubCommand::with_name("count").about("Get number of key-value pairs in the database."),
         )
+        .subcommand(get_subcommand(None, config))
+        .subcommand(scan_subcommand(None, config))
+        .subcommand(put_subcommand(None, config))
+        .subcommand(remove_subcommand(None, config))
+        .subcommand(head_subcommand(None, config))
+        .subcommand(tail_subcommand(None, config))
+        .subcommand(grep_subcommand(None, config))
         .subcommand(
-            SubCommand::with_name("get")
-                .about("Get key-value pair by its key.")
-                .arg(Arg::with_name("key").required(true))
-                .arg(key_format_arg.clone())
-                .arg(value_format_arg.clone()),
-        )
-        .subcommand(
-            SubCommand::with_name("scan")
-                .about("Get all key-value pairs within a range.")
+            SubCommand::with_name("remove-range")
+                .about("Remove all key-value pairs within a range.")
                 .arg(
                     Arg::with_name("key_start")
                         .value_name("START")
+                        .required(true)
                         .help("Starting key range (inclusive)."),
                 )
                 .arg(
                     Arg::with_name("key_end")
                         .value_name("END")
+                        .required(true)
                         .help("Ending key range (exclusive)."),
                 )
                 .arg(
-                    Arg::with_name("keys_only")
-                        .long("keys-only")
-                        .short("k")
-                        .help("Show only keys and don't print values."),
+                    Arg::with_name("dry_run")
+                        .long("dry-run")
+                        .help("Show the number of key-value pairs without removing them."),
                 )
-                .arg(key_format_arg.clone())
-                .arg(value_format_arg.clone()),
-        )
-        .subcommand(
-            SubCommand::with_name("put")
-                .about("Insert a key-value pair.")
-                .arg(Arg::with_name("key").required(true))
-                .arg(Arg::with_name("value").required(true))
-                .arg(key_format_arg.clone())
-                .arg(value_format_arg.clone()),
-        )
-        .subcommand(
-            SubCommand::with_name("remove")
-                .about("Remove a key-value pair by its key.")
-                .arg(Arg::with_name("key").required(true))
-                .arg(key_format_arg.clone()),
+                .arg(key_encoding_arg(config.key_encoding)),
         )
         .subcommand(
             SubCommand::with_name("preview")
@@ -149,20 +435,106 @@ fn build_command_args() -> App<'static, 'static> {
                         .required(true)
                         .possible_values(&DocumentFormat::list()),
                 )
-                .arg(key_format_arg.clone()),
+                .arg(key_encoding_arg(config.key_encoding)),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Show an overview of the database structure and disk usage."),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Check the database for internal consistency and data integrity."),
+        )
+        .subcommand(
+            SubCommand::with_name("meta").about("Show the UUID, revision, and other metadata."),
         )
         .subcommand(SubCommand::with_name("flush").about("Persist changes to database."))
         .subcommand(SubCommand::with_name("exit").about("Exit the inspector."))
 }
 
+/// Run one of the get, scan, head, tail, grep, put, or remove commands
+/// outside of an interactive session. Returns false if the key of a get
+/// command is not found or a grep command finds nothing.
+pub fn run_command(
+    database_path: &Path,
+    name: &str,
+    args: &ArgMatches,
+    options: Options,
+) -> anyhow::Result<bool> {
+    let write = matches!(name, "put" | "remove");
+    let options = Options {
+        open_mode: if write {
+            OpenMode::LoadOnly
+        } else {
+            OpenMode::ReadOnly
+        },
+        automatic_flush: false,
+        ..options
+    };
+
+    let mut database = crate::archive::open_database(database_path, options)?;
+    let mut found = true;
+
+    match name {
+        "get" => found = get_command(&mut database, args)?,
+        "scan" => scan_command(&mut database, args)?,
+        "head" => head_command(&mut database, args)?,
+        "tail" => tail_command(&mut database, args)?,
+        "grep" => found = grep_command(&mut database, args)?,
+        "put" => put_command(&mut database, args)?,
+        "remove" => remove_command(&mut database, args)?,
+        _ => unreachable!(),
+    }
+
+    if write {
+        database.flush()?;
+    }
+
+    Ok(found)
+}
+
+// State of the interactive session needed by some commands
+struct Session<'a> {
+    database_path: &'a Path,
+    write: bool,
+    batch_mode: bool,
+    config: InspectConfig,
+}
+
+// Errors are returned instead of continuing like batch mode. Modifications
+// are flushed like the commands run outside of the inspector.
+fn execute_commands(
+    database: &mut Database,
+    commands: &[&str],
+    session: &Session,
+) -> anyhow::Result<()> {
+    for command in commands {
+        match execute_command(database, command, session)? {
+            CommandResult::Continue => {}
+            CommandResult::Exit => break,
+            CommandResult::Error(error) => return Err(error),
+        }
+    }
+
+    if session.write {
+        database.flush()?;
+    }
+
+    Ok(())
+}
+
 enum CommandResult {
     Continue,
     Exit,
     Error(anyhow::Error),
 }
 
-fn execute_command(database: &mut Database, line: &str) -> anyhow::Result<CommandResult> {
-    let args = build_command_args();
+fn execute_command(
+    database: &mut Database,
+    line: &str,
+    session: &Session,
+) -> anyhow::Result<CommandResult> {
+    let args = build_command_args(&session.config);
 
     match args.get_matches_from_safe(shell_words::split(line)?) {
         Ok(matches) => match matches.subcommand() {
@@ -178,6 +550,18 @@ fn execute_command(database: &mut Database, line: &str) -> anyhow::Result<Comman
                 scan_command(database, sub_args.unwrap())?;
                 Ok(CommandResult::Continue)
             }
+            ("head", sub_args) => {
+                head_command(database, sub_args.unwrap())?;
+                Ok(CommandResult::Continue)
+            }
+            ("tail", sub_args) => {
+                tail_command(database, sub_args.unwrap())?;
+                Ok(CommandResult::Continue)
+            }
+            ("grep", sub_args) => {
+                grep_command(database, sub_args.unwrap())?;
+                Ok(CommandResult::Continue)
+            }
             ("put", sub_args) => {
                 put_command(database, sub_args.unwrap())?;
                 Ok(CommandResult::Continue)
@@ -186,6 +570,25 @@ fn execute_command(database: &mut Database, line: &str) -> anyhow::Result<Comman
                 remove_command(database, sub_args.unwrap())?;
                 Ok(CommandResult::Continue)
             }
+            ("remove-range", sub_args) => {
+                remove_range_command(database, sub_args.unwrap(), session)?;
+                Ok(CommandResult::Continue)
+            }
+            ("stats", _) => {
+                crate::stats::print_stats(database, session.database_path)?;
+                Ok(CommandResult::Continue)
+            }
+            ("verify", _) => {
+                crate::verify::verify_database(
+                    database,
+                    crate::progress::ProgressStyle::from_flags(false, true),
+                )?;
+                Ok(CommandResult::Continue)
+            }
+            ("meta", _) => {
+                meta_command(database);
+                Ok(CommandResult::Continue)
+            }
             ("flush", _) => {
                 flush_command(database)?;
                 Ok(CommandResult::Continue)
@@ -208,6 +611,13 @@ fn encoding_from_args<'a>(args: &'a ArgMatches, name: &str) -> Encoding {
         .unwrap_or(Encoding::Utf8)
 }
 
+fn output_format_from_args(args: &ArgMatches) -> OutputFormat {
+    args.value_of("format")
+        .unwrap_or_default()
+        .try_into()
+        .unwrap_or(OutputFormat::Raw)
+}
+
 fn text_or_error_from_args<'a>(args: &'a ArgMatches, name: &str) -> anyhow::Result<&'a str> {
     let text = args
         .value_of(name)
@@ -215,6 +625,16 @@ fn text_or_error_from_args<'a>(args: &'a ArgMatches, name: &str) -> anyhow::Resu
     Ok(text)
 }
 
+fn count_from_args(args: &ArgMatches, name: &str) -> anyhow::Result<Option<usize>> {
+    match args.value_of(name) {
+        Some(text) => match text.parse() {
+            Ok(count) => Ok(Some(count)),
+            Err(_) => Err(anyhow::anyhow!("Invalid number: {}", text)),
+        },
+        None => Ok(None),
+    }
+}
+
 fn count_command(database: &mut Database) {
     let metadata = database.metadata();
     metadata.key_value_count();
@@ -222,26 +642,33 @@ fn count_command(database: &mut Database) {
     println!("{}", metadata.key_value_count());
 }
 
-fn get_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::Result<()> {
+// Returns whether the key was found
+fn get_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::Result<bool> {
     let key_encoding = encoding_from_args(args, "key_encoding");
     let value_encoding = encoding_from_args(args, "value_encoding");
 
     let key = text_or_error_from_args(args, "key")?;
     let key = self::encoding::text_to_binary(key, key_encoding)?;
 
-    let value = database.get(key)?;
-    if let Some(value) = value {
-        let value = self::encoding::binary_to_text(&value, value_encoding);
+    let value = database.get(&key)?;
+    if let Some(value) = &value {
+        if let Some(path) = args.value_of_os("output") {
+            std::fs::write(path, value)?;
+            println!("OK");
+        } else if output_format_from_args(args) == OutputFormat::Raw {
+            let value = self::encoding::binary_to_text(value, value_encoding);
 
-        println!("{}", value);
+            println!("{}", value);
+        } else {
+            print_pairs(std::iter::once((key, value.clone())), args);
+        }
     }
 
-    Ok(())
+    Ok(value.is_some())
 }
 
 fn scan_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::Result<()> {
     let key_encoding = encoding_from_args(args, "key_encoding");
-    let value_encoding = encoding_from_args(args, "value_encoding");
 
     let key_start = args.value_of("key_start").unwrap_or_default();
     let key_start = self::encoding::text_to_binary(key_start, key_encoding)?;
@@ -249,7 +676,10 @@ fn scan_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::Re
     let key_end = args.value_of("key_end").unwrap_or_default();
     let key_end = self::encoding::text_to_binary(key_end, key_encoding)?;
 
-    let cursor = {
+    let limit = count_from_args(args, "limit")?.unwrap_or(usize::MAX);
+    let offset = count_from_args(args, "offset")?.unwrap_or(0);
+
+    let mut cursor = {
         if !key_end.is_empty() {
             database.cursor_range(key_start..key_end)?
         } else {
@@ -257,20 +687,174 @@ fn scan_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::Re
         }
     };
 
-    for (key, value) in cursor {
-        let key = self::encoding::binary_to_text(&key, key_encoding);
-        let value = self::encoding::binary_to_text(&value, value_encoding);
+    let pairs: Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)>> = if args.is_present("reverse") {
+        // The cursor only moves forward, so only the last pairs that
+        // can be shown are kept while reading through the range
+        let window_size = offset.saturating_add(limit);
+        let mut window = VecDeque::new();
 
-        println!("{}", key);
+        for pair in cursor.by_ref() {
+            window.push_back(pair);
 
-        if !args.is_present("keys_only") {
-            println!("{}", value);
+            if window.len() > window_size {
+                window.pop_front();
+            }
         }
+
+        Box::new(window.into_iter().rev().skip(offset).take(limit))
+    } else {
+        Box::new(cursor.by_ref().skip(offset).take(limit))
+    };
+
+    if args.is_present("count_only") {
+        let count = pairs.count();
+        check_cursor_error(cursor.error())?;
+        println!("{}", count);
+        return Ok(());
     }
 
+    print_pairs(pairs, args);
+
+    check_cursor_error(cursor.error())
+}
+
+fn head_command(database: &mut Database, args: &ArgMatches) -> anyhow::Result<()> {
+    let limit = count_from_args(args, "limit")?.unwrap_or(usize::MAX);
+
+    let mut cursor = database.cursor()?;
+
+    print_pairs(cursor.by_ref().take(limit), args);
+
+    check_cursor_error(cursor.error())
+}
+
+// The pairs are shown in ascending order like the scan command
+fn tail_command(database: &mut Database, args: &ArgMatches) -> anyhow::Result<()> {
+    let limit = count_from_args(args, "limit")?.unwrap_or(usize::MAX);
+    let mut window = VecDeque::new();
+    let mut cursor = database.cursor()?;
+
+    for pair in cursor.by_ref() {
+        window.push_back(pair);
+
+        if window.len() > limit {
+            window.pop_front();
+        }
+    }
+
+    check_cursor_error(cursor.error())?;
+    print_pairs(window.into_iter(), args);
+
     Ok(())
 }
 
+// Returns whether any pairs were found
+fn grep_command(database: &mut Database, args: &ArgMatches) -> anyhow::Result<bool> {
+    let pattern_encoding = encoding_from_args(args, "pattern_encoding");
+    let pattern = text_or_error_from_args(args, "pattern")?;
+    let pattern = self::encoding::text_to_binary(pattern, pattern_encoding)?;
+
+    let ignore_case = args.is_present("ignore_case");
+    let search_keys = !args.is_present("search_values");
+    let search_values = !args.is_present("search_keys");
+    let limit = count_from_args(args, "limit")?.unwrap_or(usize::MAX);
+
+    let mut cursor = database.cursor()?;
+    let pairs = cursor
+        .by_ref()
+        .filter(|(key, value)| {
+            (search_keys && contains_pattern(key, &pattern, ignore_case))
+                || (search_values && contains_pattern(value, &pattern, ignore_case))
+        })
+        .take(limit);
+
+    if args.is_present("count_only") {
+        let count = pairs.count();
+        check_cursor_error(cursor.error())?;
+        println!("{}", count);
+        return Ok(count > 0);
+    }
+
+    let mut found = false;
+
+    print_pairs(pairs.inspect(|_| found = true), args);
+    check_cursor_error(cursor.error())?;
+
+    Ok(found)
+}
+
+// The iterators of the cursor end early on an error instead of returning it
+fn check_cursor_error(error: Option<&grebedb::Error>) -> anyhow::Result<()> {
+    match error {
+        Some(error) => anyhow::bail!("reading the database stopped early: {}", error),
+        None => Ok(()),
+    }
+}
+
+fn contains_pattern(data: &[u8], pattern: &[u8], ignore_case: bool) -> bool {
+    pattern.is_empty()
+        || data.windows(pattern.len()).any(|window| {
+            if ignore_case {
+                window.eq_ignore_ascii_case(pattern)
+            } else {
+                window == pattern
+            }
+        })
+}
+
+fn print_pairs<I>(pairs: I, args: &ArgMatches)
+where
+    I: Iterator<Item = (Vec<u8>, Vec<u8>)>,
+{
+    let key_encoding = encoding_from_args(args, "key_encoding");
+    let value_encoding = encoding_from_args(args, "value_encoding");
+    let keys_only = args.is_present("keys_only");
+    let pairs = pairs.map(|(key, value)| {
+        (
+            self::encoding::binary_to_text(&key, key_encoding),
+            self::encoding::binary_to_text(&value, value_encoding),
+        )
+    });
+
+    match output_format_from_args(args) {
+        OutputFormat::Raw => {
+            for (key, value) in pairs {
+                println!("{}", key);
+
+                if !keys_only {
+                    println!("{}", value);
+                }
+            }
+        }
+        OutputFormat::Json => {
+            for (key, value) in pairs {
+                if keys_only {
+                    println!("{}", serde_json::json!({ "key": key }));
+                } else {
+                    println!("{}", serde_json::json!({ "key": key, "value": value }));
+                }
+            }
+        }
+        OutputFormat::Table => {
+            // The rows are collected to find the width of the key column
+            let rows: Vec<(String, String)> = pairs.collect();
+            let width = rows
+                .iter()
+                .map(|(key, _value)| key.chars().count())
+                .max()
+                .unwrap_or(0);
+
+            for (key, value) in rows {
+                if keys_only {
+                    println!("{}", key);
+                } else {
+                    println!("{:width$}  {}", key, value, width = width);
+                }
+            }
+        }
+    }
+}
+
 fn put_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::Result<()> {
     let key_encoding = encoding_from_args(args, "key_encoding");
     let value_encoding = encoding_from_args(args, "value_encoding");
@@ -278,8 +862,12 @@ fn put_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::Res
     let key = text_or_error_from_args(args, "key")?;
     let key = self::encoding::text_to_binary(key, key_encoding)?;
 
-    let value = text_or_error_from_args(args, "value")?;
-    let value = self::encoding::text_to_binary(value, value_encoding)?;
+    let value = if let Some(path) = args.value_of_os("input") {
+        std::fs::read(path)?
+    } else {
+        let value = text_or_error_from_args(args, "value")?;
+        self::encoding::text_to_binary(value, value_encoding)?
+    };
 
     database.put(key, value)?;
     println!("OK");
@@ -299,6 +887,86 @@ fn remove_command<'a>(database: &mut Database, args: &'a ArgMatches) -> anyhow::
     Ok(())
 }
 
+// Confirmation is asked only in interactive sessions
+fn remove_range_command(
+    database: &mut Database,
+    args: &ArgMatches,
+    session: &Session,
+) -> anyhow::Result<()> {
+    let key_encoding = encoding_from_args(args, "key_encoding");
+
+    let key_start = text_or_error_from_args(args, "key_start")?;
+    let key_start = self::encoding::text_to_binary(key_start, key_encoding)?;
+
+    let key_end = text_or_error_from_args(args, "key_end")?;
+    let key_end = self::encoding::text_to_binary(key_end, key_encoding)?;
+
+    let dry_run = args.is_present("dry_run");
+
+    if !session.write && !dry_run {
+        anyhow::bail!("Removing requires the inspector to be opened with --write");
+    }
+
+    let range = key_start.as_slice()..key_end.as_slice();
+    let mut keys = database.keys(range.clone())?;
+    let count = keys.by_ref().count();
+    check_cursor_error(keys.error())?;
+
+    if dry_run {
+        println!("{} key-value pairs would be removed", count);
+        return Ok(());
+    }
+
+    if count == 0 {
+        println!("No key-value pairs in range");
+        return Ok(());
+    }
+
+    if !session.batch_mode {
+        eprint!("Remove {} key-value pairs? [y/N] ", count);
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Cancelled");
+            return Ok(());
+        }
+    }
+
+    let count = database.remove_range(range)?;
+    println!("Removed {} key-value pairs", count);
+
+    Ok(())
+}
+
+fn meta_command(database: &mut Database) {
+    let metadata = database.metadata();
+
+    println!("UUID: {}", metadata.uuid());
+    println!("Revision: {}", database.revision());
+    println!("Key-value pairs: {}", metadata.key_value_count());
+
+    if let Some(revision) = metadata.last_export_revision() {
+        println!("Last export revision: {}", revision);
+    }
+
+    if let Some(source) = metadata.import_source() {
+        println!("Import source: {}", source);
+    }
+
+    if let Some((applied_count, offset)) = metadata.import_progress() {
+        println!(
+            "Unfinished import: {} pairs, offset {}",
+            applied_count, offset
+        );
+    }
+
+    if let Some(app_metadata) = metadata.app_metadata() {
+        println!("Application metadata: {} bytes", app_metadata.len());
+    }
+}
+
 fn flush_command(database: &mut Database) -> anyhow::Result<()> {
     database.flush()?;
     println!("OK");
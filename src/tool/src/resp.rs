@@ -0,0 +1,488 @@
+use std::{
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use grebedb::{Database, OpenMode, Options};
+
+const DEFAULT_SCAN_COUNT: usize = 10;
+// Same limits as the Redis defaults for inline commands, bulk strings,
+// and the query buffer of a client
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+const MAX_BULK_LENGTH: usize = 512 * 1024 * 1024;
+const MAX_COMMAND_LENGTH: usize = 1024 * 1024 * 1024;
+
+enum Reply {
+    Status(&'static str),
+    Error(String),
+    Integer(u64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+pub fn serve_resp(
+    database_path: &Path,
+    address: &str,
+    read_only: bool,
+    options: Options,
+) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: if read_only {
+            OpenMode::ReadOnly
+        } else {
+            OpenMode::LoadOnly
+        },
+        ..options
+    };
+
+    let database = Arc::new(Mutex::new(Database::open_path(database_path, options)?));
+    let listener = TcpListener::bind(address)?;
+
+    eprintln!("Listening on {}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let database = database.clone();
+
+        std::thread::spawn(move || {
+            if let Err(error) = handle_connection(stream, &database, read_only) {
+                eprintln!("Connection error: {}", error);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    database: &Mutex<Database>,
+    read_only: bool,
+) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    while let Some(command) = read_command(&mut reader, MAX_COMMAND_LENGTH)? {
+        if command.is_empty() {
+            continue;
+        }
+
+        let name = String::from_utf8_lossy(&command[0]).to_ascii_uppercase();
+
+        if name == "QUIT" {
+            write_reply(&mut writer, &Reply::Status("OK"))?;
+            writer.flush()?;
+            break;
+        }
+
+        let reply = {
+            let mut database = database.lock().unwrap();
+
+            match run_command(&mut database, &name, &command[1..], read_only) {
+                Ok(reply) => reply,
+                Err(error) => Reply::Error(format!("ERR {}", error)),
+            }
+        };
+
+        write_reply(&mut writer, &reply)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+fn run_command(
+    database: &mut Database,
+    name: &str,
+    args: &[Vec<u8>],
+    read_only: bool,
+) -> anyhow::Result<Reply> {
+    let arity_ok = match name {
+        "PING" => args.len() <= 1,
+        "GET" => args.len() == 1,
+        "SET" => args.len() == 2,
+        "DEL" | "EXISTS" | "SCAN" => !args.is_empty(),
+        _ => return Ok(Reply::Error(format!("ERR unknown command '{}'", name))),
+    };
+
+    if !arity_ok {
+        return Ok(Reply::Error(format!(
+            "ERR wrong number of arguments for '{}' command",
+            name.to_ascii_lowercase()
+        )));
+    }
+
+    if read_only && (name == "SET" || name == "DEL") {
+        return Ok(Reply::Error(
+            "READONLY You can't write against a read only database.".to_string(),
+        ));
+    }
+
+    let reply = match name {
+        "PING" => match args.first() {
+            Some(message) => Reply::Bulk(Some(message.clone())),
+            None => Reply::Status("PONG"),
+        },
+        "GET" => Reply::Bulk(database.get(&args[0])?),
+        "SET" => {
+            database.put(args[0].as_slice(), args[1].as_slice())?;
+            database.flush()?;
+
+            Reply::Status("OK")
+        }
+        "DEL" => {
+            let mut count = 0;
+
+            for key in args {
+                if database.contains_key(key)? {
+                    database.remove(key)?;
+                    count += 1;
+                }
+            }
+
+            database.flush()?;
+
+            Reply::Integer(count)
+        }
+        "EXISTS" => {
+            let mut count = 0;
+
+            for key in args {
+                if database.contains_key(key)? {
+                    count += 1;
+                }
+            }
+
+            Reply::Integer(count)
+        }
+        "SCAN" => scan(database, args)?,
+        _ => unreachable!(),
+    };
+
+    Ok(reply)
+}
+
+// The cursor is the number of keys visited by the previous calls, so keys
+// inserted or removed between calls may be skipped or returned twice
+fn scan(database: &mut Database, args: &[Vec<u8>]) -> anyhow::Result<Reply> {
+    let syntax_error = || Ok(Reply::Error("ERR syntax error".to_string()));
+    let position: usize = match String::from_utf8_lossy(&args[0]).parse() {
+        Ok(position) => position,
+        Err(_) => return Ok(Reply::Error("ERR invalid cursor".to_string())),
+    };
+    let mut pattern = None;
+    let mut count = DEFAULT_SCAN_COUNT;
+
+    for option in args[1..].chunks(2) {
+        let (name, value) = match option {
+            [name, value] => (String::from_utf8_lossy(name).to_ascii_uppercase(), value),
+            _ => return syntax_error(),
+        };
+
+        match name.as_str() {
+            "MATCH" => pattern = Some(value.clone()),
+            "COUNT" => match String::from_utf8_lossy(value).parse() {
+                Ok(value) if value > 0 => count = value,
+                _ => return syntax_error(),
+            },
+            _ => return syntax_error(),
+        }
+    }
+
+    let mut cursor = database.cursor()?;
+    let mut key = Vec::new();
+    let mut value = Vec::new();
+    let mut replied_keys = Vec::new();
+    let mut visited = 0;
+    let limit = position.saturating_add(count);
+
+    while visited < limit && cursor.next_buf(&mut key, &mut value)? {
+        visited += 1;
+
+        if visited <= position {
+            continue;
+        }
+
+        let is_match = match &pattern {
+            Some(pattern) => glob_match(pattern, &key),
+            None => true,
+        };
+
+        if is_match {
+            replied_keys.push(Reply::Bulk(Some(key.clone())));
+        }
+    }
+
+    let next_position = if visited == limit && cursor.next_buf(&mut key, &mut value)? {
+        visited
+    } else {
+        0
+    };
+
+    Ok(Reply::Array(vec![
+        Reply::Bulk(Some(next_position.to_string().into_bytes())),
+        Reply::Array(replied_keys),
+    ]))
+}
+
+// Supports the `*` and `?` wildcards. Only the last `*` is backtracked to,
+// so the time is bounded by the pattern length times the text length.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let mut pattern_index = 0;
+    let mut text_index = 0;
+    let mut last_star = None;
+
+    while text_index < text.len() {
+        match pattern.get(pattern_index) {
+            Some(b'*') => {
+                pattern_index += 1;
+                last_star = Some((pattern_index, text_index));
+            }
+            Some(&byte) if byte == b'?' || byte == text[text_index] => {
+                pattern_index += 1;
+                text_index += 1;
+            }
+            _ => match last_star {
+                Some((star_pattern_index, star_text_index)) => {
+                    pattern_index = star_pattern_index;
+                    text_index = star_text_index + 1;
+                    last_star = Some((star_pattern_index, text_index));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pattern_index..].iter().all(|&byte| byte == b'*')
+}
+
+// Returns none when the connection is closed. The maximum length is the
+// number of bytes of the whole command including the protocol framing.
+fn read_command<R: BufRead>(
+    reader: &mut R,
+    max_length: usize,
+) -> anyhow::Result<Option<Vec<Vec<u8>>>> {
+    let line = match read_line(reader)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+
+    // Inline commands are used by telnet and similar tools
+    let count = match line.strip_prefix(b"*") {
+        Some(count) => parse_length(count)?,
+        None => {
+            return Ok(Some(
+                line.split(|byte| byte.is_ascii_whitespace())
+                    .filter(|arg| !arg.is_empty())
+                    .map(|arg| arg.to_vec())
+                    .collect(),
+            ))
+        }
+    };
+
+    let mut command = Vec::with_capacity(count.min(1024));
+    let mut command_length = line.len() + 2;
+
+    for _ in 0..count {
+        let line = read_line(reader)?.ok_or_else(|| anyhow::anyhow!("unexpected end"))?;
+        let length = match line.strip_prefix(b"$") {
+            Some(length) => parse_length(length)?,
+            None => anyhow::bail!("expected bulk string"),
+        };
+
+        if length > MAX_BULK_LENGTH {
+            anyhow::bail!("bulk string too long");
+        }
+
+        command_length += line.len() + length + 4;
+
+        if command_length > max_length {
+            anyhow::bail!("command too long");
+        }
+
+        // Read as the data arrives so a large length alone does not allocate
+        let mut data = Vec::new();
+        reader.take(length as u64).read_to_end(&mut data)?;
+
+        if data.len() != length {
+            anyhow::bail!("unexpected end");
+        }
+
+        let mut terminator = [0u8; 2];
+        reader.read_exact(&mut terminator)?;
+
+        if &terminator != b"\r\n" {
+            anyhow::bail!("expected CRLF after bulk string");
+        }
+
+        command.push(data);
+    }
+
+    Ok(Some(command))
+}
+
+// The maximum length includes the line ending
+fn read_line<R: BufRead>(reader: &mut R) -> anyhow::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+
+    if reader
+        .take(MAX_LINE_LENGTH as u64)
+        .read_until(b'\n', &mut line)?
+        == 0
+    {
+        return Ok(None);
+    }
+
+    if line.len() == MAX_LINE_LENGTH && line.last() != Some(&b'\n') {
+        anyhow::bail!("line too long");
+    }
+
+    while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    Ok(Some(line))
+}
+
+fn parse_length(text: &[u8]) -> anyhow::Result<usize> {
+    Ok(std::str::from_utf8(text)?.parse()?)
+}
+
+fn write_reply<W: Write>(writer: &mut W, reply: &Reply) -> std::io::Result<()> {
+    match reply {
+        Reply::Status(message) => write!(writer, "+{}\r\n", message),
+        Reply::Error(message) => write!(writer, "-{}\r\n", message.replace(['\r', '\n'], " ")),
+        Reply::Integer(value) => write!(writer, ":{}\r\n", value),
+        Reply::Bulk(None) => write!(writer, "$-1\r\n"),
+        Reply::Bulk(Some(data)) => {
+            write!(writer, "${}\r\n", data.len())?;
+            writer.write_all(data)?;
+            writer.write_all(b"\r\n")
+        }
+        Reply::Array(items) => {
+            write!(writer, "*{}\r\n", items.len())?;
+
+            for item in items {
+                write_reply(writer, item)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_read_command() {
+        let mut reader = Cursor::new(b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\nPING\r\n".to_vec());
+
+        assert_eq!(
+            read_command(&mut reader, MAX_COMMAND_LENGTH).unwrap(),
+            Some(vec![b"GET".to_vec(), b"hello".to_vec()])
+        );
+        assert_eq!(
+            read_command(&mut reader, MAX_COMMAND_LENGTH).unwrap(),
+            Some(vec![b"PING".to_vec()])
+        );
+        assert_eq!(read_command(&mut reader, MAX_COMMAND_LENGTH).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_command_partial_frame() {
+        for data in [
+            &b"*2\r\n"[..],
+            b"*2\r\n$3\r\nGET\r\n",
+            b"*1\r\n$5\r\nhel",
+            b"*1\r\n$5\r\nhello",
+            b"*1\r\n$5\r\nhello\r",
+            b"*1\r\n$5\r\nhelloXY",
+        ] {
+            assert!(read_command(&mut Cursor::new(data.to_vec()), MAX_COMMAND_LENGTH).is_err());
+        }
+    }
+
+    #[test]
+    fn test_read_command_oversized_length() {
+        let data = format!("*1\r\n${}\r\nabc\r\n", MAX_BULK_LENGTH + 1);
+        assert!(read_command(&mut Cursor::new(data.into_bytes()), MAX_COMMAND_LENGTH).is_err());
+
+        // Not allocated up front, so this fails only because the data is missing
+        let data = format!("*1\r\n${}\r\nabc\r\n", MAX_BULK_LENGTH);
+        assert!(read_command(&mut Cursor::new(data.into_bytes()), MAX_COMMAND_LENGTH).is_err());
+
+        let data = b"*1\r\n$99999999999999999999999\r\n".to_vec();
+        assert!(read_command(&mut Cursor::new(data), MAX_COMMAND_LENGTH).is_err());
+    }
+
+    #[test]
+    fn test_read_command_line_length() {
+        let mut data = vec![b'a'; MAX_LINE_LENGTH - 2];
+        data.extend_from_slice(b"\r\n");
+        let command = read_command(&mut Cursor::new(data), MAX_COMMAND_LENGTH).unwrap();
+        assert_eq!(command.unwrap()[0].len(), MAX_LINE_LENGTH - 2);
+
+        let mut data = vec![b'a'; MAX_LINE_LENGTH - 1];
+        data.extend_from_slice(b"\r\n");
+        let error = read_command(&mut Cursor::new(data), MAX_COMMAND_LENGTH).unwrap_err();
+        assert_eq!(error.to_string(), "line too long");
+
+        // A line without an end is not read past the maximum
+        let mut reader = std::io::BufReader::new(std::io::repeat(b'a'));
+        let error = read_command(&mut reader, MAX_COMMAND_LENGTH).unwrap_err();
+        assert_eq!(error.to_string(), "line too long");
+    }
+
+    #[test]
+    fn test_read_command_total_length() {
+        let data = b"*2\r\n$3\r\nGET\r\n$5\r\nhello\r\n";
+
+        assert!(read_command(&mut Cursor::new(data.to_vec()), data.len())
+            .unwrap()
+            .is_some());
+        let error = read_command(&mut Cursor::new(data.to_vec()), data.len() - 1).unwrap_err();
+        assert_eq!(error.to_string(), "command too long");
+
+        // Many empty strings also count toward the maximum
+        let mut data = b"*1000000\r\n".to_vec();
+        data.extend(b"$0\r\n\r\n".repeat(1000));
+        let error = read_command(&mut Cursor::new(data), 1024).unwrap_err();
+        assert_eq!(error.to_string(), "command too long");
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"", b""));
+        assert!(!glob_match(b"", b"a"));
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"**", b"abc"));
+        assert!(!glob_match(b"?", b""));
+        assert!(glob_match(b"a?c", b"abc"));
+        assert!(!glob_match(b"a?c", b"ac"));
+        assert!(glob_match(b"user:*", b"user:1"));
+        assert!(!glob_match(b"user:*", b"session:1"));
+        assert!(glob_match(b"*:1", b"user:1"));
+        assert!(glob_match(b"a*b*c", b"aXbYbZc"));
+        assert!(!glob_match(b"a*b*c", b"aXbYbZ"));
+        assert!(glob_match(b"*a*", b"bab"));
+        assert!(!glob_match(b"abc", b"abcd"));
+    }
+
+    #[test]
+    fn test_glob_match_many_stars() {
+        let pattern = b"a*"
+            .repeat(64)
+            .into_iter()
+            .chain(*b"b")
+            .collect::<Vec<u8>>();
+        let text = vec![b'a'; 4096];
+
+        assert!(!glob_match(&pattern, &text));
+    }
+}
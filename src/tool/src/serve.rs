@@ -0,0 +1,227 @@
+use std::{io::Read, ops::Bound, path::Path};
+
+use grebedb::{vfs::Vfs, Database, OpenMode, Options};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::repl::encoding::{binary_to_text, Encoding};
+
+const KEYS_PATH: &str = "/keys";
+const METRICS_PATH: &str = "/metrics";
+const DEFAULT_SCAN_LIMIT: usize = 1000;
+const MAX_BODY_LENGTH: usize = 64 * 1024 * 1024;
+
+type HttpResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+pub fn serve(
+    database_path: &Path,
+    address: &str,
+    read_only: bool,
+    options: Options,
+) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: if read_only {
+            OpenMode::ReadOnly
+        } else {
+            OpenMode::LoadOnly
+        },
+        ..options
+    };
+
+    let mut database = Database::open_path(database_path, options)?;
+    let server = Server::http(address).map_err(|error| anyhow::anyhow!(error))?;
+
+    eprintln!("Listening on http://{}", server.server_addr());
+
+    for mut request in server.incoming_requests() {
+        let response = match handle_request(&mut database, database_path, &mut request, read_only) {
+            Ok(response) => response,
+            Err(error) => text_response(500, &error.to_string()),
+        };
+
+        if let Err(error) = request.respond(response) {
+            eprintln!("Failed to send response: {}", error);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_request<F>(
+    database: &mut Database<F>,
+    database_path: &Path,
+    request: &mut Request,
+    read_only: bool,
+) -> anyhow::Result<HttpResponse>
+where
+    F: Vfs,
+{
+    let method = request.method().clone();
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+
+    if path == METRICS_PATH {
+        return match method {
+            Method::Get => metrics(database, database_path),
+            _ => Ok(text_response(405, "method not allowed")),
+        };
+    }
+
+    if path == KEYS_PATH {
+        return match method {
+            Method::Get => scan(database, query),
+            _ => Ok(text_response(405, "method not allowed")),
+        };
+    }
+
+    let key = match path
+        .strip_prefix(KEYS_PATH)
+        .and_then(|path| path.strip_prefix('/'))
+    {
+        Some(key) => decode_component(key),
+        None => return Ok(text_response(404, "not found")),
+    };
+
+    match method {
+        Method::Get => match database.get(&key)? {
+            Some(value) => Ok(Response::from_data(value)),
+            None => Ok(text_response(404, "key not found")),
+        },
+        Method::Put | Method::Delete if read_only => {
+            Ok(text_response(405, "database is read-only"))
+        }
+        Method::Put => {
+            let body_length = request.body_length();
+            let value = match read_body(request.as_reader(), body_length, MAX_BODY_LENGTH)? {
+                Some(value) => value,
+                None => return Ok(text_response(413, "value too large")),
+            };
+
+            database.put(key, value)?;
+            database.flush()?;
+
+            Ok(Response::from_data(Vec::new()).with_status_code(204))
+        }
+        Method::Delete => {
+            database.remove(key)?;
+            database.flush()?;
+
+            Ok(Response::from_data(Vec::new()).with_status_code(204))
+        }
+        _ => Ok(text_response(405, "method not allowed")),
+    }
+}
+
+// Query parameters are `start` (inclusive), `end` (exclusive), `limit`,
+// and `encoding` of the returned keys and values
+fn scan<F>(database: &mut Database<F>, query: &str) -> anyhow::Result<HttpResponse>
+where
+    F: Vfs,
+{
+    let mut start = Bound::Unbounded;
+    let mut end = Bound::Unbounded;
+    let mut limit = DEFAULT_SCAN_LIMIT;
+    let mut encoding = Encoding::Percent;
+
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let value = decode_component(value);
+
+        match name {
+            "start" => start = Bound::Included(value),
+            "end" => end = Bound::Excluded(value),
+            "limit" => match String::from_utf8_lossy(&value).parse() {
+                Ok(value) => limit = value,
+                Err(_) => return Ok(text_response(400, "invalid limit")),
+            },
+            "encoding" => match String::from_utf8_lossy(&value).as_ref().try_into() {
+                Ok(value) => encoding = value,
+                Err(_) => return Ok(text_response(400, "invalid encoding")),
+            },
+            _ => return Ok(text_response(400, "unknown parameter")),
+        }
+    }
+
+    let mut cursor = database.cursor_range::<Vec<u8>, _>((start, end))?;
+    let mut pairs = Vec::new();
+
+    for (key, value) in cursor.by_ref().take(limit) {
+        pairs.push(serde_json::json!({
+            "key": binary_to_text(&key, encoding),
+            "value": binary_to_text(&value, encoding),
+        }));
+    }
+
+    if let Some(error) = cursor.error() {
+        return Ok(text_response(500, &error.to_string()));
+    }
+
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+
+    Ok(Response::from_data(serde_json::to_vec(&pairs)?).with_header(header))
+}
+
+fn metrics<F>(database: &Database<F>, database_path: &Path) -> anyhow::Result<HttpResponse>
+where
+    F: Vfs,
+{
+    let text = crate::metrics::format_metrics(database, database_path)?;
+    let header = Header::from_bytes(
+        &b"Content-Type"[..],
+        &b"text/plain; version=0.0.4; charset=utf-8"[..],
+    )
+    .unwrap();
+
+    Ok(Response::from_string(text).with_header(header))
+}
+
+// Returns none if the body is longer than the maximum. The declared length
+// is checked first so that the body is not read at all in that case.
+fn read_body<R: Read>(
+    reader: R,
+    body_length: Option<usize>,
+    max_length: usize,
+) -> std::io::Result<Option<Vec<u8>>> {
+    if matches!(body_length, Some(length) if length > max_length) {
+        return Ok(None);
+    }
+
+    let mut body = Vec::new();
+    reader.take(max_length as u64 + 1).read_to_end(&mut body)?;
+
+    if body.len() > max_length {
+        Ok(None)
+    } else {
+        Ok(Some(body))
+    }
+}
+
+fn decode_component(text: &str) -> Vec<u8> {
+    percent_encoding::percent_decode_str(text).collect()
+}
+
+fn text_response(status_code: u16, message: &str) -> HttpResponse {
+    Response::from_string(message).with_status_code(status_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_read_body() {
+        assert_eq!(
+            read_body(Cursor::new(b"hello"), Some(5), 5).unwrap(),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(
+            read_body(Cursor::new(b"hello"), None, 5).unwrap(),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(read_body(Cursor::new(b"hello"), Some(6), 5).unwrap(), None);
+        assert_eq!(read_body(Cursor::new(b"hello!"), None, 5).unwrap(), None);
+        assert_eq!(read_body(std::io::repeat(0), Some(4), 5).unwrap(), None);
+        assert_eq!(read_body(std::io::repeat(0), None, 5).unwrap(), None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use grebedb::{Database, OpenMode, Options};
+
+pub fn stats(database_path: &Path, options: Options) -> anyhow::Result<()> {
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        ..options
+    };
+
+    let mut database = crate::archive::open_database(database_path, options)?;
+
+    print_stats(&mut database, database_path)
+}
+
+pub fn print_stats(database: &mut Database, database_path: &Path) -> anyhow::Result<()> {
+    let tree_stats = database.tree_stats()?;
+
+    println!("UUID: {}", tree_stats.uuid());
+    println!("Revision: {}", tree_stats.revision());
+    println!("Key-value pairs: {}", database.metadata().key_value_count());
+    println!("Tree height: {}", tree_stats.height());
+
+    if let Some(keys_per_node) = database.metadata().keys_per_node() {
+        println!("Keys per node: {}", keys_per_node);
+    }
+
+    for (level, page_count) in tree_stats.level_page_counts().iter().enumerate() {
+        println!("Level {} pages: {}", level, page_count);
+    }
+
+    println!("Total pages: {}", tree_stats.page_count());
+    println!("Fill factor: {:.1}%", tree_stats.fill_factor() * 100.0);
+    println!("Free pages: {}", tree_stats.free_page_count());
+    println!("Disk usage: {} bytes", disk_usage(database_path)?);
+
+    Ok(())
+}
+
+// Total size of the files in the directory and its subdirectories, or the
+// size of an archive file
+pub fn disk_usage(path: &Path) -> anyhow::Result<u64> {
+    if path.is_file() {
+        return Ok(path.metadata()?.len());
+    }
+
+    let mut total = 0;
+
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+
+        if metadata.is_dir() {
+            total += disk_usage(&entry.path())?;
+        } else {
+            total += metadata.len();
+        }
+    }
+
+    Ok(total)
+}
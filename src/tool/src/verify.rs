@@ -2,32 +2,64 @@ use std::path::Path;
 
 use grebedb::{Database, OpenMode, Options};
 
-pub fn verify(database_path: &Path, write: bool, verbose: bool) -> anyhow::Result<()> {
+use crate::progress::{ProgressBar, ProgressStyle};
+
+pub fn verify(
+    database_path: &Path,
+    write: bool,
+    progress_style: ProgressStyle,
+    options: Options,
+) -> anyhow::Result<()> {
     let options = Options {
         open_mode: if write {
             OpenMode::LoadOnly
         } else {
             OpenMode::ReadOnly
         },
-        ..Default::default()
+        ..options
     };
 
-    let mut database = Database::open_path(database_path, options)?;
+    let mut database = crate::archive::open_database(database_path, options)?;
+
+    verify_database(&mut database, progress_style)?;
+
+    if write {
+        clean_up_database(&mut database)?;
+    }
+
+    Ok(())
+}
+
+pub fn verify_database(
+    database: &mut Database,
+    progress_style: ProgressStyle,
+) -> anyhow::Result<()> {
+    let mut progress_bar = ProgressBar::new(progress_style, "Verifying", "pages");
 
     database.verify(|current, total| {
-        if verbose {
-            let percent = if total > 0 {
-                current as f64 / total as f64 * 100.0
-            } else {
-                0.0
-            };
-            eprintln!("\t{:.1}%\t{}\t{}", percent, current, total);
-        }
+        progress_bar.update(current as u64, Some(total as u64));
     })?;
 
-    if verbose {
+    progress_bar.finish();
+
+    for page_id in database.recovered_pages() {
+        eprintln!("Page {} was recovered from a torn write", page_id);
+    }
+
+    if !progress_style.is_hidden() {
         eprintln!("OK");
     }
 
     Ok(())
 }
+
+fn clean_up_database(database: &mut Database) -> anyhow::Result<()> {
+    let report = database.clean_up()?;
+
+    eprintln!("Reclaimed page IDs: {}", report.reclaimed_ids());
+    eprintln!("Removed files: {}", report.removed_files());
+    eprintln!("Promoted files: {}", report.promoted_files());
+    eprintln!("Removed directories: {}", report.removed_dirs());
+
+    Ok(())
+}
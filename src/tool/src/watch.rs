@@ -0,0 +1,108 @@
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use grebedb::{Database, OpenMode, Options};
+
+use crate::repl::encoding::{binary_to_text, Encoding};
+
+// Checksums of the values are kept instead of the values to limit memory use
+type Snapshot = BTreeMap<Vec<u8>, u32>;
+
+pub fn watch(
+    database_path: &Path,
+    prefix: &[u8],
+    interval: Duration,
+    show_values: bool,
+    encoding: Encoding,
+    options: Options,
+) -> anyhow::Result<()> {
+    // The lock would prevent the other process from opening the database
+    let options = Options {
+        open_mode: OpenMode::ReadOnly,
+        file_locking: false,
+        ..options
+    };
+
+    let mut database = Database::open_path(database_path, options)?;
+    let mut snapshot = Snapshot::new();
+    let mut revision = database.revision();
+
+    scan_prefix(&mut database, prefix, |key, value| {
+        snapshot.insert(key.to_vec(), crc32c::crc32c(value));
+    })?;
+
+    eprintln!("Watching revision {}", revision);
+
+    loop {
+        std::thread::sleep(interval);
+
+        // The files may be replaced by the other process while they are read
+        if let Err(error) = database.reopen() {
+            eprintln!("Retrying after error: {}", error);
+            continue;
+        }
+
+        if database.revision() == revision {
+            continue;
+        }
+
+        let mut new_snapshot = Snapshot::new();
+        let mut changes = Vec::new();
+        let encode = |value: &[u8]| binary_to_text(value, encoding);
+
+        let result = scan_prefix(&mut database, prefix, |key, value| {
+            let checksum = crc32c::crc32c(value);
+            let symbol = match snapshot.get(key) {
+                None => "+",
+                Some(old_checksum) if *old_checksum != checksum => "~",
+                Some(_) => "",
+            };
+
+            if !symbol.is_empty() && show_values {
+                changes.push(format!("{} {}\t{}", symbol, encode(key), encode(value)));
+            } else if !symbol.is_empty() {
+                changes.push(format!("{} {}", symbol, encode(key)));
+            }
+
+            new_snapshot.insert(key.to_vec(), checksum);
+        });
+
+        if let Err(error) = result {
+            eprintln!("Retrying after error: {}", error);
+            continue;
+        }
+
+        for key in snapshot.keys() {
+            if !new_snapshot.contains_key(key) {
+                changes.push(format!("- {}", encode(key)));
+            }
+        }
+
+        for change in changes {
+            println!("{}", change);
+        }
+
+        snapshot = new_snapshot;
+        revision = database.revision();
+    }
+}
+
+fn scan_prefix<C>(database: &mut Database, prefix: &[u8], mut callback: C) -> anyhow::Result<()>
+where
+    C: FnMut(&[u8], &[u8]),
+{
+    let mut cursor = database.cursor()?;
+    let mut key = Vec::new();
+    let mut value = Vec::new();
+
+    cursor.seek(prefix)?;
+
+    while cursor.next_buf(&mut key, &mut value)? {
+        if !key.starts_with(prefix) {
+            break;
+        }
+
+        callback(&key, &value);
+    }
+
+    Ok(())
+}